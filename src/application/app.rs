@@ -1,10 +1,15 @@
 use std::path::{Path, PathBuf};
 
-use crate::domain::{Cartridge, Emulator, RomHeader};
+use crate::domain::{Cartridge, Emulator, Movie, RomHeader};
+use crate::infrastructure::config::{
+    ConfigError, GameConfig, GlobalSettings, default_config_root, game_config_path,
+    global_config_path, load_config, load_settings, save_config, save_settings, settings_path,
+};
 use crate::infrastructure::persistence::{
-    AutoResumeMetadata, ResumeError, default_resume_path, load_last_session, save_last_session,
+    self, AutoResumeMetadata, MovieFileError, ResumeError, ResumeMode, ResumePlan, ResumeState,
+    default_resume_path, default_resume_state_path, load_last_session, save_last_session,
 };
-use crate::infrastructure::rom_loader::{self, RomLoadError, RomSaveError};
+use crate::infrastructure::rom_loader::{self, ExtensionPolicy, RomLoadError, RomSaveError};
 
 pub fn run() {
     let _emulator = Emulator::new();
@@ -15,27 +20,67 @@ pub fn load_rom_header(path: impl AsRef<Path>) -> Result<RomHeader, RomLoadError
     Ok(cartridge.header)
 }
 
+/// Loads a ROM, letting its file extension pick CGB or DMG mode when the global config
+/// sets `extension_policy = "extension"`.
 pub fn load_rom(path: impl AsRef<Path>) -> Result<Cartridge, RomLoadError> {
-    rom_loader::load_rom(path)
+    load_rom_with_save_root(path, None)
+}
+
+fn extension_policy() -> ExtensionPolicy {
+    let settings = match load_global_settings() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Failed to load extension policy setting: {:?}", err);
+            return ExtensionPolicy::default();
+        }
+    };
+    let Some(name) = settings.extension_policy else {
+        return ExtensionPolicy::default();
+    };
+    ExtensionPolicy::from_name(&name).unwrap_or_else(|| {
+        eprintln!("Ignoring unknown extension policy '{}'", name);
+        ExtensionPolicy::default()
+    })
 }
 
+/// Like `load_rom`, looking for the battery save under `save_root` when one is given.
 pub fn load_rom_with_save_root(
     path: impl AsRef<Path>,
     save_root: Option<&Path>,
 ) -> Result<Cartridge, RomLoadError> {
-    rom_loader::load_rom_with_save_root(path, save_root)
+    rom_loader::load_rom_with_policy(path, save_root, extension_policy())
 }
 
-pub fn save_battery_ram(path: impl AsRef<Path>, cartridge: &Cartridge) -> Result<(), RomSaveError> {
-    rom_loader::save_battery_ram(path, cartridge)
+pub fn save_battery_ram(cartridge: &Cartridge) -> Result<(), RomSaveError> {
+    rom_loader::save_battery_ram(cartridge)
 }
 
 pub fn save_battery_ram_with_root(
-    path: impl AsRef<Path>,
     save_root: Option<&Path>,
     cartridge: &Cartridge,
 ) -> Result<(), RomSaveError> {
-    rom_loader::save_battery_ram_with_root(path, save_root, cartridge)
+    rom_loader::save_battery_ram_with_root(save_root, cartridge)
+}
+
+/// Writes the loaded game's `.sav`, RTC block included, if its cartridge keeps one and
+/// the game has written RAM or set the clock since it was loaded. A clock left running
+/// needs no write; it catches up from the old timestamp on the next load.
+pub fn save_battery(emulator: &Emulator, save_root: Option<&Path>) -> Result<(), RomSaveError> {
+    let (Some(cartridge), Ok(data)) = (emulator.cartridge(), emulator.export_save()) else {
+        return Ok(());
+    };
+    if !cartridge.is_ram_dirty() {
+        return Ok(());
+    }
+    rom_loader::save_battery_data_with_root(save_root, cartridge, &data)
+}
+
+pub fn load_movie(path: impl AsRef<Path>) -> Result<Movie, MovieFileError> {
+    persistence::load_movie(path)
+}
+
+pub fn save_movie(path: impl AsRef<Path>, movie: &Movie) -> Result<(), MovieFileError> {
+    persistence::save_movie(path, movie)
 }
 
 pub fn save_auto_resume(metadata: &AutoResumeMetadata) -> Result<(), ResumeError> {
@@ -57,3 +102,135 @@ pub fn load_auto_resume() -> Result<Option<AutoResumeMetadata>, ResumeError> {
 pub fn load_auto_resume_path() -> Result<Option<(PathBuf, Option<PathBuf>)>, ResumeError> {
     Ok(load_auto_resume()?.map(|meta| (meta.rom_path, meta.save_root)))
 }
+
+/// What to load at launch, following the `auto_resume` preference.
+pub fn load_resume_plan() -> Result<Option<ResumePlan>, ResumeError> {
+    let mode = resume_mode_preference();
+    if mode == ResumeMode::Off {
+        return Ok(None);
+    }
+    Ok(mode.plan(load_auto_resume()?))
+}
+
+/// Under `auto_resume = "state"`, saves where the running game is and points the resume
+/// entry at it. Does nothing under the other modes or with no cartridge loaded.
+pub fn save_resume_state(
+    emulator: &Emulator,
+    rom_path: impl Into<PathBuf>,
+) -> Result<(), ResumeError> {
+    if resume_mode_preference() != ResumeMode::RomAndState {
+        return Ok(());
+    }
+    let Some(state) = ResumeState::capture(emulator) else {
+        return Ok(());
+    };
+    let state_path = default_resume_state_path();
+    persistence::save_resume_state(&state_path, &state)?;
+    let mut metadata = AutoResumeMetadata::with_save_root(rom_path, None);
+    metadata.state_path = Some(state_path);
+    save_auto_resume(&metadata)
+}
+
+/// Puts the game `emulator` has loaded back where the state at `path` left it.
+pub fn restore_resume_state(
+    emulator: &mut Emulator,
+    path: impl AsRef<Path>,
+) -> Result<(), ResumeError> {
+    persistence::load_resume_state(path)?.restore(emulator)
+}
+
+pub fn resume_mode_preference() -> ResumeMode {
+    let settings = match load_global_settings() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Failed to load auto-resume setting: {:?}", err);
+            return ResumeMode::default();
+        }
+    };
+    let Some(name) = settings.auto_resume else {
+        return ResumeMode::default();
+    };
+    ResumeMode::from_name(&name).unwrap_or_else(|| {
+        eprintln!("Ignoring unknown auto-resume mode '{}'", name);
+        ResumeMode::default()
+    })
+}
+
+pub fn load_game_config(header: &RomHeader) -> Result<GameConfig, ConfigError> {
+    load_game_config_with_root(&default_config_root(), header)
+}
+
+pub fn load_game_config_with_root(
+    root: &Path,
+    header: &RomHeader,
+) -> Result<GameConfig, ConfigError> {
+    let (global, game) = load_config_layers_with_root(root, header)?;
+    Ok(global.merged_with(&game))
+}
+
+/// The global config and the game's own overrides, unmerged.
+pub fn load_config_layers(header: &RomHeader) -> Result<(GameConfig, GameConfig), ConfigError> {
+    load_config_layers_with_root(&default_config_root(), header)
+}
+
+fn load_config_layers_with_root(
+    root: &Path,
+    header: &RomHeader,
+) -> Result<(GameConfig, GameConfig), ConfigError> {
+    let global = load_config(global_config_path(root))?.unwrap_or_default();
+    let game = load_config(game_config_path(root, header))?.unwrap_or_default();
+    Ok((global, game))
+}
+
+fn load_global_settings() -> Result<GlobalSettings, ConfigError> {
+    Ok(load_settings(settings_path(&default_config_root()))?.unwrap_or_default())
+}
+
+fn update_global_settings(update: impl FnOnce(&mut GlobalSettings)) -> Result<(), ConfigError> {
+    let path = settings_path(&default_config_root());
+    let mut settings = load_settings(&path)?.unwrap_or_default();
+    update(&mut settings);
+    save_settings(path, &settings)
+}
+
+/// The boot ROM to use when none is given on the command line.
+pub fn default_boot_rom_path() -> Result<Option<PathBuf>, ConfigError> {
+    Ok(load_global_settings()?.boot_rom.map(PathBuf::from))
+}
+
+pub fn save_default_boot_rom_path(boot_rom: &Path) -> Result<(), ConfigError> {
+    let boot_rom = std::fs::canonicalize(boot_rom).unwrap_or_else(|_| boot_rom.to_path_buf());
+    update_global_settings(|settings| {
+        settings.boot_rom = Some(boot_rom.to_string_lossy().into_owned());
+    })
+}
+
+/// Whether Escape toggles the menu or shows it only while held, by name.
+pub fn menu_mode_preference() -> Result<Option<String>, ConfigError> {
+    Ok(load_global_settings()?.menu_mode)
+}
+
+/// What a ROM loaded from the menu waits for before taking input, by name.
+pub fn load_gate_preference() -> Result<Option<String>, ConfigError> {
+    Ok(load_global_settings()?.load_gate)
+}
+
+pub fn save_menu_mode_preference(mode: &str) -> Result<(), ConfigError> {
+    update_global_settings(|settings| settings.menu_mode = Some(mode.to_string()))
+}
+
+pub fn present_mode_preference() -> Result<Option<String>, ConfigError> {
+    Ok(load_global_settings()?.present_mode)
+}
+
+pub fn save_present_mode_preference(mode: &str) -> Result<(), ConfigError> {
+    update_global_settings(|settings| settings.present_mode = Some(mode.to_string()))
+}
+
+pub fn is_valid_boot_rom_size(len: usize) -> bool {
+    rom_loader::is_valid_boot_rom_size(len)
+}
+
+pub fn save_game_config(header: &RomHeader, config: &GameConfig) -> Result<(), ConfigError> {
+    save_config(game_config_path(&default_config_root(), header), config)
+}
@@ -1,9 +1,10 @@
 use std::collections::VecDeque;
+use std::f32::consts::PI;
 
 const CPU_HZ: f64 = 4_194_304.0;
 const FRAME_CYCLES: u32 = 70_224;
 pub(crate) const DEFAULT_OUTPUT_SAMPLE_RATE_HZ: f64 = 48_000.0;
-const MAX_SAMPLE_QUEUE: usize = 2_048;
+const DEFAULT_MAX_SAMPLE_QUEUE: usize = 2_048;
 
 const REG_NR10: u16 = 0xFF10;
 const REG_NR11: u16 = 0xFF11;
@@ -33,8 +34,17 @@ const REG_NR50: u16 = 0xFF24;
 const REG_NR51: u16 = 0xFF25;
 const REG_NR52: u16 = 0xFF26;
 
+const SOFT_CLIP_KNEE: i32 = 64;
+const SOFT_CLIP_HEADROOM: i32 = 127 - SOFT_CLIP_KNEE;
+/// Distance between output levels in 4-bit mode: 16 levels across the 8-bit range.
+const FOUR_BIT_STEP: i32 = 16;
+
 const FREQ_DIVISOR: u32 = 131072;
 const FRAME_SEQUENCER_CYCLES: u32 = 8192;
+/// Cycles per step while a pitch check runs, about one instruction's worth.
+const PITCH_CHECK_STEP: u32 = 4;
+/// How far a measured pitch may stray, relative to the programmed one.
+const PITCH_TOLERANCE: f32 = 0.02;
 
 const DUTY_CYCLES: [[u8; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1],
@@ -735,6 +745,217 @@ impl NoiseChannel {
     }
 }
 
+/// How the mixer limits samples that exceed the signed 8-bit output range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipMode {
+    /// Clamp straight to the range, matching the hardware's behaviour.
+    #[default]
+    Hard,
+    /// Compress levels past a knee so loud passages round off instead of flattening.
+    Soft,
+}
+
+impl ClipMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hard => "hard",
+            Self::Soft => "soft",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "hard" => Some(Self::Hard),
+            "soft" => Some(Self::Soft),
+            _ => None,
+        }
+    }
+}
+
+/// Resolution of the final mixed sample.
+///
+/// `FourBit` snaps every output to one of 16 levels, like a single channel's DAC. Quiet
+/// passages and envelope fades then move in audible steps and pick up a gritty
+/// quantization hiss; `Full` keeps the mixer's fine steps for a cleaner, smoother sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleDepth {
+    #[default]
+    Full,
+    FourBit,
+}
+
+impl SampleDepth {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::FourBit => "4bit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "full" => Some(Self::Full),
+            "4bit" => Some(Self::FourBit),
+            _ => None,
+        }
+    }
+}
+
+fn quantize_sample(value: i32, depth: SampleDepth) -> i32 {
+    match depth {
+        SampleDepth::Full => value,
+        SampleDepth::FourBit => value.div_euclid(FOUR_BIT_STEP) * FOUR_BIT_STEP,
+    }
+}
+
+/// One of the four sound generators, for per-channel mixer settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelId {
+    Pulse1,
+    Pulse2,
+    Wave,
+    Noise,
+}
+
+impl ChannelId {
+    pub const ALL: [Self; 4] = [Self::Pulse1, Self::Pulse2, Self::Wave, Self::Noise];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pulse1 => "pulse1",
+            Self::Pulse2 => "pulse2",
+            Self::Wave => "wave",
+            Self::Noise => "noise",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|channel| channel.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Position in NR51's nibbles and in the mixer's per-channel arrays.
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+fn clip_sample(value: i32, mode: ClipMode) -> i32 {
+    let value = match mode {
+        ClipMode::Hard => value,
+        ClipMode::Soft => {
+            let magnitude = value.abs();
+            if magnitude <= SOFT_CLIP_KNEE {
+                value
+            } else {
+                let over = magnitude - SOFT_CLIP_KNEE;
+                let compressed =
+                    SOFT_CLIP_KNEE + over * SOFT_CLIP_HEADROOM / (over + SOFT_CLIP_HEADROOM);
+                value.signum() * compressed
+            }
+        }
+    };
+    value.clamp(-128, 127)
+}
+
+/// Power of `freq` in `samples`, taken from the nearest Goertzel bin.
+pub fn goertzel(samples: &[f32], freq: f32, sample_rate: f32) -> f32 {
+    if samples.is_empty() || freq <= 0.0 || sample_rate <= 0.0 {
+        return 0.0;
+    }
+    let n = samples.len() as f32;
+    let k = (0.5 + (n * freq / sample_rate)).floor();
+    let w = 2.0 * PI * k / n;
+    let cosine = w.cos();
+    let sine = w.sin();
+    let coeff = 2.0 * cosine;
+    let mut q1 = 0.0;
+    let mut q2 = 0.0;
+    for &sample in samples {
+        let q0 = coeff * q1 - q2 + sample;
+        q2 = q1;
+        q1 = q0;
+    }
+    let real = q1 - q2 * cosine;
+    let imag = q2 * sine;
+    real * real + imag * imag
+}
+
+/// The strongest Goertzel bin between `min_freq` and `max_freq`, in Hz.
+pub fn dominant_frequency(samples: &[f32], sample_rate: f32, min_freq: f32, max_freq: f32) -> f32 {
+    if samples.is_empty() || sample_rate <= 0.0 {
+        return 0.0;
+    }
+    let bin_width = sample_rate / samples.len() as f32;
+    let first = (min_freq / bin_width).ceil().max(1.0) as usize;
+    let last = (max_freq / bin_width).floor() as usize;
+    let mut best = (0.0, 0.0);
+    for bin in first..=last {
+        let freq = bin as f32 * bin_width;
+        let power = goertzel(samples, freq, sample_rate);
+        if power > best.1 {
+            best = (freq, power);
+        }
+    }
+    best.0
+}
+
+/// Programmed against measured pitch from a pulse channel check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApuReport {
+    pub expected_hz: f32,
+    pub measured_hz: f32,
+}
+
+impl ApuReport {
+    pub fn passed(&self) -> bool {
+        (self.measured_hz - self.expected_hz).abs() <= self.expected_hz * PITCH_TOLERANCE
+    }
+}
+
+/// Plays pulse channel 1 at the 11-bit `frequency` register value on a fresh APU for
+/// `cycles`, then measures the pitch that came out.
+pub fn measure_pulse_pitch(frequency: u16, cycles: u32) -> ApuReport {
+    let frequency = frequency.min(0x7FF);
+    let mut apu = Apu::new();
+    apu.write_io(REG_NR52, 0x80);
+    apu.write_io(REG_NR50, 0x77);
+    apu.write_io(REG_NR51, 0x11);
+    apu.write_io(REG_NR10, 0x00);
+    // 50% duty, full volume with no envelope, and no length counter.
+    apu.write_io(REG_NR11, 0x80);
+    apu.write_io(REG_NR12, 0xF0);
+    apu.write_io(REG_NR13, frequency as u8);
+    apu.write_io(REG_NR14, 0x80 | (frequency >> 8) as u8);
+
+    let mut samples = Vec::new();
+    let mut elapsed = 0;
+    while elapsed < cycles {
+        let _ = apu.step(PITCH_CHECK_STEP);
+        elapsed += PITCH_CHECK_STEP;
+        while apu.has_sample() {
+            samples.push(apu.take_sample() as f32);
+        }
+    }
+    let mean = samples.iter().sum::<f32>() / samples.len().max(1) as f32;
+    for sample in &mut samples {
+        *sample -= mean;
+    }
+
+    let expected_hz = FREQ_DIVISOR as f32 / (2048 - u32::from(frequency)) as f32;
+    let sample_rate = apu.sample_rate_hz() as f32;
+    ApuReport {
+        expected_hz,
+        measured_hz: dominant_frequency(
+            &samples,
+            sample_rate,
+            expected_hz / 2.0,
+            expected_hz * 2.0,
+        ),
+    }
+}
+
 #[derive(Debug)]
 pub struct Apu {
     frame_sequencer_cycles: u32,
@@ -747,6 +968,7 @@ pub struct Apu {
     sample_rate_hz: f64,
     cycles_per_sample: f64,
     samples: VecDeque<[i32; 2]>,
+    max_queue: usize,
     current_sample: i32,
     current_sample_left: i32,
     current_sample_right: i32,
@@ -754,6 +976,9 @@ pub struct Apu {
     master_volume_right: u8,
     nr51: u8,
     sound_enabled: bool,
+    clip_mode: ClipMode,
+    sample_depth: SampleDepth,
+    channel_gains: [f32; 4],
 }
 
 impl Default for Apu {
@@ -775,6 +1000,7 @@ impl Apu {
             sample_rate_hz: DEFAULT_OUTPUT_SAMPLE_RATE_HZ,
             cycles_per_sample: CPU_HZ / DEFAULT_OUTPUT_SAMPLE_RATE_HZ,
             samples: VecDeque::new(),
+            max_queue: DEFAULT_MAX_SAMPLE_QUEUE,
             current_sample: 0,
             current_sample_left: 0,
             current_sample_right: 0,
@@ -782,6 +1008,9 @@ impl Apu {
             master_volume_right: 0,
             nr51: 0,
             sound_enabled: false,
+            clip_mode: ClipMode::default(),
+            sample_depth: SampleDepth::default(),
+            channel_gains: [1.0; 4],
         }
     }
 
@@ -801,16 +1030,19 @@ impl Apu {
         while self.sample_cycle_accumulator >= self.cycles_per_sample {
             self.sample_cycle_accumulator -= self.cycles_per_sample;
             self.mix_sample();
-            if self.samples.len() >= MAX_SAMPLE_QUEUE {
-                self.samples.pop_front();
-            }
-            self.samples
-                .push_back([self.current_sample_left, self.current_sample_right]);
+            self.push_sample([self.current_sample_left, self.current_sample_right]);
         }
 
         Ok(())
     }
 
+    fn push_sample(&mut self, sample: [i32; 2]) {
+        while self.samples.len() >= self.max_queue {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
     fn step_frame_sequencer(&mut self) {
         match self.frame_sequencer_step {
             0 => {
@@ -851,44 +1083,32 @@ impl Apu {
             return;
         }
 
-        let pulse1_out = self.pulse_channel.output();
-        let pulse2_out = self.pulse_channel2.output();
-        let wave_out = self.wave_channel.output();
-        let noise_out = self.noise_channel.output();
-
-        let mut left = 0;
-        let mut right = 0;
+        let outputs = [
+            self.pulse_channel.output(),
+            self.pulse_channel2.output(),
+            self.wave_channel.output(),
+            self.noise_channel.output(),
+        ];
 
-        if self.nr51 & 0x10 != 0 {
-            left += pulse1_out;
-        }
-        if self.nr51 & 0x20 != 0 {
-            left += pulse2_out;
-        }
-        if self.nr51 & 0x40 != 0 {
-            left += wave_out;
-        }
-        if self.nr51 & 0x80 != 0 {
-            left += noise_out;
-        }
-
-        if self.nr51 & 0x01 != 0 {
-            right += pulse1_out;
-        }
-        if self.nr51 & 0x02 != 0 {
-            right += pulse2_out;
-        }
-        if self.nr51 & 0x04 != 0 {
-            right += wave_out;
-        }
-        if self.nr51 & 0x08 != 0 {
-            right += noise_out;
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (index, (output, gain)) in outputs.iter().zip(self.channel_gains).enumerate() {
+            let contribution = *output as f32 * gain;
+            if self.nr51 & (0x10 << index) != 0 {
+                left += contribution;
+            }
+            if self.nr51 & (0x01 << index) != 0 {
+                right += contribution;
+            }
         }
 
-        let left_scaled = left * (self.master_volume_left as i32 + 1);
-        let right_scaled = right * (self.master_volume_right as i32 + 1);
-        self.current_sample_left = (left_scaled / 8).clamp(-128, 127);
-        self.current_sample_right = (right_scaled / 8).clamp(-128, 127);
+        // Truncating matches the integer mix exactly while every gain is 1.0.
+        let left_scaled = left * (self.master_volume_left as f32 + 1.0);
+        let right_scaled = right * (self.master_volume_right as f32 + 1.0);
+        let left = clip_sample((left_scaled / 8.0) as i32, self.clip_mode);
+        let right = clip_sample((right_scaled / 8.0) as i32, self.clip_mode);
+        self.current_sample_left = quantize_sample(left, self.sample_depth);
+        self.current_sample_right = quantize_sample(right, self.sample_depth);
         self.current_sample =
             ((self.current_sample_left + self.current_sample_right) / 2).clamp(-128, 127);
     }
@@ -1106,6 +1326,48 @@ impl Apu {
         self.current_sample_right = 0;
     }
 
+    pub fn clip_mode(&self) -> ClipMode {
+        self.clip_mode
+    }
+
+    pub fn set_clip_mode(&mut self, mode: ClipMode) {
+        self.clip_mode = mode;
+    }
+
+    pub fn sample_depth(&self) -> SampleDepth {
+        self.sample_depth
+    }
+
+    pub fn set_sample_depth(&mut self, depth: SampleDepth) {
+        self.sample_depth = depth;
+    }
+
+    pub fn channel_gain(&self, channel: ChannelId) -> f32 {
+        self.channel_gains[channel.index()]
+    }
+
+    /// Scales one channel's contribution to the mix, from 0.0 (silent) to 1.0 (as the
+    /// game set it). Survives resets like the clip mode.
+    pub fn set_channel_gain(&mut self, channel: ChannelId, gain: f32) {
+        if !gain.is_finite() {
+            return;
+        }
+        self.channel_gains[channel.index()] = gain.clamp(0.0, 1.0);
+    }
+
+    pub fn max_queue(&self) -> usize {
+        self.max_queue
+    }
+
+    /// Caps the queued samples, dropping the oldest first, so the queue holds no more
+    /// than the audio output's latency budget.
+    pub fn set_max_queue(&mut self, max_queue: usize) {
+        self.max_queue = max_queue.max(1);
+        while self.samples.len() > self.max_queue {
+            self.samples.pop_front();
+        }
+    }
+
     pub fn has_sample(&self) -> bool {
         !self.samples.is_empty()
     }
@@ -1134,10 +1396,108 @@ impl Apu {
 #[cfg(test)]
 mod tests {
     use super::{
-        Apu, CPU_HZ, DEFAULT_OUTPUT_SAMPLE_RATE_HZ, FRAME_CYCLES, NoiseChannel, PulseChannel,
-        WaveChannel,
+        Apu, CPU_HZ, ChannelId, ClipMode, DEFAULT_OUTPUT_SAMPLE_RATE_HZ, FRAME_CYCLES,
+        NoiseChannel, PulseChannel, SampleDepth, WaveChannel, clip_sample,
     };
 
+    #[test]
+    fn four_bit_depth_snaps_samples_to_the_step_grid() {
+        let mut apu = Apu::new();
+        apu.apply_post_boot_state();
+        apu.pulse_channel.output_volume = 8;
+        apu.pulse_channel2.output_volume = 4;
+        apu.wave_channel.output_volume = 2;
+        apu.noise_channel.output_volume = 7;
+
+        apu.mix_sample();
+        let (left, right) = apu.sample_stereo();
+        assert_eq!((left, right), (21, 12));
+        assert!(left % 16 != 0 && right % 16 != 0);
+
+        apu.set_sample_depth(SampleDepth::FourBit);
+        apu.mix_sample();
+        assert_eq!(apu.sample_stereo(), (16, 0));
+
+        apu.noise_channel.output_volume = 15;
+        apu.mix_sample();
+        let (left, right) = apu.sample_stereo();
+        assert_eq!(left % 16, 0);
+        assert_eq!(right % 16, 0);
+        assert_eq!(SampleDepth::from_name("4BIT"), Some(SampleDepth::FourBit));
+    }
+
+    #[test]
+    fn soft_clip_rounds_off_large_samples() {
+        assert_eq!(clip_sample(400, ClipMode::Hard), 127);
+        assert_eq!(clip_sample(-400, ClipMode::Hard), -128);
+
+        let soft = clip_sample(400, ClipMode::Soft);
+        assert!((-128..=127).contains(&soft));
+        assert!(soft < 127);
+        assert_eq!(clip_sample(-400, ClipMode::Soft), -soft);
+
+        for value in -64..=64 {
+            assert_eq!(clip_sample(value, ClipMode::Soft), value);
+        }
+        // Hard clipping flattens everything past the limit; the soft curve keeps rising.
+        let mut previous = clip_sample(64, ClipMode::Soft);
+        for value in 65..=400 {
+            let current = clip_sample(value, ClipMode::Soft);
+            assert!(current >= previous);
+            assert!(current <= clip_sample(value, ClipMode::Hard));
+            previous = current;
+        }
+        assert!(clip_sample(200, ClipMode::Soft) > clip_sample(130, ClipMode::Soft));
+        assert_eq!(
+            clip_sample(200, ClipMode::Hard),
+            clip_sample(130, ClipMode::Hard)
+        );
+    }
+
+    #[test]
+    fn channel_gain_scales_only_that_channel() {
+        let mut apu = Apu::new();
+        apu.apply_post_boot_state();
+        // Post-boot NR51 sends every channel left and both pulses right at full volume.
+        apu.pulse_channel.output_volume = 8;
+        apu.pulse_channel2.output_volume = 4;
+        apu.wave_channel.output_volume = 2;
+        apu.noise_channel.output_volume = 6;
+
+        apu.mix_sample();
+        assert_eq!(apu.sample_stereo(), (20, 12));
+
+        apu.set_channel_gain(ChannelId::Pulse1, 0.5);
+        apu.mix_sample();
+        assert_eq!(apu.sample_stereo(), (16, 8));
+        assert_eq!(apu.channel_gain(ChannelId::Pulse2), 1.0);
+
+        apu.set_channel_gain(ChannelId::Noise, 3.0);
+        assert_eq!(apu.channel_gain(ChannelId::Noise), 1.0);
+        assert_eq!(ChannelId::from_name("WAVE"), Some(ChannelId::Wave));
+    }
+
+    #[test]
+    fn sample_queue_stays_under_the_cap_dropping_oldest() {
+        let mut apu = Apu::new();
+        apu.apply_post_boot_state();
+        let _ = apu.step(FRAME_CYCLES);
+        assert!(apu.samples.len() > 64);
+
+        apu.set_max_queue(64);
+        assert_eq!(apu.samples.len(), 64);
+
+        let _ = apu.step(FRAME_CYCLES);
+        assert_eq!(apu.samples.len(), 64);
+
+        for value in 0..100 {
+            apu.push_sample([value, -value]);
+            assert!(apu.samples.len() <= 64);
+        }
+        assert_eq!(apu.take_sample_stereo(), (36, -36));
+        assert_eq!(apu.samples.back(), Some(&[99, -99]));
+    }
+
     #[test]
     fn new_apu_initializes_correctly() {
         let apu = Apu::new();
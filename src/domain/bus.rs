@@ -1,6 +1,19 @@
-use super::{Apu, Cartridge, Mbc, MbcError, RtcMode};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::rom::SgbFlag;
+use super::{
+    Apu, Cartridge, ChannelId, ClipMode, Mapper, Mbc, MbcError, RamBankSelection, RtcMode,
+    RtcSnapshot, RtcSource, SampleDepth, SgbCommand, SgbPacketDecoder, TimeSource,
+};
 
 const BOOT_ROM_SIZE: usize = 0x100;
+const CGB_BOOT_ROM_SIZE: usize = 0x900;
+/// CGB dumps without the header gap; their second part starts at 0x0200 all the same.
+const CGB_BOOT_ROM_GAPLESS_SIZE: usize = 0x800;
+const CGB_BOOT_HEADER_START: usize = 0x100;
+const CGB_BOOT_HEADER_END: usize = 0x200;
 const VRAM_SIZE: usize = 0x2000;
 const WRAM_SIZE: usize = 0x2000;
 const OAM_SIZE: usize = 0xA0;
@@ -12,6 +25,7 @@ const CYCLES_PER_LINE: u16 = 456;
 const VBLANK_START: u8 = 144;
 const TOTAL_LINES: u8 = 154;
 const DMA_CYCLES: u32 = 160;
+const POST_BOOT_DIV_COUNTER: u16 = 0xABCC;
 
 const REG_JOYP: u16 = 0xFF00;
 const REG_LCDC: u16 = 0xFF40;
@@ -20,6 +34,8 @@ const REG_TIMA: u16 = 0xFF05;
 const REG_TMA: u16 = 0xFF06;
 const REG_TAC: u16 = 0xFF07;
 const REG_IF: u16 = 0xFF0F;
+const REG_SB: u16 = 0xFF01;
+const REG_SC: u16 = 0xFF02;
 const REG_NR10: u16 = 0xFF10;
 const REG_NR11: u16 = 0xFF11;
 const REG_NR12: u16 = 0xFF12;
@@ -64,6 +80,13 @@ const REG_OBPD: u16 = 0xFF6B;
 const IF_VBLANK: u8 = 0x01;
 const IF_STAT: u8 = 0x02;
 const IF_TIMER: u8 = 0x04;
+const IF_SERIAL: u8 = 0x08;
+const IF_JOYPAD: u8 = 0x10;
+const SC_TRANSFER_INTERNAL: u8 = 0x81;
+const SC_FAST_CLOCK: u8 = 0x02;
+/// Eight bits at 8192 Hz, or at 262144 Hz with the CGB fast clock.
+const SERIAL_TRANSFER_CYCLES: u32 = 8 * 512;
+const SERIAL_FAST_TRANSFER_CYCLES: u32 = 8 * 16;
 
 const HDMA_BLOCK_SIZE: usize = 0x10;
 
@@ -74,6 +97,57 @@ enum HdmaMode {
     General,
 }
 
+/// A slice of the address map that can be snapshotted, e.g. to search for cheat addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// Video RAM in the current bank.
+    Vram,
+    /// Cartridge RAM in the current bank, as the CPU sees it.
+    CartRam,
+    Wram,
+    Oam,
+    Hram,
+}
+
+impl MemoryRegion {
+    pub fn start(self) -> u16 {
+        match self {
+            Self::Vram => 0x8000,
+            Self::CartRam => 0xA000,
+            Self::Wram => 0xC000,
+            Self::Oam => 0xFE00,
+            Self::Hram => 0xFF80,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Self::Vram => VRAM_SIZE,
+            Self::CartRam => 0x2000,
+            Self::Wram => WRAM_SIZE,
+            Self::Oam => OAM_SIZE,
+            Self::Hram => HRAM_SIZE,
+        }
+    }
+}
+
+/// Addresses whose byte differs between two snapshots of `region`, with the old and new
+/// values. Bytes past the shorter snapshot are ignored.
+pub fn diff_snapshots(region: MemoryRegion, prev: &[u8], now: &[u8]) -> Vec<(u16, u8, u8)> {
+    prev.iter()
+        .zip(now)
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(offset, (&old, &new))| (region.start().wrapping_add(offset as u16), old, new))
+        .collect()
+}
+
+/// A component clocked in lockstep with the bus, such as the frame renderer. It sees the
+/// bus after the timers, APU and LCD state machine have advanced for the same cycles.
+pub trait Clocked {
+    fn tick(&mut self, cycles: u32, bus: &Bus);
+}
+
 #[derive(Debug)]
 pub struct Bus {
     cartridge: Cartridge,
@@ -85,6 +159,9 @@ pub struct Bus {
     vram: [Vec<u8>; 2],
     wram: Vec<u8>,
     oam: Vec<u8>,
+    /// OAM as each visible line's mode 2 scan saw it, once that line has been scanned.
+    line_oam: Vec<u8>,
+    line_oam_latched: Vec<bool>,
     io: Vec<u8>,
     hram: Vec<u8>,
     div: u8,
@@ -101,10 +178,19 @@ pub struct Bus {
     joyp_select: u8,
     joyp_buttons: u8,
     joyp_dpad: u8,
+    sgb: Option<SgbPacketDecoder>,
+    serial_output: Vec<u8>,
+    serial_active: bool,
+    serial_cycles_remaining: u32,
     dma: u8,
     dma_active: bool,
     dma_cycles_remaining: u32,
     dma_base: u16,
+    /// Whether an OAM DMA locks the CPU out of everything below the IO registers.
+    dma_bus_conflicts: bool,
+    /// Whether the prohibited area reads back the last byte on the data bus.
+    open_bus_last_byte: bool,
+    last_read: Cell<u8>,
     double_speed: bool,
     speed_switch_pending: bool,
     cgb_mode: bool,
@@ -134,8 +220,52 @@ impl Bus {
         boot_rom: Option<Vec<u8>>,
     ) -> Result<Self, MbcError> {
         let mbc = Mbc::new(&cartridge)?;
+        Ok(Self::from_parts(cartridge, mbc, boot_rom, Apu::new()))
+    }
+
+    /// Like `with_boot_rom`, but banked by `mapper` whatever the header says.
+    pub fn with_mapper(
+        cartridge: Cartridge,
+        mapper: Box<dyn Mapper>,
+        boot_rom: Option<Vec<u8>>,
+    ) -> Self {
+        Self::from_parts(cartridge, Mbc::with_mapper(mapper), boot_rom, Apu::new())
+    }
+
+    /// Returns the console to its power-on state, keeping the cartridge (and its RAM),
+    /// the mapper's clock, the boot ROM and the APU output configuration.
+    pub fn soft_reset(self) -> Self {
+        let Self {
+            cartridge,
+            mut mbc,
+            boot_rom,
+            mut apu,
+            ..
+        } = self;
+        mbc.reset();
+        apu.reset();
+        Self::from_parts(cartridge, mbc, boot_rom, apu)
+    }
+
+    /// Like `soft_reset`, but also wipes cartridge RAM and the mapper's clock.
+    pub fn power_cycle(self) -> Self {
+        let Self {
+            mut cartridge,
+            mut mbc,
+            boot_rom,
+            mut apu,
+            ..
+        } = self;
+        cartridge.wipe_ram();
+        mbc.power_cycle();
+        apu.reset();
+        Self::from_parts(cartridge, mbc, boot_rom, apu)
+    }
+
+    fn from_parts(cartridge: Cartridge, mbc: Mbc, boot_rom: Option<Vec<u8>>, mut apu: Apu) -> Self {
         let boot_rom_enabled = boot_rom.is_some();
-        let is_cgb = cartridge.is_cgb();
+        let is_cgb = cartridge.runs_as_cgb();
+        let sgb = (cartridge.header.sgb_flag == SgbFlag::Supported).then(SgbPacketDecoder::new);
 
         let mut io = vec![0; IO_SIZE];
         let mut stat = 0;
@@ -183,12 +313,11 @@ impl Bus {
             stat = 0x85;
         }
 
-        let mut apu = Apu::new();
         if !boot_rom_enabled {
             apu.apply_post_boot_state();
         }
 
-        Ok(Self {
+        Self {
             cartridge,
             mbc,
             boot_rom,
@@ -198,6 +327,8 @@ impl Bus {
             vram: [vec![0; VRAM_SIZE], vec![0; VRAM_SIZE]],
             wram: vec![0; WRAM_SIZE],
             oam: vec![0; OAM_SIZE],
+            line_oam: vec![0; OAM_SIZE * VBLANK_START as usize],
+            line_oam_latched: vec![false; VBLANK_START as usize],
             io,
             hram: vec![0; HRAM_SIZE],
             div: 0,
@@ -211,13 +342,21 @@ impl Bus {
             stat,
             ppu_line_cycles: 0,
             ppu_mode: 0,
-            joyp_select: 0x30,
+            // Both groups deselected at power-on; the boot ROM leaves both selected.
+            joyp_select: if boot_rom_enabled { 0x30 } else { 0x00 },
             joyp_buttons: 0x0F,
             joyp_dpad: 0x0F,
+            sgb,
+            serial_output: Vec::new(),
             dma,
+            serial_active: false,
+            serial_cycles_remaining: 0,
             dma_active: false,
             dma_cycles_remaining: 0,
             dma_base: 0,
+            dma_bus_conflicts: false,
+            open_bus_last_byte: false,
+            last_read: Cell::new(OPEN_BUS),
             double_speed: false,
             speed_switch_pending: false,
             cgb_mode: is_cgb,
@@ -235,13 +374,29 @@ impl Bus {
             hdma_blocks_remaining: 0,
             hdma_active: false,
             hdma_mode: HdmaMode::Inactive,
-        })
+        }
     }
 
     pub fn cartridge(&self) -> &Cartridge {
         &self.cartridge
     }
 
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    pub fn current_rom_bank(&self) -> usize {
+        self.mbc.current_rom_bank(&self.cartridge)
+    }
+
+    pub fn current_ram_bank(&self) -> Option<RamBankSelection> {
+        self.mbc.current_ram_bank(&self.cartridge)
+    }
+
+    pub fn ram_bank_count(&self) -> usize {
+        self.mbc.ram_bank_count(&self.cartridge)
+    }
+
     pub fn boot_rom_enabled(&self) -> bool {
         self.boot_rom_enabled
     }
@@ -254,6 +409,53 @@ impl Bus {
         was_disabled
     }
 
+    /// Sets what the cartridge bus floats to when external RAM is disabled or missing.
+    pub fn set_open_bus(&mut self, value: u8) {
+        self.mbc.set_open_bus(value);
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
+    /// While an OAM DMA is copying, the cartridge, VRAM, WRAM and OAM buses are busy and
+    /// the CPU reads them as 0xFF; only IO, HRAM and IE stay reachable. Off by default.
+    pub fn set_dma_bus_conflicts(&mut self, enabled: bool) {
+        self.dma_bus_conflicts = enabled;
+    }
+
+    /// Makes 0xFEA0-0xFEFF return the last byte the CPU read instead of 0xFF, as some CPU
+    /// test ROMs expect of a floating bus.
+    pub fn set_open_bus_last_byte(&mut self, enabled: bool) {
+        self.open_bus_last_byte = enabled;
+    }
+
+    /// True from the write to DMA until the last byte lands in OAM.
+    pub fn dma_active(&self) -> bool {
+        self.dma_active
+    }
+
+    /// Reads every byte of `region` through the bus, as the CPU would see it.
+    pub fn snapshot(&self, region: MemoryRegion) -> Vec<u8> {
+        (0..region.size())
+            .map(|offset| self.read8(region.start().wrapping_add(offset as u16)))
+            .collect()
+    }
+
+    /// OAM as latched by the scan at the start of `line`, so writes later in the line
+    /// don't move its sprites. Falls back to live OAM before the line has been scanned.
+    pub fn line_oam(&self, line: usize) -> &[u8] {
+        match self.line_oam_latched.get(line) {
+            Some(true) => &self.line_oam[line * OAM_SIZE..(line + 1) * OAM_SIZE],
+            _ => &self.oam,
+        }
+    }
+
+    fn latch_line_oam(&mut self, line: usize) {
+        self.line_oam[line * OAM_SIZE..(line + 1) * OAM_SIZE].copy_from_slice(&self.oam);
+        self.line_oam_latched[line] = true;
+    }
+
     pub fn vram(&self) -> &[u8] {
         &self.vram[self.vram_bank as usize]
     }
@@ -279,11 +481,30 @@ impl Bus {
     }
 
     pub fn set_joyp_buttons(&mut self, mask: u8) {
+        let previous = self.read_joyp();
         self.joyp_buttons = mask & 0x0F;
+        self.raise_joypad_on_press(previous);
+    }
+
+    /// Bytes sent over the serial port since the last call.
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.serial_output)
+    }
+
+    /// Next SGB command packet the game sent through P1, for SGB-flagged cartridges.
+    pub fn take_sgb_command(&mut self) -> Option<SgbCommand> {
+        self.sgb.as_mut().and_then(SgbPacketDecoder::take_command)
+    }
+
+    /// Active-low button and d-pad lines, as last set by the frontend.
+    pub fn joyp_masks(&self) -> (u8, u8) {
+        (self.joyp_buttons, self.joyp_dpad)
     }
 
     pub fn set_joyp_dpad(&mut self, mask: u8) {
+        let previous = self.read_joyp();
         self.joyp_dpad = mask & 0x0F;
+        self.raise_joypad_on_press(previous);
     }
 
     pub fn speed_switch_pending(&self) -> bool {
@@ -313,13 +534,57 @@ impl Bus {
         self.boot_rom_enabled = false;
     }
 
-    pub fn read8(&self, addr: u16) -> u8 {
-        if self.boot_rom_enabled
-            && let Some(boot_rom) = &self.boot_rom
-            && (addr as usize) < BOOT_ROM_SIZE
-            && boot_rom.len() >= BOOT_ROM_SIZE
+    /// The boot ROM byte overlaying `addr`, if any. A DMG boot ROM covers 0x0000-0x00FF;
+    /// anything longer is taken as a CGB boot ROM, which also covers 0x0200-0x08FF and
+    /// leaves the cartridge header visible in between. Gapless 0x800-byte dumps hold
+    /// that second part from offset 0x100.
+    fn boot_rom_byte(&self, addr: u16) -> Option<u8> {
+        if !self.boot_rom_enabled {
+            return None;
+        }
+        let boot_rom = self.boot_rom.as_ref()?;
+        let addr = addr as usize;
+        if boot_rom.len() == BOOT_ROM_SIZE {
+            return (addr < BOOT_ROM_SIZE).then(|| boot_rom[addr]);
+        }
+        if boot_rom.len() < BOOT_ROM_SIZE
+            || addr >= CGB_BOOT_ROM_SIZE
+            || (CGB_BOOT_HEADER_START..CGB_BOOT_HEADER_END).contains(&addr)
         {
-            return boot_rom[addr as usize];
+            return None;
+        }
+        let offset = if boot_rom.len() == CGB_BOOT_ROM_GAPLESS_SIZE && addr >= CGB_BOOT_HEADER_END {
+            addr - (CGB_BOOT_HEADER_END - CGB_BOOT_HEADER_START)
+        } else {
+            addr
+        };
+        boot_rom.get(offset).copied()
+    }
+
+    pub fn read8(&self, addr: u16) -> u8 {
+        if self.dma_active && self.dma_bus_conflicts && addr < 0xFF00 {
+            return OPEN_BUS;
+        }
+        if self.open_bus_last_byte && (0xFEA0..=0xFEFF).contains(&addr) {
+            return self.last_read.get();
+        }
+        self.read8_unblocked(addr)
+    }
+
+    /// A read by the CPU, the only reader that puts its byte on the bus for open-bus
+    /// reads to return. The PPU, debug views and snapshots use `read8`.
+    pub fn cpu_read8(&self, addr: u16) -> u8 {
+        let value = self.read8(addr);
+        if self.open_bus_last_byte && !(0xFEA0..=0xFEFF).contains(&addr) {
+            self.last_read.set(value);
+        }
+        value
+    }
+
+    /// Reads without the OAM DMA lockout, for transfers that own the bus themselves.
+    fn read8_unblocked(&self, addr: u16) -> u8 {
+        if let Some(value) = self.boot_rom_byte(addr) {
+            return value;
         }
 
         match addr {
@@ -363,6 +628,7 @@ impl Bus {
     pub fn step(&mut self, cycles: u32) {
         self.step_div(cycles);
         self.step_timer(cycles);
+        self.step_serial(cycles);
         let _ = self.apu.step(cycles);
         self.step_ppu(cycles);
         self.step_hdma();
@@ -370,10 +636,77 @@ impl Bus {
         self.mbc.tick(cycles);
     }
 
+    /// Steps the bus and then `device`, so one call advances every clocked component.
+    pub fn step_with(&mut self, cycles: u32, device: &mut impl Clocked) {
+        self.step(cycles);
+        device.tick(cycles, self);
+    }
+
     pub fn set_rtc_mode(&mut self, mode: RtcMode) {
         self.mbc.set_rtc_mode(mode);
     }
 
+    pub fn set_rtc_source(&mut self, source: RtcSource) {
+        self.mbc.set_rtc_source(source);
+    }
+
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.mbc.set_time_source(clock);
+    }
+
+    pub fn take_mbc_fault(&mut self) -> Option<MbcError> {
+        self.mbc.take_fault()
+    }
+
+    pub fn rtc_snapshot(&self) -> Option<RtcSnapshot> {
+        self.mbc.rtc_snapshot()
+    }
+
+    pub fn rtc_latched_registers(&self) -> Option<[u8; 5]> {
+        self.mbc.rtc_latched_registers()
+    }
+
+    pub fn restore_rtc(&mut self, snapshot: RtcSnapshot, elapsed: Duration) {
+        self.mbc.restore_rtc(snapshot, elapsed);
+    }
+
+    pub fn restore_rtc_latch(&mut self, registers: [u8; 5]) {
+        self.mbc.restore_rtc_latch(registers);
+    }
+
+    pub fn set_camera_image(&mut self, image: &[u8]) -> Result<(), MbcError> {
+        self.mbc.set_camera_image(image)
+    }
+
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_ram(data);
+    }
+
+    /// Overwrites the start of work RAM (0xC000-0xDFFF) with `data`; the rest is kept.
+    pub fn load_wram(&mut self, data: &[u8]) {
+        let len = self.wram.len().min(data.len());
+        self.wram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Overwrites the start of high RAM (0xFF80-0xFFFE) with `data`; the rest is kept.
+    pub fn load_hram(&mut self, data: &[u8]) {
+        let len = self.hram.len().min(data.len());
+        self.hram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Overwrites the start of the current VRAM bank with `data`; the rest is kept.
+    pub fn load_vram(&mut self, data: &[u8]) {
+        let bank = &mut self.vram[self.vram_bank as usize];
+        let len = bank.len().min(data.len());
+        bank[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Overwrites the start of OAM with `data`; the rest is kept.
+    pub fn load_oam(&mut self, data: &[u8]) {
+        let len = self.oam.len().min(data.len());
+        self.oam[..len].copy_from_slice(&data[..len]);
+    }
+
     pub fn apu_step(&mut self, cycles: u32) {
         let _ = self.apu.step(cycles);
     }
@@ -386,6 +719,22 @@ impl Bus {
         self.apu.set_sample_rate_hz(sample_rate_hz);
     }
 
+    pub fn apu_set_clip_mode(&mut self, mode: ClipMode) {
+        self.apu.set_clip_mode(mode);
+    }
+
+    pub fn apu_set_max_queue(&mut self, max_queue: usize) {
+        self.apu.set_max_queue(max_queue);
+    }
+
+    pub fn apu_set_sample_depth(&mut self, depth: SampleDepth) {
+        self.apu.set_sample_depth(depth);
+    }
+
+    pub fn apu_set_channel_gain(&mut self, channel: ChannelId, gain: f32) {
+        self.apu.set_channel_gain(channel, gain);
+    }
+
     pub fn apu_has_sample(&self) -> bool {
         self.apu.has_sample()
     }
@@ -434,6 +783,15 @@ impl Bus {
         self.apu.reset();
     }
 
+    /// Post-boot state plus the timer phase the DMG boot ROM leaves behind, so DIV ticks
+    /// over at the same point it would on hardware instead of a full 256 cycles later.
+    pub fn apply_post_boot_timing(&mut self) {
+        self.apply_post_boot_state();
+        self.div_counter = POST_BOOT_DIV_COUNTER;
+        self.div = (POST_BOOT_DIV_COUNTER >> 8) as u8;
+        self.sync_timer_phase();
+    }
+
     pub fn apply_post_boot_state(&mut self) {
         self.boot_rom_enabled = false;
         self.div = 0xAB;
@@ -449,6 +807,8 @@ impl Bus {
         self.ppu_line_cycles = 0;
         self.ppu_mode = 0;
         self.stat = 0x80;
+        // The boot ROM leaves both groups selected, so P1 reads 0xCF with nothing held.
+        self.joyp_select = 0x00;
 
         self.set_io_reg(REG_NR10, 0x80);
         self.set_io_reg(REG_NR11, 0xBF);
@@ -540,14 +900,40 @@ impl Bus {
 
     fn write_io(&mut self, addr: u16, value: u8) {
         match addr {
-            REG_JOYP => self.joyp_select = value & 0x30,
+            REG_JOYP => {
+                let previous = self.read_joyp();
+                self.joyp_select = value & 0x30;
+                self.raise_joypad_on_press(previous);
+                if let Some(sgb) = self.sgb.as_mut() {
+                    sgb.write_p1(value);
+                }
+            }
             REG_DIV => {
                 self.div = 0;
                 self.div_counter = 0;
             }
+            REG_SC => {
+                self.set_io_reg(REG_SC, value);
+                // Only an internally clocked transfer progresses; nothing drives an
+                // external clock.
+                self.serial_active = value & SC_TRANSFER_INTERNAL == SC_TRANSFER_INTERNAL;
+                if self.serial_active {
+                    // Kept as the transfer starts, so output from code that doesn't wait
+                    // for each byte still comes through whole.
+                    self.serial_output.push(self.io[(REG_SB - 0xFF00) as usize]);
+                }
+                self.serial_cycles_remaining = if self.cgb_mode && value & SC_FAST_CLOCK != 0 {
+                    SERIAL_FAST_TRANSFER_CYCLES
+                } else {
+                    SERIAL_TRANSFER_CYCLES
+                };
+            }
             REG_TIMA => self.tima = value,
             REG_TMA => self.tma = value,
-            REG_TAC => self.tac = value,
+            REG_TAC => {
+                self.tac = value;
+                self.sync_timer_phase();
+            }
             REG_IF => self.interrupt_flag = value,
             REG_STAT => self.stat = (self.stat & 0x07) | (value & 0xF8),
             REG_LY => {
@@ -644,25 +1030,54 @@ impl Bus {
         }
     }
 
+    fn step_serial(&mut self, cycles: u32) {
+        if !self.serial_active {
+            return;
+        }
+        self.serial_cycles_remaining = self.serial_cycles_remaining.saturating_sub(cycles);
+        if self.serial_cycles_remaining == 0 {
+            self.complete_serial_transfer();
+        }
+    }
+
+    /// Finishes an internally clocked transfer once all eight bits have shifted. Nothing
+    /// is connected, so the byte shifted in is 0xFF.
+    fn complete_serial_transfer(&mut self) {
+        self.io[(REG_SB - 0xFF00) as usize] = 0xFF;
+        let sc = self.io[(REG_SC - 0xFF00) as usize];
+        self.set_io_reg(REG_SC, sc & !0x80);
+        self.serial_active = false;
+        self.interrupt_flag |= IF_SERIAL;
+    }
+
     fn step_div(&mut self, cycles: u32) {
         let new = self.div_counter.wrapping_add(cycles as u16);
         self.div_counter = new;
         self.div = (new >> 8) as u8;
     }
 
+    /// CPU cycles per TIMA increment at the frequency TAC selects.
+    fn timer_period(&self) -> u32 {
+        match self.tac & 0x03 {
+            0x00 => 1024,
+            0x01 => 16,
+            0x02 => 64,
+            _ => 256,
+        }
+    }
+
+    /// Lines the timer up with DIV, which drives it on hardware, so a new TAC period
+    /// starts part way through rather than with a backlog of increments.
+    fn sync_timer_phase(&mut self) {
+        self.tima_counter = u32::from(self.div_counter) & (self.timer_period() - 1);
+    }
+
     fn step_timer(&mut self, cycles: u32) {
         if self.tac & 0x04 == 0 {
             return;
         }
 
-        let period = match self.tac & 0x03 {
-            0x00 => 1024,
-            0x01 => 16,
-            0x02 => 64,
-            0x03 => 256,
-            _ => 1024,
-        };
-
+        let period = self.timer_period();
         self.tima_counter += cycles;
         while self.tima_counter >= period {
             self.tima_counter -= period;
@@ -688,7 +1103,7 @@ impl Bus {
         if self.dma_cycles_remaining == 0 {
             let base = self.dma_base;
             for i in 0..OAM_SIZE {
-                let byte = self.read8(base.wrapping_add(i as u16));
+                let byte = self.read8_unblocked(base.wrapping_add(i as u16));
                 self.oam[i] = byte;
             }
             self.dma_active = false;
@@ -701,7 +1116,7 @@ impl Bus {
 
         for _ in 0..self.hdma_blocks_remaining {
             for i in 0..HDMA_BLOCK_SIZE {
-                let byte = self.read8(source.wrapping_add(i as u16));
+                let byte = self.read8_unblocked(source.wrapping_add(i as u16));
                 let vram_idx = (dest as usize) % VRAM_SIZE;
                 self.vram[self.vram_bank as usize][vram_idx] = byte;
                 dest = dest.wrapping_add(1);
@@ -752,7 +1167,7 @@ impl Bus {
         let mut dest = self.hdma_dest & 0x1FF0;
 
         for i in 0..HDMA_BLOCK_SIZE {
-            let byte = self.read8(source.wrapping_add(i as u16));
+            let byte = self.read8_unblocked(source.wrapping_add(i as u16));
             self.vram[self.vram_bank as usize][dest as usize] = byte;
             dest = dest.wrapping_add(1);
         }
@@ -767,9 +1182,14 @@ impl Bus {
             self.ly = 0;
             self.ppu_line_cycles = 0;
             self.ppu_mode = 0;
+            self.line_oam_latched.fill(false);
             self.update_stat();
             return;
         }
+        // The first line after the LCD turns on scans OAM straight away.
+        if self.ly == 0 && !self.line_oam_latched[0] {
+            self.latch_line_oam(0);
+        }
 
         let mut remaining = cycles;
         while remaining > 0 {
@@ -785,6 +1205,9 @@ impl Bus {
                 if self.ly >= TOTAL_LINES {
                     self.ly = 0;
                 }
+                if self.ly < VBLANK_START {
+                    self.latch_line_oam(self.ly as usize);
+                }
                 self.update_stat();
             }
         }
@@ -823,6 +1246,13 @@ impl Bus {
         self.stat = stat;
     }
 
+    /// Requests the joypad interrupt when a selected input line falls from high to low.
+    fn raise_joypad_on_press(&mut self, previous: u8) {
+        if previous & !self.read_joyp() & 0x0F != 0 {
+            self.interrupt_flag |= IF_JOYPAD;
+        }
+    }
+
     fn read_joyp(&self) -> u8 {
         let mut value = 0x0F;
         if self.joyp_select & 0x10 == 0 {
@@ -908,14 +1338,32 @@ impl Bus {
 
 #[cfg(test)]
 mod tests {
+    use super::Clocked;
     use super::{
-        BOOT_ROM_SIZE, Bus, DMA_CYCLES, IF_TIMER, REG_BGP, REG_BGPD, REG_BGPI, REG_DIV, REG_DMA,
-        REG_HDMA1, REG_HDMA2, REG_HDMA3, REG_HDMA4, REG_HDMA5, REG_IF, REG_JOYP, REG_KEY0,
-        REG_KEY1, REG_LCDC, REG_LY, REG_LYC, REG_OBP0, REG_OBP1, REG_OBPD, REG_OBPI, REG_SCX,
-        REG_SCY, REG_STAT, REG_TAC, REG_TIMA, REG_TMA, REG_VBK, REG_WX, REG_WY,
+        BOOT_ROM_SIZE, Bus, CGB_BOOT_HEADER_END, CGB_BOOT_HEADER_START, CGB_BOOT_ROM_GAPLESS_SIZE,
+        CGB_BOOT_ROM_SIZE, DMA_CYCLES, IF_JOYPAD, IF_TIMER, REG_BGP, REG_BGPD, REG_BGPI, REG_DIV,
+        REG_DMA, REG_HDMA1, REG_HDMA2, REG_HDMA3, REG_HDMA4, REG_HDMA5, REG_IF, REG_JOYP, REG_KEY0,
+        REG_KEY1, REG_LCDC, REG_LY, REG_LYC, REG_OBP0, REG_OBP1, REG_OBPD, REG_OBPI, REG_SB,
+        REG_SC, REG_SCX, REG_SCY, REG_STAT, REG_TAC, REG_TIMA, REG_TMA, REG_VBK, REG_WX, REG_WY,
+        SERIAL_TRANSFER_CYCLES,
     };
-    use crate::domain::Cartridge;
     use crate::domain::cartridge::ROM_BANK_SIZE;
+    use crate::domain::{Cartridge, SaveError};
+
+    #[derive(Default)]
+    struct CycleCounter {
+        cycles: u32,
+        last_div: u8,
+        last_tima: u8,
+    }
+
+    impl Clocked for CycleCounter {
+        fn tick(&mut self, cycles: u32, bus: &Bus) {
+            self.cycles += cycles;
+            self.last_div = bus.read8(REG_DIV);
+            self.last_tima = bus.read8(REG_TIMA);
+        }
+    }
 
     #[test]
     fn bus_reads_from_selected_rom_bank() {
@@ -932,6 +1380,25 @@ mod tests {
         assert_eq!(bus.read8(0x4000), 0x20);
     }
 
+    #[test]
+    fn bus_reads_open_bus_past_end_of_odd_sized_rom() {
+        let mut bytes = vec![0x10; ROM_BANK_SIZE + 100];
+        bytes[ROM_BANK_SIZE..].fill(0x20);
+        bytes[0x0147] = 0x01; // MBC1
+
+        let cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+
+        assert_eq!(bus.read8(0x4000), 0x20);
+        assert_eq!(bus.read8(0x4000 + 99), 0x20);
+        assert_eq!(bus.read8(0x4000 + 100), 0xFF);
+        assert_eq!(bus.read8(0x7FFF), 0xFF);
+
+        bus.write8(0x2000, 0x03);
+        assert_eq!(bus.read8(0x4000), 0x20);
+        assert_eq!(bus.read8(0x7FFF), 0xFF);
+    }
+
     #[test]
     fn boot_rom_overlays_and_can_be_disabled() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -951,6 +1418,48 @@ mod tests {
         assert_eq!(bus.read8(0x0000), 0x11);
     }
 
+    #[test]
+    fn cgb_boot_rom_leaves_header_visible() {
+        let mut rom = vec![0x11; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+
+        let mut boot_rom = vec![0xAA; CGB_BOOT_ROM_SIZE];
+        boot_rom[CGB_BOOT_HEADER_START..CGB_BOOT_HEADER_END].fill(0xBB);
+        let mut bus = Bus::with_boot_rom(cartridge, Some(boot_rom)).expect("bus");
+
+        assert_eq!(bus.read8(0x0000), 0xAA);
+        assert_eq!(bus.read8(0x00FF), 0xAA);
+        assert_eq!(bus.read8(0x0100), 0x11);
+        assert_eq!(bus.read8(0x0147), 0x00);
+        assert_eq!(bus.read8(0x01FF), 0x11);
+        assert_eq!(bus.read8(0x0200), 0xAA);
+        assert_eq!(bus.read8(0x08FF), 0xAA);
+        assert_eq!(bus.read8(0x0900), 0x11);
+
+        bus.write8(0xFF50, 0x01);
+        assert_eq!(bus.read8(0x0200), 0x11);
+    }
+
+    #[test]
+    fn gapless_cgb_boot_rom_maps_its_second_part_from_0x200() {
+        let mut rom = vec![0x11; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+
+        let mut boot_rom = vec![0xAA; CGB_BOOT_ROM_GAPLESS_SIZE];
+        boot_rom[0x0100] = 0xCC;
+        boot_rom[CGB_BOOT_ROM_GAPLESS_SIZE - 1] = 0xDD;
+        let bus = Bus::with_boot_rom(cartridge, Some(boot_rom)).expect("bus");
+
+        assert_eq!(bus.read8(0x00FF), 0xAA);
+        assert_eq!(bus.read8(0x0100), 0x11);
+        assert_eq!(bus.read8(0x0147), 0x00);
+        assert_eq!(bus.read8(0x0200), 0xCC);
+        assert_eq!(bus.read8(0x08FF), 0xDD);
+        assert_eq!(bus.read8(0x0900), 0x11);
+    }
+
     #[test]
     fn take_boot_rom_disabled_signals_transition() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1057,6 +1566,34 @@ mod tests {
         assert_eq!(bus.read8(0xFFFF), 0xBC);
     }
 
+    #[test]
+    fn bus_joyp_press_edge_requests_interrupt() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.write8(REG_JOYP, 0x30);
+        bus.write8(REG_IF, 0x00);
+
+        bus.set_joyp_buttons(0x0E);
+        assert_eq!(bus.read8(REG_IF) & IF_JOYPAD, 0, "unselected group");
+
+        bus.write8(REG_JOYP, 0x10);
+        assert_eq!(
+            bus.read8(REG_IF) & IF_JOYPAD,
+            IF_JOYPAD,
+            "selecting a held group"
+        );
+
+        bus.write8(REG_IF, 0x00);
+        bus.set_joyp_buttons(0x0E);
+        bus.set_joyp_buttons(0x0F);
+        assert_eq!(bus.read8(REG_IF) & IF_JOYPAD, 0, "hold and release");
+
+        bus.set_joyp_buttons(0x07);
+        assert_eq!(bus.read8(REG_IF) & IF_JOYPAD, IF_JOYPAD, "new press");
+    }
+
     #[test]
     fn bus_joyp_selects_groups() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1075,6 +1612,89 @@ mod tests {
         assert_eq!(bus.read8(REG_JOYP), 0xDE);
     }
 
+    #[test]
+    fn bus_joyp_read_masks_for_each_selection() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        assert_eq!(bus.read8(REG_JOYP), 0xCF, "post-boot value");
+
+        bus.set_joyp_buttons(0x0A);
+        bus.set_joyp_dpad(0x06);
+        // Writes only reach the select bits; bits 6-7 and the line nibble read back fixed.
+        for (select, expected) in [(0x30, 0xFF), (0x20, 0xE6), (0x10, 0xDA), (0x00, 0xC2)] {
+            bus.write8(REG_JOYP, select | 0xCF);
+            assert_eq!(bus.read8(REG_JOYP), expected, "select {select:#04x}");
+            bus.write8(REG_JOYP, select);
+            assert_eq!(bus.read8(REG_JOYP), expected, "select {select:#04x}");
+        }
+    }
+
+    #[test]
+    fn bus_captures_sgb_packets_for_sgb_cartridges() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0146] = 0x03;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+
+        bus.write8(REG_JOYP, 0x00);
+        bus.write8(REG_JOYP, 0x30);
+        for bit in 0..129 {
+            // MLT_REQ, one packet: 0x89 sent LSB first, then zero bits and the stop bit.
+            let one = bit < 8 && 0x89u8 & (1 << bit) != 0;
+            bus.write8(REG_JOYP, if one { 0x10 } else { 0x20 });
+            bus.write8(REG_JOYP, 0x30);
+        }
+
+        let command = bus.take_sgb_command().expect("command");
+        assert_eq!(command.id, 0x11);
+        assert_eq!(bus.read8(REG_JOYP), 0xFF);
+    }
+
+    #[test]
+    fn bus_serial_transfer_captures_byte_and_requests_interrupt() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.write8(REG_IF, 0x00);
+
+        bus.write8(REG_SB, b'O');
+        bus.write8(REG_SC, 0x81);
+        bus.step(SERIAL_TRANSFER_CYCLES);
+        bus.write8(REG_SB, b'K');
+        bus.write8(REG_SC, 0x80);
+        bus.step(SERIAL_TRANSFER_CYCLES);
+
+        assert_eq!(bus.take_serial_output(), b"O".to_vec());
+        assert_eq!(bus.read8(REG_SC) & 0x80, 0x80);
+        assert_eq!(bus.read8(REG_IF) & 0x08, 0x08);
+        assert!(bus.take_serial_output().is_empty());
+    }
+
+    #[test]
+    fn bus_serial_transfer_completes_after_eight_bit_times() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.write8(REG_IF, 0x00);
+
+        bus.write8(REG_SB, b'A');
+        bus.write8(REG_SC, 0x81);
+        bus.step(SERIAL_TRANSFER_CYCLES - 4);
+        assert_eq!(bus.read8(REG_SC) & 0x80, 0x80);
+        assert_eq!(bus.read8(REG_IF) & 0x08, 0x00);
+        assert_eq!(bus.read8(REG_SB), b'A');
+
+        bus.step(4);
+        assert_eq!(bus.read8(REG_SC) & 0x80, 0x00);
+        assert_eq!(bus.read8(REG_IF) & 0x08, 0x08);
+        assert_eq!(bus.read8(REG_SB), 0xFF);
+        assert_eq!(bus.take_serial_output(), b"A".to_vec());
+    }
+
     #[test]
     fn bus_dma_copies_to_oam() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1093,6 +1713,48 @@ mod tests {
         assert_eq!(bus.read8(0xFE9F), 0xA0);
     }
 
+    #[test]
+    fn bus_dma_conflicts_leave_only_hram_readable() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.set_dma_bus_conflicts(true);
+
+        bus.write8(0xC000, 0x42);
+        bus.write8(0xFF80, 0x24);
+        bus.write8(REG_DMA, 0xC0);
+        assert!(bus.dma_active());
+        assert_eq!(bus.read8(0xC000), 0xFF);
+        assert_eq!(bus.read8(0xFF80), 0x24);
+
+        bus.step(DMA_CYCLES);
+        assert!(!bus.dma_active());
+        assert_eq!(bus.read8(0xC000), 0x42);
+        assert_eq!(bus.read8(0xFE00), 0x42);
+    }
+
+    #[test]
+    fn bus_open_bus_can_return_last_byte_read() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        rom[0x0150] = 0x3C;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+
+        assert_eq!(bus.read8(0x0150), 0x3C);
+        assert_eq!(bus.read8(0xFEA0), 0xFF);
+
+        bus.set_open_bus_last_byte(true);
+        assert_eq!(bus.cpu_read8(0x0150), 0x3C);
+        assert_eq!(bus.read8(0xFEA0), 0x3C);
+        assert_eq!(bus.cpu_read8(0xFEFF), 0x3C);
+
+        // Reads from outside the CPU don't move the bus.
+        assert_eq!(bus.read8(0xFF44), 0x00);
+        assert_eq!(bus.read8(0xFEA0), 0x3C);
+    }
+
     #[test]
     fn bus_updates_ly_and_stat_mode() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1181,6 +1843,26 @@ mod tests {
         assert_eq!(bus.read8(REG_IF) & IF_TIMER, IF_TIMER);
     }
 
+    #[test]
+    fn bus_step_with_advances_timer_and_device_together() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        let mut counter = CycleCounter::default();
+
+        bus.write8(REG_TAC, 0x05);
+        for _ in 0..4 {
+            bus.step_with(64, &mut counter);
+        }
+
+        assert_eq!(counter.cycles, 256);
+        assert_eq!(bus.read8(REG_DIV), 1);
+        assert_eq!(bus.read8(REG_TIMA), 16);
+        assert_eq!(counter.last_div, 1);
+        assert_eq!(counter.last_tima, 16);
+    }
+
     #[test]
     fn bus_cgb_mode_from_cartridge() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1217,6 +1899,97 @@ mod tests {
         assert_eq!(bus.read8(REG_KEY0), 0xFF);
     }
 
+    #[test]
+    fn bus_post_boot_timing_matches_boot_rom_handover() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom.clone()).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.apply_post_boot_timing();
+
+        assert_eq!(bus.read8(REG_DIV), 0xAB);
+        assert_eq!(bus.read8(REG_IF), 0xE1);
+        assert_eq!(bus.read8(REG_LCDC), 0x91);
+        assert_eq!(bus.read8(REG_BGP), 0xFC);
+        bus.step(0x34);
+        assert_eq!(bus.read8(REG_DIV), 0xAC);
+
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut plain = Bus::new(cartridge).expect("bus");
+        plain.apply_post_boot_state();
+        plain.step(0x34);
+        assert_eq!(plain.read8(REG_DIV), 0xAB);
+    }
+
+    #[test]
+    fn bus_post_boot_timer_ticks_once_per_period_after_tac_write() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        bus.apply_post_boot_timing();
+        bus.write8(REG_TAC, 0x05);
+
+        bus.step(4);
+        assert_eq!(bus.read8(REG_TIMA), 0x01);
+        for expected in 2..=5 {
+            bus.step(16);
+            assert_eq!(bus.read8(REG_TIMA), expected);
+        }
+        assert_eq!(bus.read8(REG_IF) & IF_TIMER, 0);
+    }
+
+    #[test]
+    fn bus_disabled_ext_ram_reads_configured_open_bus() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+
+        assert_eq!(bus.read8(0xA000), 0xFF);
+
+        bus.set_open_bus(0x00);
+        assert_eq!(bus.read8(0xA000), 0x00);
+        assert_eq!(bus.read8(0xBFFF), 0x00);
+
+        bus.write8(0x0000, 0x0A);
+        bus.write8(0xA000, 0x42);
+        assert_eq!(bus.read8(0xA000), 0x42);
+
+        bus.write8(0x0000, 0x00);
+        assert_eq!(bus.read8(0xA000), 0x00);
+        let bus = bus.soft_reset();
+        assert_eq!(bus.read8(0xA000), 0x00);
+    }
+
+    #[test]
+    fn bus_reads_ext_ram_loaded_through_the_cartridge() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut bus = Bus::new(cartridge).expect("bus");
+        let save: Vec<u8> = (0..0x2000).map(|index| index as u8).collect();
+
+        assert_eq!(
+            bus.cartridge_mut().load_ext_ram(&save[..0x100]),
+            Err(SaveError::SizeMismatch {
+                expected: 0x2000,
+                actual: 0x100,
+            })
+        );
+        bus.cartridge_mut()
+            .load_ext_ram(&save)
+            .expect("load ext ram");
+        assert!(!bus.cartridge().is_ram_dirty());
+
+        bus.write8(0x0000, 0x0A);
+        assert_eq!(bus.read8(0xA000), 0x00);
+        assert_eq!(bus.read8(0xA123), 0x23);
+        assert_eq!(bus.read8(0xBFFF), 0xFF);
+    }
+
     #[test]
     fn bus_cgb_bg_palette_write_and_read() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1,4 +1,5 @@
-use super::rom::{RomHeader, RomHeaderError};
+use super::rom::{CartridgeType, RomHeader, RomHeaderError, nintendo_logo_matches, repair_header};
+use super::{RtcFooter, SaveError};
 
 pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
 const ROM_FIXED_START: usize = 0x0000;
@@ -6,6 +7,16 @@ const ROM_FIXED_END: usize = 0x3FFF;
 const ROM_SWITCH_START: usize = 0x4000;
 const ROM_SWITCH_END: usize = 0x7FFF;
 const OPEN_BUS: u8 = 0xFF;
+/// The menu an MMM01 cart shows at power-on, whose header names the mapper.
+const MMM01_MENU_SIZE: usize = 0x8000;
+
+/// Opt-in fixes applied while loading. The default loads the bytes untouched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CartridgeOptions {
+    /// Restore a blank or damaged Nintendo logo and header checksum, as homebrew tools
+    /// sometimes leave them, so the ROM can run through a real boot ROM.
+    pub repair_header: bool,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cartridge {
@@ -14,11 +25,26 @@ pub struct Cartridge {
     pub ext_ram: Vec<u8>,
     ram_dirty: bool,
     ram_dirty_generation: u64,
+    /// Overrides the header's CGB flag, e.g. from the file extension of a mislabeled dump.
+    cgb_hint: Option<bool>,
+    /// The RTC block of a loaded `.sav`, until the emulator restores the clock from it.
+    save_rtc: Option<RtcFooter>,
 }
 
 impl Cartridge {
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RomHeaderError> {
+        Self::from_bytes_with_options(bytes, CartridgeOptions::default())
+    }
+
+    pub fn from_bytes_with_options(
+        mut bytes: Vec<u8>,
+        options: CartridgeOptions,
+    ) -> Result<Self, RomHeaderError> {
+        if options.repair_header {
+            repair_header(&mut bytes);
+        }
         let header = RomHeader::parse(&bytes)?;
+        let header = mmm01_header(&bytes).unwrap_or(header);
         let ext_ram = vec![0; ext_ram_size(header.cartridge_type, header.ram_size)];
         Ok(Self {
             bytes,
@@ -26,6 +52,8 @@ impl Cartridge {
             ext_ram,
             ram_dirty: false,
             ram_dirty_generation: 0,
+            cgb_hint: None,
+            save_rtc: None,
         })
     }
 
@@ -41,6 +69,10 @@ impl Cartridge {
         RomBankMapping::with_switchable_bank(&self.bytes, switchable_bank).read(addr)
     }
 
+    pub fn title_slug(&self) -> String {
+        self.header.title_slug()
+    }
+
     pub fn declared_bank_count(&self) -> Option<usize> {
         self.header.rom_size.bank_count()
     }
@@ -60,6 +92,15 @@ impl Cartridge {
         )
     }
 
+    /// Whether the bus runs this cartridge in CGB mode: the hint if set, else the header.
+    pub fn runs_as_cgb(&self) -> bool {
+        self.cgb_hint.unwrap_or_else(|| self.is_cgb())
+    }
+
+    pub fn set_cgb_hint(&mut self, hint: Option<bool>) {
+        self.cgb_hint = hint;
+    }
+
     pub fn is_cgb_only(&self) -> bool {
         matches!(self.header.cgb_flag, super::rom::CgbFlag::CgbOnly)
     }
@@ -74,6 +115,14 @@ impl Cartridge {
         &mut self.ext_ram
     }
 
+    /// Zeroes cartridge RAM without flagging it dirty, so a power cycle doesn't write
+    /// the blank RAM over the game's `.sav`.
+    pub fn wipe_ram(&mut self) {
+        self.ext_ram.fill(0);
+        self.ram_dirty = false;
+        self.ram_dirty_generation = 0;
+    }
+
     pub fn load_ram(&mut self, data: &[u8]) {
         let len = self.ext_ram.len().min(data.len());
         self.ext_ram[..len].copy_from_slice(&data[..len]);
@@ -81,6 +130,29 @@ impl Cartridge {
         self.ram_dirty_generation = 0;
     }
 
+    /// Loads a `.sav`: battery RAM, then the RTC block common emulators append for timer
+    /// carts, kept until the cartridge is loaded so the clock can catch up.
+    pub fn load_save(&mut self, data: &[u8]) {
+        self.load_ram(data);
+        self.save_rtc = data.get(self.ext_ram.len()..).and_then(RtcFooter::decode);
+    }
+
+    pub(crate) fn take_save_rtc(&mut self) -> Option<RtcFooter> {
+        self.save_rtc.take()
+    }
+
+    /// Replaces external RAM with `data`, which must match its size exactly.
+    pub fn load_ext_ram(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        if data.len() != self.ext_ram.len() {
+            return Err(SaveError::SizeMismatch {
+                expected: self.ext_ram.len(),
+                actual: data.len(),
+            });
+        }
+        self.load_ram(data);
+        Ok(())
+    }
+
     pub fn is_ram_dirty(&self) -> bool {
         self.ram_dirty
     }
@@ -218,6 +290,8 @@ impl<'a> RomBankMapping<'a> {
         self.read_at(index)
     }
 
+    /// Odd-sized dumps leave the last bank short; the missing tail reads as open bus
+    /// rather than padding the image in memory.
     fn read_at(&self, index: usize) -> u8 {
         self.bytes.get(index).copied().unwrap_or(OPEN_BUS)
     }
@@ -231,9 +305,49 @@ fn bank_count_from_len(len: usize) -> usize {
     }
 }
 
+/// MMM01 dumps keep the cart's own header in the last 32KB, where the menu the mapper
+/// powers up into lives; the one at 0x0100 belongs to the first game. The menu must carry
+/// the logo, as the boot ROM checks it there, so stray bytes in other ROMs don't match.
+fn mmm01_header(bytes: &[u8]) -> Option<RomHeader> {
+    let start = bytes
+        .len()
+        .checked_sub(MMM01_MENU_SIZE)
+        .filter(|&start| start > 0)?;
+    let menu = &bytes[start..];
+    if nintendo_logo_matches(menu) != Some(true) {
+        return None;
+    }
+    let header = RomHeader::parse(menu).ok()?;
+    matches!(
+        header.cartridge_type,
+        CartridgeType::Mmm01 | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery
+    )
+    .then_some(header)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Cartridge, ROM_BANK_SIZE};
+    use super::{Cartridge, CartridgeOptions, ROM_BANK_SIZE};
+    use crate::domain::{compute_header_checksum, nintendo_logo_matches};
+
+    #[test]
+    fn header_repair_is_opt_in() {
+        let rom = vec![0; 0x8000];
+        let plain = Cartridge::from_bytes(rom.clone()).expect("cartridge");
+        assert_eq!(plain.bytes, rom);
+        assert_eq!(nintendo_logo_matches(&plain.bytes), Some(false));
+
+        let options = CartridgeOptions {
+            repair_header: true,
+        };
+        let repaired = Cartridge::from_bytes_with_options(rom, options).expect("cartridge");
+        assert_eq!(nintendo_logo_matches(&repaired.bytes), Some(true));
+        assert_eq!(
+            compute_header_checksum(&repaired.bytes),
+            Some(repaired.header.header_checksum)
+        );
+        assert_eq!(repaired.bytes[0x014D], repaired.header.header_checksum);
+    }
 
     #[test]
     fn banked_rom_splits_into_16k_chunks() {
@@ -349,4 +463,29 @@ mod tests {
         assert!(cart.is_cgb());
         assert!(cart.is_cgb_only());
     }
+
+    fn cartridge_with_title(title: &[u8], global_checksum: u16) -> Cartridge {
+        let mut bytes = vec![0; ROM_BANK_SIZE];
+        bytes[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        bytes[0x014E..=0x014F].copy_from_slice(&global_checksum.to_be_bytes());
+        Cartridge::from_bytes(bytes).expect("cartridge")
+    }
+
+    #[test]
+    fn title_slug_replaces_spaces() {
+        let cart = cartridge_with_title(b"POKEMON RED", 0x1234);
+        assert_eq!(cart.title_slug(), "POKEMON_RED");
+    }
+
+    #[test]
+    fn title_slug_replaces_non_ascii_and_trims() {
+        let cart = cartridge_with_title(b" \xFFZELDA\xFE ", 0x1234);
+        assert_eq!(cart.title_slug(), "ZELDA");
+    }
+
+    #[test]
+    fn title_slug_falls_back_to_checksum_for_empty_title() {
+        let cart = cartridge_with_title(b"", 0xBEEF);
+        assert_eq!(cart.title_slug(), "beef");
+    }
 }
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::time::SystemTime;
+
+/// Where the emulator reads the current wall-clock time, so time-dependent features can be
+/// driven deterministically in tests.
+pub trait TimeSource: fmt::Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to, for the crate's own tests.
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::TimeSource;
+    use std::sync::Mutex;
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Debug)]
+    pub struct MockClock {
+        now: Mutex<SystemTime>,
+    }
+
+    impl MockClock {
+        pub fn new(start: SystemTime) -> Self {
+            Self {
+                now: Mutex::new(start),
+            }
+        }
+
+        pub fn advance(&self, by: Duration) {
+            let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+            *now += by;
+        }
+
+        pub fn set(&self, time: SystemTime) {
+            *self.now.lock().unwrap_or_else(|err| err.into_inner()) = time;
+        }
+    }
+
+    impl TimeSource for MockClock {
+        fn now(&self) -> SystemTime {
+            *self.now.lock().unwrap_or_else(|err| err.into_inner())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeSource;
+    use super::mock::MockClock;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn mock_clock_moves_only_when_advanced() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        assert_eq!(clock.now(), UNIX_EPOCH);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_millis(1500));
+
+        clock.set(UNIX_EPOCH + Duration::from_secs(60));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,141 @@
+use super::Cartridge;
+use super::rom::Licensee;
+
+const TITLE_START: usize = 0x0134;
+const TITLE_END: usize = 0x0143;
+const NINTENDO_OLD_LICENSEE: u8 = 0x01;
+const NINTENDO_NEW_LICENSEE: [u8; 2] = *b"01";
+
+pub type Palette = [[u8; 3]; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteSet {
+    pub bg: Palette,
+    pub obj0: Palette,
+    pub obj1: Palette,
+}
+
+const WHITE: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+const RED: Palette = [WHITE, [0xFF, 0x84, 0x84], [0x94, 0x3A, 0x3A], BLACK];
+const GREEN: Palette = [WHITE, [0x7B, 0xFF, 0x31], [0x00, 0x84, 0x00], BLACK];
+const BLUE: Palette = [WHITE, [0x63, 0xA5, 0xFF], [0x00, 0x00, 0xFF], BLACK];
+const LIME: Palette = [WHITE, [0x52, 0xFF, 0x00], [0xFF, 0x42, 0x00], BLACK];
+const ORANGE: Palette = [WHITE, [0xFF, 0xFF, 0x00], [0xFF, 0x00, 0x00], BLACK];
+const GRAY: Palette = [WHITE, [0xAA, 0xAA, 0xAA], [0x55, 0x55, 0x55], BLACK];
+
+impl PaletteSet {
+    pub const GRAYSCALE: Self = Self::uniform(GRAY);
+
+    pub const fn uniform(palette: Palette) -> Self {
+        Self {
+            bg: palette,
+            obj0: palette,
+            obj1: palette,
+        }
+    }
+
+    /// Picks the palettes the CGB boot ROM would assign to a DMG cartridge. Only games
+    /// published by Nintendo are recognized; everything else falls back to grayscale.
+    pub fn for_cartridge(cartridge: &Cartridge) -> Self {
+        let nintendo = match cartridge.header.licensee {
+            Licensee::Old(code) => code == NINTENDO_OLD_LICENSEE,
+            Licensee::New(code) => code == NINTENDO_NEW_LICENSEE,
+        };
+        if !nintendo {
+            return Self::GRAYSCALE;
+        }
+        let checksum = title_checksum(&cartridge.bytes);
+        COLORIZATION_TABLE
+            .iter()
+            .find(|entry| entry.title_checksum == checksum)
+            .map(|entry| entry.palettes)
+            .unwrap_or(Self::GRAYSCALE)
+    }
+}
+
+struct ColorizationEntry {
+    title_checksum: u8,
+    palettes: PaletteSet,
+}
+
+// A subset of the boot ROM's title checksum table.
+const COLORIZATION_TABLE: [ColorizationEntry; 4] = [
+    // POKEMON RED
+    ColorizationEntry {
+        title_checksum: 0x14,
+        palettes: PaletteSet {
+            bg: RED,
+            obj0: GREEN,
+            obj1: RED,
+        },
+    },
+    // POKEMON BLUE
+    ColorizationEntry {
+        title_checksum: 0x61,
+        palettes: PaletteSet {
+            bg: BLUE,
+            obj0: RED,
+            obj1: BLUE,
+        },
+    },
+    // POKEMON GREEN
+    ColorizationEntry {
+        title_checksum: 0xAA,
+        palettes: PaletteSet::uniform(LIME),
+    },
+    // TETRIS
+    ColorizationEntry {
+        title_checksum: 0xDB,
+        palettes: PaletteSet::uniform(ORANGE),
+    },
+];
+
+/// Sum of the 16 title bytes, as computed by the CGB boot ROM.
+pub fn title_checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .get(TITLE_START..=TITLE_END)
+        .unwrap_or(&[])
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ORANGE, PaletteSet, title_checksum};
+    use crate::domain::Cartridge;
+
+    fn cartridge_with_title(title: &[u8], old_licensee: u8) -> Cartridge {
+        let mut rom = vec![0; 0x0150];
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        rom[0x014B] = old_licensee;
+        Cartridge::from_bytes(rom).expect("cartridge")
+    }
+
+    #[test]
+    fn title_checksum_sums_title_bytes() {
+        let cartridge = cartridge_with_title(b"TETRIS", 0x01);
+        assert_eq!(title_checksum(&cartridge.bytes), 0xDB);
+    }
+
+    #[test]
+    fn known_title_maps_to_palette_set() {
+        let cartridge = cartridge_with_title(b"TETRIS", 0x01);
+        assert_eq!(
+            PaletteSet::for_cartridge(&cartridge),
+            PaletteSet::uniform(ORANGE)
+        );
+    }
+
+    #[test]
+    fn unknown_title_falls_back_to_grayscale() {
+        let cartridge = cartridge_with_title(b"HOMEBREW", 0x01);
+        assert_eq!(PaletteSet::for_cartridge(&cartridge), PaletteSet::GRAYSCALE);
+    }
+
+    #[test]
+    fn non_nintendo_licensee_falls_back_to_grayscale() {
+        let cartridge = cartridge_with_title(b"TETRIS", 0x08);
+        assert_eq!(PaletteSet::for_cartridge(&cartridge), PaletteSet::GRAYSCALE);
+    }
+}
@@ -1,12 +1,19 @@
-use super::Bus;
+use super::cartridge::ROM_BANK_SIZE;
+use super::{Bus, Cartridge};
 
 const FLAG_Z: u8 = 0x80;
 const FLAG_N: u8 = 0x40;
 const FLAG_H: u8 = 0x20;
 const FLAG_C: u8 = 0x10;
+const REG_JOYP: u16 = 0xFF00;
 const REG_IF: u16 = 0xFF0F;
 const REG_IE: u16 = 0xFFFF;
 const INTERRUPT_MASK: u8 = 0x1F;
+const CB_PREFIX: u8 = 0xCB;
+/// Opcodes that lock up the real CPU and so are never implemented.
+pub const ILLEGAL_OPCODES: [u8; 11] = [
+    0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+];
 
 #[derive(Debug, Clone, Copy)]
 pub struct Registers {
@@ -180,6 +187,62 @@ impl Registers {
         }
         self.f &= 0xF0;
     }
+
+    /// Every register at once, in 8-bit, 16-bit and flag views.
+    pub fn snapshot(&self) -> RegisterSnapshot {
+        RegisterSnapshot {
+            a: self.a,
+            f: self.f,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            af: self.af(),
+            bc: self.bc(),
+            de: self.de(),
+            hl: self.hl(),
+            flag_z: self.flag_z(),
+            flag_n: self.flag_n(),
+            flag_h: self.flag_h(),
+            flag_c: self.flag_c(),
+        }
+    }
+
+    /// Loads the 8-bit fields of `snapshot`; the pair and flag views are derived from
+    /// them. F's low nibble is dropped, as on hardware.
+    pub fn restore(&mut self, snapshot: &RegisterSnapshot) {
+        self.a = snapshot.a;
+        self.f = snapshot.f & 0xF0;
+        self.b = snapshot.b;
+        self.c = snapshot.c;
+        self.d = snapshot.d;
+        self.e = snapshot.e;
+        self.h = snapshot.h;
+        self.l = snapshot.l;
+    }
+}
+
+/// A copy of [`Registers`] for debuggers, trace logs and save states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub flag_z: bool,
+    pub flag_n: bool,
+    pub flag_h: bool,
+    pub flag_c: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -188,6 +251,22 @@ pub enum CpuError {
     UnimplementedCbOpcode(u8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeCoverage {
+    pub base_implemented: usize,
+    pub cb_implemented: usize,
+}
+
+impl OpcodeCoverage {
+    /// Opcodes a complete CPU implements: every CB opcode and every legal base opcode
+    /// other than the CB prefix itself.
+    pub const IMPLEMENTABLE: usize = 256 - ILLEGAL_OPCODES.len() - 1 + 256;
+
+    pub fn implemented(&self) -> usize {
+        self.base_implemented + self.cb_implemented
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Reg8 {
     B,
@@ -246,6 +325,19 @@ impl Cond {
     }
 }
 
+/// Cycles for a conditional JR/RET/JP/CALL, taken or not, from the Pan Docs opcode table.
+/// A taken branch pays for the extra PC write (and the stack traffic for RET/CALL).
+fn conditional_cycles(opcode: u8, taken: bool) -> u32 {
+    let (taken_cycles, skipped_cycles) = match opcode {
+        0x20 | 0x28 | 0x30 | 0x38 => (12, 8),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => (20, 8),
+        0xC2 | 0xCA | 0xD2 | 0xDA => (16, 12),
+        0xC4 | 0xCC | 0xD4 | 0xDC => (24, 12),
+        _ => unreachable!("{opcode:#04x} is not a conditional branch"),
+    };
+    if taken { taken_cycles } else { skipped_cycles }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Reg16Stack {
     BC,
@@ -286,6 +378,9 @@ pub struct Cpu {
     halt_bug: bool,
     halted: bool,
     stopped: bool,
+    /// P1 input lines (low = pressed) as of the last stopped step, so STOP wakes only
+    /// on a press made after it was entered.
+    stop_lines: u8,
 }
 
 impl Default for Cpu {
@@ -305,6 +400,44 @@ impl Cpu {
             halt_bug: false,
             halted: false,
             stopped: false,
+            stop_lines: 0x0F,
+        }
+    }
+
+    /// Whether `step` decodes `opcode` (CB-prefixed when `cb` is set), found by running it
+    /// on a scratch bus so the answer can't drift from the decoder.
+    pub fn opcode_implemented(opcode: u8, cb: bool) -> bool {
+        if !cb && opcode == CB_PREFIX {
+            return false;
+        }
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        if cb {
+            rom[0] = CB_PREFIX;
+            rom[1] = opcode;
+        } else {
+            rom[0] = opcode;
+        }
+        let Ok(cartridge) = Cartridge::from_bytes(rom) else {
+            return false;
+        };
+        let Ok(mut bus) = Bus::new(cartridge) else {
+            return false;
+        };
+        !matches!(
+            Cpu::new().step(&mut bus),
+            Err(CpuError::UnimplementedOpcode(_) | CpuError::UnimplementedCbOpcode(_))
+        )
+    }
+
+    pub fn opcode_coverage() -> OpcodeCoverage {
+        let count = |cb| {
+            (0..=u8::MAX)
+                .filter(|&opcode| Self::opcode_implemented(opcode, cb))
+                .count()
+        };
+        OpcodeCoverage {
+            base_implemented: count(false),
+            cb_implemented: count(true),
         }
     }
 
@@ -320,6 +453,7 @@ impl Cpu {
         self.halt_bug = false;
         self.halted = false;
         self.stopped = false;
+        self.stop_lines = 0x0F;
     }
 
     pub fn regs(&self) -> &Registers {
@@ -350,6 +484,14 @@ impl Cpu {
         self.ime
     }
 
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
     pub fn set_ime(&mut self, value: bool) {
         self.ime = value;
         if !value {
@@ -358,6 +500,19 @@ impl Cpu {
     }
 
     pub fn step(&mut self, bus: &mut Bus) -> Result<u32, CpuError> {
+        // A button press ends STOP even when the joypad interrupt itself is masked off.
+        // IF is sticky, so the press is taken from the P1 lines rather than IF bit 4.
+        if self.stopped {
+            let lines = bus.read8(REG_JOYP) & 0x0F;
+            let pressed = self.stop_lines & !lines;
+            self.stop_lines = lines;
+            if pressed != 0 {
+                self.stopped = false;
+                if self.pending_interrupts(bus) == 0 {
+                    return Ok(4);
+                }
+            }
+        }
         let pending = self.pending_interrupts(bus);
         if pending != 0 {
             if self.halted {
@@ -437,7 +592,7 @@ impl Cpu {
             }
             0x0A => {
                 let addr = self.regs.bc();
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 Ok(8)
             }
             0x0E => {
@@ -471,7 +626,7 @@ impl Cpu {
             }
             0x1A => {
                 let addr = self.regs.de();
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 Ok(8)
             }
             0x1E => {
@@ -531,7 +686,7 @@ impl Cpu {
             }
             0x2A => {
                 let addr = self.regs.hl();
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 self.regs.set_hl(addr.wrapping_add(1));
                 Ok(8)
             }
@@ -549,7 +704,7 @@ impl Cpu {
             }
             0x3A => {
                 let addr = self.regs.hl();
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 self.regs.set_hl(addr.wrapping_sub(1));
                 Ok(8)
             }
@@ -635,13 +790,11 @@ impl Cpu {
             }
             0x20 | 0x28 | 0x30 | 0x38 => {
                 let offset = self.fetch8(bus) as i8;
-                let cond = Cond::from_bits(opcode >> 3);
-                if self.test_cond(cond) {
+                let taken = self.test_cond(Cond::from_bits(opcode >> 3));
+                if taken {
                     self.pc = self.pc.wrapping_add(offset as u16);
-                    Ok(12)
-                } else {
-                    Ok(8)
                 }
+                Ok(conditional_cycles(opcode, taken))
             }
             0x76 => {
                 let pending = self.pending_interrupts(bus);
@@ -658,6 +811,7 @@ impl Cpu {
                     bus.perform_speed_switch();
                 } else {
                     self.stopped = true;
+                    self.stop_lines = bus.read8(REG_JOYP) & 0x0F;
                 }
                 Ok(4)
             }
@@ -672,36 +826,29 @@ impl Cpu {
                 Ok(16)
             }
             0xC0 | 0xC8 | 0xD0 | 0xD8 => {
-                let cond = Cond::from_bits(opcode >> 3);
-                if self.test_cond(cond) {
-                    let addr = self.pop16(bus);
-                    self.pc = addr;
-                    Ok(20)
-                } else {
-                    Ok(8)
+                let taken = self.test_cond(Cond::from_bits(opcode >> 3));
+                if taken {
+                    self.pc = self.pop16(bus);
                 }
+                Ok(conditional_cycles(opcode, taken))
             }
             0xC2 | 0xCA | 0xD2 | 0xDA => {
                 let addr = self.fetch16(bus);
-                let cond = Cond::from_bits(opcode >> 3);
-                if self.test_cond(cond) {
+                let taken = self.test_cond(Cond::from_bits(opcode >> 3));
+                if taken {
                     self.pc = addr;
-                    Ok(16)
-                } else {
-                    Ok(12)
                 }
+                Ok(conditional_cycles(opcode, taken))
             }
             0xC4 | 0xCC | 0xD4 | 0xDC => {
                 let addr = self.fetch16(bus);
-                let cond = Cond::from_bits(opcode >> 3);
-                if self.test_cond(cond) {
+                let taken = self.test_cond(Cond::from_bits(opcode >> 3));
+                if taken {
                     let ret_addr = self.pc;
                     self.push16(bus, ret_addr);
                     self.pc = addr;
-                    Ok(24)
-                } else {
-                    Ok(12)
                 }
+                Ok(conditional_cycles(opcode, taken))
             }
             0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
                 let addr = match opcode {
@@ -801,13 +948,13 @@ impl Cpu {
             }
             0xFA => {
                 let addr = self.fetch16(bus);
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 Ok(16)
             }
             0xF0 => {
                 let offset = self.fetch8(bus);
                 let addr = 0xFF00u16.wrapping_add(offset as u16);
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 Ok(12)
             }
             0xF6 => {
@@ -823,7 +970,7 @@ impl Cpu {
             }
             0xF2 => {
                 let addr = 0xFF00u16.wrapping_add(self.regs.c as u16);
-                self.regs.a = bus.read8(addr);
+                self.regs.a = bus.cpu_read8(addr);
                 Ok(8)
             }
             0xF9 => {
@@ -884,7 +1031,7 @@ impl Cpu {
     }
 
     fn fetch8(&mut self, bus: &mut Bus) -> u8 {
-        let value = bus.read8(self.pc);
+        let value = bus.cpu_read8(self.pc);
         if self.halt_bug {
             self.halt_bug = false;
         } else {
@@ -907,7 +1054,7 @@ impl Cpu {
             Reg8::E => self.regs.e,
             Reg8::H => self.regs.h,
             Reg8::L => self.regs.l,
-            Reg8::Hl => bus.read8(self.regs.hl()),
+            Reg8::Hl => bus.cpu_read8(self.regs.hl()),
             Reg8::A => self.regs.a,
         }
     }
@@ -961,9 +1108,9 @@ impl Cpu {
     }
 
     fn pop16(&mut self, bus: &Bus) -> u16 {
-        let lo = bus.read8(self.sp);
+        let lo = bus.cpu_read8(self.sp);
         let sp_next = self.sp.wrapping_add(1);
-        let hi = bus.read8(sp_next);
+        let hi = bus.cpu_read8(sp_next);
         self.sp = self.sp.wrapping_add(2);
         u16::from_be_bytes([hi, lo])
     }
@@ -1026,8 +1173,9 @@ impl Cpu {
         self.regs.a = next;
         self.regs.set_flag_z(next == 0);
         self.regs.set_flag_n(true);
-        self.regs
-            .set_flag_h((a & 0x0F) < ((value & 0x0F) + carry_in));
+        // Borrow out of bit 4, counting the incoming carry, in a width that can't wrap.
+        let low_nibble = (a & 0x0F) as i16 - (value & 0x0F) as i16 - carry_in as i16;
+        self.regs.set_flag_h(low_nibble < 0);
         self.regs.set_flag_c((a as u16) < value_with_carry);
     }
 
@@ -1309,7 +1457,9 @@ impl Cpu {
 
 #[cfg(test)]
 mod tests {
-    use super::{Cpu, REG_IE, REG_IF, Registers};
+    use super::{
+        Cpu, ILLEGAL_OPCODES, OpcodeCoverage, REG_IE, REG_IF, REG_JOYP, RegisterSnapshot, Registers,
+    };
     use crate::domain::Bus;
     use crate::domain::Cartridge;
     use crate::domain::cartridge::ROM_BANK_SIZE;
@@ -1323,6 +1473,56 @@ mod tests {
         Bus::new(cartridge).expect("bus")
     }
 
+    #[test]
+    fn opcode_coverage_does_not_regress() {
+        let coverage = Cpu::opcode_coverage();
+        assert_eq!(coverage.cb_implemented, 256);
+        assert_eq!(coverage.base_implemented, 244);
+        assert_eq!(coverage.implemented(), OpcodeCoverage::IMPLEMENTABLE);
+        for opcode in ILLEGAL_OPCODES {
+            assert!(!Cpu::opcode_implemented(opcode, false), "{opcode:#04x}");
+        }
+    }
+
+    #[test]
+    fn conditional_branches_take_reference_cycles() {
+        // (opcode, taken, not taken) for JR, RET, JP and CALL under NZ, Z, NC and C.
+        let groups: [([u8; 4], u32, u32); 4] = [
+            ([0x20, 0x28, 0x30, 0x38], 12, 8),
+            ([0xC0, 0xC8, 0xD0, 0xD8], 20, 8),
+            ([0xC2, 0xCA, 0xD2, 0xDA], 16, 12),
+            ([0xC4, 0xCC, 0xD4, 0xDC], 24, 12),
+        ];
+        for (opcodes, taken_cycles, skipped_cycles) in groups {
+            for opcode in opcodes {
+                // Bits 3-4 pick NZ/Z/NC/C; `set` is the flag value that makes it true.
+                let (zero_flag, set) = match (opcode >> 3) & 0x03 {
+                    0 => (true, false),
+                    1 => (true, true),
+                    2 => (false, false),
+                    _ => (false, true),
+                };
+                for taken in [true, false] {
+                    let mut rom = vec![0; ROM_BANK_SIZE];
+                    rom[0x0000] = opcode;
+                    let mut bus = bus_with_rom(rom);
+                    let mut cpu = Cpu::new();
+                    cpu.set_sp(0xDFF0);
+                    let flag = if taken { set } else { !set };
+                    if zero_flag {
+                        cpu.regs_mut().set_flag_z(flag);
+                    } else {
+                        cpu.regs_mut().set_flag_c(flag);
+                    }
+
+                    let cycles = cpu.step(&mut bus).expect("step");
+                    let expected = if taken { taken_cycles } else { skipped_cycles };
+                    assert_eq!(cycles, expected, "{opcode:#04x} taken={taken}");
+                }
+            }
+        }
+    }
+
     #[test]
     fn registers_mask_lower_flags() {
         let mut regs = Registers::new();
@@ -1330,6 +1530,34 @@ mod tests {
         assert_eq!(regs.af(), 0x12F0);
     }
 
+    #[test]
+    fn register_snapshot_round_trips_with_masked_flags() {
+        let mut regs = Registers::new();
+        regs.set_af(0x12B0);
+        regs.set_bc(0x3456);
+        regs.set_de(0x789A);
+        regs.set_hl(0xBCDE);
+
+        let snapshot = regs.snapshot();
+        assert_eq!((snapshot.a, snapshot.f), (0x12, 0xB0));
+        assert_eq!(snapshot.bc, 0x3456);
+        assert_eq!((snapshot.h, snapshot.l), (0xBC, 0xDE));
+        assert!(snapshot.flag_z && !snapshot.flag_n && snapshot.flag_h && snapshot.flag_c);
+
+        let mut restored = Registers::new();
+        restored.restore(&snapshot);
+        assert_eq!(restored.snapshot(), snapshot);
+
+        let dirty = RegisterSnapshot {
+            f: 0xFF,
+            ..snapshot
+        };
+        restored.restore(&dirty);
+        assert_eq!(restored.f(), 0xF0);
+        assert_eq!(restored.af(), 0x12F0);
+        assert!(restored.snapshot().flag_n);
+    }
+
     #[test]
     fn cpu_executes_nop() {
         let rom = vec![0x00; ROM_BANK_SIZE];
@@ -1462,6 +1690,54 @@ mod tests {
         assert!(!cpu.regs().flag_c());
     }
 
+    #[test]
+    fn cpu_sbc_half_carry_boundaries() {
+        // (a, value, carry_in, result, z, h, c)
+        let cases = [
+            (0x10, 0x0F, true, 0x00, true, true, false),
+            (0x10, 0x0F, false, 0x01, false, true, false),
+            (0x0F, 0x0F, true, 0xFF, false, true, true),
+            (0x0F, 0x0E, true, 0x00, true, false, false),
+            (0x00, 0xFF, true, 0x00, true, true, true),
+            (0x1F, 0x0F, false, 0x10, false, false, false),
+            (0x00, 0x00, true, 0xFF, false, true, true),
+        ];
+        for (a, value, carry_in, result, z, h, c) in cases {
+            let mut cpu = Cpu::new();
+            cpu.regs_mut().set_a(a);
+            cpu.regs_mut().set_flag_c(carry_in);
+
+            cpu.alu_sbc(value);
+
+            let label = format!("{a:#04x} - {value:#04x} - {}", carry_in as u8);
+            assert_eq!(cpu.regs().a(), result, "{label}");
+            assert_eq!(cpu.regs().flag_z(), z, "{label} Z");
+            assert!(cpu.regs().flag_n(), "{label} N");
+            assert_eq!(cpu.regs().flag_h(), h, "{label} H");
+            assert_eq!(cpu.regs().flag_c(), c, "{label} C");
+        }
+    }
+
+    #[test]
+    fn cpu_sub_half_carry_boundaries() {
+        // (a, value, h, c)
+        let cases = [
+            (0x10, 0x01, true, false),
+            (0x10, 0x10, false, false),
+            (0x0F, 0x0F, false, false),
+            (0x00, 0x01, true, true),
+        ];
+        for (a, value, h, c) in cases {
+            let mut cpu = Cpu::new();
+            cpu.regs_mut().set_a(a);
+
+            cpu.alu_sub(value);
+
+            assert_eq!(cpu.regs().flag_h(), h, "{a:#04x} - {value:#04x} H");
+            assert_eq!(cpu.regs().flag_c(), c, "{a:#04x} - {value:#04x} C");
+        }
+    }
+
     #[test]
     fn cpu_ld_rr_and_hl_increment_decrement() {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -1899,6 +2175,98 @@ mod tests {
         assert_eq!(cpu.regs().a(), 0x77);
     }
 
+    fn halted_on_joypad_bus() -> (Cpu, Bus) {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0000] = 0x76;
+        rom[0x0001] = 0x3E;
+        rom[0x0002] = 0x21;
+        rom[0x0060] = 0xD9;
+        let mut bus = bus_with_rom(rom);
+        let mut cpu = Cpu::new();
+        bus.write8(REG_JOYP, 0x10);
+
+        cpu.step(&mut bus).expect("halt");
+        assert!(cpu.halted());
+        cpu.step(&mut bus).expect("halted");
+        assert!(cpu.halted());
+        (cpu, bus)
+    }
+
+    #[test]
+    fn cpu_halt_wakes_on_joypad_press() {
+        let (mut cpu, mut bus) = halted_on_joypad_bus();
+        bus.write8(REG_IE, 0x10);
+
+        bus.set_joyp_buttons(0x0E);
+        cpu.step(&mut bus).expect("wake");
+        assert!(!cpu.halted());
+
+        cpu.step(&mut bus).expect("ld a,d8");
+        assert_eq!(cpu.regs().a(), 0x21);
+    }
+
+    #[test]
+    fn cpu_halt_dispatches_joypad_interrupt_when_enabled() {
+        let (mut cpu, mut bus) = halted_on_joypad_bus();
+        cpu.set_ime(true);
+        bus.write8(REG_IE, 0x10);
+
+        bus.set_joyp_buttons(0x0E);
+        let cycles = cpu.step(&mut bus).expect("interrupt");
+        assert_eq!(cycles, 20);
+        assert_eq!(cpu.pc(), 0x0060);
+        assert_eq!(bus.read8(REG_IF) & 0x10, 0);
+
+        cpu.step(&mut bus).expect("reti");
+        assert_eq!(cpu.pc(), 0x0001);
+    }
+
+    #[test]
+    fn cpu_stop_wakes_on_masked_joypad_press() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0000] = 0x10;
+        rom[0x0001] = 0x00;
+        rom[0x0002] = 0x3E;
+        rom[0x0003] = 0x66;
+        let mut bus = bus_with_rom(rom);
+        let mut cpu = Cpu::new();
+        bus.write8(REG_JOYP, 0x20);
+
+        cpu.step(&mut bus).expect("stop");
+        cpu.step(&mut bus).expect("stopped");
+        assert!(cpu.stopped());
+        assert!(!cpu.halted());
+
+        bus.set_joyp_dpad(0x0B);
+        cpu.step(&mut bus).expect("wake");
+        cpu.step(&mut bus).expect("ld a,d8");
+        assert_eq!(cpu.regs().a(), 0x66);
+    }
+
+    #[test]
+    fn cpu_stop_ignores_a_joypad_flag_raised_before_it() {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0000] = 0x10;
+        rom[0x0001] = 0x00;
+        let mut bus = bus_with_rom(rom);
+        let mut cpu = Cpu::new();
+        bus.write8(REG_JOYP, 0x20);
+        bus.set_joyp_dpad(0x0B);
+        bus.set_joyp_dpad(0x0F);
+        assert_eq!(bus.read8(REG_IF) & 0x10, 0x10);
+
+        cpu.step(&mut bus).expect("stop");
+        for _ in 0..4 {
+            cpu.step(&mut bus).expect("stopped");
+            assert!(cpu.stopped());
+        }
+        assert_eq!(cpu.pc(), 0x0002);
+
+        bus.set_joyp_dpad(0x0B);
+        cpu.step(&mut bus).expect("wake");
+        assert!(!cpu.stopped());
+    }
+
     #[test]
     fn cpu_stop_speed_switch_does_not_halt() {
         const REG_KEY1: u16 = 0xFF4D;
@@ -1,5 +1,198 @@
-use super::apu::DEFAULT_OUTPUT_SAMPLE_RATE_HZ;
-use super::{Bus, Cartridge, Cpu, CpuError, Framebuffer, MbcError, Ppu};
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::apu::{ApuReport, DEFAULT_OUTPUT_SAMPLE_RATE_HZ, measure_pulse_pitch};
+use super::mbc::check_camera_image;
+use super::ppu::sprite_height;
+use super::{
+    Bus, Button, ButtonState, Cartridge, ChannelId, ClipMode, Clocked, Cpu, CpuError, FRAME_CYCLES,
+    Framebuffer, FramebufferError, Mapper, MbcError, MemoryRegion, MovieFrame, NINTENDO_LOGO,
+    PaletteSet, Ppu, RTC_FOOTER_SIZE, RamBankSelection, RomHeaderError, RtcFooter, RtcSnapshot,
+    RtcSource, SampleDepth, SaveError, SpriteInfo, SystemClock, TILE_MAP_SIZE, TimeSource,
+    TraceLog, nintendo_logo_matches, parse_oam, trace_line,
+};
+
+/// Pulse frequency register value for about A4 (440 Hz), and how long the APU
+/// self-test plays it.
+const SELFTEST_FREQUENCY: u16 = 1750;
+const SELFTEST_CYCLES: u32 = FRAME_CYCLES * 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Header(RomHeaderError),
+    Mbc(MbcError),
+}
+
+impl From<RomHeaderError> for LoadError {
+    fn from(err: RomHeaderError) -> Self {
+        Self::Header(err)
+    }
+}
+
+impl From<MbcError> for LoadError {
+    fn from(err: MbcError) -> Self {
+        Self::Mbc(err)
+    }
+}
+
+/// Why `run_until_vblank` returned without a completed frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunError {
+    NoCartridge,
+    Cpu(CpuError),
+    Mbc(MbcError),
+    /// The cycle cap ran out first; `cycles` is how far it got.
+    Timeout {
+        cycles: u32,
+    },
+}
+
+impl From<CpuError> for RunError {
+    fn from(err: CpuError) -> Self {
+        Self::Cpu(err)
+    }
+}
+
+impl From<FrameError> for RunError {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::UnsupportedOpcode(event) => Self::Cpu(event.cpu_error()),
+            FrameError::Mbc(err) => Self::Mbc(err),
+        }
+    }
+}
+
+/// Why `load_vram` or `load_oam` refused a blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLoadError {
+    NoCartridge,
+    /// The blob is longer than the region it was meant for.
+    TooLarge {
+        max: usize,
+        actual: usize,
+    },
+}
+
+/// Why `step_frame` couldn't finish a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The CPU stopped on an opcode it can't decode, under `OpcodePolicy::Stop`. Every
+    /// later step returns the same error until the next load or reset.
+    UnsupportedOpcode(UnsupportedOpcode),
+    /// The cartridge's mapper reported a fault. Sticks like an unsupported opcode.
+    Mbc(MbcError),
+}
+
+impl From<MbcError> for FrameError {
+    fn from(err: MbcError) -> Self {
+        Self::Mbc(err)
+    }
+}
+
+impl FrameError {
+    pub fn cpu_error(&self) -> Option<CpuError> {
+        match self {
+            Self::UnsupportedOpcode(event) => Some(event.cpu_error()),
+            Self::Mbc(_) => None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::UnsupportedOpcode(event) => format!("CPU stopped: {}", event.message()),
+            Self::Mbc(MbcError::Fault(reason)) => format!("Cartridge stopped: {reason}"),
+            Self::Mbc(err) => format!("Cartridge stopped: {err:?}"),
+        }
+    }
+}
+
+const NOP_CYCLES: u32 = 4;
+/// About a second of logo before the cartridge takes over.
+const BOOT_ANIMATION_FRAMES: u32 = 60;
+const BOOT_LOGO_SCROLL_FRAMES: u32 = 40;
+/// Where the boot ROM leaves the logo, in screen lines.
+const BOOT_LOGO_TOP: u32 = 64;
+const REG_LCDC: u16 = 0xFF40;
+const REG_SCY: u16 = 0xFF42;
+const REG_SCX: u16 = 0xFF43;
+const DEFAULT_OPEN_BUS: u8 = 0xFF;
+
+/// What `step_frame` does when the CPU hits an opcode it can't decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpcodePolicy {
+    /// Halt the CPU; every later `step_frame` returns the error.
+    #[default]
+    Stop,
+    /// Treat the byte as a NOP and keep running.
+    Nop,
+}
+
+impl OpcodePolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Nop => "nop",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "stop" => Some(Self::Stop),
+            "nop" => Some(Self::Nop),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedOpcode {
+    pub opcode: u8,
+    pub cb: bool,
+    pub pc: u16,
+}
+
+impl UnsupportedOpcode {
+    fn from_error(err: CpuError, pc_after_fetch: u16) -> Self {
+        match err {
+            CpuError::UnimplementedOpcode(opcode) => Self {
+                opcode,
+                cb: false,
+                pc: pc_after_fetch.wrapping_sub(1),
+            },
+            CpuError::UnimplementedCbOpcode(opcode) => Self {
+                opcode,
+                cb: true,
+                pc: pc_after_fetch.wrapping_sub(2),
+            },
+        }
+    }
+
+    pub fn cpu_error(&self) -> CpuError {
+        if self.cb {
+            CpuError::UnimplementedCbOpcode(self.opcode)
+        } else {
+            CpuError::UnimplementedOpcode(self.opcode)
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let prefix = if self.cb { "0xCB " } else { "" };
+        format!(
+            "unsupported opcode {}{:#04x} at {:#06x}",
+            prefix, self.opcode, self.pc
+        )
+    }
+}
+
+/// What a `step_cycles` call got through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StepResult {
+    /// Cycles actually run, which can pass the budget by the tail of one instruction.
+    pub cycles: u32,
+    pub frame_completed: bool,
+}
 
 #[derive(Debug)]
 pub struct Emulator {
@@ -7,8 +200,29 @@ pub struct Emulator {
     framebuffer: Framebuffer,
     cpu: Cpu,
     bus: Option<Bus>,
-    cpu_error: Option<CpuError>,
+    frame_error: Option<FrameError>,
     ppu: Ppu,
+    colorize: bool,
+    boot_timing: bool,
+    open_bus: u8,
+    dma_bus_conflicts: bool,
+    open_bus_last_byte: bool,
+    rtc_source: RtcSource,
+    clip_mode: ClipMode,
+    sample_depth: SampleDepth,
+    max_sample_queue: Option<usize>,
+    channel_gains: [f32; 4],
+    camera_image: Option<Vec<u8>>,
+    time_source: Arc<dyn TimeSource>,
+    opcode_policy: OpcodePolicy,
+    reported_opcodes: HashSet<(bool, u8)>,
+    unsupported_opcodes: VecDeque<UnsupportedOpcode>,
+    last_frame_cycles: u32,
+    new_frame: bool,
+    boot_rom_disabled: bool,
+    boot_animation_frames: u32,
+    buttons: ButtonState,
+    trace: Option<TraceLog>,
 }
 
 impl Default for Emulator {
@@ -24,8 +238,29 @@ impl Emulator {
             framebuffer: Framebuffer::new(),
             cpu: Cpu::new(),
             bus: None,
-            cpu_error: None,
+            frame_error: None,
             ppu: Ppu::new(),
+            colorize: false,
+            boot_timing: false,
+            open_bus: DEFAULT_OPEN_BUS,
+            dma_bus_conflicts: false,
+            open_bus_last_byte: false,
+            rtc_source: RtcSource::default(),
+            clip_mode: ClipMode::default(),
+            sample_depth: SampleDepth::default(),
+            max_sample_queue: None,
+            channel_gains: [1.0; 4],
+            camera_image: None,
+            time_source: Arc::new(SystemClock),
+            opcode_policy: OpcodePolicy::default(),
+            reported_opcodes: HashSet::new(),
+            unsupported_opcodes: VecDeque::new(),
+            last_frame_cycles: 0,
+            new_frame: false,
+            boot_rom_disabled: false,
+            boot_animation_frames: 0,
+            buttons: ButtonState::new(),
+            trace: None,
         }
     }
 
@@ -33,6 +268,141 @@ impl Emulator {
         self.booted
     }
 
+    /// When no boot ROM is supplied, start from the exact timer phase the boot ROM hands
+    /// over with rather than just its coarse DIV value. Takes effect on the next load or reset.
+    pub fn set_boot_timing(&mut self, enabled: bool) {
+        self.boot_timing = enabled;
+    }
+
+    /// The value disabled or missing cartridge RAM reads back as. Kept across loads.
+    pub fn set_open_bus(&mut self, value: u8) {
+        self.open_bus = value;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.set_open_bus(value);
+        }
+    }
+
+    /// Lock the CPU out of everything but IO and HRAM during OAM DMA. Kept across loads.
+    pub fn set_dma_bus_conflicts(&mut self, enabled: bool) {
+        self.dma_bus_conflicts = enabled;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.set_dma_bus_conflicts(enabled);
+        }
+    }
+
+    /// Let the prohibited area echo the last byte read, for accuracy test ROMs. Kept
+    /// across loads.
+    pub fn set_open_bus_last_byte(&mut self, enabled: bool) {
+        self.open_bus_last_byte = enabled;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.set_open_bus_last_byte(enabled);
+        }
+    }
+
+    /// An emulator that reads wall-clock time from `time_source` instead of the system.
+    pub fn with_time_source(time_source: Arc<dyn TimeSource>) -> Self {
+        Self {
+            time_source,
+            ..Self::new()
+        }
+    }
+
+    pub fn rtc_snapshot(&self) -> Option<RtcSnapshot> {
+        self.bus.as_ref().and_then(Bus::rtc_snapshot)
+    }
+
+    /// Battery RAM in the common `.sav` layout: raw SRAM, followed for MBC3 timer carts by
+    /// the 48-byte BGB/VBA-M RTC block, so saves move between emulators.
+    pub fn export_save(&self) -> Result<Vec<u8>, SaveError> {
+        let bus = self.bus.as_ref().ok_or(SaveError::NoCartridge)?;
+        let mut data = bus.cartridge().ram().to_vec();
+        if let Some(snapshot) = bus.rtc_snapshot() {
+            let footer = RtcFooter {
+                registers: snapshot.registers,
+                latched: bus.rtc_latched_registers().unwrap_or(snapshot.registers),
+                timestamp: self.unix_seconds(),
+            };
+            data.extend_from_slice(&footer.encode());
+        }
+        Ok(data)
+    }
+
+    /// Loads a `.sav` written by `export_save` or another emulator. The clock is advanced
+    /// by the time that passed since the file's timestamp.
+    pub fn import_save(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        let now = self.unix_seconds();
+        let bus = self.bus.as_mut().ok_or(SaveError::NoCartridge)?;
+        let ram_len = bus.cartridge().ram().len();
+        let has_rtc = bus.rtc_snapshot().is_some();
+        let footer = match data.get(ram_len..) {
+            Some([]) => None,
+            Some(tail) if has_rtc => RtcFooter::decode(tail),
+            _ => None,
+        };
+        if data.len() != ram_len && footer.is_none() {
+            let expected = if has_rtc {
+                ram_len + RTC_FOOTER_SIZE
+            } else {
+                ram_len
+            };
+            return Err(SaveError::SizeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        bus.load_ram(&data[..ram_len]);
+        if let Some(footer) = footer {
+            restore_rtc_footer(bus, footer, now);
+        }
+        Ok(())
+    }
+
+    /// The loaded cartridge, if any.
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.bus.as_ref().map(|bus| bus.cartridge())
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.bus.as_mut().map(|bus| bus.cartridge_mut())
+    }
+
+    fn unix_seconds(&self) -> u64 {
+        self.time_source
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Whether an MBC3 clock advances with emulated cycles or with real time, so turbo
+    /// doesn't rush daily events. Kept across loads.
+    pub fn set_rtc_source(&mut self, source: RtcSource) {
+        self.rtc_source = source;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.set_rtc_source(source);
+        }
+    }
+
+    /// Feeds the Pocket Camera's sensor a 128x112 grayscale image, one byte per pixel with
+    /// 0 black. Kept across loads; cartridges without a camera ignore it.
+    pub fn set_camera_image(&mut self, image: &[u8]) -> Result<(), MbcError> {
+        check_camera_image(image)?;
+        self.camera_image = Some(image.to_vec());
+        if let Some(bus) = self.bus.as_mut() {
+            bus.set_camera_image(image)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a ROM image already in memory. Battery RAM is left blank; restoring a save
+    /// is up to the caller.
+    pub fn load_rom_bytes(&mut self, rom: &[u8], boot_rom: Option<&[u8]>) -> Result<(), LoadError> {
+        let cartridge = Cartridge::from_bytes(rom.to_vec())?;
+        self.load_cartridge_with_boot_rom(cartridge, boot_rom.map(<[u8]>::to_vec))?;
+        Ok(())
+    }
+
     pub fn load_cartridge(&mut self, cartridge: Cartridge) -> Result<(), MbcError> {
         self.load_cartridge_with_boot_rom(cartridge, None)
     }
@@ -42,19 +412,147 @@ impl Emulator {
         cartridge: Cartridge,
         boot_rom: Option<Vec<u8>>,
     ) -> Result<(), MbcError> {
-        let mut bus = Bus::with_boot_rom(cartridge, boot_rom)?;
-        self.cpu = Cpu::new();
-        self.cpu_error = None;
+        let bus = Bus::with_boot_rom(cartridge, boot_rom)?;
         self.ppu = Ppu::new();
+        self.install_bus(bus);
+        Ok(())
+    }
+
+    /// Loads a cartridge banked by a custom mapper instead of the one its header names.
+    pub fn load_cartridge_with_mapper(&mut self, cartridge: Cartridge, mapper: Box<dyn Mapper>) {
+        let bus = Bus::with_mapper(cartridge, mapper, None);
+        self.ppu = Ppu::new();
+        self.install_bus(bus);
+    }
+
+    /// An emulator already past boot with the given CPU state and RAM contents, so tests
+    /// and fuzzers can start from states a game would take a long time to reach.
+    pub fn from_state_parts(
+        cartridge: Cartridge,
+        cpu: Cpu,
+        wram: &[u8],
+        hram: &[u8],
+    ) -> Result<Self, MbcError> {
+        let mut emulator = Self::new();
+        emulator.load_cartridge(cartridge)?;
+        emulator.restore_state_parts(cpu, wram, hram);
+        Ok(emulator)
+    }
+
+    /// Like `from_state_parts`, for the cartridge already loaded, keeping this emulator's
+    /// settings. A boot ROM still running is skipped. Does nothing with no cartridge loaded.
+    pub fn restore_state_parts(&mut self, cpu: Cpu, wram: &[u8], hram: &[u8]) {
+        let Some(bus) = self.bus.as_mut() else {
+            return;
+        };
+        if bus.boot_rom_enabled() {
+            bus.apply_post_boot_state();
+            self.booted = true;
+        }
+        self.boot_animation_frames = 0;
+        self.cpu = cpu;
+        bus.load_wram(wram);
+        bus.load_hram(hram);
+    }
+
+    /// The CPU, for saving its registers alongside `memory_snapshot`.
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// Copies `data` over the start of the current VRAM bank (0x8000-0x9FFF), for setting
+    /// up graphics in one go instead of byte by byte.
+    pub fn load_vram(&mut self, data: &[u8]) -> Result<(), MemoryLoadError> {
+        let bus = self.bus_for_region(data, MemoryRegion::Vram)?;
+        bus.load_vram(data);
+        Ok(())
+    }
+
+    /// Copies `data` over the start of OAM (0xFE00-0xFE9F).
+    pub fn load_oam(&mut self, data: &[u8]) -> Result<(), MemoryLoadError> {
+        let bus = self.bus_for_region(data, MemoryRegion::Oam)?;
+        bus.load_oam(data);
+        Ok(())
+    }
+
+    fn bus_for_region(
+        &mut self,
+        data: &[u8],
+        region: MemoryRegion,
+    ) -> Result<&mut Bus, MemoryLoadError> {
+        let bus = self.bus.as_mut().ok_or(MemoryLoadError::NoCartridge)?;
+        if data.len() > region.size() {
+            return Err(MemoryLoadError::TooLarge {
+                max: region.size(),
+                actual: data.len(),
+            });
+        }
+        Ok(bus)
+    }
+
+    /// Reboots the loaded cartridge, keeping its battery RAM. Runs the boot ROM again if
+    /// one was supplied, otherwise starts from the post-boot state.
+    pub fn soft_reset(&mut self) {
+        let Some(bus) = self.bus.take() else {
+            return;
+        };
+        self.ppu.reset();
+        self.framebuffer = Framebuffer::new();
+        self.install_bus(bus.soft_reset());
+    }
+
+    /// Reboots the loaded cartridge with its RAM cleared, as if the save battery had died.
+    pub fn power_cycle(&mut self) {
+        let Some(bus) = self.bus.take() else {
+            return;
+        };
+        self.ppu.reset();
+        self.framebuffer = Framebuffer::new();
+        self.install_bus(bus.power_cycle());
+    }
+
+    fn install_bus(&mut self, mut bus: Bus) {
+        self.cpu = Cpu::new();
+        self.frame_error = None;
+        self.reported_opcodes.clear();
+        self.unsupported_opcodes.clear();
+        self.new_frame = false;
+        self.boot_rom_disabled = false;
+        self.boot_animation_frames = 0;
+        self.buttons = ButtonState::new();
+        bus.set_open_bus(self.open_bus);
+        bus.set_dma_bus_conflicts(self.dma_bus_conflicts);
+        bus.set_open_bus_last_byte(self.open_bus_last_byte);
+        bus.set_time_source(Arc::clone(&self.time_source));
+        bus.set_rtc_source(self.rtc_source);
+        if let Some(footer) = bus.cartridge_mut().take_save_rtc()
+            && bus.rtc_snapshot().is_some()
+        {
+            restore_rtc_footer(&mut bus, footer, self.unix_seconds());
+        }
+        bus.apu_set_clip_mode(self.clip_mode);
+        bus.apu_set_sample_depth(self.sample_depth);
+        if let Some(max_queue) = self.max_sample_queue {
+            bus.apu_set_max_queue(max_queue);
+        }
+        for (channel, gain) in ChannelId::ALL.into_iter().zip(self.channel_gains) {
+            bus.apu_set_channel_gain(channel, gain);
+        }
+        if let Some(image) = &self.camera_image {
+            let _ = bus.set_camera_image(image);
+        }
         if bus.boot_rom_enabled() {
             self.booted = false;
         } else {
             self.cpu.apply_post_boot_state();
-            bus.apply_post_boot_state();
+            if self.boot_timing {
+                bus.apply_post_boot_timing();
+            } else {
+                bus.apply_post_boot_state();
+            }
             self.booted = true;
         }
         self.bus = Some(bus);
-        Ok(())
     }
 
     pub fn framebuffer(&self) -> &Framebuffer {
@@ -65,10 +563,107 @@ impl Emulator {
         &mut self.framebuffer
     }
 
+    /// Copies the current frame into `out` as packed RGBA, `FRAME_RGBA_SIZE` bytes.
+    pub fn copy_framebuffer_rgba(&self, out: &mut [u8]) -> Result<(), FramebufferError> {
+        self.framebuffer.copy_rgba(out)
+    }
+
     pub fn set_palette(&mut self, palette: [[u8; 3]; 4]) {
         self.ppu.set_palette(palette);
     }
 
+    /// Colors a DMG cartridge with the palettes the CGB boot ROM would pick for it, or
+    /// with the palette an SGB game sends once it does. CGB cartridges and disabled
+    /// colorization render with the selected palette.
+    pub fn set_colorization(&mut self, enabled: bool) {
+        self.colorize = enabled;
+        let colorization = self
+            .bus
+            .as_ref()
+            .filter(|bus| enabled && !bus.is_cgb())
+            .map(|bus| PaletteSet::for_cartridge(bus.cartridge()));
+        self.ppu.set_colorization(colorization);
+    }
+
+    pub fn colorization(&self) -> Option<PaletteSet> {
+        self.ppu.colorization()
+    }
+
+    /// Debug toggle for a PPU layer (`LAYER_BG`, `LAYER_WINDOW` or `LAYER_SPRITES`).
+    pub fn set_layer_enabled(&mut self, layer: u8, enabled: bool) {
+        self.ppu.set_layer_enabled(layer, enabled);
+    }
+
+    pub fn layer_enabled(&self, layer: u8) -> bool {
+        self.ppu.layer_enabled(layer)
+    }
+
+    /// Renders the full background tile map into `out` as RGB, `TILE_MAP_SIZE` bytes.
+    pub fn render_bg_map(&self, out: &mut [u8]) -> Result<(), FramebufferError> {
+        self.render_tile_map(false, out)
+    }
+
+    /// Like `render_bg_map`, for the map LCDC selects for the window.
+    pub fn render_window_map(&self, out: &mut [u8]) -> Result<(), FramebufferError> {
+        self.render_tile_map(true, out)
+    }
+
+    fn render_tile_map(&self, window: bool, out: &mut [u8]) -> Result<(), FramebufferError> {
+        if out.len() < TILE_MAP_SIZE {
+            return Err(FramebufferError::BufferTooSmall {
+                needed: TILE_MAP_SIZE,
+                actual: out.len(),
+            });
+        }
+        if let Some(bus) = self.bus.as_ref() {
+            self.ppu
+                .render_tile_map(bus, window, &mut out[..TILE_MAP_SIZE]);
+        }
+        Ok(())
+    }
+
+    /// SCX and SCY: the top-left corner of the visible frame on the background map.
+    pub fn bg_scroll(&self) -> Option<(u8, u8)> {
+        self.bus
+            .as_ref()
+            .map(|bus| (bus.read8(REG_SCX), bus.read8(REG_SCY)))
+    }
+
+    /// OAM entries that currently overlap the visible frame.
+    pub fn sprite_entries(&self) -> Vec<SpriteInfo> {
+        let Some(bus) = self.bus.as_ref() else {
+            return Vec::new();
+        };
+        let height = sprite_height(bus.read8(REG_LCDC));
+        parse_oam(bus.oam(), height)
+            .into_iter()
+            .filter(SpriteInfo::is_on_screen)
+            .collect()
+    }
+
+    /// CPU registers and the bytes at PC, in the same format as the instruction trace.
+    pub fn cpu_state(&self) -> Option<String> {
+        self.bus.as_ref().map(|bus| trace_line(&self.cpu, bus))
+    }
+
+    /// IE, IF and IME, for spotting a game stuck waiting on an interrupt it never enabled.
+    /// IE and IF read as 0 with no cartridge loaded.
+    pub fn interrupt_state(&self) -> (u8, u8, bool) {
+        let (enable, flag) = self
+            .bus
+            .as_ref()
+            .map_or((0, 0), |bus| (bus.read8(0xFFFF), bus.read8(0xFF0F)));
+        (enable, flag, self.cpu.ime())
+    }
+
+    /// A copy of `region` for diffing with [`diff_snapshots`](super::diff_snapshots);
+    /// empty with no cartridge loaded.
+    pub fn memory_snapshot(&self, region: MemoryRegion) -> Vec<u8> {
+        self.bus
+            .as_ref()
+            .map_or_else(Vec::new, |bus| bus.snapshot(region))
+    }
+
     pub fn has_bus(&self) -> bool {
         self.bus.is_some()
     }
@@ -85,6 +680,53 @@ impl Emulator {
         }
     }
 
+    /// Presses or releases one button, leaving the others as they were. Mixing this with
+    /// `set_joyp_buttons`/`set_joyp_dpad` overwrites whichever group those set.
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.buttons.set(button, pressed);
+        let (buttons, dpad) = self.buttons.joyp_masks();
+        self.set_joyp_buttons(buttons);
+        self.set_joyp_dpad(dpad);
+    }
+
+    /// Joypad lines for a movie; everything released when no cartridge is loaded.
+    pub fn movie_frame(&self) -> MovieFrame {
+        self.bus
+            .as_ref()
+            .map(|bus| {
+                let (buttons, dpad) = bus.joyp_masks();
+                MovieFrame { buttons, dpad }
+            })
+            .unwrap_or(MovieFrame::RELEASED)
+    }
+
+    pub fn apply_movie_frame(&mut self, frame: MovieFrame) {
+        self.set_joyp_buttons(frame.buttons);
+        self.set_joyp_dpad(frame.dpad);
+    }
+
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        self.bus
+            .as_mut()
+            .map(Bus::take_serial_output)
+            .unwrap_or_default()
+    }
+
+    pub fn current_rom_bank(&self) -> Option<usize> {
+        self.bus.as_ref().map(|bus| bus.current_rom_bank())
+    }
+
+    pub fn current_ram_bank(&self) -> Option<RamBankSelection> {
+        self.bus.as_ref().and_then(|bus| bus.current_ram_bank())
+    }
+
+    pub fn ram_bank_count(&self) -> usize {
+        self.bus
+            .as_ref()
+            .map(|bus| bus.ram_bank_count())
+            .unwrap_or(0)
+    }
+
     pub fn is_cgb(&self) -> bool {
         self.bus.as_ref().map(|bus| bus.is_cgb()).unwrap_or(false)
     }
@@ -102,34 +744,207 @@ impl Emulator {
         }
     }
 
-    pub fn step_frame(&mut self) -> Result<u32, CpuError> {
-        if let Some(err) = self.cpu_error {
+    pub fn set_opcode_policy(&mut self, policy: OpcodePolicy) {
+        self.opcode_policy = policy;
+    }
+
+    /// True once after the boot ROM unmaps itself and hands control to the cartridge.
+    pub fn take_boot_rom_disabled(&mut self) -> bool {
+        std::mem::take(&mut self.boot_rom_disabled)
+    }
+
+    /// Next opcode the CPU couldn't decode. Each opcode is reported once per emulator.
+    pub fn take_unsupported_opcode(&mut self) -> Option<UnsupportedOpcode> {
+        self.unsupported_opcodes.pop_front()
+    }
+
+    /// Logs every executed instruction to `writer` in the Gameboy Doctor format. Tracing
+    /// stops on the first write error. Pass a buffered writer; this runs per instruction.
+    pub fn enable_trace(&mut self, writer: impl Write + Send + 'static) {
+        self.trace = Some(TraceLog::new(Box::new(writer)));
+    }
+
+    pub fn disable_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(mut trace) => trace.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// CPU cycles the most recent completed `step_frame` ran, nominally `FRAME_CYCLES`.
+    pub fn last_frame_cycles(&self) -> u32 {
+        self.last_frame_cycles
+    }
+
+    /// Shows the header logo scrolling into place for about a second before the
+    /// cartridge starts, standing in for the boot ROM. Does nothing when a real boot ROM
+    /// runs or the header logo doesn't match. Returns whether the animation started.
+    /// Every stepping call, `step_instruction` included, plays a whole animation frame.
+    pub fn start_boot_animation(&mut self) -> bool {
+        let Some(bus) = self.bus.as_ref() else {
+            return false;
+        };
+        if bus.boot_rom_enabled() || nintendo_logo_matches(&bus.cartridge().bytes) != Some(true) {
+            return false;
+        }
+        self.boot_animation_frames = BOOT_ANIMATION_FRAMES;
+        true
+    }
+
+    pub fn boot_animation_active(&self) -> bool {
+        self.boot_animation_frames > 0
+    }
+
+    fn step_boot_animation(&mut self) -> u32 {
+        let elapsed = BOOT_ANIMATION_FRAMES - self.boot_animation_frames;
+        let top = BOOT_LOGO_TOP * elapsed.min(BOOT_LOGO_SCROLL_FRAMES) / BOOT_LOGO_SCROLL_FRAMES;
+        self.ppu
+            .render_boot_logo(&NINTENDO_LOGO, top as usize, &mut self.framebuffer);
+        self.boot_animation_frames -= 1;
+        self.new_frame = true;
+        self.last_frame_cycles = FRAME_CYCLES;
+        FRAME_CYCLES
+    }
+
+    /// The error that stopped the CPU, if any; cleared by the next load or reset.
+    pub fn frame_error(&self) -> Option<FrameError> {
+        self.frame_error
+    }
+
+    pub fn step_frame(&mut self) -> Result<u32, FrameError> {
+        if let Some(err) = self.frame_error {
+            return Err(err);
+        }
+        if self.bus.is_none() {
+            return Ok(0);
+        }
+
+        let mut cycles: u32 = 0;
+        loop {
+            let (step_cycles, frame_ready) = self.step_once()?;
+            cycles = cycles.saturating_add(step_cycles);
+            if frame_ready {
+                break;
+            }
+        }
+        self.last_frame_cycles = cycles;
+        Ok(cycles)
+    }
+
+    /// Runs until `budget` cycles have passed or a frame completes, whichever is first,
+    /// for hosts that present on their own schedule. A boot animation frame counts as a
+    /// whole frame.
+    pub fn step_cycles(&mut self, budget: u32) -> Result<StepResult, FrameError> {
+        if let Some(err) = self.frame_error {
             return Err(err);
         }
+        if self.bus.is_none() {
+            return Ok(StepResult::default());
+        }
 
-        if let Some(bus) = self.bus.as_mut() {
-            let mut cycles: u32 = 0;
-            let mut frame_ready = false;
-            while !frame_ready {
-                let step_cycles = match self.cpu.step(bus) {
-                    Ok(count) => count,
-                    Err(err) => {
-                        self.cpu_error = Some(err);
-                        return Err(err);
-                    }
-                };
-                bus.step(step_cycles);
-                frame_ready = self.ppu.step(step_cycles, bus, &mut self.framebuffer);
-                cycles = cycles.saturating_add(step_cycles);
-
-                if bus.take_boot_rom_disabled() {
-                    self.booted = true;
+        let mut result = StepResult::default();
+        while result.cycles < budget && !result.frame_completed {
+            let (step_cycles, frame_ready) = self.step_once()?;
+            result.cycles = result.cycles.saturating_add(step_cycles);
+            result.frame_completed = frame_ready;
+        }
+        Ok(result)
+    }
+
+    /// Runs one instruction (or one halted tick) and everything clocked alongside it.
+    pub fn step_instruction(&mut self) -> Result<u32, FrameError> {
+        if let Some(err) = self.frame_error {
+            return Err(err);
+        }
+        self.step_once().map(|(cycles, _)| cycles)
+    }
+
+    /// Steps until the PPU completes a frame, giving up after `max_cycles`. Unlike
+    /// `step_frame` it can't spin forever, which suits embedders and tests.
+    pub fn run_until_vblank(&mut self, max_cycles: u32) -> Result<(), RunError> {
+        if let Some(err) = self.frame_error {
+            return Err(err.into());
+        }
+        if self.bus.is_none() {
+            return Err(RunError::NoCartridge);
+        }
+        let mut cycles: u32 = 0;
+        while cycles < max_cycles {
+            let (step_cycles, frame_ready) = self.step_once()?;
+            if frame_ready {
+                return Ok(());
+            }
+            cycles = cycles.saturating_add(step_cycles);
+        }
+        Err(RunError::Timeout { cycles })
+    }
+
+    /// The framebuffer, but only if a frame has completed since the last call.
+    pub fn take_new_frame(&mut self) -> Option<&[u8]> {
+        if !std::mem::take(&mut self.new_frame) {
+            return None;
+        }
+        Some(self.framebuffer.present())
+    }
+
+    fn step_once(&mut self) -> Result<(u32, bool), FrameError> {
+        if self.boot_animation_frames > 0 {
+            return Ok((self.step_boot_animation(), true));
+        }
+        let Some(bus) = self.bus.as_mut() else {
+            return Ok((0, false));
+        };
+
+        if let Some(trace) = self.trace.as_mut()
+            && !self.cpu.halted()
+            && !self.cpu.stopped()
+            && trace.record(&self.cpu, bus).is_err()
+        {
+            self.trace = None;
+        }
+        let step_cycles = match self.cpu.step(bus) {
+            Ok(count) => count,
+            Err(err) => {
+                let event = UnsupportedOpcode::from_error(err, self.cpu.pc());
+                if self.reported_opcodes.insert((event.cb, event.opcode)) {
+                    self.unsupported_opcodes.push_back(event);
                 }
+                if self.opcode_policy == OpcodePolicy::Stop {
+                    let err = FrameError::UnsupportedOpcode(event);
+                    self.frame_error = Some(err);
+                    return Err(err);
+                }
+                NOP_CYCLES
             }
-            Ok(cycles)
-        } else {
-            Ok(0)
+        };
+        if let Some(fault) = bus.take_mbc_fault() {
+            let err = FrameError::Mbc(fault);
+            self.frame_error = Some(err);
+            return Err(err);
+        }
+        let mut renderer = FrameRenderer {
+            ppu: &mut self.ppu,
+            framebuffer: &mut self.framebuffer,
+            frame_ready: false,
+        };
+        bus.step_with(step_cycles, &mut renderer);
+        let frame_ready = renderer.frame_ready;
+        self.new_frame |= frame_ready;
+
+        while let Some(command) = bus.take_sgb_command() {
+            if self.colorize
+                && let Some(palette) = command.palette()
+            {
+                self.ppu
+                    .set_colorization(Some(PaletteSet::uniform(palette)));
+            }
+        }
+
+        if bus.take_boot_rom_disabled() {
+            self.booted = true;
+            self.boot_rom_disabled = true;
         }
+        Ok((step_cycles, frame_ready))
     }
 
     pub fn apu_step(&mut self, cycles: u32) {
@@ -145,9 +960,45 @@ impl Emulator {
             .unwrap_or(DEFAULT_OUTPUT_SAMPLE_RATE_HZ)
     }
 
-    pub fn apu_set_sample_rate_hz(&mut self, sample_rate_hz: f64) {
+    pub fn apu_set_sample_rate_hz(&mut self, sample_rate_hz: f64) {
+        if let Some(bus) = self.bus.as_mut() {
+            bus.apu_set_sample_rate_hz(sample_rate_hz);
+        }
+    }
+
+    /// How the audio mixer limits loud samples. Kept across loads.
+    pub fn apu_set_clip_mode(&mut self, mode: ClipMode) {
+        self.clip_mode = mode;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.apu_set_clip_mode(mode);
+        }
+    }
+
+    /// Most stereo samples the APU buffers before dropping the oldest. Kept across loads.
+    pub fn apu_set_max_queue(&mut self, max_queue: usize) {
+        self.max_sample_queue = Some(max_queue);
+        if let Some(bus) = self.bus.as_mut() {
+            bus.apu_set_max_queue(max_queue);
+        }
+    }
+
+    /// Whether mixed samples keep full resolution or snap to 4-bit levels. Kept across loads.
+    pub fn apu_set_sample_depth(&mut self, depth: SampleDepth) {
+        self.sample_depth = depth;
         if let Some(bus) = self.bus.as_mut() {
-            bus.apu_set_sample_rate_hz(sample_rate_hz);
+            bus.apu_set_sample_depth(depth);
+        }
+    }
+
+    /// Per-channel mixer gain, from 0.0 to 1.0. Kept across loads.
+    pub fn apu_set_channel_gain(&mut self, channel: ChannelId, gain: f32) {
+        if !gain.is_finite() {
+            return;
+        }
+        let gain = gain.clamp(0.0, 1.0);
+        self.channel_gains[channel.index()] = gain;
+        if let Some(bus) = self.bus.as_mut() {
+            bus.apu_set_channel_gain(channel, gain);
         }
     }
 
@@ -229,13 +1080,56 @@ impl Emulator {
             bus.apu_reset();
         }
     }
+
+    /// Plays a mid-range note on a standalone APU and reports the pitch it measured,
+    /// to catch regressions in the channel timers without a ROM or audio device.
+    pub fn apu_selftest() -> ApuReport {
+        measure_pulse_pitch(SELFTEST_FREQUENCY, SELFTEST_CYCLES)
+    }
+}
+
+/// Clocks the PPU's renderer off the bus, remembering whether a frame completed.
+struct FrameRenderer<'a> {
+    ppu: &'a mut Ppu,
+    framebuffer: &'a mut Framebuffer,
+    frame_ready: bool,
+}
+
+impl Clocked for FrameRenderer<'_> {
+    fn tick(&mut self, cycles: u32, bus: &Bus) {
+        self.frame_ready |= self.ppu.step(cycles, bus, self.framebuffer);
+    }
+}
+
+/// Sets the clock from a `.sav` RTC block, advanced by the time since it was written.
+/// The latched values come back as they were; they only change when the game latches.
+fn restore_rtc_footer(bus: &mut Bus, footer: RtcFooter, now: u64) {
+    let snapshot = RtcSnapshot {
+        registers: footer.registers,
+        subsecond_cycles: 0,
+    };
+    let elapsed = Duration::from_secs(now.saturating_sub(footer.timestamp));
+    bus.restore_rtc(snapshot, elapsed);
+    bus.restore_rtc_latch(footer.latched);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DEFAULT_OUTPUT_SAMPLE_RATE_HZ, Emulator};
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{
+        DEFAULT_OUTPUT_SAMPLE_RATE_HZ, Emulator, FrameError, LoadError, MemoryLoadError,
+        OpcodePolicy, RunError, UnsupportedOpcode,
+    };
     use crate::domain::cartridge::ROM_BANK_SIZE;
-    use crate::domain::{Cartridge, FRAME_SIZE};
+    use crate::domain::{
+        Button, Cartridge, ChannelId, CpuError, FRAME_CYCLES, FRAME_SIZE, FramebufferError,
+        MbcError, MemoryRegion, MockClock, NINTENDO_LOGO, PaletteSet, RTC_FOOTER_SIZE,
+        RomHeaderError, RtcFooter, RtcSource, SaveError, TILE_MAP_SIZE, TILE_MAP_WIDTH,
+        diff_snapshots,
+    };
 
     fn emulator_with_rom() -> Emulator {
         let mut rom = vec![0; ROM_BANK_SIZE];
@@ -246,6 +1140,20 @@ mod tests {
         emulator
     }
 
+    #[test]
+    fn interrupt_state_reports_ie_if_and_ime() {
+        let mut emulator = emulator_with_rom();
+        let bus = emulator.bus.as_mut().expect("bus");
+        bus.write8(0xFFFF, 0x05);
+        bus.write8(0xFF0F, 0x01);
+        emulator.cpu.set_ime(true);
+
+        let (enable, flag, ime) = emulator.interrupt_state();
+        assert_eq!(enable, 0x05);
+        assert_eq!(flag & 0x1F, 0x01);
+        assert!(ime);
+    }
+
     #[test]
     fn new_emulator_starts_unbooted() {
         let emulator = Emulator::new();
@@ -260,6 +1168,501 @@ mod tests {
         assert_eq!(emulator.framebuffer().len(), FRAME_SIZE);
     }
 
+    #[test]
+    fn load_rom_bytes_runs_a_frame() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x18; // JR -2
+        rom[0x0101] = 0xFE;
+        let mut emulator = Emulator::new();
+
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        assert!(emulator.is_booted());
+        assert!(emulator.step_frame().expect("step frame") > 0);
+        assert_eq!(emulator.current_rom_bank(), Some(1));
+    }
+
+    #[test]
+    fn channel_gain_is_clamped_before_it_is_kept() {
+        let mut emulator = Emulator::new();
+
+        emulator.apu_set_channel_gain(ChannelId::Wave, 3.0);
+        emulator.apu_set_channel_gain(ChannelId::Noise, f32::NAN);
+
+        assert_eq!(emulator.channel_gains, [1.0; 4]);
+        emulator.apu_set_channel_gain(ChannelId::Wave, -1.0);
+        assert_eq!(emulator.channel_gains[ChannelId::Wave.index()], 0.0);
+    }
+
+    #[test]
+    fn load_rom_bytes_rejects_truncated_rom() {
+        let mut emulator = Emulator::new();
+
+        let err = emulator
+            .load_rom_bytes(&[0; 0x100], None)
+            .expect_err("truncated rom");
+
+        assert_eq!(
+            err,
+            LoadError::Header(RomHeaderError::TooSmall { actual: 0x100 })
+        );
+        assert!(!emulator.has_bus());
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().expect("buffer lock").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_logs_each_instruction() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        // LD A,$42; INC A; JR -3
+        rom[0x0100..0x0105].copy_from_slice(&[0x3E, 0x42, 0x3C, 0x18, 0xFD]);
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+        let buffer = SharedBuffer::default();
+        emulator.enable_trace(buffer.clone());
+
+        emulator.step_frame().expect("step frame");
+        emulator.disable_trace().expect("flush trace");
+
+        let log = String::from_utf8(buffer.0.lock().expect("buffer lock").clone()).expect("utf8");
+        let lines: Vec<&str> = log.lines().take(4).collect();
+        assert_eq!(
+            lines,
+            [
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:3E,42,3C,18",
+                "A:42 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0102 PCMEM:3C,18,FD,00",
+                "A:43 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0103 PCMEM:18,FD,00,00",
+                "A:43 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0102 PCMEM:3C,18,FD,00",
+            ]
+        );
+
+        emulator.step_frame().expect("step frame");
+        assert_eq!(log.len(), buffer.0.lock().expect("buffer lock").len());
+    }
+
+    #[test]
+    fn mock_time_source_drives_wallclock_rtc() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        let clock = Arc::new(MockClock::new(UNIX_EPOCH));
+        let mut emulator = Emulator::with_time_source(clock.clone());
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+        emulator.set_rtc_source(RtcSource::Wallclock);
+
+        clock.advance(Duration::from_secs(3_661));
+        let snapshot = emulator.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.registers, [1, 1, 1, 0, 0]);
+
+        emulator.soft_reset();
+        clock.advance(Duration::from_millis(500));
+        let snapshot = emulator.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.registers, [1, 1, 1, 0, 0]);
+        assert_eq!(snapshot.subsecond_cycles, 2_097_152);
+    }
+
+    #[test]
+    fn save_round_trips_sram_and_rtc_footer() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = Arc::new(MockClock::new(now));
+        let mut emulator = Emulator::with_time_source(clock.clone());
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        let mut blob = vec![0; 0x2000];
+        blob[0] = 0x5A;
+        blob[0x1FFF] = 0xA5;
+        let footer = RtcFooter {
+            registers: [10, 20, 3, 0x45, 0x01],
+            latched: [10, 20, 3, 0x45, 0x01],
+            timestamp: 1_700_000_000,
+        };
+        blob.extend_from_slice(&footer.encode());
+
+        emulator.import_save(&blob).expect("import save");
+        let snapshot = emulator.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.registers, footer.registers);
+        assert_eq!(emulator.export_save().expect("export save"), blob);
+
+        let stale = RtcFooter {
+            timestamp: 1_700_000_000 - 90,
+            ..footer
+        };
+        blob.truncate(0x2000);
+        blob.extend_from_slice(&stale.encode());
+        emulator.import_save(&blob).expect("import save");
+        let snapshot = emulator.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.registers, [40, 21, 3, 0x45, 0x01]);
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert_eq!(bus.cartridge().ram()[0], 0x5A);
+        assert_eq!(bus.cartridge().ram()[0x1FFF], 0xA5);
+
+        assert_eq!(
+            emulator.import_save(&blob[..100]),
+            Err(SaveError::SizeMismatch {
+                expected: 0x2000 + RTC_FOOTER_SIZE,
+                actual: 100,
+            })
+        );
+    }
+
+    #[test]
+    fn import_save_restores_the_latched_rtc_registers() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = Arc::new(MockClock::new(now));
+        let mut emulator = Emulator::with_time_source(clock);
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        let mut blob = vec![0; 0x2000];
+        let footer = RtcFooter {
+            registers: [10, 20, 3, 0x45, 0x01],
+            latched: [7, 19, 3, 0x45, 0x01],
+            timestamp: 1_700_000_000,
+        };
+        blob.extend_from_slice(&footer.encode());
+        emulator.import_save(&blob).expect("import save");
+
+        let bus = emulator.bus.as_mut().expect("bus");
+        assert_eq!(bus.rtc_latched_registers(), Some(footer.latched));
+        bus.write8(0x0000, 0x0A);
+        bus.write8(0x4000, 0x08);
+        assert_eq!(bus.read8(0xA000), 7);
+        bus.write8(0x4000, 0x09);
+        assert_eq!(bus.read8(0xA000), 19);
+        assert_eq!(emulator.export_save().expect("export save"), blob);
+    }
+
+    #[test]
+    fn open_bus_returns_the_last_byte_the_cpu_read() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x00; // NOP
+        rom[0x0101] = 0xFA; // LD A,(0xFEA0)
+        rom[0x0102] = 0xA0;
+        rom[0x0103] = 0xFE;
+        let mut emulator = Emulator::new();
+        emulator.set_open_bus_last_byte(true);
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        emulator.step_instruction().expect("step instruction");
+        // Observers reading IE and IF in between leave the bus alone.
+        emulator.interrupt_state();
+        emulator.step_instruction().expect("step instruction");
+        assert_eq!(emulator.cpu.regs().a(), 0xFE);
+    }
+
+    #[test]
+    fn take_new_frame_reports_each_frame_once() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x18; // JR -2
+        rom[0x0101] = 0xFE;
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        for _ in 0..10 {
+            emulator.step_instruction().expect("step instruction");
+        }
+        assert!(emulator.take_new_frame().is_none());
+
+        emulator.step_frame().expect("step frame");
+        let frame = emulator.take_new_frame().expect("new frame");
+        assert_eq!(frame.len(), FRAME_SIZE);
+        assert!(emulator.take_new_frame().is_none());
+    }
+
+    #[test]
+    fn boot_animation_shows_the_logo_before_the_cartridge_runs() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x18; // JR 0x0152
+        rom[0x0101] = 0x50;
+        rom[0x0152] = 0x18; // JR -2
+        rom[0x0153] = 0xFE;
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        assert!(emulator.start_boot_animation());
+        assert_eq!(emulator.step_frame(), Ok(FRAME_CYCLES));
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+        let frame = emulator.take_new_frame().expect("logo frame").to_vec();
+        let blank = &frame[..3];
+        let logo_pixels = frame
+            .chunks_exact(3)
+            .filter(|pixel| *pixel != blank)
+            .count();
+        assert!(logo_pixels > 0);
+
+        while emulator.boot_animation_active() {
+            emulator.step_frame().expect("animation frame");
+        }
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+        emulator.step_frame().expect("cartridge frame");
+        assert_eq!(emulator.cpu.pc(), 0x0152);
+    }
+
+    #[test]
+    fn boot_animation_plays_through_every_stepping_call() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x18; // JR -2
+        rom[0x0101] = 0xFE;
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        assert!(emulator.start_boot_animation());
+        assert_eq!(emulator.step_instruction(), Ok(FRAME_CYCLES));
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+        assert!(emulator.take_new_frame().is_some());
+        assert_eq!(emulator.run_until_vblank(FRAME_CYCLES), Ok(()));
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+        assert!(emulator.take_new_frame().is_some());
+        assert!(emulator.boot_animation_active());
+    }
+
+    #[test]
+    fn boot_animation_needs_a_matching_logo() {
+        let mut emulator = emulator_with_rom();
+
+        assert!(!emulator.start_boot_animation());
+        assert!(!emulator.boot_animation_active());
+    }
+
+    #[test]
+    fn render_bg_map_draws_the_seeded_tile() {
+        let mut emulator = emulator_with_rom();
+        emulator.set_palette([[0x00; 3], [0x55; 3], [0xAA; 3], [0xFF; 3]]);
+        {
+            let bus = emulator.bus.as_mut().expect("bus");
+            bus.write8(0xFF40, 0x91); // LCD on, BG map at 0x9800, tiles from 0x8000
+            bus.write8(0xFF47, 0xE4);
+            bus.write8(0x8010, 0xF0); // tile 1, row 0: four pixels of color 1
+            bus.write8(0x8011, 0x00);
+            bus.write8(0x9800 + 32 + 2, 0x01); // tile 1 at map column 2, row 1
+        }
+
+        let mut map = vec![0xEE; TILE_MAP_SIZE];
+        assert_eq!(emulator.render_bg_map(&mut map), Ok(()));
+        let pixel = |x: usize, y: usize| {
+            let idx = (y * TILE_MAP_WIDTH + x) * 3;
+            [map[idx], map[idx + 1], map[idx + 2]]
+        };
+        assert_eq!(pixel(16, 8), [0x55; 3]);
+        assert_eq!(pixel(19, 8), [0x55; 3]);
+        assert_eq!(pixel(20, 8), [0x00; 3]);
+        assert_eq!(pixel(16, 9), [0x00; 3]);
+        assert_eq!(pixel(0, 0), [0x00; 3]);
+        assert!(matches!(
+            emulator.render_bg_map(&mut map[..10]),
+            Err(FramebufferError::BufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn set_button_updates_both_joyp_groups() {
+        let mut emulator = emulator_with_rom();
+
+        emulator.set_button(Button::A, true);
+        emulator.set_button(Button::Right, true);
+        let frame = emulator.movie_frame();
+        assert_eq!(frame.buttons, 0x0E);
+        assert_eq!(frame.dpad, 0x0E);
+
+        emulator.set_button(Button::A, false);
+        assert_eq!(emulator.movie_frame().buttons, 0x0F);
+        assert_eq!(emulator.movie_frame().dpad, 0x0E);
+    }
+
+    #[test]
+    fn emulator_is_send() {
+        // The GUI's threaded mode moves the emulator onto a worker thread.
+        fn assert_send<T: Send>() {}
+        assert_send::<Emulator>();
+    }
+
+    #[test]
+    fn run_until_vblank_completes_a_frame_within_the_cap() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100..0x0106].copy_from_slice(&[
+            0x3E, 0x91, // LD A,0x91
+            0xE0, 0x40, // LDH (LCDC),A
+            0x18, 0xFE, // JR -2
+        ]);
+        let mut emulator = Emulator::new();
+        assert_eq!(
+            emulator.run_until_vblank(FRAME_CYCLES),
+            Err(RunError::NoCartridge)
+        );
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        assert!(matches!(
+            emulator.run_until_vblank(1_000),
+            Err(RunError::Timeout { cycles }) if cycles >= 1_000
+        ));
+        emulator
+            .run_until_vblank(FRAME_CYCLES * 2)
+            .expect("frame within cap");
+        assert!(emulator.take_new_frame().is_some());
+    }
+
+    #[test]
+    fn step_frame_runs_about_one_frame_of_cycles() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x18; // JR -2
+        rom[0x0101] = 0xFE;
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+        assert_eq!(emulator.last_frame_cycles(), 0);
+
+        // The LCD starts at LY 0, so the first frame ends at the first VBlank, 144 lines in.
+        let cycles = emulator.step_frame().expect("step frame");
+        assert!(cycles.abs_diff(144 * 456) <= 12, "{cycles}");
+        for _ in 0..3 {
+            let cycles = emulator.step_frame().expect("step frame");
+            assert_eq!(emulator.last_frame_cycles(), cycles);
+            // Frames end on an instruction boundary, so each can overshoot by one JR.
+            assert!(cycles.abs_diff(FRAME_CYCLES) <= 12, "{cycles}");
+        }
+    }
+
+    fn rom_with_illegal_opcode() -> Vec<u8> {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0xEC; // illegal
+        rom[0x0101] = 0x3E; // LD A,0x42
+        rom[0x0102] = 0x42;
+        rom[0x0103] = 0x18; // JR -2
+        rom[0x0104] = 0xFE;
+        rom
+    }
+
+    #[test]
+    fn unsupported_opcode_stops_by_default() {
+        let mut emulator = Emulator::new();
+        emulator
+            .load_rom_bytes(&rom_with_illegal_opcode(), None)
+            .expect("load rom");
+
+        let expected = FrameError::UnsupportedOpcode(UnsupportedOpcode {
+            opcode: 0xEC,
+            cb: false,
+            pc: 0x0100,
+        });
+        assert_eq!(emulator.step_frame(), Err(expected));
+        assert_eq!(emulator.step_frame(), Err(expected));
+        assert_eq!(emulator.frame_error(), Some(expected));
+        assert_eq!(
+            expected.cpu_error(),
+            Some(CpuError::UnimplementedOpcode(0xEC))
+        );
+        assert_eq!(
+            expected.message(),
+            "CPU stopped: unsupported opcode 0xec at 0x0100"
+        );
+        let event = emulator.take_unsupported_opcode().expect("event");
+        assert_eq!(event.message(), "unsupported opcode 0xec at 0x0100");
+        assert!(emulator.take_unsupported_opcode().is_none());
+    }
+
+    #[test]
+    fn mapper_fault_stops_the_frame() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0xFE; // HuC3
+        rom[0x0149] = 0x02;
+        rom[0x0100] = 0x3E; // LD A,0x05
+        rom[0x0101] = 0x05;
+        rom[0x0102] = 0xEA; // LD (0x0000),A: an undefined mode
+        rom[0x0103] = 0x00;
+        rom[0x0104] = 0x00;
+        rom[0x0105] = 0xEA; // LD (0xA000),A
+        rom[0x0106] = 0x00;
+        rom[0x0107] = 0xA0;
+        rom[0x0108] = 0x18; // JR -2
+        rom[0x0109] = 0xFE;
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        let expected = FrameError::Mbc(MbcError::Fault("HuC3 write in an undefined mode"));
+        assert_eq!(emulator.step_frame(), Err(expected));
+        assert_eq!(emulator.step_instruction(), Err(expected));
+        assert_eq!(emulator.frame_error(), Some(expected));
+        assert_eq!(expected.cpu_error(), None);
+        assert_eq!(
+            expected.message(),
+            "Cartridge stopped: HuC3 write in an undefined mode"
+        );
+        assert!(emulator.take_unsupported_opcode().is_none());
+    }
+
+    #[test]
+    fn nop_policy_skips_unsupported_opcode() {
+        let mut emulator = Emulator::new();
+        emulator.set_opcode_policy(OpcodePolicy::Nop);
+        emulator
+            .load_rom_bytes(&rom_with_illegal_opcode(), None)
+            .expect("load rom");
+
+        emulator.step_frame().expect("frame keeps running");
+        emulator.step_frame().expect("second frame");
+
+        // LD A,0x42 right after the skipped byte ran, and the JR loop holds the CPU there.
+        assert_eq!(emulator.cpu.regs().a(), 0x42);
+        assert_eq!(emulator.cpu.pc(), 0x0103);
+    }
+
+    #[test]
+    fn nop_policy_reports_each_opcode_once() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0xEC;
+        rom[0x0101] = 0x18; // JR -3, back onto the illegal byte
+        rom[0x0102] = 0xFD;
+        let mut emulator = Emulator::new();
+        emulator.set_opcode_policy(OpcodePolicy::Nop);
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+
+        emulator.step_frame().expect("frame");
+
+        assert_eq!(
+            emulator.take_unsupported_opcode(),
+            Some(UnsupportedOpcode {
+                opcode: 0xEC,
+                cb: false,
+                pc: 0x0100,
+            })
+        );
+        assert!(emulator.take_unsupported_opcode().is_none());
+    }
+
+    #[test]
+    fn colorization_applies_to_dmg_cartridges_only() {
+        let mut emulator = emulator_with_rom();
+        emulator.set_colorization(true);
+        assert_eq!(emulator.colorization(), Some(PaletteSet::GRAYSCALE));
+        emulator.set_colorization(false);
+        assert_eq!(emulator.colorization(), None);
+
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0143] = 0x80;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        emulator.load_cartridge(cartridge).expect("load cartridge");
+        emulator.set_colorization(true);
+        assert_eq!(emulator.colorization(), None);
+    }
+
     #[test]
     fn emulator_apu_has_correct_sample_rate() {
         let emulator = emulator_with_rom();
@@ -350,4 +1753,248 @@ mod tests {
 
         assert!(!emulator.is_cgb());
     }
+
+    #[test]
+    fn memory_snapshots_diff_to_the_poked_address() {
+        let mut emulator = Emulator::new();
+        assert!(emulator.memory_snapshot(MemoryRegion::Wram).is_empty());
+        let cartridge = Cartridge::from_bytes(vec![0; ROM_BANK_SIZE]).expect("cartridge");
+        emulator.load_cartridge(cartridge).expect("load cartridge");
+
+        let before = emulator.memory_snapshot(MemoryRegion::Wram);
+        assert_eq!(before.len(), 0x2000);
+        let old = before[0x0123];
+        emulator
+            .bus
+            .as_mut()
+            .expect("bus")
+            .write8(0xC123, old.wrapping_add(1));
+        let after = emulator.memory_snapshot(MemoryRegion::Wram);
+
+        assert_eq!(
+            diff_snapshots(MemoryRegion::Wram, &before, &after),
+            [(0xC123, old, old.wrapping_add(1))]
+        );
+    }
+
+    fn emulator_with_program(boot_rom: Option<Vec<u8>>) -> Emulator {
+        let mut rom = vec![0; ROM_BANK_SIZE];
+        rom[0x0147] = 0x00;
+        // LD A,0x42; LD (0xC000),A; JR -2
+        rom[0x0100..0x0107].copy_from_slice(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let mut emulator = Emulator::new();
+        emulator
+            .load_cartridge_with_boot_rom(cartridge, boot_rom)
+            .expect("load cartridge");
+        emulator
+    }
+
+    #[test]
+    fn emulator_soft_reset_restarts_at_entry_and_clears_wram() {
+        let mut emulator = emulator_with_program(None);
+        emulator.step_frame().expect("step frame");
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert_eq!(bus.read8(0xC000), 0x42);
+        assert_ne!(emulator.cpu.pc(), 0x0100);
+
+        emulator.soft_reset();
+
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+        assert_eq!(bus.read8(0xC000), 0x00);
+        assert!(emulator.is_booted());
+        assert!(!emulator.apu_has_sample());
+    }
+
+    #[test]
+    fn emulator_soft_reset_reenables_boot_rom() {
+        // JR -2 at 0x0000 keeps the boot ROM mapped.
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0..2].copy_from_slice(&[0x18, 0xFE]);
+        let mut emulator = emulator_with_program(Some(boot_rom));
+        emulator.step_frame().expect("step frame");
+        emulator.bus.as_mut().expect("bus").write8(0xFF50, 0x01);
+
+        emulator.soft_reset();
+
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert_eq!(emulator.cpu.pc(), 0x0000);
+        assert!(bus.boot_rom_enabled());
+        assert!(!emulator.is_booted());
+    }
+
+    #[test]
+    fn step_cycles_stops_at_the_budget_mid_frame() {
+        let mut emulator = emulator_with_program(None);
+
+        let result = emulator.step_cycles(1000).expect("step cycles");
+        assert!((1000..1024).contains(&result.cycles), "{result:?}");
+        assert!(!result.frame_completed);
+
+        let mut ran = result.cycles;
+        loop {
+            let result = emulator.step_cycles(1000).expect("step cycles");
+            ran += result.cycles;
+            if result.frame_completed {
+                break;
+            }
+        }
+        assert!(ran <= FRAME_CYCLES + 24, "{ran}");
+    }
+
+    #[test]
+    fn frames_complete_as_ly_enters_vblank() {
+        let mut emulator = emulator_with_program(None);
+        let ly = |emulator: &Emulator| emulator.bus.as_ref().expect("bus").read8(0xFF44);
+
+        for _ in 0..2 {
+            loop {
+                let before = ly(&emulator);
+                let result = emulator.step_cycles(1).expect("step");
+                if result.frame_completed {
+                    assert!(before < 144, "LY {before}");
+                    break;
+                }
+            }
+            assert_eq!(ly(&emulator), 144);
+        }
+    }
+
+    #[test]
+    fn loaded_vram_and_oam_render_without_byte_writes() {
+        let mut emulator = emulator_with_program(None);
+        // Tile 1 is solid color 3; the map stays on tile 0, which is blank.
+        let mut vram = vec![0; 0x2000];
+        vram[0x10..0x20].fill(0xFF);
+        emulator.load_vram(&vram).expect("vram");
+        // One sprite at the top-left corner using tile 1.
+        emulator.load_oam(&[16, 8, 1, 0]).expect("oam");
+        let bus = emulator.bus.as_mut().expect("bus");
+        bus.write8(0xFF40, 0x93);
+        bus.write8(0xFF47, 0xE4);
+        bus.write8(0xFF48, 0xE4);
+
+        emulator.step_frame().expect("step frame");
+        emulator.step_frame().expect("step frame");
+        let frame = emulator.framebuffer().present();
+        let pixel = |x: usize, y: usize| &frame[(y * 160 + x) * 3..(y * 160 + x) * 3 + 3];
+        assert_eq!(pixel(0, 0), pixel(7, 7));
+        assert_ne!(pixel(0, 0), pixel(8, 0));
+        assert_eq!(pixel(8, 0), pixel(159, 143));
+
+        assert_eq!(
+            emulator.load_oam(&[0; 0xA1]),
+            Err(MemoryLoadError::TooLarge {
+                max: 0xA0,
+                actual: 0xA1
+            })
+        );
+        assert_eq!(
+            Emulator::new().load_vram(&vram),
+            Err(MemoryLoadError::NoCartridge)
+        );
+    }
+
+    #[test]
+    fn emulator_reports_boot_rom_handoff_once() {
+        // LD A,0x01; LDH (0x50),A; JR -2
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0..6].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50, 0x18, 0xFE]);
+        let mut emulator = emulator_with_program(Some(boot_rom));
+        assert!(!emulator.take_boot_rom_disabled());
+
+        emulator.step_frame().expect("step frame");
+        assert!(emulator.take_boot_rom_disabled());
+        assert!(!emulator.take_boot_rom_disabled());
+
+        emulator.step_frame().expect("step frame");
+        assert!(!emulator.take_boot_rom_disabled());
+    }
+
+    fn emulator_with_battery_ram() -> Emulator {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x0149] = 0x02; // 8KB
+        let mut cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        cartridge.ram_mut()[0] = 0x5A;
+        let mut emulator = Emulator::new();
+        emulator.load_cartridge(cartridge).expect("load cartridge");
+        emulator
+    }
+
+    #[test]
+    fn emulator_soft_reset_keeps_ext_ram() {
+        let mut emulator = emulator_with_battery_ram();
+
+        emulator.soft_reset();
+
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert_eq!(bus.cartridge().ext_ram[0], 0x5A);
+    }
+
+    #[test]
+    fn emulator_power_cycle_clears_ext_ram() {
+        let mut emulator = emulator_with_battery_ram();
+
+        emulator.power_cycle();
+
+        let bus = emulator.bus.as_ref().expect("bus");
+        assert!(bus.cartridge().ext_ram.iter().all(|&byte| byte == 0));
+        assert!(!bus.cartridge().is_ram_dirty());
+        assert_eq!(emulator.cpu.pc(), 0x0100);
+    }
+
+    #[test]
+    fn apu_selftest_measures_the_programmed_pitch() {
+        let report = Emulator::apu_selftest();
+        assert!((report.expected_hz - 439.8).abs() < 0.1);
+        assert!(
+            (report.measured_hz - report.expected_hz).abs() < report.expected_hz * 0.02,
+            "{report:?}"
+        );
+        assert!(report.passed());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::Emulator;
+    use crate::domain::cartridge::ROM_BANK_SIZE;
+    use crate::domain::{Cartridge, Cpu};
+    use proptest::prelude::*;
+
+    // Property: arbitrary CPU state and RAM never panic, whatever the bytes decode to
+    proptest! {
+        #[test]
+        fn prop_arbitrary_state_steps_without_panic(
+            wram in proptest::collection::vec(any::<u8>(), 0x2000),
+            hram in proptest::collection::vec(any::<u8>(), 0x7F),
+            registers in any::<[u8; 8]>(),
+            pc in 0xC000u16..0xE000,
+            sp in any::<u16>(),
+            ime in any::<bool>(),
+        ) {
+            let cartridge =
+                Cartridge::from_bytes(vec![0; ROM_BANK_SIZE * 2]).expect("cartridge");
+            let mut cpu = Cpu::new();
+            let [a, f, b, c, d, e, h, l] = registers;
+            cpu.regs_mut().set_af(u16::from_be_bytes([a, f]));
+            cpu.regs_mut().set_bc(u16::from_be_bytes([b, c]));
+            cpu.regs_mut().set_de(u16::from_be_bytes([d, e]));
+            cpu.regs_mut().set_hl(u16::from_be_bytes([h, l]));
+            cpu.set_pc(pc);
+            cpu.set_sp(sp);
+            cpu.set_ime(ime);
+
+            let mut emulator =
+                Emulator::from_state_parts(cartridge, cpu, &wram, &hram).expect("emulator");
+            prop_assert_eq!(emulator.cpu.pc(), pc);
+            for _ in 0..256 {
+                if emulator.step_instruction().is_err() {
+                    break;
+                }
+            }
+        }
+    }
 }
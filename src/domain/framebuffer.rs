@@ -2,10 +2,23 @@ pub const FRAME_WIDTH: usize = 160;
 pub const FRAME_HEIGHT: usize = 144;
 pub const FRAME_CHANNELS: usize = 3;
 pub const FRAME_SIZE: usize = FRAME_WIDTH * FRAME_HEIGHT * FRAME_CHANNELS;
+pub const FRAME_RGBA_SIZE: usize = FRAME_WIDTH * FRAME_HEIGHT * 4;
+const RGB_BATCH: usize = FRAME_CHANNELS * 4;
+const RGBA_BATCH: usize = 16;
+const LCD_DARK: [u8; 3] = [0x2A, 0x3A, 0x22];
+const LCD_LIGHT: [u8; 3] = [0xC4, 0xCF, 0xA1];
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferError {
+    BufferTooSmall { needed: usize, actual: usize },
+}
+
+/// A double-buffered RGB frame. Rendering draws into the back buffer; `swap` publishes
+/// it to the front, which is what `present` and the display side read.
 #[derive(Debug, Clone)]
 pub struct Framebuffer {
     pixels: Vec<u8>,
+    front: Vec<u8>,
 }
 
 impl Default for Framebuffer {
@@ -18,9 +31,11 @@ impl Framebuffer {
     pub fn new() -> Self {
         Self {
             pixels: vec![0; FRAME_SIZE],
+            front: vec![0; FRAME_SIZE],
         }
     }
 
+    /// The back buffer, as drawn so far.
     pub fn as_slice(&self) -> &[u8] {
         &self.pixels
     }
@@ -29,6 +44,13 @@ impl Framebuffer {
         &mut self.pixels
     }
 
+    /// Paints every pixel of the back buffer `color`.
+    pub fn fill(&mut self, color: [u8; 3]) {
+        for pixel in self.pixels.chunks_exact_mut(FRAME_CHANNELS) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.pixels.len()
     }
@@ -36,4 +58,200 @@ impl Framebuffer {
     pub fn is_empty(&self) -> bool {
         self.pixels.is_empty()
     }
+
+    /// The last completed frame. It only changes on `swap`, so a reader never sees a
+    /// half-drawn frame, and its address stays put for embedders holding a pointer.
+    pub fn present(&self) -> &[u8] {
+        &self.front
+    }
+
+    /// Publishes the back buffer as the new front. The back buffer keeps its contents.
+    pub fn swap(&mut self) {
+        self.front.copy_from_slice(&self.pixels);
+    }
+
+    /// Writes the presented frame as tightly packed, fully opaque RGBA into the start of
+    /// `out`.
+    pub fn copy_rgba(&self, out: &mut [u8]) -> Result<(), FramebufferError> {
+        let needed = self.front.len() / FRAME_CHANNELS * 4;
+        if out.len() < needed {
+            return Err(FramebufferError::BufferTooSmall {
+                needed,
+                actual: out.len(),
+            });
+        }
+        rgb_to_rgba(&self.front, out);
+        Ok(())
+    }
+
+    /// Filters both buffers, so the result shows whichever one is read.
+    pub fn apply_dmg_lcd(&mut self) {
+        dmg_lcd_filter(&mut self.pixels);
+        dmg_lcd_filter(&mut self.front);
+    }
+}
+
+/// Runs `dmg_lcd_color` over every pixel of a packed RGB frame.
+pub fn dmg_lcd_filter(rgb: &mut [u8]) {
+    for pixel in rgb.chunks_exact_mut(FRAME_CHANNELS) {
+        let color = dmg_lcd_color([pixel[0], pixel[1], pixel[2]]);
+        pixel.copy_from_slice(&color);
+    }
+}
+
+/// Expands packed RGB pixels into opaque RGBA, as many as fit in both slices.
+pub fn rgb_to_rgba(rgb: &[u8], out: &mut [u8]) {
+    // Four pixels at a time give the compiler whole 16-byte stores to vectorize.
+    let batches = (rgb.len() / RGB_BATCH).min(out.len() / RGBA_BATCH);
+    for (s, d) in rgb
+        .chunks_exact(RGB_BATCH)
+        .zip(out.chunks_exact_mut(RGBA_BATCH))
+    {
+        d.copy_from_slice(&[
+            s[0], s[1], s[2], 0xFF, s[3], s[4], s[5], 0xFF, s[6], s[7], s[8], 0xFF, s[9], s[10],
+            s[11], 0xFF,
+        ]);
+    }
+    let rgb = &rgb[batches * RGB_BATCH..];
+    let out = &mut out[batches * RGBA_BATCH..];
+    for (s, d) in rgb
+        .chunks_exact(FRAME_CHANNELS)
+        .zip(out.chunks_exact_mut(4))
+    {
+        d.copy_from_slice(&[s[0], s[1], s[2], 0xFF]);
+    }
+}
+
+/// Approximates the original DMG LCD: luminance is squeezed into its narrow green-gray
+/// range, and only a quarter of the source color survives on top.
+pub fn dmg_lcd_color(color: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = color.map(u32::from);
+    let luma = (77 * r + 150 * g + 29 * b) >> 8;
+    let mut out = [0; 3];
+    for channel in 0..3 {
+        let dark = u32::from(LCD_DARK[channel]);
+        let light = u32::from(LCD_LIGHT[channel]);
+        let tone = dark + luma * (light - dark) / 255;
+        out[channel] = ((tone * 3 + u32::from(color[channel])) / 4) as u8;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FRAME_CHANNELS, FRAME_RGBA_SIZE, Framebuffer, FramebufferError, dmg_lcd_color, rgb_to_rgba,
+    };
+
+    #[test]
+    fn copy_rgba_adds_opaque_alpha() {
+        let mut framebuffer = Framebuffer::new();
+        for (index, byte) in framebuffer.as_mut_slice().iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        framebuffer.swap();
+        let mut out = vec![0; FRAME_RGBA_SIZE];
+
+        framebuffer.copy_rgba(&mut out).expect("copy rgba");
+
+        for (rgb, rgba) in framebuffer
+            .present()
+            .chunks_exact(FRAME_CHANNELS)
+            .zip(out.chunks_exact(4))
+        {
+            assert_eq!(&rgba[..3], rgb);
+            assert_eq!(rgba[3], 0xFF);
+        }
+    }
+
+    #[test]
+    fn fill_sets_every_pixel() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.fill([0x12, 0x34, 0x56]);
+
+        assert!(
+            framebuffer
+                .as_slice()
+                .chunks_exact(FRAME_CHANNELS)
+                .all(|pixel| pixel == [0x12, 0x34, 0x56])
+        );
+        assert!(framebuffer.present().iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn rgb_to_rgba_matches_per_pixel_expansion() {
+        // 23 pixels leaves a partial batch at the end.
+        let rgb: Vec<u8> = (0..23 * FRAME_CHANNELS).map(|i| (i * 7) as u8).collect();
+        let mut expected = Vec::new();
+        for pixel in rgb.chunks_exact(FRAME_CHANNELS) {
+            expected.extend_from_slice(pixel);
+            expected.push(0xFF);
+        }
+        let mut out = vec![0; expected.len()];
+        rgb_to_rgba(&rgb, &mut out);
+        assert_eq!(out, expected);
+
+        let mut short = vec![0; 4 * 5 + 2];
+        rgb_to_rgba(&rgb, &mut short);
+        assert_eq!(&short[..20], &expected[..20]);
+        assert_eq!(&short[20..], &[0, 0]);
+    }
+
+    #[test]
+    fn back_buffer_writes_stay_hidden_until_swap() {
+        let mut framebuffer = Framebuffer::new();
+        let front = framebuffer.present().as_ptr();
+        framebuffer.as_mut_slice().fill(0x7F);
+        assert!(framebuffer.present().iter().all(|&byte| byte == 0));
+
+        framebuffer.swap();
+        assert!(framebuffer.present().iter().all(|&byte| byte == 0x7F));
+        assert_eq!(framebuffer.present().as_ptr(), front);
+
+        framebuffer.as_mut_slice()[0] = 0x01;
+        assert_eq!(framebuffer.present()[0], 0x7F);
+    }
+
+    #[test]
+    fn copy_rgba_rejects_short_buffer() {
+        let framebuffer = Framebuffer::new();
+        let mut out = vec![0; FRAME_RGBA_SIZE - 1];
+
+        assert_eq!(
+            framebuffer.copy_rgba(&mut out),
+            Err(FramebufferError::BufferTooSmall {
+                needed: FRAME_RGBA_SIZE,
+                actual: FRAME_RGBA_SIZE - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn dmg_lcd_compresses_white_and_black() {
+        assert_eq!(dmg_lcd_color([0xFF, 0xFF, 0xFF]), [0xD2, 0xDB, 0xB8]);
+        assert_eq!(dmg_lcd_color([0x00, 0x00, 0x00]), [0x1F, 0x2B, 0x19]);
+    }
+
+    #[test]
+    fn dmg_lcd_desaturates_colors() {
+        let [r, g, b] = dmg_lcd_color([0xFF, 0x00, 0x00]);
+        let spread = r.max(g).max(b) - r.min(g).min(b);
+        assert!(spread < 0x60, "spread {spread:#x}");
+    }
+
+    #[test]
+    fn dmg_lcd_keeps_gray_ordering() {
+        let dark = dmg_lcd_color([0x55, 0x55, 0x55]);
+        let light = dmg_lcd_color([0xAA, 0xAA, 0xAA]);
+        assert!((0..3).all(|channel| dark[channel] < light[channel]));
+    }
+
+    #[test]
+    fn apply_dmg_lcd_maps_every_pixel() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.apply_dmg_lcd();
+        for pixel in framebuffer.as_slice().chunks_exact(FRAME_CHANNELS) {
+            assert_eq!(pixel, dmg_lcd_color([0, 0, 0]));
+        }
+    }
 }
@@ -0,0 +1,372 @@
+pub const DPAD_RIGHT: u8 = 0x01;
+pub const DPAD_LEFT: u8 = 0x02;
+pub const DPAD_UP: u8 = 0x04;
+pub const DPAD_DOWN: u8 = 0x08;
+pub const DEFAULT_AUTOFIRE_RATE: u32 = 1;
+const HORIZONTAL: u8 = DPAD_RIGHT | DPAD_LEFT;
+const VERTICAL: u8 = DPAD_UP | DPAD_DOWN;
+
+const BUTTON_A: u8 = 0x01;
+const BUTTON_B: u8 = 0x02;
+const BUTTON_SELECT: u8 = 0x04;
+const BUTTON_START: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "right" => Some(Self::Right),
+            "left" => Some(Self::Left),
+            "up" => Some(Self::Up),
+            "down" => Some(Self::Down),
+            "a" => Some(Self::A),
+            "b" => Some(Self::B),
+            "select" => Some(Self::Select),
+            "start" => Some(Self::Start),
+            _ => None,
+        }
+    }
+
+    /// Whether the button is on the d-pad group, and its JOYP line within the group.
+    fn line(self) -> (bool, u8) {
+        match self {
+            Self::Right => (true, DPAD_RIGHT),
+            Self::Left => (true, DPAD_LEFT),
+            Self::Up => (true, DPAD_UP),
+            Self::Down => (true, DPAD_DOWN),
+            Self::A => (false, BUTTON_A),
+            Self::B => (false, BUTTON_B),
+            Self::Select => (false, BUTTON_SELECT),
+            Self::Start => (false, BUTTON_START),
+        }
+    }
+}
+
+/// Held buttons, for callers that would rather not build JOYP nibbles themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ButtonState {
+    dpad: u8,
+    buttons: u8,
+}
+
+impl ButtonState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, button: Button, pressed: bool) {
+        let (is_dpad, line) = button.line();
+        let group = if is_dpad {
+            &mut self.dpad
+        } else {
+            &mut self.buttons
+        };
+        if pressed {
+            *group |= line;
+        } else {
+            *group &= !line;
+        }
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        let (is_dpad, line) = button.line();
+        let group = if is_dpad { self.dpad } else { self.buttons };
+        group & line != 0
+    }
+
+    /// Active-low JOYP nibbles, buttons then d-pad, as `Bus::joyp_masks` returns them.
+    pub fn joyp_masks(&self) -> (u8, u8) {
+        (!self.buttons & 0x0F, !self.dpad & 0x0F)
+    }
+}
+
+/// How simultaneous opposing directions (SOCD) reach the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SocdMode {
+    /// Both lines go low, as on hardware.
+    #[default]
+    Passthrough,
+    /// Opposing directions cancel out.
+    Neutral,
+    /// The most recently pressed direction wins.
+    LastInput,
+}
+
+impl SocdMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Passthrough => "Off",
+            Self::Neutral => "Neutral",
+            Self::LastInput => "Last",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Passthrough),
+            "neutral" => Some(Self::Neutral),
+            "last" => Some(Self::LastInput),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Passthrough => Self::Neutral,
+            Self::Neutral => Self::LastInput,
+            Self::LastInput => Self::Passthrough,
+        }
+    }
+}
+
+/// Held d-pad directions, remembering the latest press on each axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dpad {
+    held: u8,
+    last_horizontal: u8,
+    last_vertical: u8,
+}
+
+impl Dpad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, direction: u8, pressed: bool) {
+        if pressed && self.held & direction == 0 {
+            if direction & HORIZONTAL != 0 {
+                self.last_horizontal = direction;
+            } else {
+                self.last_vertical = direction;
+            }
+        }
+        if pressed {
+            self.held |= direction;
+        } else {
+            self.held &= !direction;
+        }
+    }
+
+    /// Directions held on either pad. Where `other` holds its latest press on an axis,
+    /// that press counts as the latest.
+    pub fn merged(&self, other: &Dpad) -> Dpad {
+        let latest = |own: u8, theirs: u8| {
+            if other.held & theirs != 0 {
+                theirs
+            } else {
+                own
+            }
+        };
+        Dpad {
+            held: self.held | other.held,
+            last_horizontal: latest(self.last_horizontal, other.last_horizontal),
+            last_vertical: latest(self.last_vertical, other.last_vertical),
+        }
+    }
+
+    /// Active-low JOYP d-pad nibble with opposing directions resolved per `mode`.
+    pub fn joyp_nibble(&self, mode: SocdMode) -> u8 {
+        let held = resolve_axis(self.held, HORIZONTAL, self.last_horizontal, mode)
+            | resolve_axis(self.held, VERTICAL, self.last_vertical, mode);
+        !held & 0x0F
+    }
+}
+
+fn resolve_axis(held: u8, axis: u8, last: u8, mode: SocdMode) -> u8 {
+    let pressed = held & axis;
+    if pressed != axis {
+        return pressed;
+    }
+    match mode {
+        SocdMode::Passthrough => pressed,
+        SocdMode::Neutral => 0,
+        SocdMode::LastInput => last,
+    }
+}
+
+/// Which buttons pulse on their own while held, and how fast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Autofire {
+    pub a: bool,
+    pub b: bool,
+    /// Frames each press and release lasts.
+    pub rate: u32,
+}
+
+impl Autofire {
+    pub fn new() -> Self {
+        Self {
+            a: false,
+            b: false,
+            rate: DEFAULT_AUTOFIRE_RATE,
+        }
+    }
+
+    /// Whether a held autofire button reads as pressed on `frame`.
+    pub fn pressed_on(&self, frame: u64) -> bool {
+        let rate = u64::from(self.rate.max(1));
+        (frame / rate).is_multiple_of(2)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match (self.a, self.b) {
+            (false, false) => "Off",
+            (true, false) => "A",
+            (false, true) => "B",
+            (true, true) => "A+B",
+        }
+    }
+
+    pub fn set_label(&mut self, label: &str) -> bool {
+        let (a, b) = match label.to_ascii_uppercase().as_str() {
+            "OFF" => (false, false),
+            "A" => (true, false),
+            "B" => (false, true),
+            "A+B" => (true, true),
+            _ => return false,
+        };
+        self.a = a;
+        self.b = b;
+        true
+    }
+
+    /// Steps through Off, A, B and A+B.
+    pub fn cycle(&mut self) {
+        let (a, b) = match (self.a, self.b) {
+            (false, false) => (true, false),
+            (true, false) => (false, true),
+            (false, true) => (true, true),
+            (true, true) => (false, false),
+        };
+        self.a = a;
+        self.b = b;
+    }
+}
+
+impl Default for Autofire {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Autofire, Button, ButtonState, DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP, Dpad, SocdMode,
+    };
+
+    #[test]
+    fn button_state_tracks_each_group() {
+        let mut state = ButtonState::new();
+        assert_eq!(state.joyp_masks(), (0x0F, 0x0F));
+
+        state.set(Button::Start, true);
+        state.set(Button::Down, true);
+        state.set(Button::Up, true);
+        state.set(Button::Up, false);
+        assert_eq!(state.joyp_masks(), (0x07, 0x07));
+        assert!(state.is_pressed(Button::Start));
+        assert!(!state.is_pressed(Button::Up));
+        assert_eq!(Button::from_name("SELECT"), Some(Button::Select));
+    }
+
+    fn left_then_right() -> Dpad {
+        let mut dpad = Dpad::new();
+        dpad.set(DPAD_LEFT, true);
+        dpad.set(DPAD_RIGHT, true);
+        dpad
+    }
+
+    #[test]
+    fn passthrough_reports_both_directions() {
+        assert_eq!(left_then_right().joyp_nibble(SocdMode::Passthrough), 0x0C);
+    }
+
+    #[test]
+    fn neutral_cancels_opposing_directions() {
+        assert_eq!(left_then_right().joyp_nibble(SocdMode::Neutral), 0x0F);
+    }
+
+    #[test]
+    fn last_input_prefers_latest_press() {
+        let mut dpad = left_then_right();
+        assert_eq!(dpad.joyp_nibble(SocdMode::LastInput), 0x0E);
+
+        dpad.set(DPAD_LEFT, false);
+        dpad.set(DPAD_LEFT, true);
+        assert_eq!(dpad.joyp_nibble(SocdMode::LastInput), 0x0D);
+    }
+
+    #[test]
+    fn socd_leaves_other_axis_alone() {
+        let mut dpad = left_then_right();
+        dpad.set(DPAD_UP, true);
+        assert_eq!(dpad.joyp_nibble(SocdMode::Neutral), 0x0B);
+
+        dpad.set(DPAD_DOWN, true);
+        assert_eq!(dpad.joyp_nibble(SocdMode::LastInput), 0x06);
+    }
+
+    #[test]
+    fn merged_dpad_holds_both_and_keeps_the_other_latest_press() {
+        let mut keys = Dpad::new();
+        keys.set(DPAD_LEFT, true);
+        let mut pad = Dpad::new();
+        pad.set(DPAD_RIGHT, true);
+        pad.set(DPAD_UP, true);
+
+        let merged = keys.merged(&pad);
+        assert_eq!(merged.joyp_nibble(SocdMode::Passthrough), 0x08);
+        assert_eq!(merged.joyp_nibble(SocdMode::LastInput), 0x0A);
+        assert_eq!(pad.merged(&keys).joyp_nibble(SocdMode::LastInput), 0x09);
+    }
+
+    #[test]
+    fn autofire_alternates_every_rate_frames() {
+        let mut autofire = Autofire::new();
+        let pattern: Vec<bool> = (0..6).map(|frame| autofire.pressed_on(frame)).collect();
+        assert_eq!(pattern, [true, false, true, false, true, false]);
+
+        autofire.rate = 3;
+        let pattern: Vec<bool> = (0..8).map(|frame| autofire.pressed_on(frame)).collect();
+        assert_eq!(pattern, [true, true, true, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn autofire_zero_rate_behaves_like_one() {
+        let autofire = Autofire {
+            a: true,
+            b: false,
+            rate: 0,
+        };
+        assert!(autofire.pressed_on(0));
+        assert!(!autofire.pressed_on(1));
+    }
+
+    #[test]
+    fn autofire_label_roundtrips_through_cycle() {
+        let mut autofire = Autofire::new();
+        let mut labels = Vec::new();
+        for _ in 0..4 {
+            labels.push(autofire.label());
+            autofire.cycle();
+        }
+        assert_eq!(labels, ["Off", "A", "B", "A+B"]);
+
+        let mut parsed = Autofire::new();
+        assert!(parsed.set_label("a+b"));
+        assert!(parsed.a && parsed.b);
+        assert!(!parsed.set_label("turbo"));
+    }
+}
@@ -1,5 +1,7 @@
 use super::cartridge::ROM_BANK_SIZE;
-use super::{Cartridge, CartridgeType, RomBankMapping};
+use super::{Cartridge, CartridgeType, RomBankMapping, SystemClock, TimeSource};
+use std::fmt;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const EXT_RAM_START: u16 = 0xA000;
@@ -9,15 +11,43 @@ const MBC2_RAM_SIZE: usize = 512;
 const MBC2_RAM_END: u16 = 0xA1FF;
 const OPEN_BUS: u8 = 0xFF;
 const CYCLES_PER_SECOND: u32 = 4_194_304;
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+const RTC_DAY_HIGH_BIT: u8 = 0x01;
+const RTC_HALT: u8 = 0x40;
+const RTC_DAY_CARRY: u8 = 0x80;
+const HUC3_MODE_RAM_READ: u8 = 0x0;
+const HUC3_MODE_RAM: u8 = 0xA;
+const HUC3_MODE_RTC_COMMAND: u8 = 0xB;
+const HUC3_MODE_RTC_RESPONSE: u8 = 0xC;
+const HUC3_MODE_RTC_SEMAPHORE: u8 = 0xD;
+const HUC3_MODE_IR: u8 = 0xE;
+const HUC3_IR_NO_LIGHT: u8 = 0xC0;
+const HUC3_MINUTES_PER_DAY: u16 = 1440;
+const HUC3_DAY_MASK: u16 = 0x0FFF;
+pub const CAMERA_IMAGE_WIDTH: usize = 128;
+pub const CAMERA_IMAGE_HEIGHT: usize = 112;
+const CAMERA_REGISTER_SELECT: u8 = 0x10;
+const CAMERA_REGISTER_COUNT: usize = 0x36;
+const CAMERA_CAPTURE_BUSY: u8 = 0x01;
+const CAMERA_DITHER_START: usize = 0x06;
+const CAMERA_PHOTO_OFFSET: usize = 0x0100;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MbcError {
     UnsupportedCartridgeType(CartridgeType),
+    CameraImageSize {
+        expected: usize,
+        actual: usize,
+    },
+    /// A mapper hit something mid-game it can't emulate, reported through
+    /// `Mapper::take_fault`.
+    Fault(&'static str),
 }
 
 #[derive(Debug, Clone)]
 pub struct Mbc {
-    kind: MbcKind,
+    mapper: Box<dyn Mapper>,
+    open_bus: u8,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -26,25 +56,123 @@ pub enum RtcMode {
     HostSync,
 }
 
-#[derive(Debug, Clone)]
-enum MbcKind {
-    RomOnly,
-    Mbc1(Mbc1),
-    Mbc2(Mbc2),
-    Mbc3(Mbc3),
-    Mbc5(Mbc5),
+/// What advances the MBC3 clock once it's running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RtcSource {
+    /// Emulated cycles, so the clock speeds up with fast-forward.
+    #[default]
+    Cycles,
+    /// Host wall-clock time, whatever the emulation speed.
+    Wallclock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamBankSelection {
+    Bank(usize),
+    Rtc(RtcRegister),
+    /// The Pocket Camera's sensor registers.
+    Camera,
+}
+
+impl RamBankSelection {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Bank(bank) => bank.to_string(),
+            Self::Rtc(reg) => format!("RTC:{}", reg.label()),
+            Self::Camera => "CAM".to_string(),
+        }
+    }
+}
+
+/// A cartridge's banking hardware. `Mbc::new` picks the built-in one for the header's
+/// cartridge type; anything else can be plugged in through `Mbc::with_mapper`. Only the
+/// reads and writes are required, and the defaults describe a plain 32KB ROM.
+pub trait Mapper: MapperClone + fmt::Debug + Send {
+    /// Reads 0x0000-0x7FFF or 0xA000-0xBFFF. `open_bus` is what unmapped RAM returns.
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8;
+
+    fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8);
+
+    /// Advances anything clocked on the cartridge, like a real-time clock.
+    fn tick(&mut self, _cycles: u32) {}
+
+    /// The ROM bank currently mapped into 0x4000-0x7FFF.
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        normalize_switchable_bank(1, bank_count(&cartridge.bytes))
+    }
+
+    /// What 0xA000-0xBFFF currently maps to, or `None` when there's no RAM there.
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        normalize_ram_bank(0, ram_bank_count_for(cartridge, 1)).map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 1)
+    }
+
+    /// Restores the banking registers to their power-on values.
+    fn reset(&mut self) {}
+
+    /// Like `reset`, but as if the cartridge's battery were pulled too.
+    fn power_cycle(&mut self) {
+        self.reset();
+    }
+
+    fn set_rtc_mode(&mut self, _mode: RtcMode) {}
+
+    fn set_rtc_source(&mut self, _source: RtcSource) {}
+
+    fn set_time_source(&mut self, _clock: Arc<dyn TimeSource>) {}
+
+    fn rtc_snapshot(&self) -> Option<RtcSnapshot> {
+        None
+    }
+
+    fn rtc_latched_registers(&self) -> Option<[u8; 5]> {
+        None
+    }
+
+    fn restore_rtc(&mut self, _snapshot: RtcSnapshot, _elapsed: Duration) {}
+
+    fn restore_rtc_latch(&mut self, _registers: [u8; 5]) {}
+
+    /// Receives an image already checked to be the camera sensor's size.
+    fn set_camera_image(&mut self, _image: &[u8]) {}
+
+    /// A fault the accesses so far ran into, reported once. The emulator stops the game
+    /// on it rather than run on with the cartridge in a state the mapper can't model.
+    fn take_fault(&mut self) -> Option<MbcError> {
+        None
+    }
+}
+
+/// Lets `Mbc` stay `Clone` with a boxed mapper; implemented for every `Mapper + Clone`.
+pub trait MapperClone {
+    fn clone_box(&self) -> Box<dyn Mapper>;
+}
+
+impl<T: Mapper + Clone + 'static> MapperClone for T {
+    fn clone_box(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
 
 impl Mbc {
     pub fn new(cartridge: &Cartridge) -> Result<Self, MbcError> {
-        let kind = match cartridge.header.cartridge_type {
+        let mapper: Box<dyn Mapper> = match cartridge.header.cartridge_type {
             CartridgeType::RomOnly | CartridgeType::RomRam | CartridgeType::RomRamBattery => {
-                MbcKind::RomOnly
+                Box::new(RomOnly)
             }
             CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery => {
-                MbcKind::Mbc1(Mbc1::new())
+                Box::new(Mbc1::new())
             }
-            CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => MbcKind::Mbc2(Mbc2::new()),
+            CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => Box::new(Mbc2::new()),
             CartridgeType::Mbc3
             | CartridgeType::Mbc3Ram
             | CartridgeType::Mbc3RamBattery
@@ -54,48 +182,150 @@ impl Mbc {
                     cartridge.header.cartridge_type,
                     CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery
                 );
-                MbcKind::Mbc3(Mbc3::new(has_rtc))
+                Box::new(Mbc3::new(has_rtc))
             }
             CartridgeType::Mbc5
             | CartridgeType::Mbc5Ram
             | CartridgeType::Mbc5RamBattery
             | CartridgeType::Mbc5Rumble
             | CartridgeType::Mbc5RumbleRam
-            | CartridgeType::Mbc5RumbleRamBattery => MbcKind::Mbc5(Mbc5::new()),
+            | CartridgeType::Mbc5RumbleRamBattery => Box::new(Mbc5::new()),
+            CartridgeType::Mmm01 | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery => {
+                Box::new(Mmm01::new())
+            }
+            CartridgeType::HuC3 => Box::new(HuC3::new()),
+            CartridgeType::PocketCamera => Box::new(PocketCamera::new()),
             other => return Err(MbcError::UnsupportedCartridgeType(other)),
         };
-        Ok(Self { kind })
+        Ok(Self::with_mapper(mapper))
     }
 
-    pub fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
-        match &self.kind {
-            MbcKind::RomOnly => read_rom_only(cartridge, addr),
-            MbcKind::Mbc1(mbc1) => mbc1.read8(cartridge, addr),
-            MbcKind::Mbc2(mbc2) => mbc2.read8(cartridge, addr),
-            MbcKind::Mbc3(mbc3) => mbc3.read8(cartridge, addr),
-            MbcKind::Mbc5(mbc5) => mbc5.read8(cartridge, addr),
+    /// Wraps a mapper the header can't describe, such as one supplied by another crate.
+    pub fn with_mapper(mapper: Box<dyn Mapper>) -> Self {
+        Self {
+            mapper,
+            open_bus: OPEN_BUS,
         }
     }
 
+    /// The value read back from external RAM while it's disabled or absent. Defaults to
+    /// 0xFF; some carts float to other values that test ROMs probe for.
+    pub fn set_open_bus(&mut self, value: u8) {
+        self.open_bus = value;
+    }
+
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    pub fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
+        self.mapper.read8(cartridge, addr, self.open_bus)
+    }
+
     pub fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8) {
-        match &mut self.kind {
-            MbcKind::RomOnly => write_rom_only(cartridge, addr, value),
-            MbcKind::Mbc1(mbc1) => mbc1.write8(cartridge, addr, value),
-            MbcKind::Mbc2(mbc2) => mbc2.write8(cartridge, addr, value),
-            MbcKind::Mbc3(mbc3) => mbc3.write8(cartridge, addr, value),
-            MbcKind::Mbc5(mbc5) => mbc5.write8(cartridge, addr, value),
-        }
+        self.mapper.write8(cartridge, addr, value);
     }
 
     pub fn tick(&mut self, cycles: u32) {
-        if let MbcKind::Mbc3(mbc3) = &mut self.kind {
-            mbc3.tick(cycles);
-        }
+        self.mapper.tick(cycles);
+    }
+
+    pub fn take_fault(&mut self) -> Option<MbcError> {
+        self.mapper.take_fault()
+    }
+
+    /// Replaces what the Pocket Camera's sensor sees: a `CAMERA_IMAGE_WIDTH` by
+    /// `CAMERA_IMAGE_HEIGHT` grayscale image, one byte per pixel, 0 black and 255 white.
+    /// Other cartridges ignore it.
+    pub fn set_camera_image(&mut self, image: &[u8]) -> Result<(), MbcError> {
+        check_camera_image(image)?;
+        self.mapper.set_camera_image(image);
+        Ok(())
     }
 
     pub fn set_rtc_mode(&mut self, mode: RtcMode) {
-        if let MbcKind::Mbc3(mbc3) = &mut self.kind {
-            mbc3.set_rtc_mode(mode);
+        self.mapper.set_rtc_mode(mode);
+    }
+
+    /// Chooses whether the running clock follows emulated cycles or real time. Switching
+    /// keeps the current time; unlike `RtcMode::HostSync` it never jumps to the host date.
+    pub fn set_rtc_source(&mut self, source: RtcSource) {
+        self.mapper.set_rtc_source(source);
+    }
+
+    pub fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        self.mapper.set_time_source(clock);
+    }
+
+    /// The MBC3 clock as it stands now, for carrying across sessions.
+    pub fn rtc_snapshot(&self) -> Option<RtcSnapshot> {
+        self.mapper.rtc_snapshot()
+    }
+
+    /// The values the game last latched, which can differ from the running clock.
+    pub fn rtc_latched_registers(&self) -> Option<[u8; 5]> {
+        self.mapper.rtc_latched_registers()
+    }
+
+    /// Restores a clock saved by `rtc_snapshot`, advanced by the wall time that passed
+    /// while the game was closed.
+    pub fn restore_rtc(&mut self, snapshot: RtcSnapshot, elapsed: Duration) {
+        self.mapper.restore_rtc(snapshot, elapsed);
+    }
+
+    /// Puts back values the game had latched, so it reads them until it latches again.
+    pub fn restore_rtc_latch(&mut self, registers: [u8; 5]) {
+        self.mapper.restore_rtc_latch(registers);
+    }
+
+    /// The ROM bank currently mapped into 0x4000-0x7FFF.
+    pub fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.mapper.current_rom_bank(cartridge)
+    }
+
+    /// What 0xA000-0xBFFF currently maps to, or `None` when the cartridge has no RAM there.
+    pub fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        self.mapper.current_ram_bank(cartridge)
+    }
+
+    pub fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        self.mapper.ram_bank_count(cartridge)
+    }
+
+    /// Restores the banking registers to their power-on values. The MBC3 clock keeps running.
+    pub fn reset(&mut self) {
+        self.mapper.reset();
+    }
+
+    /// Like `reset`, but also stops and clears the MBC3 clock as if its battery were pulled.
+    pub fn power_cycle(&mut self) {
+        self.mapper.power_cycle();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RomOnly;
+
+impl Mapper for RomOnly {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => {
+                let bank_count = bank_count(&cartridge.bytes);
+                let bank = normalize_switchable_bank(1, bank_count);
+                RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
+            }
+            EXT_RAM_START..=EXT_RAM_END => {
+                let ram_bank = normalize_ram_bank(0, ram_bank_count_for(cartridge, 1));
+                read_ext_ram(cartridge, ram_bank, addr, open_bus)
+            }
+            _ => open_bus,
+        }
+    }
+
+    fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8) {
+        if matches!(addr, EXT_RAM_START..=EXT_RAM_END) {
+            let ram_bank = normalize_ram_bank(0, ram_bank_count_for(cartridge, 1));
+            write_ext_ram(cartridge, ram_bank, addr, value);
         }
     }
 }
@@ -126,7 +356,36 @@ impl Mbc1 {
         }
     }
 
-    fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        let ram_bank = match self.mode {
+            Mbc1Mode::RomBanking => 0,
+            Mbc1Mode::RamBanking => self.ram_bank as usize,
+        };
+        normalize_ram_bank(ram_bank, ram_bank_count_for(cartridge, 4))
+    }
+
+    fn rom_banks(&self, bank_count: usize) -> (usize, usize) {
+        let mut low5 = (self.rom_bank_low5 & 0x1F) as usize;
+        if low5 == 0 {
+            low5 = 1;
+        }
+        let upper = (self.bank_high2 & 0x03) as usize;
+        match self.mode {
+            Mbc1Mode::RomBanking => {
+                let switchable = normalize_switchable_bank((upper << 5) | low5, bank_count);
+                (normalize_bank(0, bank_count), switchable)
+            }
+            Mbc1Mode::RamBanking => {
+                let fixed = normalize_bank(upper << 5, bank_count);
+                let switchable = normalize_switchable_bank(low5, bank_count);
+                (fixed, switchable)
+            }
+        }
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
         match addr {
             0x0000..=0x7FFF => {
                 let bank_count = bank_count(&cartridge.bytes);
@@ -135,16 +394,11 @@ impl Mbc1 {
             }
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
-                    return OPEN_BUS;
+                    return open_bus;
                 }
-                let ram_bank = match self.mode {
-                    Mbc1Mode::RomBanking => 0,
-                    Mbc1Mode::RamBanking => self.ram_bank as usize,
-                };
-                let ram_bank = normalize_ram_bank(ram_bank, ram_bank_count_for(cartridge, 4));
-                read_ext_ram(cartridge, ram_bank, addr)
+                read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
             }
-            _ => OPEN_BUS,
+            _ => open_bus,
         }
     }
 
@@ -172,34 +426,28 @@ impl Mbc1 {
                 if !self.ram_enabled {
                     return;
                 }
-                let ram_bank = match self.mode {
-                    Mbc1Mode::RomBanking => 0,
-                    Mbc1Mode::RamBanking => self.ram_bank as usize,
-                };
-                let ram_bank = normalize_ram_bank(ram_bank, ram_bank_count_for(cartridge, 4));
+                let ram_bank = self.selected_ram_bank(cartridge);
                 write_ext_ram(cartridge, ram_bank, addr, value);
             }
             _ => {}
         }
     }
 
-    fn rom_banks(&self, bank_count: usize) -> (usize, usize) {
-        let mut low5 = (self.rom_bank_low5 & 0x1F) as usize;
-        if low5 == 0 {
-            low5 = 1;
-        }
-        let upper = (self.bank_high2 & 0x03) as usize;
-        match self.mode {
-            Mbc1Mode::RomBanking => {
-                let switchable = normalize_switchable_bank((upper << 5) | low5, bank_count);
-                (normalize_bank(0, bank_count), switchable)
-            }
-            Mbc1Mode::RamBanking => {
-                let fixed = normalize_bank(upper << 5, bank_count);
-                let switchable = normalize_switchable_bank(low5, bank_count);
-                (fixed, switchable)
-            }
-        }
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.rom_banks(bank_count(&cartridge.bytes)).1
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 4)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
     }
 }
 
@@ -217,20 +465,25 @@ impl Mbc2 {
         }
     }
 
-    fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
+    fn switchable_rom_bank(&self, bank_count: usize) -> usize {
+        normalize_switchable_bank(self.rom_bank as usize, bank_count)
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
         match addr {
             0x0000..=0x7FFF => {
-                let bank_count = bank_count(&cartridge.bytes);
-                let bank = normalize_switchable_bank(self.rom_bank as usize, bank_count);
+                let bank = self.switchable_rom_bank(bank_count(&cartridge.bytes));
                 RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
             }
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
-                    return OPEN_BUS;
+                    return open_bus;
                 }
-                read_mbc2_ram(cartridge, addr)
+                read_mbc2_ram(cartridge, addr, open_bus)
             }
-            _ => OPEN_BUS,
+            _ => open_bus,
         }
     }
 
@@ -256,10 +509,26 @@ impl Mbc2 {
             _ => {}
         }
     }
+
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.switchable_rom_bank(bank_count(&cartridge.bytes))
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        (cartridge.ext_ram.len() >= MBC2_RAM_SIZE).then_some(RamBankSelection::Bank(0))
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        usize::from(cartridge.ext_ram.len() >= MBC2_RAM_SIZE)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum RtcRegister {
+pub enum RtcRegister {
     Seconds,
     Minutes,
     Hours,
@@ -267,6 +536,27 @@ enum RtcRegister {
     DayHigh,
 }
 
+impl RtcRegister {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Seconds => "S",
+            Self::Minutes => "M",
+            Self::Hours => "H",
+            Self::DayLow => "DL",
+            Self::DayHigh => "DH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcSnapshot {
+    /// Seconds, minutes, hours, day low and day high, as the game sees them.
+    pub registers: [u8; 5],
+    /// Progress into the current second, in CPU cycles. Keeping it means repeated
+    /// save/restore doesn't round time away.
+    pub subsecond_cycles: u32,
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Rtc {
     seconds: u8,
@@ -293,21 +583,48 @@ impl Rtc {
             RtcRegister::Minutes => self.minutes = value,
             RtcRegister::Hours => self.hours = value,
             RtcRegister::DayLow => self.day_low = value,
-            RtcRegister::DayHigh => self.day_high = value & 0xC1,
+            RtcRegister::DayHigh => {
+                self.day_high = value & (RTC_DAY_CARRY | RTC_HALT | RTC_DAY_HIGH_BIT)
+            }
+        }
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+    }
+
+    fn from_registers(registers: [u8; 5]) -> Self {
+        let [seconds, minutes, hours, day_low, day_high] = registers;
+        Self {
+            seconds,
+            minutes,
+            hours,
+            day_low,
+            day_high,
         }
     }
 
+    fn is_halted(&self) -> bool {
+        self.day_high & RTC_HALT != 0
+    }
+
     fn tick_seconds(&mut self, seconds: u32) {
         self.add_seconds(u64::from(seconds));
     }
 
     fn day_counter(&self) -> u16 {
-        let high = (self.day_high & 0x01) as u16;
+        let high = (self.day_high & RTC_DAY_HIGH_BIT) as u16;
         u16::from(self.day_low) | (high << 8)
     }
 
     fn add_seconds(&mut self, seconds: u64) {
-        if self.day_high & 0x40 != 0 {
+        if self.is_halted() {
             return;
         }
 
@@ -324,18 +641,18 @@ impl Rtc {
         let minutes = ((remainder / 60) % 60) as u8;
         let secs = (remainder % 60) as u8;
 
-        let mut carry = self.day_high & 0x80;
-        if carry == 0 && days >= 512 {
-            carry = 0x80;
+        // The carry sticks until the game clears it through DayHigh.
+        let mut carry = self.day_high & RTC_DAY_CARRY;
+        if days >= 512 {
+            carry = RTC_DAY_CARRY;
         }
 
         let day_mod = (days % 512) as u16;
-        let halt = self.day_high & 0x40;
         self.seconds = secs;
         self.minutes = minutes;
         self.hours = hours;
         self.day_low = (day_mod & 0xFF) as u8;
-        self.day_high = halt | carry | ((day_mod >> 8) as u8 & 0x01);
+        self.day_high = carry | ((day_mod >> 8) as u8 & RTC_DAY_HIGH_BIT);
     }
 
     fn from_unix_seconds(seconds: u64) -> Self {
@@ -345,13 +662,13 @@ impl Rtc {
         let minutes = ((remainder / 60) % 60) as u8;
         let secs = (remainder % 60) as u8;
         let day_mod = (days % 512) as u16;
-        let carry = if days >= 512 { 0x80 } else { 0x00 };
+        let carry = if days >= 512 { RTC_DAY_CARRY } else { 0x00 };
         Self {
             seconds: secs,
             minutes,
             hours,
             day_low: (day_mod & 0xFF) as u8,
-            day_high: carry | ((day_mod >> 8) as u8 & 0x01),
+            day_high: carry | ((day_mod >> 8) as u8 & RTC_DAY_HIGH_BIT),
         }
     }
 }
@@ -365,6 +682,8 @@ struct Mbc3 {
     latch_pending: bool,
     has_rtc: bool,
     rtc_mode: RtcMode,
+    rtc_source: RtcSource,
+    clock: Arc<dyn TimeSource>,
     rtc_host_base: Option<SystemTime>,
     rtc_counter: u32,
     rtc: Rtc,
@@ -389,6 +708,8 @@ impl Mbc3 {
             latch_pending: false,
             has_rtc,
             rtc_mode: RtcMode::Deterministic,
+            rtc_source: RtcSource::Cycles,
+            clock: Arc::new(SystemClock),
             rtc_host_base: None,
             rtc_counter: 0,
             rtc,
@@ -397,16 +718,50 @@ impl Mbc3 {
         }
     }
 
-    fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
+    fn switchable_rom_bank(&self, bank_count: usize) -> usize {
+        normalize_switchable_bank(self.rom_bank as usize, bank_count)
+    }
+
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        normalize_ram_bank(self.ram_bank as usize, ram_bank_count_for(cartridge, 4))
+    }
+
+    fn current_rtc(&self) -> Rtc {
+        match self.rtc_source {
+            RtcSource::Cycles => self.rtc,
+            RtcSource::Wallclock => {
+                let mut rtc = self.rtc;
+                rtc.add_seconds(self.host_elapsed().as_secs());
+                rtc
+            }
+        }
+    }
+
+    fn host_elapsed(&self) -> Duration {
+        self.rtc_host_base
+            .and_then(|base| self.clock.now().duration_since(base).ok())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Moves whole elapsed seconds into the registers, leaving the fraction on the base.
+    fn fold_host_elapsed(&mut self) {
+        let seconds = self.host_elapsed().as_secs();
+        self.rtc.add_seconds(seconds);
+        let base = self.rtc_host_base.unwrap_or_else(|| self.clock.now());
+        self.rtc_host_base = Some(base + Duration::from_secs(seconds));
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
         match addr {
             0x0000..=0x7FFF => {
-                let bank_count = bank_count(&cartridge.bytes);
-                let bank = normalize_switchable_bank(self.rom_bank as usize, bank_count);
+                let bank = self.switchable_rom_bank(bank_count(&cartridge.bytes));
                 RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
             }
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
-                    return OPEN_BUS;
+                    return open_bus;
                 }
                 if self.has_rtc {
                     if let Some(reg) = self.rtc_reg {
@@ -416,21 +771,13 @@ impl Mbc3 {
                             self.current_rtc().read(reg)
                         }
                     } else {
-                        let ram_bank = normalize_ram_bank(
-                            self.ram_bank as usize,
-                            ram_bank_count_for(cartridge, 4),
-                        );
-                        read_ext_ram(cartridge, ram_bank, addr)
+                        read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
                     }
                 } else {
-                    let ram_bank = normalize_ram_bank(
-                        self.ram_bank as usize,
-                        ram_bank_count_for(cartridge, 4),
-                    );
-                    read_ext_ram(cartridge, ram_bank, addr)
+                    read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
                 }
             }
-            _ => OPEN_BUS,
+            _ => open_bus,
         }
     }
 
@@ -482,22 +829,20 @@ impl Mbc3 {
                 }
                 if self.has_rtc {
                     if let Some(reg) = self.rtc_reg {
-                        self.rtc.write(reg, value);
-                        if self.rtc_mode == RtcMode::HostSync {
-                            self.rtc_host_base = Some(SystemTime::now());
+                        if self.rtc_source == RtcSource::Wallclock {
+                            // Fold in the time elapsed so far, or the write would rewind
+                            // every other register to the last base.
+                            self.fold_host_elapsed();
                         }
+                        self.rtc.write(reg, value);
+                        // The clock is saved with RAM, so setting it needs a save too.
+                        cartridge.mark_ram_dirty();
                     } else {
-                        let ram_bank = normalize_ram_bank(
-                            self.ram_bank as usize,
-                            ram_bank_count_for(cartridge, 4),
-                        );
+                        let ram_bank = self.selected_ram_bank(cartridge);
                         write_ext_ram(cartridge, ram_bank, addr, value);
                     }
                 } else {
-                    let ram_bank = normalize_ram_bank(
-                        self.ram_bank as usize,
-                        ram_bank_count_for(cartridge, 4),
-                    );
+                    let ram_bank = self.selected_ram_bank(cartridge);
                     write_ext_ram(cartridge, ram_bank, addr, value);
                 }
             }
@@ -509,7 +854,7 @@ impl Mbc3 {
         if !self.has_rtc {
             return;
         }
-        if self.rtc_mode != RtcMode::Deterministic {
+        if self.rtc_source != RtcSource::Cycles || self.rtc.is_halted() {
             return;
         }
         self.rtc_counter = self.rtc_counter.wrapping_add(cycles);
@@ -528,13 +873,11 @@ impl Mbc3 {
         }
         match mode {
             RtcMode::Deterministic => {
-                self.rtc = self.current_rtc();
-                self.rtc_host_base = None;
-                self.rtc_counter = 0;
+                self.set_rtc_source(RtcSource::Cycles);
                 self.rtc_mode = mode;
             }
             RtcMode::HostSync => {
-                let now = SystemTime::now();
+                let now = self.clock.now();
                 let seconds = now
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or(Duration::ZERO)
@@ -542,6 +885,7 @@ impl Mbc3 {
                 self.rtc = Rtc::from_unix_seconds(seconds);
                 self.rtc_host_base = Some(now);
                 self.rtc_counter = 0;
+                self.rtc_source = RtcSource::Wallclock;
                 self.rtc_mode = mode;
             }
         }
@@ -549,24 +893,129 @@ impl Mbc3 {
         self.latched = false;
     }
 
-    fn current_rtc(&self) -> Rtc {
-        match self.rtc_mode {
-            RtcMode::Deterministic => self.rtc,
-            RtcMode::HostSync => {
-                let base = self.rtc;
-                let base_time = self.rtc_host_base.unwrap_or_else(SystemTime::now);
-                let elapsed = SystemTime::now()
-                    .duration_since(base_time)
-                    .unwrap_or(Duration::ZERO)
-                    .as_secs();
-                let mut rtc = base;
-                rtc.add_seconds(elapsed);
-                rtc
+    fn set_rtc_source(&mut self, source: RtcSource) {
+        if !self.has_rtc || self.rtc_source == source {
+            return;
+        }
+        match source {
+            RtcSource::Cycles => {
+                self.rtc = self.current_rtc();
+                self.rtc_host_base = None;
+            }
+            RtcSource::Wallclock => {
+                self.rtc_host_base = Some(self.clock.now());
+            }
+        }
+        self.rtc_counter = 0;
+        self.rtc_source = source;
+    }
+
+    fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        if self.rtc_source == RtcSource::Wallclock {
+            self.fold_host_elapsed();
+            self.rtc_host_base = Some(clock.now());
+        }
+        self.clock = clock;
+    }
+
+    fn rtc_snapshot(&self) -> Option<RtcSnapshot> {
+        if !self.has_rtc {
+            return None;
+        }
+        let subsecond_cycles = match self.rtc_source {
+            RtcSource::Cycles => self.rtc_counter,
+            RtcSource::Wallclock => {
+                duration_to_cycles(self.host_elapsed()) as u32 % CYCLES_PER_SECOND
+            }
+        };
+        Some(RtcSnapshot {
+            registers: self.current_rtc().registers(),
+            subsecond_cycles,
+        })
+    }
+
+    fn restore_rtc(&mut self, snapshot: RtcSnapshot, elapsed: Duration) {
+        if !self.has_rtc {
+            return;
+        }
+        let cycles = u128::from(snapshot.subsecond_cycles) + duration_to_cycles(elapsed);
+        let seconds = cycles / u128::from(CYCLES_PER_SECOND);
+        let remainder = (cycles % u128::from(CYCLES_PER_SECOND)) as u32;
+
+        self.rtc = Rtc::from_registers(snapshot.registers);
+        self.rtc.add_seconds(seconds as u64);
+        match self.rtc_source {
+            RtcSource::Cycles => self.rtc_counter = remainder,
+            RtcSource::Wallclock => {
+                let fraction = cycles_to_duration(remainder);
+                let now = self.clock.now();
+                self.rtc_host_base = Some(now.checked_sub(fraction).unwrap_or(now));
             }
         }
+        self.rtc_latched = self.rtc;
+        self.latched = false;
+    }
+
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.switchable_rom_bank(bank_count(&cartridge.bytes))
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        if self.has_rtc
+            && let Some(reg) = self.rtc_reg
+        {
+            return Some(RamBankSelection::Rtc(reg));
+        }
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 4)
+    }
+
+    /// Restores the banking registers; the clock keeps running.
+    fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.rtc_reg = None;
+        self.ram_enabled = false;
+        self.latch_pending = false;
+    }
+
+    /// Also stops and clears the clock, keeping how it's driven.
+    fn power_cycle(&mut self) {
+        let mode = self.rtc_mode;
+        let source = self.rtc_source;
+        let clock = self.clock.clone();
+        *self = Self::new(self.has_rtc);
+        self.set_time_source(clock);
+        self.set_rtc_mode(mode);
+        self.set_rtc_source(source);
+    }
+
+    fn rtc_latched_registers(&self) -> Option<[u8; 5]> {
+        self.has_rtc.then(|| self.rtc_latched.registers())
+    }
+
+    fn restore_rtc_latch(&mut self, registers: [u8; 5]) {
+        if !self.has_rtc {
+            return;
+        }
+        self.rtc_latched = Rtc::from_registers(registers);
+        self.latched = true;
     }
 }
 
+fn duration_to_cycles(duration: Duration) -> u128 {
+    duration.as_nanos() * u128::from(CYCLES_PER_SECOND) / NANOS_PER_SECOND
+}
+
+fn cycles_to_duration(cycles: u32) -> Duration {
+    let nanos = u128::from(cycles) * NANOS_PER_SECOND / u128::from(CYCLES_PER_SECOND);
+    Duration::from_nanos(nanos as u64)
+}
+
 #[derive(Debug, Clone)]
 struct Mbc5 {
     rom_bank_low: u8,
@@ -585,23 +1034,30 @@ impl Mbc5 {
         }
     }
 
-    fn read8(&self, cartridge: &Cartridge, addr: u16) -> u8 {
+    fn switchable_rom_bank(&self, bank_count: usize) -> usize {
+        let bank = ((self.rom_bank_high as usize) << 8) | self.rom_bank_low as usize;
+        normalize_bank(bank, bank_count)
+    }
+
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        normalize_ram_bank(self.ram_bank as usize, ram_bank_count_for(cartridge, 16))
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
         match addr {
             0x0000..=0x7FFF => {
-                let bank_count = bank_count(&cartridge.bytes);
-                let bank = ((self.rom_bank_high as usize) << 8) | self.rom_bank_low as usize;
-                let bank = normalize_bank(bank, bank_count);
+                let bank = self.switchable_rom_bank(bank_count(&cartridge.bytes));
                 RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
             }
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
-                    return OPEN_BUS;
+                    return open_bus;
                 }
-                let ram_bank =
-                    normalize_ram_bank(self.ram_bank as usize, ram_bank_count_for(cartridge, 16));
-                read_ext_ram(cartridge, ram_bank, addr)
+                read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
             }
-            _ => OPEN_BUS,
+            _ => open_bus,
         }
     }
 
@@ -623,58 +1079,633 @@ impl Mbc5 {
                 if !self.ram_enabled {
                     return;
                 }
-                let ram_bank =
-                    normalize_ram_bank(self.ram_bank as usize, ram_bank_count_for(cartridge, 16));
+                let ram_bank = self.selected_ram_bank(cartridge);
                 write_ext_ram(cartridge, ram_bank, addr, value);
             }
             _ => {}
         }
     }
-}
 
-fn read_rom_only(cartridge: &Cartridge, addr: u16) -> u8 {
-    match addr {
-        0x0000..=0x7FFF => {
-            let bank_count = bank_count(&cartridge.bytes);
-            let bank = normalize_switchable_bank(1, bank_count);
-            RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
-        }
-        EXT_RAM_START..=EXT_RAM_END => {
-            let ram_bank = normalize_ram_bank(0, ram_bank_count_for(cartridge, 1));
-            read_ext_ram(cartridge, ram_bank, addr)
-        }
-        _ => OPEN_BUS,
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.switchable_rom_bank(bank_count(&cartridge.bytes))
     }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 16)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// The MMM01 multicart mapper. It powers up unmapped, showing the last 32KB of ROM where
+/// the menu lives. The menu programs a base bank and masks for the chosen game, then sets
+/// bit 6 of 0x0000-0x1FFF to lock them; from there the game sees an MBC1-like mapper
+/// confined to its slice of the ROM.
+#[derive(Debug, Clone)]
+struct Mmm01 {
+    /// RA14-RA18. Bits covered by `rom_bank_mask` stay fixed once locked.
+    rom_bank_low: u8,
+    /// RA19-RA20, writable only before locking.
+    rom_bank_mid: u8,
+    /// RA21-RA22, writable only before locking.
+    rom_bank_high: u8,
+    /// Which of RA15-RA18 the game can't change.
+    rom_bank_mask: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    /// Which of the two low RAM bank bits the game can't change.
+    ram_bank_mask: u8,
+    ram_enabled: bool,
+    locked: bool,
+    mbc1_mode: bool,
+    mbc1_mode_disabled: bool,
+    /// Swaps the RAM bank lines with RA19-RA20.
+    multiplex: bool,
 }
 
-fn write_rom_only(cartridge: &mut Cartridge, addr: u16, value: u8) {
-    if matches!(addr, EXT_RAM_START..=EXT_RAM_END) {
-        let ram_bank = normalize_ram_bank(0, ram_bank_count_for(cartridge, 1));
-        write_ext_ram(cartridge, ram_bank, addr, value);
+impl Mmm01 {
+    fn new() -> Self {
+        Self {
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            ram_enabled: false,
+            locked: false,
+            mbc1_mode: false,
+            mbc1_mode_disabled: false,
+            multiplex: false,
+        }
+    }
+
+    fn rom_banks(&self, bank_count: usize) -> (usize, usize) {
+        if !self.locked {
+            return (bank_count.saturating_sub(2), bank_count.saturating_sub(1));
+        }
+        let mask = (self.rom_bank_mask << 1) & 0x1E;
+        let (fixed_mid, switchable_mid) = if self.multiplex {
+            let mid = if self.mbc1_mode { 0 } else { self.ram_bank_low };
+            (mid, self.ram_bank_low)
+        } else {
+            (self.rom_bank_mid, self.rom_bank_mid)
+        };
+        let high = usize::from(self.rom_bank_high) << 7;
+        let fixed = usize::from(self.rom_bank_low & mask) | usize::from(fixed_mid) << 5 | high;
+        let mut switchable =
+            usize::from(self.rom_bank_low) | usize::from(switchable_mid) << 5 | high;
+        // Bank 0 maps to bank 1 as on MBC1, but only over the bits the game controls.
+        if self.rom_bank_low & !mask & 0x1F == 0 {
+            switchable |= 1;
+        }
+        (
+            normalize_bank(fixed, bank_count),
+            normalize_bank(switchable, bank_count),
+        )
+    }
+
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        let low = if self.multiplex {
+            self.rom_bank_mid
+        } else {
+            self.ram_bank_low
+        };
+        let bank = usize::from(low) | usize::from(self.ram_bank_high) << 2;
+        normalize_ram_bank(bank, ram_bank_count_for(cartridge, 16))
     }
 }
 
-fn read_ext_ram(cartridge: &Cartridge, bank: Option<usize>, addr: u16) -> u8 {
-    if cartridge.ext_ram.is_empty() {
-        return OPEN_BUS;
+impl Mapper for Mmm01 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => {
+                let (fixed_bank, switchable_bank) = self.rom_banks(bank_count(&cartridge.bytes));
+                RomBankMapping::with_banks(&cartridge.bytes, fixed_bank, switchable_bank).read(addr)
+            }
+            EXT_RAM_START..=EXT_RAM_END => {
+                if !self.ram_enabled {
+                    return open_bus;
+                }
+                read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
+            }
+            _ => open_bus,
+        }
+    }
+
+    fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+                if !self.locked {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.locked = value & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                if !self.locked {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+                let mask = (self.rom_bank_mask << 1) & 0x1E;
+                self.rom_bank_low = (self.rom_bank_low & mask) | (value & !mask & 0x1F);
+            }
+            0x4000..=0x5FFF => {
+                let mask = self.ram_bank_mask;
+                self.ram_bank_low = (self.ram_bank_low & mask) | (value & !mask & 0x03);
+                if !self.locked {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.mbc1_mode_disabled = value & 0x40 != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mbc1_mode_disabled {
+                    self.mbc1_mode = value & 0x01 != 0;
+                }
+                if !self.locked {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+            EXT_RAM_START..=EXT_RAM_END => {
+                if !self.ram_enabled {
+                    return;
+                }
+                let ram_bank = self.selected_ram_bank(cartridge);
+                write_ext_ram(cartridge, ram_bank, addr, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.rom_banks(bank_count(&cartridge.bytes)).1
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 16)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Hudson's HuC3: MBC3-style banking, with 0x0000-0x1FFF choosing what 0xA000-0xBFFF
+/// talks to. Besides RAM, that can be a command port into the RTC chip's nibble-wide
+/// memory, a response port, a ready semaphore, or the IR port (stubbed as no light).
+/// A write there under any other mode is a fault: nothing documents what the chip does.
+#[derive(Debug, Clone)]
+struct HuC3 {
+    rom_bank: u8,
+    ram_bank: u8,
+    mode: u8,
+    fault: Option<MbcError>,
+    /// The RTC chip's 256 nibbles of scratch memory.
+    rtc_memory: Box<[u8; 256]>,
+    rtc_address: u8,
+    rtc_command: u8,
+    rtc_result: u8,
+    /// Minutes into the current day and days elapsed, as the chip counts them.
+    minutes: u16,
+    days: u16,
+    rtc_counter: u32,
+    rtc_source: RtcSource,
+    clock: Arc<dyn TimeSource>,
+    /// Under `RtcSource::Wallclock`, the host time `minutes` and `days` were last true at.
+    rtc_host_base: Option<SystemTime>,
+}
+
+impl HuC3 {
+    fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: HUC3_MODE_RAM_READ,
+            fault: None,
+            rtc_memory: Box::new([0; 256]),
+            rtc_address: 0,
+            rtc_command: 0,
+            rtc_result: 0,
+            minutes: 0,
+            days: 0,
+            rtc_counter: 0,
+            rtc_source: RtcSource::Cycles,
+            clock: Arc::new(SystemClock),
+            rtc_host_base: None,
+        }
+    }
+
+    fn add_minutes(&mut self, minutes: u64) {
+        let total = u64::from(self.minutes) + minutes;
+        let days = total / u64::from(HUC3_MINUTES_PER_DAY);
+        self.minutes = (total % u64::from(HUC3_MINUTES_PER_DAY)) as u16;
+        self.days = ((u64::from(self.days) + days) & u64::from(HUC3_DAY_MASK)) as u16;
+    }
+
+    /// Moves whole minutes of host time into the clock, leaving the rest on the base.
+    fn fold_host_elapsed(&mut self) {
+        let Some(base) = self.rtc_host_base else {
+            return;
+        };
+        let elapsed = self
+            .clock
+            .now()
+            .duration_since(base)
+            .unwrap_or(Duration::ZERO);
+        let minutes = elapsed.as_secs() / 60;
+        self.add_minutes(minutes);
+        self.rtc_host_base = Some(base + Duration::from_secs(minutes * 60));
+    }
+
+    fn switchable_rom_bank(&self, bank_count: usize) -> usize {
+        normalize_switchable_bank(self.rom_bank as usize, bank_count)
+    }
+
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        normalize_ram_bank(self.ram_bank as usize, ram_bank_count_for(cartridge, 4))
+    }
+
+    /// Runs one command written to the RTC port: the high nibble picks the command, the
+    /// low nibble is its argument.
+    fn rtc_command(&mut self, value: u8) {
+        let command = (value >> 4) & 0x07;
+        let arg = value & 0x0F;
+        match command {
+            0x1 => {
+                self.rtc_result = self.rtc_memory[usize::from(self.rtc_address)];
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            0x3 => {
+                self.rtc_memory[usize::from(self.rtc_address)] = arg;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            0x4 => self.rtc_address = (self.rtc_address & 0xF0) | arg,
+            0x5 => self.rtc_address = (self.rtc_address & 0x0F) | (arg << 4),
+            0x6 => match arg {
+                0x0 => self.store_time(),
+                0x1 => self.load_time(),
+                0x2 => self.rtc_result = 0x1,
+                _ => {}
+            },
+            _ => {}
+        }
+        self.rtc_command = command;
+    }
+
+    /// Copies the clock into memory 0x00-0x05: minutes then days, 12 bits each, low
+    /// nibble first.
+    fn store_time(&mut self) {
+        self.fold_host_elapsed();
+        for (index, value) in [self.minutes, self.days].into_iter().enumerate() {
+            for nibble in 0..3 {
+                self.rtc_memory[index * 3 + nibble] = ((value >> (nibble * 4)) & 0x0F) as u8;
+            }
+        }
+    }
+
+    fn load_time(&mut self) {
+        let read = |start: usize| {
+            (0..3).fold(0u16, |value, nibble| {
+                value | u16::from(self.rtc_memory[start + nibble] & 0x0F) << (nibble * 4)
+            })
+        };
+        self.minutes = read(0) % HUC3_MINUTES_PER_DAY;
+        self.days = read(3) & HUC3_DAY_MASK;
+        self.rtc_counter = 0;
+        if self.rtc_source == RtcSource::Wallclock {
+            self.rtc_host_base = Some(self.clock.now());
+        }
+    }
+}
+
+impl Mapper for HuC3 {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => {
+                let bank = self.switchable_rom_bank(bank_count(&cartridge.bytes));
+                RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
+            }
+            EXT_RAM_START..=EXT_RAM_END => match self.mode {
+                HUC3_MODE_RAM_READ | HUC3_MODE_RAM => {
+                    read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
+                }
+                HUC3_MODE_RTC_RESPONSE => 0x80 | (self.rtc_command << 4) | self.rtc_result,
+                HUC3_MODE_RTC_SEMAPHORE => 0x01,
+                HUC3_MODE_IR => HUC3_IR_NO_LIGHT,
+                _ => open_bus,
+            },
+            _ => open_bus,
+        }
+    }
+
+    fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.mode = value & 0x0F;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank = value & 0x7F;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank = value & 0x03;
+            }
+            EXT_RAM_START..=EXT_RAM_END => match self.mode {
+                HUC3_MODE_RAM => {
+                    let ram_bank = self.selected_ram_bank(cartridge);
+                    write_ext_ram(cartridge, ram_bank, addr, value);
+                }
+                HUC3_MODE_RTC_COMMAND => self.rtc_command(value),
+                HUC3_MODE_RAM_READ
+                | HUC3_MODE_RTC_RESPONSE
+                | HUC3_MODE_RTC_SEMAPHORE
+                | HUC3_MODE_IR => {}
+                _ => self.fault = Some(MbcError::Fault("HuC3 write in an undefined mode")),
+            },
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        const CYCLES_PER_MINUTE: u32 = CYCLES_PER_SECOND * 60;
+        if self.rtc_source == RtcSource::Wallclock {
+            return;
+        }
+        self.rtc_counter = self.rtc_counter.wrapping_add(cycles);
+        while self.rtc_counter >= CYCLES_PER_MINUTE {
+            self.rtc_counter -= CYCLES_PER_MINUTE;
+            self.add_minutes(1);
+        }
+    }
+
+    fn set_rtc_source(&mut self, source: RtcSource) {
+        if self.rtc_source == source {
+            return;
+        }
+        match source {
+            RtcSource::Cycles => {
+                self.fold_host_elapsed();
+                self.rtc_host_base = None;
+            }
+            RtcSource::Wallclock => self.rtc_host_base = Some(self.clock.now()),
+        }
+        self.rtc_counter = 0;
+        self.rtc_source = source;
+    }
+
+    fn set_time_source(&mut self, clock: Arc<dyn TimeSource>) {
+        if self.rtc_source == RtcSource::Wallclock {
+            self.fold_host_elapsed();
+            self.rtc_host_base = Some(clock.now());
+        }
+        self.clock = clock;
+    }
+
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.switchable_rom_bank(bank_count(&cartridge.bytes))
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 4)
+    }
+
+    /// Restores the banking registers; the clock and its memory are kept.
+    fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.mode = HUC3_MODE_RAM_READ;
+        self.fault = None;
+    }
+
+    /// Also clears the clock and its memory, keeping how the clock is driven.
+    fn power_cycle(&mut self) {
+        let source = self.rtc_source;
+        let clock = self.clock.clone();
+        *self = Self::new();
+        self.set_time_source(clock);
+        self.set_rtc_source(source);
+    }
+
+    fn take_fault(&mut self) -> Option<MbcError> {
+        self.fault.take()
+    }
+}
+
+/// Nintendo's MAC-GBD, the Pocket Camera mapper: MBC5-style ROM and RAM banking, with
+/// RAM bank values that set bit 4 mapping the sensor registers into 0xA000-0xBFFF instead.
+/// Captures finish instantly, dithering `image` (or a test gradient) through the
+/// game-supplied threshold matrix into the photo area of RAM bank 0.
+#[derive(Debug, Clone)]
+struct PocketCamera {
+    rom_bank: u8,
+    ram_bank: u8,
+    ram_enabled: bool,
+    registers: [u8; CAMERA_REGISTER_COUNT],
+    image: Option<Box<[u8]>>,
+}
+
+impl PocketCamera {
+    fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            registers: [0; CAMERA_REGISTER_COUNT],
+            image: None,
+        }
+    }
+
+    fn rom_bank(&self, bank_count: usize) -> usize {
+        normalize_bank(self.rom_bank as usize, bank_count)
+    }
+
+    fn registers_selected(&self) -> bool {
+        self.ram_bank & CAMERA_REGISTER_SELECT != 0
+    }
+
+    fn selected_ram_bank(&self, cartridge: &Cartridge) -> Option<usize> {
+        normalize_ram_bank(
+            (self.ram_bank & 0x0F) as usize,
+            ram_bank_count_for(cartridge, 16),
+        )
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        match &self.image {
+            Some(image) => image[y * CAMERA_IMAGE_WIDTH + x],
+            None => ((x + y) * 255 / (CAMERA_IMAGE_WIDTH + CAMERA_IMAGE_HEIGHT - 2)) as u8,
+        }
+    }
+
+    /// Writes the image into RAM bank 0 as 16x14 tiles of 2bpp data. Each pixel picks its
+    /// shade by comparing against the three thresholds at its position in the 4x4 matrix.
+    fn capture(&self, cartridge: &mut Cartridge) {
+        for y in 0..CAMERA_IMAGE_HEIGHT {
+            for x in 0..CAMERA_IMAGE_WIDTH {
+                let matrix = CAMERA_DITHER_START + ((y & 3) * 4 + (x & 3)) * 3;
+                let thresholds = &self.registers[matrix..matrix + 3];
+                let value = self.pixel(x, y);
+                let shade = thresholds.iter().filter(|&&limit| value < limit).count() as u8;
+                let tile = (y / 8) * (CAMERA_IMAGE_WIDTH / 8) + x / 8;
+                let offset = CAMERA_PHOTO_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                for (plane, mask) in [(0, 0x01), (1, 0x02)] {
+                    if let Some(byte) = cartridge.ext_ram.get_mut(offset + plane) {
+                        if shade & mask != 0 {
+                            *byte |= bit;
+                        } else {
+                            *byte &= !bit;
+                        }
+                    }
+                }
+            }
+        }
+        cartridge.mark_ram_dirty();
+    }
+}
+
+impl Mapper for PocketCamera {
+    fn read8(&self, cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => {
+                let bank = self.rom_bank(bank_count(&cartridge.bytes));
+                RomBankMapping::with_banks(&cartridge.bytes, 0, bank).read(addr)
+            }
+            EXT_RAM_START..=EXT_RAM_END => {
+                if self.registers_selected() {
+                    // Only the capture control register reads back.
+                    return if addr & 0x7F == 0 {
+                        self.registers[0]
+                    } else {
+                        0x00
+                    };
+                }
+                // RAM reads work even while writes are disabled.
+                read_ext_ram(cartridge, self.selected_ram_bank(cartridge), addr, open_bus)
+            }
+            _ => open_bus,
+        }
+    }
+
+    fn write8(&mut self, cartridge: &mut Cartridge, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank = value & 0x3F;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank = value & 0x1F;
+            }
+            EXT_RAM_START..=EXT_RAM_END => {
+                if self.registers_selected() {
+                    let index = usize::from(addr & 0x7F);
+                    if index == 0 {
+                        self.registers[0] = value & 0x07;
+                        if value & CAMERA_CAPTURE_BUSY != 0 {
+                            self.capture(cartridge);
+                            self.registers[0] &= !CAMERA_CAPTURE_BUSY;
+                        }
+                    } else if index < CAMERA_REGISTER_COUNT {
+                        self.registers[index] = value;
+                    }
+                    return;
+                }
+                if !self.ram_enabled {
+                    return;
+                }
+                let ram_bank = self.selected_ram_bank(cartridge);
+                write_ext_ram(cartridge, ram_bank, addr, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn current_rom_bank(&self, cartridge: &Cartridge) -> usize {
+        self.rom_bank(bank_count(&cartridge.bytes))
+    }
+
+    fn current_ram_bank(&self, cartridge: &Cartridge) -> Option<RamBankSelection> {
+        if self.registers_selected() {
+            return Some(RamBankSelection::Camera);
+        }
+        self.selected_ram_bank(cartridge)
+            .map(RamBankSelection::Bank)
+    }
+
+    fn ram_bank_count(&self, cartridge: &Cartridge) -> usize {
+        ram_bank_count_for(cartridge, 16)
+    }
+
+    /// Clears the banking and sensor registers, keeping the supplied image.
+    fn reset(&mut self) {
+        *self = Self {
+            image: self.image.take(),
+            ..Self::new()
+        };
+    }
+
+    fn set_camera_image(&mut self, image: &[u8]) {
+        self.image = Some(image.into());
+    }
+}
+
+pub(crate) fn check_camera_image(image: &[u8]) -> Result<(), MbcError> {
+    let expected = CAMERA_IMAGE_WIDTH * CAMERA_IMAGE_HEIGHT;
+    if image.len() != expected {
+        return Err(MbcError::CameraImageSize {
+            expected,
+            actual: image.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Where `addr` in the given bank lands in cartridge RAM. A 2KB chip only decodes the low
+/// 11 address lines, so it repeats every 0x800 bytes across the whole window.
+fn ext_ram_index(cartridge: &Cartridge, bank: Option<usize>, addr: u16) -> Option<usize> {
+    let len = cartridge.ext_ram.len();
+    if len == 0 {
+        return None;
     }
-    let Some(bank) = bank else {
-        return OPEN_BUS;
-    };
     let offset = addr as usize - EXT_RAM_START as usize;
-    let index = bank * EXT_RAM_BANK_SIZE + offset;
-    cartridge.ext_ram.get(index).copied().unwrap_or(OPEN_BUS)
+    let index = bank? * EXT_RAM_BANK_SIZE + offset;
+    Some(if len < EXT_RAM_BANK_SIZE {
+        index % len
+    } else {
+        index
+    })
+}
+
+fn read_ext_ram(cartridge: &Cartridge, bank: Option<usize>, addr: u16, open_bus: u8) -> u8 {
+    ext_ram_index(cartridge, bank, addr)
+        .and_then(|index| cartridge.ext_ram.get(index).copied())
+        .unwrap_or(open_bus)
 }
 
 fn write_ext_ram(cartridge: &mut Cartridge, bank: Option<usize>, addr: u16, value: u8) {
-    if cartridge.ext_ram.is_empty() {
-        return;
-    }
-    let Some(bank) = bank else {
+    let Some(index) = ext_ram_index(cartridge, bank, addr) else {
         return;
     };
-    let offset = addr as usize - EXT_RAM_START as usize;
-    let index = bank * EXT_RAM_BANK_SIZE + offset;
     if let Some(byte) = cartridge.ext_ram.get_mut(index)
         && *byte != value
     {
@@ -683,12 +1714,12 @@ fn write_ext_ram(cartridge: &mut Cartridge, bank: Option<usize>, addr: u16, valu
     }
 }
 
-fn read_mbc2_ram(cartridge: &Cartridge, addr: u16) -> u8 {
+fn read_mbc2_ram(cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
     if addr > MBC2_RAM_END {
-        return OPEN_BUS;
+        return open_bus;
     }
     if cartridge.ext_ram.len() < MBC2_RAM_SIZE {
-        return OPEN_BUS;
+        return open_bus;
     }
     let offset = (addr as usize - EXT_RAM_START as usize) & 0x01FF;
     let value = cartridge.ext_ram.get(offset).copied().unwrap_or(0) & 0x0F;
@@ -758,9 +1789,295 @@ fn normalize_switchable_bank(bank: usize, bank_count: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{CYCLES_PER_SECOND, Mbc, RtcMode, bank_count};
-    use crate::domain::Cartridge;
+    use super::{
+        CAMERA_IMAGE_HEIGHT, CAMERA_IMAGE_WIDTH, CYCLES_PER_SECOND, EXT_RAM_START, Mapper, Mbc,
+        MbcError, RamBankSelection, RtcMode, RtcRegister, RtcSource, bank_count,
+    };
     use crate::domain::cartridge::ROM_BANK_SIZE;
+    use crate::domain::{Cartridge, CartridgeType, MockClock, NINTENDO_LOGO};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn is_supported_matches_mbc_acceptance() {
+        for code in 0..=u8::MAX {
+            let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+            bytes[0x0147] = code;
+            let cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+            let cartridge_type = cartridge.header.cartridge_type;
+            assert_eq!(
+                Mbc::new(&cartridge).is_ok(),
+                cartridge_type.is_supported(),
+                "{:?}",
+                cartridge_type
+            );
+        }
+        for cartridge_type in CartridgeType::KNOWN {
+            let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+            bytes[0x0147] = cartridge_type.code();
+            let cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+            assert_eq!(cartridge.header.cartridge_type, cartridge_type);
+        }
+    }
+
+    #[test]
+    fn mmm01_maps_menu_then_locks_to_selected_game() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 16];
+        for (bank, chunk) in bytes.chunks_mut(ROM_BANK_SIZE).enumerate() {
+            chunk.fill(bank as u8);
+        }
+        bytes[0x0147] = 0x0B; // MMM01
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        // Unmapped: the menu in the last 32KB.
+        assert_eq!(mbc.read8(&cartridge, 0x0000), 14);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 15);
+
+        // The menu picks a 4-bank game at bank 4, masks RA16-RA18, then locks.
+        mbc.write8(&mut cartridge, 0x2000, 0x04);
+        mbc.write8(&mut cartridge, 0x6000, 0x0E << 2);
+        mbc.write8(&mut cartridge, 0x4000, 0x00);
+        mbc.write8(&mut cartridge, 0x0000, 0x40);
+        assert_eq!(mbc.read8(&cartridge, 0x0000), 4);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 5);
+
+        // The game banks within its slice and can't escape or unlock it.
+        mbc.write8(&mut cartridge, 0x2000, 0x02);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 6);
+        mbc.write8(&mut cartridge, 0x2000, 0x1F);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 7);
+        mbc.write8(&mut cartridge, 0x2000, 0x00);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 5);
+        mbc.write8(&mut cartridge, 0x0000, 0x00);
+        mbc.write8(&mut cartridge, 0x6000, 0x00);
+        mbc.write8(&mut cartridge, 0x2000, 0x03);
+        assert_eq!(mbc.read8(&cartridge, 0x0000), 4);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 7);
+    }
+
+    #[test]
+    fn mmm01_is_detected_from_the_menu_header_in_the_last_32kb() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 16];
+        for (bank, chunk) in bytes.chunks_mut(ROM_BANK_SIZE).enumerate() {
+            chunk.fill(bank as u8);
+        }
+        bytes[0x0147] = 0x01; // the first game's MBC1 header
+        bytes[0x0149] = 0x00;
+        let menu = bytes.len() - ROM_BANK_SIZE * 2;
+        bytes[menu + 0x0104..menu + 0x0134].copy_from_slice(&NINTENDO_LOGO);
+        bytes[menu + 0x0147] = 0x0D; // MMM01+RAM+BATTERY
+        bytes[menu + 0x0149] = 0x02;
+        let cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+
+        assert_eq!(
+            cartridge.header.cartridge_type,
+            CartridgeType::Mmm01RamBattery
+        );
+        assert!(cartridge.has_ram());
+        let mbc = Mbc::new(&cartridge).expect("mbc");
+        assert_eq!(mbc.read8(&cartridge, 0x0000), 14);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 15);
+    }
+
+    #[test]
+    fn huc3_banks_rom_and_gates_ram_by_mode() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 8];
+        for (bank, chunk) in bytes.chunks_mut(ROM_BANK_SIZE).enumerate() {
+            chunk.fill(bank as u8);
+        }
+        bytes[0x0147] = 0xFE; // HuC3
+        bytes[0x0149] = 0x02; // 8KB
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        mbc.write8(&mut cartridge, 0x2000, 0x03);
+        assert_eq!(mbc.read8(&cartridge, 0x4000), 3);
+
+        mbc.write8(&mut cartridge, 0x0000, 0x0A);
+        mbc.write8(&mut cartridge, 0xA000, 0x42);
+        assert_eq!(mbc.read8(&cartridge, 0xA000), 0x42);
+
+        mbc.write8(&mut cartridge, 0x0000, 0x00);
+        mbc.write8(&mut cartridge, 0xA000, 0x99);
+        assert_eq!(mbc.read8(&cartridge, 0xA000), 0x42);
+
+        mbc.write8(&mut cartridge, 0x0000, 0x05);
+        assert_eq!(mbc.read8(&cartridge, 0xA000), 0xFF);
+        mbc.write8(&mut cartridge, 0x0000, 0x0E);
+        assert_eq!(mbc.read8(&cartridge, 0xA000), 0xC0);
+        assert_eq!(mbc.take_fault(), None);
+    }
+
+    #[test]
+    fn huc3_faults_once_on_a_write_in_an_undefined_mode() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0xFE; // HuC3
+        bytes[0x0149] = 0x02; // 8KB
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        mbc.write8(&mut cartridge, 0x0000, 0x0E);
+        mbc.write8(&mut cartridge, 0xA000, 0x01);
+        assert_eq!(mbc.take_fault(), None);
+
+        mbc.write8(&mut cartridge, 0x0000, 0x05);
+        mbc.write8(&mut cartridge, 0xA000, 0x01);
+        assert_eq!(
+            mbc.take_fault(),
+            Some(MbcError::Fault("HuC3 write in an undefined mode"))
+        );
+        assert_eq!(mbc.take_fault(), None);
+    }
+
+    #[test]
+    fn huc3_rtc_command_interface_reads_and_writes_memory() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0xFE; // HuC3
+        bytes[0x0149] = 0x02;
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+        let command = |mbc: &mut Mbc, cartridge: &mut Cartridge, commands: &[u8]| {
+            mbc.write8(cartridge, 0x0000, 0x0B);
+            for &value in commands {
+                mbc.write8(cartridge, 0xA000, value);
+            }
+            mbc.write8(cartridge, 0x0000, 0x0C);
+            mbc.read8(cartridge, 0xA000)
+        };
+
+        // Write 7 to address 0x10, then read it back.
+        command(&mut mbc, &mut cartridge, &[0x40, 0x51, 0x37]);
+        let response = command(&mut mbc, &mut cartridge, &[0x40, 0x10]);
+        assert_eq!(response, 0x97);
+
+        // Set the clock to 5 minutes, let a minute pass, and read it back.
+        command(
+            &mut mbc,
+            &mut cartridge,
+            &[0x40, 0x50, 0x35, 0x30, 0x30, 0x30, 0x30, 0x30, 0x61],
+        );
+        mbc.tick(CYCLES_PER_SECOND * 60);
+        let response = command(&mut mbc, &mut cartridge, &[0x60, 0x40, 0x50, 0x10]);
+        assert_eq!(response & 0x0F, 6);
+    }
+
+    #[test]
+    fn huc3_wallclock_source_reads_the_injected_clock() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0xFE; // HuC3
+        bytes[0x0149] = 0x02;
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+        let clock = Arc::new(MockClock::new(UNIX_EPOCH + Duration::from_secs(1_000)));
+        mbc.set_time_source(clock.clone());
+        mbc.set_rtc_source(RtcSource::Wallclock);
+        let minutes = |mbc: &mut Mbc, cartridge: &mut Cartridge| {
+            mbc.write8(cartridge, 0x0000, 0x0B);
+            let mut value = 0;
+            for (shift, command) in [0x60, 0x40, 0x50, 0x10, 0x10, 0x10].into_iter().enumerate() {
+                mbc.write8(cartridge, 0xA000, command);
+                if let Some(nibble) = shift.checked_sub(3) {
+                    mbc.write8(cartridge, 0x0000, 0x0C);
+                    value |= u16::from(mbc.read8(cartridge, 0xA000) & 0x0F) << (nibble * 4);
+                    mbc.write8(cartridge, 0x0000, 0x0B);
+                }
+            }
+            value
+        };
+
+        mbc.tick(CYCLES_PER_SECOND * 600);
+        assert_eq!(minutes(&mut mbc, &mut cartridge), 0);
+        clock.advance(Duration::from_secs(90 * 60 + 30));
+        assert_eq!(minutes(&mut mbc, &mut cartridge), 90);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(minutes(&mut mbc, &mut cartridge), 91);
+    }
+
+    #[test]
+    fn pocket_camera_banks_photo_ram_and_captures() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 4];
+        bytes[0x0147] = 0xFC; // Pocket Camera
+        bytes[0x0149] = 0x04; // 128KB
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        mbc.write8(&mut cartridge, 0x0000, 0x0A);
+        mbc.write8(&mut cartridge, 0x4000, 0x03);
+        mbc.write8(&mut cartridge, 0xA123, 0x5A);
+        assert_eq!(mbc.read8(&cartridge, 0xA123), 0x5A);
+        assert_eq!(
+            mbc.current_ram_bank(&cartridge),
+            Some(RamBankSelection::Bank(3))
+        );
+        mbc.write8(&mut cartridge, 0x4000, 0x00);
+        assert_eq!(mbc.read8(&cartridge, 0xA123), 0x00);
+
+        mbc.set_camera_image(&[0; CAMERA_IMAGE_WIDTH * CAMERA_IMAGE_HEIGHT])
+            .expect("camera image");
+        mbc.write8(&mut cartridge, 0x4000, 0x10);
+        assert_eq!(
+            mbc.current_ram_bank(&cartridge),
+            Some(RamBankSelection::Camera)
+        );
+        for addr in 0xA006..0xA036 {
+            mbc.write8(&mut cartridge, addr, 0x80);
+        }
+        mbc.write8(&mut cartridge, 0xA000, 0x01);
+        assert_eq!(mbc.read8(&cartridge, 0xA000) & 0x01, 0);
+
+        mbc.write8(&mut cartridge, 0x4000, 0x00);
+        assert_eq!(mbc.read8(&cartridge, 0xA100), 0xFF);
+        assert_eq!(mbc.read8(&cartridge, 0xA101), 0xFF);
+        assert_eq!(
+            mbc.set_camera_image(&[0; 4]),
+            Err(MbcError::CameraImageSize {
+                expected: CAMERA_IMAGE_WIDTH * CAMERA_IMAGE_HEIGHT,
+                actual: 4,
+            })
+        );
+    }
+
+    /// Serves 0xA000 from a single register and everything else from the open bus.
+    #[derive(Debug, Clone, Default)]
+    struct LatchMapper {
+        latch: u8,
+    }
+
+    impl Mapper for LatchMapper {
+        fn read8(&self, _cartridge: &Cartridge, addr: u16, open_bus: u8) -> u8 {
+            if addr == EXT_RAM_START {
+                self.latch
+            } else {
+                open_bus
+            }
+        }
+
+        fn write8(&mut self, _cartridge: &mut Cartridge, addr: u16, value: u8) {
+            if addr == EXT_RAM_START {
+                self.latch = value;
+            }
+        }
+    }
+
+    #[test]
+    fn custom_mapper_services_reads_and_writes() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0xFD; // Bandai TAMA5, which has no built-in mapper
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        assert!(Mbc::new(&cartridge).is_err());
+        let mut mbc = Mbc::with_mapper(Box::new(LatchMapper::default()));
+        mbc.set_open_bus(0x00);
+
+        mbc.write8(&mut cartridge, 0xA000, 0x5A);
+        assert_eq!(mbc.read8(&cartridge, 0xA000), 0x5A);
+        assert_eq!(mbc.read8(&cartridge, 0xA001), 0x00);
+        assert_eq!(mbc.current_rom_bank(&cartridge), 1);
+
+        let copy = mbc.clone();
+        mbc.write8(&mut cartridge, 0xA000, 0x11);
+        assert_eq!(copy.read8(&cartridge, 0xA000), 0x5A);
+    }
 
     #[test]
     fn mbc1_write_changes_switchable_rom_bank() {
@@ -782,6 +2099,63 @@ mod tests {
         assert_eq!(mbc.read8(&cartridge, 0x4000), 0x22);
     }
 
+    #[test]
+    fn mbc1_current_rom_bank_reports_selected_bank() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 8];
+        bytes[0x0147] = 0x01;
+        bytes[0x0148] = 0x02;
+
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        assert_eq!(mbc.current_rom_bank(&cartridge), 1);
+        mbc.write8(&mut cartridge, 0x2000, 0x05);
+        assert_eq!(mbc.current_rom_bank(&cartridge), 5);
+        mbc.write8(&mut cartridge, 0x2000, 0x00);
+        assert_eq!(mbc.current_rom_bank(&cartridge), 1);
+    }
+
+    #[test]
+    fn mbc5_current_ram_bank_reports_selected_bank() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0x1A;
+        bytes[0x0149] = 0x03;
+
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        assert_eq!(mbc.ram_bank_count(&cartridge), 4);
+        assert_eq!(
+            mbc.current_ram_bank(&cartridge),
+            Some(RamBankSelection::Bank(0))
+        );
+        mbc.write8(&mut cartridge, 0x4000, 0x02);
+        assert_eq!(
+            mbc.current_ram_bank(&cartridge),
+            Some(RamBankSelection::Bank(2))
+        );
+    }
+
+    #[test]
+    fn mbc3_current_ram_bank_reports_rtc_register() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0x10;
+        bytes[0x0149] = 0x03;
+
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+
+        mbc.write8(&mut cartridge, 0x4000, 0x0A);
+        let selection = mbc.current_ram_bank(&cartridge).expect("selection");
+        assert_eq!(selection, RamBankSelection::Rtc(RtcRegister::Hours));
+        assert_eq!(selection.label(), "RTC:H");
+        mbc.write8(&mut cartridge, 0x4000, 0x01);
+        assert_eq!(
+            mbc.current_ram_bank(&cartridge),
+            Some(RamBankSelection::Bank(1))
+        );
+    }
+
     #[test]
     fn mbc1_mode_select_remaps_fixed_bank() {
         let mut bytes = vec![0; ROM_BANK_SIZE * 64];
@@ -860,6 +2234,28 @@ mod tests {
         assert_eq!(mbc.read8(&cartridge, 0xA000), 0x44);
     }
 
+    #[test]
+    fn mbc1_2kb_ram_mirrors_across_the_window() {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0x03;
+        bytes[0x0149] = 0x01; // 2KB
+
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        assert_eq!(cartridge.ext_ram.len(), 0x800);
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+        mbc.write8(&mut cartridge, 0x0000, 0x0A);
+
+        mbc.write8(&mut cartridge, 0xA010, 0x12);
+        mbc.write8(&mut cartridge, 0xA810, 0x34);
+        assert_eq!(mbc.read8(&cartridge, 0xA010), 0x34);
+        assert_eq!(mbc.read8(&cartridge, 0xB810), 0x34);
+        assert_eq!(cartridge.ext_ram.len(), 0x800);
+
+        mbc.write8(&mut cartridge, 0xBFFF, 0x56);
+        assert_eq!(mbc.read8(&cartridge, 0xA7FF), 0x56);
+        assert_eq!(mbc.read8(&cartridge, 0xA010), 0x34);
+    }
+
     #[test]
     fn mbc2_rom_and_ram_rules() {
         let mut bytes = vec![0; ROM_BANK_SIZE * 4];
@@ -921,6 +2317,106 @@ mod tests {
         assert_eq!(mbc.read8(&cartridge, 0xA000), 1);
     }
 
+    fn mbc3_rtc_cartridge() -> (Cartridge, Mbc) {
+        let mut bytes = vec![0; ROM_BANK_SIZE * 2];
+        bytes[0x0147] = 0x0F;
+        bytes[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::from_bytes(bytes).expect("cartridge");
+        let mut mbc = Mbc::new(&cartridge).expect("mbc");
+        mbc.set_rtc_mode(RtcMode::Deterministic);
+        mbc.write8(&mut cartridge, 0x0000, 0x0A);
+        (cartridge, mbc)
+    }
+
+    fn write_rtc(mbc: &mut Mbc, cartridge: &mut Cartridge, select: u8, value: u8) {
+        mbc.write8(cartridge, 0x4000, select);
+        mbc.write8(cartridge, 0xA000, value);
+    }
+
+    fn latch_and_read_rtc(mbc: &mut Mbc, cartridge: &mut Cartridge, select: u8) -> u8 {
+        mbc.write8(cartridge, 0x6000, 0x00);
+        mbc.write8(cartridge, 0x6000, 0x01);
+        mbc.write8(cartridge, 0x4000, select);
+        mbc.read8(cartridge, 0xA000)
+    }
+
+    #[test]
+    fn mbc3_rtc_halt_stops_and_resumes_clock() {
+        let (mut cartridge, mut mbc) = mbc3_rtc_cartridge();
+
+        write_rtc(&mut mbc, &mut cartridge, 0x0C, 0x40);
+        mbc.tick(CYCLES_PER_SECOND * 5);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 0);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0C), 0x40);
+
+        write_rtc(&mut mbc, &mut cartridge, 0x0C, 0x00);
+        mbc.tick(CYCLES_PER_SECOND);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 1);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0C), 0x00);
+    }
+
+    #[test]
+    fn mbc3_rtc_day_carry_sets_on_rollover_and_sticks() {
+        let (mut cartridge, mut mbc) = mbc3_rtc_cartridge();
+
+        write_rtc(&mut mbc, &mut cartridge, 0x08, 59);
+        write_rtc(&mut mbc, &mut cartridge, 0x09, 59);
+        write_rtc(&mut mbc, &mut cartridge, 0x0A, 23);
+        write_rtc(&mut mbc, &mut cartridge, 0x0B, 0xFF);
+        write_rtc(&mut mbc, &mut cartridge, 0x0C, 0x01);
+        mbc.tick(CYCLES_PER_SECOND);
+
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0B), 0x00);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0C), 0x80);
+
+        mbc.tick(CYCLES_PER_SECOND);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 1);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0C), 0x80);
+
+        write_rtc(&mut mbc, &mut cartridge, 0x0C, 0x00);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x0C), 0x00);
+    }
+
+    #[test]
+    fn mbc3_rtc_restore_keeps_fractional_seconds() {
+        let (mut cartridge, mut mbc) = mbc3_rtc_cartridge();
+
+        for _ in 0..10 {
+            let snapshot = mbc.rtc_snapshot().expect("rtc snapshot");
+            let (next_cartridge, mut next_mbc) = mbc3_rtc_cartridge();
+            next_mbc.restore_rtc(snapshot, Duration::from_millis(1500));
+            cartridge = next_cartridge;
+            mbc = next_mbc;
+        }
+
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 15);
+        let snapshot = mbc.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.subsecond_cycles, 0);
+    }
+
+    #[test]
+    fn mbc3_rtc_wallclock_source_follows_real_time() {
+        let (mut cartridge, mut mbc) = mbc3_rtc_cartridge();
+        let clock = Arc::new(MockClock::new(UNIX_EPOCH + Duration::from_secs(1_000)));
+        mbc.set_time_source(clock.clone());
+        write_rtc(&mut mbc, &mut cartridge, 0x08, 10);
+        mbc.set_rtc_source(RtcSource::Wallclock);
+
+        mbc.tick(CYCLES_PER_SECOND * 4);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 10);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 40);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x09), 1);
+
+        mbc.set_rtc_source(RtcSource::Cycles);
+        clock.advance(Duration::from_secs(90));
+        mbc.tick(CYCLES_PER_SECOND);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x08), 41);
+        assert_eq!(latch_and_read_rtc(&mut mbc, &mut cartridge, 0x09), 1);
+    }
+
     #[test]
     fn mbc3_without_rtc_ignores_rtc_register_selection() {
         let mut bytes = vec![0; ROM_BANK_SIZE * 2];
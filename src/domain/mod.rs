@@ -1,22 +1,56 @@
 pub mod apu;
 pub mod bus;
 pub mod cartridge;
+pub mod clock;
+pub mod colorization;
 pub mod cpu;
 pub mod emulator;
 pub mod framebuffer;
+pub mod joypad;
 pub mod mbc;
+pub mod movie;
 pub mod ppu;
 pub mod rom;
+pub mod save;
+pub mod sgb;
+pub mod trace;
 
-pub use apu::Apu;
-pub use bus::Bus;
-pub use cartridge::{Cartridge, RomBankMapping, RomBankView};
-pub use cpu::{Cpu, CpuError, Registers};
-pub use emulator::Emulator;
-pub use framebuffer::{FRAME_CHANNELS, FRAME_HEIGHT, FRAME_SIZE, FRAME_WIDTH, Framebuffer};
-pub use mbc::{Mbc, MbcError, RtcMode};
-pub use ppu::{FRAME_INTERVAL_NS, Ppu};
+pub use apu::{
+    Apu, ApuReport, ChannelId, ClipMode, SampleDepth, dominant_frequency, goertzel,
+    measure_pulse_pitch,
+};
+pub use bus::{Bus, Clocked, MemoryRegion, diff_snapshots};
+pub use cartridge::{Cartridge, CartridgeOptions, RomBankMapping, RomBankView};
+#[cfg(test)]
+pub use clock::mock::MockClock;
+pub use clock::{SystemClock, TimeSource};
+pub use colorization::PaletteSet;
+pub use cpu::{Cpu, CpuError, OpcodeCoverage, RegisterSnapshot, Registers};
+pub use emulator::{
+    Emulator, FrameError, LoadError, MemoryLoadError, OpcodePolicy, RunError, StepResult,
+    UnsupportedOpcode,
+};
+pub use framebuffer::{
+    FRAME_CHANNELS, FRAME_HEIGHT, FRAME_RGBA_SIZE, FRAME_SIZE, FRAME_WIDTH, Framebuffer,
+    FramebufferError, dmg_lcd_filter, rgb_to_rgba,
+};
+pub use joypad::{
+    Autofire, Button, ButtonState, DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP, Dpad, SocdMode,
+};
+pub use mbc::{
+    CAMERA_IMAGE_HEIGHT, CAMERA_IMAGE_WIDTH, Mapper, Mbc, MbcError, RamBankSelection, RtcMode,
+    RtcRegister, RtcSnapshot, RtcSource,
+};
+pub use movie::{MOVIE_MAGIC, Movie, MovieError, MovieFrame};
+pub use ppu::{
+    FRAME_CYCLES, FRAME_INTERVAL_NS, LAYER_BG, LAYER_SPRITES, LAYER_WINDOW, Ppu, SpriteInfo,
+    TILE_MAP_SIZE, TILE_MAP_WIDTH, parse_oam,
+};
 pub use rom::{
-    CartridgeType, CgbFlag, Destination, Licensee, RamSize, RomHeader, RomHeaderError, RomSize,
-    SgbFlag, compute_global_checksum, compute_header_checksum, nintendo_logo_matches,
+    CartridgeType, CgbFlag, Destination, DumpSize, Licensee, NINTENDO_LOGO, RamSize, RomHeader,
+    RomHeaderError, RomSize, SgbFlag, compute_global_checksum, compute_header_checksum,
+    nintendo_logo_matches, repair_header,
 };
+pub use save::{RTC_FOOTER_SIZE, RtcFooter, SaveError};
+pub use sgb::{SgbCommand, SgbPacketDecoder};
+pub use trace::{TraceLog, trace_line};
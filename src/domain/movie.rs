@@ -0,0 +1,179 @@
+use super::{Cartridge, compute_global_checksum};
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"CBMV";
+const MOVIE_VERSION: u8 = 1;
+/// Magic, version, ROM checksum and frame count.
+const MOVIE_HEADER_SIZE: usize = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated { expected: usize, actual: usize },
+    RomMismatch { expected: u16, actual: u16 },
+}
+
+/// Joypad lines for one frame, active-low like JOYP: bit 0 is A or Right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub buttons: u8,
+    pub dpad: u8,
+}
+
+impl MovieFrame {
+    pub const RELEASED: Self = Self {
+        buttons: 0x0F,
+        dpad: 0x0F,
+    };
+
+    fn encode(self) -> u8 {
+        ((self.buttons & 0x0F) << 4) | (self.dpad & 0x0F)
+    }
+
+    fn decode(byte: u8) -> Self {
+        Self {
+            buttons: byte >> 4,
+            dpad: byte & 0x0F,
+        }
+    }
+}
+
+/// Per-frame input for one ROM, so a run can be replayed exactly. Stored as a `.gbm`
+/// file: the header, then one byte per frame with buttons in the high nibble.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    rom_checksum: u16,
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(cartridge: &Cartridge) -> Self {
+        Self {
+            rom_checksum: rom_checksum(cartridge),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn rom_checksum(&self) -> u16 {
+        self.rom_checksum
+    }
+
+    pub fn frames(&self) -> &[MovieFrame] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn push(&mut self, frame: MovieFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Input for `index`; frames past the end of the movie have nothing pressed.
+    pub fn frame(&self, index: usize) -> MovieFrame {
+        self.frames
+            .get(index)
+            .copied()
+            .unwrap_or(MovieFrame::RELEASED)
+    }
+
+    /// Rejects a movie recorded against a different ROM.
+    pub fn check_cartridge(&self, cartridge: &Cartridge) -> Result<(), MovieError> {
+        let actual = rom_checksum(cartridge);
+        if actual != self.rom_checksum {
+            return Err(MovieError::RomMismatch {
+                expected: self.rom_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MOVIE_HEADER_SIZE + self.frames.len());
+        out.extend_from_slice(&MOVIE_MAGIC);
+        out.push(MOVIE_VERSION);
+        out.extend_from_slice(&self.rom_checksum.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        out.extend(self.frames.iter().map(|frame| frame.encode()));
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, MovieError> {
+        if bytes.len() < MOVIE_HEADER_SIZE {
+            return Err(MovieError::Truncated {
+                expected: MOVIE_HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[..4] != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        if bytes[4] != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(bytes[4]));
+        }
+        let rom_checksum = u16::from_le_bytes([bytes[5], bytes[6]]);
+        let count = u32::from_le_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]) as usize;
+        let body = &bytes[MOVIE_HEADER_SIZE..];
+        if body.len() < count {
+            return Err(MovieError::Truncated {
+                expected: MOVIE_HEADER_SIZE + count,
+                actual: bytes.len(),
+            });
+        }
+        Ok(Self {
+            rom_checksum,
+            frames: body[..count]
+                .iter()
+                .copied()
+                .map(MovieFrame::decode)
+                .collect(),
+        })
+    }
+}
+
+/// Checksum of the ROM as dumped, so a patched ROM with a stale header still mismatches.
+fn rom_checksum(cartridge: &Cartridge) -> u16 {
+    compute_global_checksum(&cartridge.bytes).unwrap_or(cartridge.header.global_checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Movie, MovieError, MovieFrame};
+    use crate::domain::Cartridge;
+
+    #[test]
+    fn movie_round_trips_and_checks_the_rom() {
+        let cartridge = Cartridge::from_bytes(vec![0; 0x8000]).expect("cartridge");
+        let mut movie = Movie::new(&cartridge);
+        movie.push(MovieFrame::RELEASED);
+        movie.push(MovieFrame {
+            buttons: 0x0E,
+            dpad: 0x07,
+        });
+
+        let bytes = movie.encode();
+        let decoded = Movie::decode(&bytes).expect("decode");
+        assert_eq!(decoded, movie);
+        assert_eq!(decoded.frame(1).buttons, 0x0E);
+        assert_eq!(decoded.frame(5), MovieFrame::RELEASED);
+        assert_eq!(decoded.check_cartridge(&cartridge), Ok(()));
+
+        let mut other = cartridge.clone();
+        other.bytes[0x0200] = 0x42;
+        assert!(matches!(
+            decoded.check_cartridge(&other),
+            Err(MovieError::RomMismatch { .. })
+        ));
+        assert!(matches!(
+            Movie::decode(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated { .. })
+        ));
+        assert_eq!(Movie::decode(b"NOPE0000000"), Err(MovieError::BadMagic));
+    }
+}
@@ -1,19 +1,29 @@
-use super::{Bus, FRAME_HEIGHT, FRAME_WIDTH, Framebuffer};
+use super::{Bus, FRAME_HEIGHT, FRAME_WIDTH, Framebuffer, PaletteSet};
 
-const FRAME_CYCLES: u32 = 70224;
+pub const FRAME_CYCLES: u32 = 70224;
 const CYCLES_PER_SECOND: u32 = 4_194_304;
 pub const FRAME_RATE_HZ: u32 = CYCLES_PER_SECOND / FRAME_CYCLES;
 pub const FRAME_INTERVAL_NS: u64 = 1_000_000_000 / FRAME_RATE_HZ as u64;
 const REG_LCDC: u16 = 0xFF40;
 const REG_SCY: u16 = 0xFF42;
 const REG_SCX: u16 = 0xFF43;
+const REG_LY: u16 = 0xFF44;
+const VBLANK_LINE: u8 = 144;
 const REG_BGP: u16 = 0xFF47;
 const REG_OBP0: u16 = 0xFF48;
 const REG_OBP1: u16 = 0xFF49;
 const REG_WY: u16 = 0xFF4A;
 const REG_WX: u16 = 0xFF4B;
 const VRAM_SIZE: usize = 0x2000;
+/// Width and height of a whole tile map, 32 tiles of 8 pixels.
+pub const TILE_MAP_WIDTH: usize = 256;
+pub const TILE_MAP_SIZE: usize = TILE_MAP_WIDTH * TILE_MAP_WIDTH * 3;
+pub const LAYER_BG: u8 = 0x01;
+pub const LAYER_WINDOW: u8 = 0x02;
+pub const LAYER_SPRITES: u8 = 0x04;
+const LAYER_ALL: u8 = LAYER_BG | LAYER_WINDOW | LAYER_SPRITES;
 const TILE_BYTES: usize = 16;
+const OAM_ENTRY_BYTES: usize = 4;
 const DMG_PALETTE: [[u8; 3]; 4] = [
     [0xE0, 0xF8, 0xD0],
     [0x88, 0xC0, 0x70],
@@ -21,11 +31,72 @@ const DMG_PALETTE: [[u8; 3]; 4] = [
     [0x08, 0x18, 0x20],
 ];
 
+/// A decoded OAM entry, with its position converted to screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteInfo {
+    pub x: i16,
+    pub y: i16,
+    pub tile: u8,
+    pub attr: u8,
+    /// 8 or 16, from LCDC bit 2.
+    pub height: u8,
+}
+
+impl SpriteInfo {
+    /// Whether any part of the sprite lands inside the visible frame.
+    pub fn is_on_screen(&self) -> bool {
+        self.x > -8
+            && self.x < FRAME_WIDTH as i16
+            && self.y > -(self.height as i16)
+            && self.y < FRAME_HEIGHT as i16
+    }
+}
+
+/// Splits raw OAM into its 4-byte sprite entries.
+pub fn parse_oam(oam: &[u8], height: u8) -> Vec<SpriteInfo> {
+    oam.chunks_exact(OAM_ENTRY_BYTES)
+        .map(|entry| SpriteInfo {
+            y: entry[0] as i16 - 16,
+            x: entry[1] as i16 - 8,
+            tile: entry[2],
+            attr: entry[3],
+            height,
+        })
+        .collect()
+}
+
+const BOOT_LOGO_WIDTH: usize = 48;
+const BOOT_LOGO_HEIGHT: usize = 8;
+
+/// Whether pixel (`x`, `y`) of the header logo is set. The logo is stored as 4x4 tiles
+/// of two bytes each, one nibble per row, the top row of tiles first.
+fn logo_pixel(logo: &[u8], x: usize, y: usize) -> bool {
+    let index = (y / 4) * 24 + (x / 4) * 2 + (y % 4) / 2;
+    let Some(&byte) = logo.get(index) else {
+        return false;
+    };
+    let nibble = if y.is_multiple_of(2) {
+        byte >> 4
+    } else {
+        byte & 0x0F
+    };
+    nibble & (0x08 >> (x % 4)) != 0
+}
+
+pub(crate) fn sprite_height(lcdc: u8) -> u8 {
+    if lcdc & 0x04 != 0 { 16 } else { 8 }
+}
+
 #[derive(Debug)]
 pub struct Ppu {
+    /// Cycles since the last frame, for when no VBlank comes to end one.
     cycle_counter: u32,
+    /// LY as of the previous step, to catch the move into VBlank.
+    last_ly: u8,
     bg_priority: Vec<u8>,
     palette: [[u8; 3]; 4],
+    colorization: Option<PaletteSet>,
+    layer_mask: u8,
 }
 
 impl Default for Ppu {
@@ -38,8 +109,11 @@ impl Ppu {
     pub fn new() -> Self {
         Self {
             cycle_counter: 0,
+            last_ly: 0,
             bg_priority: vec![0; FRAME_WIDTH * FRAME_HEIGHT],
             palette: DMG_PALETTE,
+            colorization: None,
+            layer_mask: LAYER_ALL,
         }
     }
 
@@ -47,25 +121,135 @@ impl Ppu {
         self.palette = palette;
     }
 
+    /// Overrides the output palette with per-layer colors, as the CGB does for DMG games.
+    pub fn set_colorization(&mut self, colorization: Option<PaletteSet>) {
+        self.colorization = colorization;
+    }
+
+    pub fn colorization(&self) -> Option<PaletteSet> {
+        self.colorization
+    }
+
+    /// Debug mask over the LCDC layer enables; a layer draws only if both allow it.
+    pub fn set_layer_enabled(&mut self, layer: u8, enabled: bool) {
+        if enabled {
+            self.layer_mask |= layer;
+        } else {
+            self.layer_mask &= !layer;
+        }
+    }
+
+    pub fn layer_enabled(&self, layer: u8) -> bool {
+        self.layer_mask & layer == layer
+    }
+
+    fn bg_palette(&self) -> [[u8; 3]; 4] {
+        self.colorization.map_or(self.palette, |set| set.bg)
+    }
+
+    fn obj_palette(&self, obp1: bool) -> [[u8; 3]; 4] {
+        match self.colorization {
+            Some(set) if obp1 => set.obj1,
+            Some(set) => set.obj0,
+            None => self.palette,
+        }
+    }
+
+    /// Restarts frame timing while keeping the selected output palette.
+    pub fn reset(&mut self) {
+        self.cycle_counter = 0;
+        self.last_ly = 0;
+        self.bg_priority.fill(0);
+    }
+
+    /// Draws the 48x8 header logo at double size, centered, with its top edge at `top`,
+    /// over a blank screen and presents it.
+    pub fn render_boot_logo(&self, logo: &[u8], top: usize, framebuffer: &mut Framebuffer) {
+        let palette = self.bg_palette();
+        let pixels = framebuffer.as_mut_slice();
+        for pixel in pixels.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&palette[0]);
+        }
+        let left = (FRAME_WIDTH - BOOT_LOGO_WIDTH * 2) / 2;
+        for y in 0..BOOT_LOGO_HEIGHT * 2 {
+            let screen_y = top + y;
+            if screen_y >= FRAME_HEIGHT {
+                break;
+            }
+            for x in 0..BOOT_LOGO_WIDTH * 2 {
+                if logo_pixel(logo, x / 2, y / 2) {
+                    let idx = (screen_y * FRAME_WIDTH + left + x) * 3;
+                    pixels[idx..idx + 3].copy_from_slice(&palette[3]);
+                }
+            }
+        }
+        framebuffer.swap();
+    }
+
+    /// Draws the whole background map, or the window map, as `TILE_MAP_WIDTH` square RGB
+    /// into `out`, using the map and tile data LCDC selects and ignoring scroll.
+    pub fn render_tile_map(&self, bus: &Bus, window: bool, out: &mut [u8]) {
+        let palette = self.bg_palette();
+        let vram = bus.vram();
+        if vram.len() < VRAM_SIZE {
+            for pixel in out.chunks_exact_mut(3) {
+                pixel.copy_from_slice(&palette[0]);
+            }
+            return;
+        }
+        let lcdc = bus.read8(REG_LCDC);
+        let bgp = bus.read8(REG_BGP);
+        let map_select = if window { 0x40 } else { 0x08 };
+        let tile_map_base = if lcdc & map_select != 0 {
+            0x1C00
+        } else {
+            0x1800
+        };
+        let use_unsigned = lcdc & 0x10 != 0;
+        for y in 0..TILE_MAP_WIDTH {
+            for x in 0..TILE_MAP_WIDTH {
+                let tile_id = vram[vram_index(tile_map_base + (y / 8) * 32 + x / 8)];
+                let tile_offset = tile_data_offset(tile_id, use_unsigned);
+                let (lo, hi) = read_tile_row(vram, tile_offset + (y % 8) * 2);
+                let bit = 7 - x % 8;
+                let color_id = ((hi >> bit) & 0x1) << 1 | ((lo >> bit) & 0x1);
+                let palette_index = (bgp >> (color_id * 2)) & 0x03;
+                let idx = (y * TILE_MAP_WIDTH + x) * 3;
+                out[idx..idx + 3].copy_from_slice(&palette[palette_index as usize]);
+            }
+        }
+    }
+
+    /// Presents a frame as LY enters VBlank, when every visible line has been drawn. With
+    /// the LCD off, or LY otherwise stuck, a frame still goes out every `FRAME_CYCLES`.
     pub fn step(&mut self, cycles: u32, bus: &Bus, framebuffer: &mut Framebuffer) -> bool {
         self.cycle_counter = self.cycle_counter.saturating_add(cycles);
-        if self.cycle_counter < FRAME_CYCLES {
+        let ly = bus.read8(REG_LY);
+        let entered_vblank = self.last_ly < VBLANK_LINE && ly >= VBLANK_LINE;
+        self.last_ly = ly;
+        if entered_vblank {
+            self.cycle_counter = 0;
+        } else if self.cycle_counter >= FRAME_CYCLES {
+            self.cycle_counter -= FRAME_CYCLES;
+        } else {
             return false;
         }
-        self.cycle_counter -= FRAME_CYCLES;
         self.render_frame(bus, framebuffer);
+        framebuffer.swap();
         true
     }
 
     pub fn render_frame(&mut self, bus: &Bus, framebuffer: &mut Framebuffer) {
         let lcdc = bus.read8(REG_LCDC);
         if lcdc & 0x80 == 0 {
-            self.clear_frame(framebuffer, self.palette[0]);
+            self.clear_frame(framebuffer, self.bg_palette()[0]);
             return;
         }
-        let bg_enabled = lcdc & 0x01 != 0;
+        // On DMG, LCDC bit 0 blanks the window along with the background.
+        let bg_window_enabled = lcdc & 0x01 != 0;
+        let bg_enabled = bg_window_enabled && self.layer_enabled(LAYER_BG);
         if !bg_enabled {
-            self.clear_frame(framebuffer, self.palette[0]);
+            self.clear_frame(framebuffer, self.bg_palette()[0]);
             self.clear_bg_priority();
         }
 
@@ -76,7 +260,7 @@ impl Ppu {
         let wx = bus.read8(REG_WX);
         let vram = bus.vram();
         if vram.len() < VRAM_SIZE {
-            self.clear_frame(framebuffer, self.palette[0]);
+            self.clear_frame(framebuffer, self.bg_palette()[0]);
             return;
         }
 
@@ -84,18 +268,26 @@ impl Ppu {
         let win_tile_map_base = if lcdc & 0x40 != 0 { 0x1C00 } else { 0x1800 };
         let use_unsigned = lcdc & 0x10 != 0;
         let window_enabled = lcdc & 0x20 != 0;
-        let window_active = window_enabled && wy <= 143 && wx <= 166;
-        let sprites_enabled = lcdc & 0x02 != 0;
-        let sprite_height = if lcdc & 0x04 != 0 { 16 } else { 8 };
+        let window_active = bg_window_enabled
+            && window_enabled
+            && self.layer_enabled(LAYER_WINDOW)
+            && wy <= 143
+            && wx <= 166;
+        let sprites_enabled = lcdc & 0x02 != 0 && self.layer_enabled(LAYER_SPRITES);
+        let sprite_height = sprite_height(lcdc);
         let width = FRAME_WIDTH;
         let height = FRAME_HEIGHT;
+        let bg_palette = self.bg_palette();
         let pixels = framebuffer.as_mut_slice();
 
-        if bg_enabled {
+        if bg_enabled || window_active {
             for y in 0..height {
                 for x in 0..width {
                     let use_window =
                         window_active && (y as u8) >= wy && (x as i16 + 7) >= wx as i16;
+                    if !use_window && !bg_enabled {
+                        continue;
+                    }
 
                     let (tile_map_base, tile_x, tile_y, line_x, line_y) = if use_window {
                         let win_x = (x as i16 + 7 - wx as i16) as usize;
@@ -116,20 +308,13 @@ impl Ppu {
                     };
 
                     let map_index = tile_y * 32 + tile_x;
-                    let tile_id = vram[tile_map_base + map_index];
-                    let tile_offset = if use_unsigned {
-                        (tile_id as usize) * TILE_BYTES
-                    } else {
-                        let signed = tile_id as i8 as i16;
-                        (0x1000i16 + signed * 16) as usize
-                    };
-                    let row = tile_offset + line_y * 2;
-                    let lo = vram[row];
-                    let hi = vram[row + 1];
+                    let tile_id = vram[vram_index(tile_map_base + map_index)];
+                    let tile_offset = tile_data_offset(tile_id, use_unsigned);
+                    let (lo, hi) = read_tile_row(vram, tile_offset + line_y * 2);
                     let bit = 7 - line_x;
                     let color_id = ((hi >> bit) & 0x1) << 1 | ((lo >> bit) & 0x1);
                     let palette_index = (bgp >> (color_id * 2)) & 0x03;
-                    let color = self.palette[palette_index as usize];
+                    let color = bg_palette[palette_index as usize];
                     let idx = (y * width + x) * 3;
                     pixels[idx] = color[0];
                     pixels[idx + 1] = color[1];
@@ -157,7 +342,7 @@ impl Ppu {
         self.bg_priority.fill(0);
     }
 
-    fn render_sprites(&self, bus: &Bus, framebuffer: &mut Framebuffer, sprite_height: usize) {
+    fn render_sprites(&self, bus: &Bus, framebuffer: &mut Framebuffer, sprite_height: u8) {
         let obp0 = bus.read8(REG_OBP0);
         let obp1 = bus.read8(REG_OBP1);
         let vram = bus.vram();
@@ -165,39 +350,32 @@ impl Ppu {
         let width = FRAME_WIDTH;
         let height = FRAME_HEIGHT;
 
-        for i in (0..40).rev() {
-            let base = 0xFE00u16 + (i * 4) as u16;
-            let y = bus.read8(base) as i16 - 16;
-            let x = bus.read8(base + 1) as i16 - 8;
-            let tile = bus.read8(base + 2);
-            let attr = bus.read8(base + 3);
-
-            if x <= -8 || x >= width as i16 || y <= -(sprite_height as i16) || y >= height as i16 {
-                continue;
-            }
-
-            let y_flip = attr & 0x40 != 0;
-            let x_flip = attr & 0x20 != 0;
-            let palette = if attr & 0x10 != 0 { obp1 } else { obp0 };
-            let priority = attr & 0x80 != 0;
-
-            for row in 0..sprite_height {
-                let screen_y = y + row as i16;
-                if screen_y < 0 || screen_y >= height as i16 {
+        let sprite_height = sprite_height as usize;
+
+        // Each line draws the sprites its own OAM scan latched.
+        for line in 0..height {
+            let screen_y = line as i16;
+            for sprite in parse_oam(bus.line_oam(line), sprite_height as u8)
+                .iter()
+                .rev()
+            {
+                let SpriteInfo {
+                    x, y, tile, attr, ..
+                } = *sprite;
+                if screen_y < y || screen_y - y >= sprite_height as i16 {
                     continue;
                 }
-                let mut tile_row = if y_flip { sprite_height - 1 - row } else { row };
-                let mut tile_index = tile as usize;
-                if sprite_height == 16 {
-                    tile_index &= 0xFE;
-                    if tile_row >= 8 {
-                        tile_index += 1;
-                        tile_row -= 8;
-                    }
-                }
-                let row_addr = tile_index * TILE_BYTES + tile_row * 2;
-                let lo = vram[row_addr];
-                let hi = vram[row_addr + 1];
+                let row = (screen_y - y) as usize;
+
+                let y_flip = attr & 0x40 != 0;
+                let x_flip = attr & 0x20 != 0;
+                let use_obp1 = attr & 0x10 != 0;
+                let palette = if use_obp1 { obp1 } else { obp0 };
+                let colors = self.obj_palette(use_obp1);
+                let priority = attr & 0x80 != 0;
+
+                let tile_row = if y_flip { sprite_height - 1 - row } else { row };
+                let (lo, hi) = read_tile_row(vram, sprite_row_addr(tile, tile_row, sprite_height));
                 for col in 0..8 {
                     let screen_x = x + col as i16;
                     if screen_x < 0 || screen_x >= width as i16 {
@@ -209,7 +387,7 @@ impl Ppu {
                         continue;
                     }
                     let palette_index = (palette >> (color_id * 2)) & 0x03;
-                    let color = self.palette[palette_index as usize];
+                    let color = colors[palette_index as usize];
                     let idx = (screen_y as usize * width + screen_x as usize) * 3;
                     if priority
                         && self.bg_priority[screen_y as usize * width + screen_x as usize] != 0
@@ -225,11 +403,45 @@ impl Ppu {
     }
 }
 
+/// VRAM offset of a tile's data: unsigned from 0x8000, or signed around 0x9000.
+fn tile_data_offset(tile_id: u8, use_unsigned: bool) -> usize {
+    if use_unsigned {
+        (tile_id as usize) * TILE_BYTES
+    } else {
+        let signed = tile_id as i8 as i16;
+        (0x1000i16 + signed * 16) as usize
+    }
+}
+
+/// Wraps a VRAM address into the 8 KiB bank so malformed tile data can't index past it.
+fn vram_index(addr: usize) -> usize {
+    addr & (VRAM_SIZE - 1)
+}
+
+/// VRAM offset of row `row` of a sprite. 8x16 sprites take the even tile for their top
+/// half and the odd one below it; the row is kept inside its tile and the bank.
+fn sprite_row_addr(tile: u8, row: usize, sprite_height: usize) -> usize {
+    let tile_index = if sprite_height == 16 {
+        (tile & 0xFE) as usize + (row / 8) % 2
+    } else {
+        tile as usize
+    };
+    vram_index(tile_index * TILE_BYTES + (row % 8) * 2)
+}
+
+/// Reads the low and high bitplanes of one tile row.
+fn read_tile_row(vram: &[u8], addr: usize) -> (u8, u8) {
+    (
+        vram[vram_index(addr)],
+        vram[vram_index(addr.wrapping_add(1))],
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Ppu;
+    use super::{LAYER_SPRITES, Ppu, SpriteInfo, parse_oam};
     use crate::domain::cartridge::ROM_BANK_SIZE;
-    use crate::domain::{Bus, Cartridge, Framebuffer};
+    use crate::domain::{Bus, Cartridge, Framebuffer, PaletteSet};
 
     fn bus_with_rom(mut rom: Vec<u8>) -> Bus {
         if rom.len() < 0x0150 {
@@ -257,6 +469,83 @@ mod tests {
         assert_eq!(framebuffer.as_slice()[0], 0x88);
     }
 
+    #[test]
+    fn parse_oam_decodes_entry_fields() {
+        let mut oam = [0u8; 0xA0];
+        oam[4..8].copy_from_slice(&[0x20, 0x0C, 0x42, 0xB0]);
+
+        let sprites = parse_oam(&oam, 16);
+
+        assert_eq!(sprites.len(), 40);
+        assert_eq!(
+            sprites[1],
+            SpriteInfo {
+                x: 4,
+                y: 16,
+                tile: 0x42,
+                attr: 0xB0,
+                height: 16,
+            }
+        );
+        assert!(sprites[1].is_on_screen());
+        assert!(!sprites[0].is_on_screen());
+    }
+
+    #[test]
+    fn render_frame_sprite_layer_toggle_hides_sprites() {
+        let rom = vec![0; ROM_BANK_SIZE];
+        let mut bus = bus_with_rom(rom);
+        let mut framebuffer = Framebuffer::new();
+        let mut ppu = Ppu::new();
+
+        bus.write8(0xFF40, 0x82);
+        bus.write8(0xFF48, 0xE4);
+        bus.write8(0x8000, 0x80);
+        bus.write8(0x8001, 0x00);
+
+        bus.write8(0xFE00, 16);
+        bus.write8(0xFE01, 8);
+        bus.write8(0xFE02, 0x00);
+        bus.write8(0xFE03, 0x00);
+
+        ppu.set_layer_enabled(LAYER_SPRITES, false);
+        ppu.render_frame(&bus, &mut framebuffer);
+        assert_eq!(framebuffer.as_slice()[0], 0xE0);
+
+        ppu.set_layer_enabled(LAYER_SPRITES, true);
+        ppu.render_frame(&bus, &mut framebuffer);
+        assert_eq!(framebuffer.as_slice()[0], 0x88);
+    }
+
+    #[test]
+    fn render_frame_colorization_uses_per_layer_palettes() {
+        let rom = vec![0; ROM_BANK_SIZE];
+        let mut bus = bus_with_rom(rom);
+        let mut framebuffer = Framebuffer::new();
+        let mut ppu = Ppu::new();
+        let black = [0x00, 0x00, 0x00];
+        ppu.set_colorization(Some(PaletteSet {
+            bg: [[0x10, 0x00, 0x00]; 4],
+            obj0: [black; 4],
+            obj1: [[0x00, 0x00, 0x30]; 4],
+        }));
+
+        bus.write8(0xFF40, 0x83);
+        bus.write8(0xFF47, 0xE4);
+        bus.write8(0xFF49, 0xE4);
+        bus.write8(0x8000, 0x80);
+        bus.write8(0x8001, 0x00);
+
+        bus.write8(0xFE00, 16);
+        bus.write8(0xFE01, 8);
+        bus.write8(0xFE02, 0x00);
+        bus.write8(0xFE03, 0x10);
+
+        ppu.render_frame(&bus, &mut framebuffer);
+        assert_eq!(framebuffer.as_slice()[0..3], [0x00, 0x00, 0x30]);
+        assert_eq!(framebuffer.as_slice()[3..6], [0x10, 0x00, 0x00]);
+    }
+
     #[test]
     fn render_frame_window_overlays_bg() {
         let rom = vec![0; ROM_BANK_SIZE];
@@ -439,6 +728,41 @@ mod tests {
         assert_eq!(framebuffer.as_slice()[0], 0x08);
     }
 
+    #[test]
+    fn render_frame_sprites_use_the_oam_each_line_latched() {
+        let rom = vec![0; ROM_BANK_SIZE];
+        let mut bus = bus_with_rom(rom);
+        let mut framebuffer = Framebuffer::new();
+        let mut ppu = Ppu::new();
+
+        // Restart the LCD so LY counts from 0.
+        bus.write8(0xFF40, 0x02);
+        bus.step(4);
+        bus.write8(0xFF40, 0x82);
+        bus.write8(0xFF48, 0xE4);
+        for row in 0..8 {
+            bus.write8(0x8000 + row * 2, 0x80);
+            bus.write8(0x8001 + row * 2, 0x00);
+        }
+        bus.write8(0xFE00, 16);
+        bus.write8(0xFE01, 8);
+        bus.write8(0xFE02, 0x00);
+        bus.write8(0xFE03, 0x00);
+
+        // Lines 0-4 scan OAM with the sprite at x = 0, then it moves to x = 16.
+        bus.step(456 * 4);
+        assert_eq!(bus.read8(0xFF44), 4);
+        bus.write8(0xFE01, 24);
+
+        ppu.render_frame(&bus, &mut framebuffer);
+        let pixel = |x: usize, y: usize| framebuffer.as_slice()[(y * 160 + x) * 3];
+        assert_eq!(pixel(0, 2), 0x88);
+        assert_eq!(pixel(16, 2), 0xE0);
+        assert_eq!(pixel(0, 4), 0x88);
+        assert_eq!(pixel(0, 6), 0xE0);
+        assert_eq!(pixel(16, 6), 0x88);
+    }
+
     #[test]
     fn render_frame_sprite_oam_priority() {
         let rom = vec![0; ROM_BANK_SIZE];
@@ -697,4 +1021,66 @@ mod proptests {
             }
         }
     }
+
+    // Property: Arbitrary VRAM, OAM and LCDC contents render without panicking
+    proptest! {
+        #[test]
+        fn prop_arbitrary_vram_no_crash(
+            vram in prop::collection::vec(any::<u8>(), VRAM_SIZE),
+            oam in prop::collection::vec(any::<u8>(), 0xA0),
+            lcdc in any::<u8>(),
+            scx in any::<u8>(),
+            scy in any::<u8>(),
+        ) {
+            let rom = vec![0; ROM_BANK_SIZE];
+            let mut bus = bus_with_rom(rom);
+            let mut framebuffer = Framebuffer::new();
+            let mut ppu = Ppu::new();
+
+            for (offset, value) in vram.iter().enumerate() {
+                bus.write8(0x8000 + offset as u16, *value);
+            }
+            for (offset, value) in oam.iter().enumerate() {
+                bus.write8(0xFE00 + offset as u16, *value);
+            }
+            bus.write8(0xFF40, lcdc | 0x80);
+            bus.write8(0xFF42, scy);
+            bus.write8(0xFF43, scx);
+
+            ppu.render_frame(&bus, &mut framebuffer);
+            prop_assert_eq!(framebuffer.as_slice().len(), FRAME_WIDTH * FRAME_HEIGHT * 3);
+        }
+    }
+
+    // Property: Sprite rows address their own tile inside the VRAM bank for any OAM tile
+    proptest! {
+        #[test]
+        fn prop_sprite_row_addr_in_range(
+            tile in any::<u8>(),
+            row in 0usize..64,
+            tall in any::<bool>(),
+        ) {
+            let sprite_height = if tall { 16 } else { 8 };
+            let addr = sprite_row_addr(tile, row, sprite_height);
+
+            prop_assert!(addr + 1 < VRAM_SIZE);
+            let expected_tile = if tall {
+                (tile & 0xFE) as usize + (row / 8) % 2
+            } else {
+                tile as usize
+            };
+            prop_assert_eq!(addr / TILE_BYTES, expected_tile);
+            prop_assert_eq!(addr % TILE_BYTES, (row % 8) * 2);
+        }
+    }
+
+    // Property: Tile row reads stay inside the VRAM bank for any address
+    proptest! {
+        #[test]
+        fn prop_tile_row_index_in_range(addr in any::<usize>()) {
+            prop_assert!(vram_index(addr) < VRAM_SIZE);
+            let vram = vec![0; VRAM_SIZE];
+            prop_assert_eq!(read_tile_row(&vram, addr), (0, 0));
+        }
+    }
 }
@@ -15,7 +15,7 @@ const HEADER_CHECKSUM_ADDR: usize = 0x014D;
 const GLOBAL_CHECKSUM_ADDR: usize = 0x014E;
 const NINTENDO_LOGO_START: usize = 0x0104;
 const NINTENDO_LOGO_END: usize = 0x0133;
-const NINTENDO_LOGO: [u8; 48] = [
+pub const NINTENDO_LOGO: [u8; 48] = [
     0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
     0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
     0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
@@ -90,6 +90,38 @@ pub enum CartridgeType {
 }
 
 impl CartridgeType {
+    /// Every cartridge type with an assigned header code.
+    pub const KNOWN: [Self; 28] = [
+        Self::RomOnly,
+        Self::Mbc1,
+        Self::Mbc1Ram,
+        Self::Mbc1RamBattery,
+        Self::Mbc2,
+        Self::Mbc2Battery,
+        Self::RomRam,
+        Self::RomRamBattery,
+        Self::Mmm01,
+        Self::Mmm01Ram,
+        Self::Mmm01RamBattery,
+        Self::Mbc3TimerBattery,
+        Self::Mbc3TimerRamBattery,
+        Self::Mbc3,
+        Self::Mbc3Ram,
+        Self::Mbc3RamBattery,
+        Self::Mbc5,
+        Self::Mbc5Ram,
+        Self::Mbc5RamBattery,
+        Self::Mbc5Rumble,
+        Self::Mbc5RumbleRam,
+        Self::Mbc5RumbleRamBattery,
+        Self::Mbc6,
+        Self::Mbc7SensorRumbleRamBattery,
+        Self::PocketCamera,
+        Self::BandaiTama5,
+        Self::HuC3,
+        Self::HuC1RamBattery,
+    ];
+
     fn from_byte(value: u8) -> Self {
         match value {
             0x00 => Self::RomOnly,
@@ -192,6 +224,7 @@ impl CartridgeType {
         }
     }
 
+    /// Whether `Mbc::new` accepts this type; keep the two in step.
     pub fn is_supported(self) -> bool {
         matches!(
             self,
@@ -203,6 +236,9 @@ impl CartridgeType {
                 | Self::Mbc1RamBattery
                 | Self::Mbc2
                 | Self::Mbc2Battery
+                | Self::Mmm01
+                | Self::Mmm01Ram
+                | Self::Mmm01RamBattery
                 | Self::Mbc3
                 | Self::Mbc3Ram
                 | Self::Mbc3RamBattery
@@ -214,6 +250,8 @@ impl CartridgeType {
                 | Self::Mbc5Rumble
                 | Self::Mbc5RumbleRam
                 | Self::Mbc5RumbleRamBattery
+                | Self::PocketCamera
+                | Self::HuC3
         )
     }
 
@@ -236,6 +274,8 @@ impl CartridgeType {
                 | Self::Mbc5RumbleRam
                 | Self::Mbc5RumbleRamBattery
                 | Self::Mbc7SensorRumbleRamBattery
+                | Self::PocketCamera
+                | Self::HuC3
                 | Self::HuC1RamBattery
         )
     }
@@ -253,6 +293,8 @@ impl CartridgeType {
                 | Self::Mbc5RamBattery
                 | Self::Mbc5RumbleRamBattery
                 | Self::Mbc7SensorRumbleRamBattery
+                | Self::PocketCamera
+                | Self::HuC3
                 | Self::HuC1RamBattery
         )
     }
@@ -334,6 +376,49 @@ impl RomSize {
     pub fn bank_count(&self) -> Option<usize> {
         self.bytes().map(|bytes| bytes / 0x4000)
     }
+
+    /// Compares a dump's length with this declared size, to explain banking oddities in
+    /// trimmed or overdumped files.
+    pub fn check_dump(&self, bytes: &[u8]) -> DumpSize {
+        let Some(expected) = self.bytes() else {
+            return DumpSize::Unknown;
+        };
+        let actual = bytes.len();
+        if actual < expected {
+            return DumpSize::Trimmed { expected, actual };
+        }
+        if actual == expected {
+            return DumpSize::Exact;
+        }
+        let extra = &bytes[expected..];
+        let padding = [0xFF, 0x00]
+            .into_iter()
+            .find(|fill| extra.iter().all(|byte| byte == fill));
+        DumpSize::Overdumped {
+            expected,
+            actual,
+            padding,
+        }
+    }
+}
+
+/// How a ROM file's length compares with the size its header declares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpSize {
+    Exact,
+    /// Shorter than declared, as when trailing padding was cut off.
+    Trimmed {
+        expected: usize,
+        actual: usize,
+    },
+    /// Longer than declared. `padding` is the fill byte when the extra bytes are all 0xFF
+    /// or all 0x00.
+    Overdumped {
+        expected: usize,
+        actual: usize,
+        padding: Option<u8>,
+    },
+    Unknown,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -509,6 +594,31 @@ impl RomHeader {
             global_checksum,
         })
     }
+
+    /// Filesystem-safe form of the title, or the global checksum in hex when the title is blank.
+    pub fn title_slug(&self) -> String {
+        let slug: String = self
+            .title
+            .chars()
+            .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+            .collect();
+        let slug = slug.trim_matches('_');
+        if slug.is_empty() {
+            format!("{:04x}", self.global_checksum)
+        } else {
+            slug.to_string()
+        }
+    }
+
+    /// A user-facing note when the ROM needs hardware the core doesn't emulate yet.
+    pub fn compatibility_warning(&self) -> Option<&'static str> {
+        match self.cgb_flag {
+            CgbFlag::CgbOnly => {
+                Some("This ROM requires a Game Boy Color; rendering may be incorrect.")
+            }
+            CgbFlag::DmgOnly | CgbFlag::CgbSupported => None,
+        }
+    }
 }
 
 fn parse_title(bytes: &[u8]) -> String {
@@ -553,6 +663,19 @@ pub fn compute_header_checksum(bytes: &[u8]) -> Option<u8> {
     Some(checksum)
 }
 
+/// Writes the Nintendo logo and a matching header checksum so a real boot ROM accepts the
+/// image. Returns whether any byte changed, or `None` when `bytes` is too short.
+pub fn repair_header(bytes: &mut [u8]) -> Option<bool> {
+    let logo_ok = nintendo_logo_matches(bytes)?;
+    if !logo_ok {
+        bytes[NINTENDO_LOGO_START..=NINTENDO_LOGO_END].copy_from_slice(&NINTENDO_LOGO);
+    }
+    let checksum = compute_header_checksum(bytes)?;
+    let checksum_ok = bytes[HEADER_CHECKSUM_ADDR] == checksum;
+    bytes[HEADER_CHECKSUM_ADDR] = checksum;
+    Some(!logo_ok || !checksum_ok)
+}
+
 pub fn compute_global_checksum(bytes: &[u8]) -> Option<u16> {
     if bytes.len() < MIN_ROM_SIZE {
         return None;
@@ -571,11 +694,41 @@ pub fn compute_global_checksum(bytes: &[u8]) -> Option<u16> {
 #[cfg(test)]
 mod tests {
     use super::{
-        CartridgeType, CgbFlag, Destination, Licensee, NINTENDO_LOGO, NINTENDO_LOGO_START, RamSize,
-        RomHeader, RomHeaderError, RomSize, SgbFlag, compute_header_checksum,
-        nintendo_logo_matches,
+        CartridgeType, CgbFlag, Destination, DumpSize, Licensee, NINTENDO_LOGO,
+        NINTENDO_LOGO_START, RamSize, RomHeader, RomHeaderError, RomSize, SgbFlag,
+        compute_header_checksum, nintendo_logo_matches,
     };
 
+    #[test]
+    fn check_dump_reports_padding_past_the_declared_size() {
+        let mut rom = vec![0x12; 32 * 1024];
+        assert_eq!(RomSize::Kb32.check_dump(&rom), DumpSize::Exact);
+
+        rom.resize(64 * 1024, 0xFF);
+        assert_eq!(
+            RomSize::Kb32.check_dump(&rom),
+            DumpSize::Overdumped {
+                expected: 32 * 1024,
+                actual: 64 * 1024,
+                padding: Some(0xFF),
+            }
+        );
+
+        rom[40 * 1024] = 0x00;
+        assert!(matches!(
+            RomSize::Kb32.check_dump(&rom),
+            DumpSize::Overdumped { padding: None, .. }
+        ));
+        assert_eq!(
+            RomSize::Kb64.check_dump(&rom[..48 * 1024]),
+            DumpSize::Trimmed {
+                expected: 64 * 1024,
+                actual: 48 * 1024,
+            }
+        );
+        assert_eq!(RomSize::Unknown(0x60).check_dump(&rom), DumpSize::Unknown);
+    }
+
     #[test]
     fn parse_header_dmg_title_includes_last_byte() {
         let mut rom = vec![0; super::MIN_ROM_SIZE];
@@ -601,6 +754,19 @@ mod tests {
         assert_eq!(header.cgb_flag, CgbFlag::CgbSupported);
     }
 
+    #[test]
+    fn compatibility_warning_flags_cgb_only_roms() {
+        let mut rom = vec![0; super::MIN_ROM_SIZE];
+        let header = RomHeader::parse(&rom).expect("header parse");
+        assert_eq!(header.cgb_flag, CgbFlag::DmgOnly);
+        assert!(header.compatibility_warning().is_none());
+
+        rom[super::CGB_FLAG_ADDR] = 0xC0;
+        let header = RomHeader::parse(&rom).expect("header parse");
+        assert_eq!(header.cgb_flag, CgbFlag::CgbOnly);
+        assert!(header.compatibility_warning().is_some());
+    }
+
     #[test]
     fn parse_header_reads_flags_and_sizes() {
         let mut rom = vec![0; super::MIN_ROM_SIZE];
@@ -0,0 +1,81 @@
+/// Size of the RTC block BGB and VBA-M append after cartridge RAM in `.sav` files.
+pub const RTC_FOOTER_SIZE: usize = 48;
+/// Older writers stored the timestamp as 32 bits.
+pub const RTC_FOOTER_SIZE_LEGACY: usize = 44;
+
+const RTC_REGISTER_COUNT: usize = 5;
+const RTC_LATCHED_OFFSET: usize = RTC_REGISTER_COUNT * 4;
+const RTC_TIMESTAMP_OFFSET: usize = RTC_LATCHED_OFFSET * 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveError {
+    NoCartridge,
+    SizeMismatch { expected: usize, actual: usize },
+}
+
+/// The MBC3 clock as BGB/VBA-M lay it out: each register widened to a little-endian
+/// `u32`, current values then latched ones, then the UNIX time the save was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcFooter {
+    pub registers: [u8; 5],
+    pub latched: [u8; 5],
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    pub fn encode(&self) -> [u8; RTC_FOOTER_SIZE] {
+        let mut out = [0; RTC_FOOTER_SIZE];
+        let values = self.registers.iter().chain(self.latched.iter());
+        for (chunk, value) in out.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&u32::from(*value).to_le_bytes());
+        }
+        out[RTC_TIMESTAMP_OFFSET..].copy_from_slice(&self.timestamp.to_le_bytes());
+        out
+    }
+
+    /// Accepts both the 48-byte and the legacy 44-byte layout.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != RTC_FOOTER_SIZE && bytes.len() != RTC_FOOTER_SIZE_LEGACY {
+            return None;
+        }
+        let register = |index: usize| {
+            let start = index * 4;
+            let word = u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap_or([0; 4]));
+            word as u8
+        };
+        let registers = std::array::from_fn(register);
+        let latched = std::array::from_fn(|index| register(RTC_REGISTER_COUNT + index));
+        let tail = &bytes[RTC_TIMESTAMP_OFFSET..];
+        let timestamp = match tail.len() {
+            8 => u64::from_le_bytes(tail.try_into().unwrap_or([0; 8])),
+            _ => u64::from(u32::from_le_bytes(tail.try_into().unwrap_or([0; 4]))),
+        };
+        Some(Self {
+            registers,
+            latched,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RTC_FOOTER_SIZE_LEGACY, RtcFooter};
+
+    #[test]
+    fn rtc_footer_round_trips_and_reads_legacy_layout() {
+        let footer = RtcFooter {
+            registers: [12, 34, 5, 0x80, 0x01],
+            latched: [11, 34, 5, 0x80, 0x01],
+            timestamp: 1_700_000_000,
+        };
+        let bytes = footer.encode();
+        assert_eq!(&bytes[0..4], &[12, 0, 0, 0]);
+        assert_eq!(RtcFooter::decode(&bytes), Some(footer));
+
+        let mut legacy = bytes[..RTC_FOOTER_SIZE_LEGACY].to_vec();
+        legacy[40..44].copy_from_slice(&1_700_000_000u32.to_le_bytes());
+        assert_eq!(RtcFooter::decode(&legacy), Some(footer));
+        assert_eq!(RtcFooter::decode(&bytes[..40]), None);
+    }
+}
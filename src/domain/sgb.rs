@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+pub const SGB_PACKET_BYTES: usize = 16;
+const PACKET_BITS: usize = SGB_PACKET_BYTES * 8;
+const P1_SELECT_MASK: u8 = 0x30;
+const P1_RESET: u8 = 0x00;
+const P1_BIT_ONE: u8 = 0x10;
+const P1_BIT_ZERO: u8 = 0x20;
+const P1_IDLE: u8 = 0x30;
+
+pub const SGB_PAL01: u8 = 0x00;
+pub const SGB_PAL23: u8 = 0x01;
+pub const SGB_PAL03: u8 = 0x02;
+pub const SGB_PAL12: u8 = 0x03;
+pub const SGB_MLT_REQ: u8 = 0x11;
+pub const SGB_CHR_TRN: u8 = 0x13;
+pub const SGB_PCT_TRN: u8 = 0x14;
+pub const SGB_MASK_EN: u8 = 0x17;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgbCommand {
+    pub id: u8,
+    /// Every byte of every packet, including the leading command/length byte.
+    pub data: Vec<u8>,
+}
+
+impl SgbCommand {
+    /// The first palette set by PAL01/PAL03, converted to RGB. Other commands only touch
+    /// palettes the single-palette renderer has no use for.
+    pub fn palette(&self) -> Option<[[u8; 3]; 4]> {
+        if self.id != SGB_PAL01 && self.id != SGB_PAL03 {
+            return None;
+        }
+        let mut palette = [[0; 3]; 4];
+        for (index, color) in palette.iter_mut().enumerate() {
+            let offset = 1 + index * 2;
+            let raw = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]);
+            *color = rgb555_to_rgb888(raw);
+        }
+        Some(palette)
+    }
+}
+
+fn rgb555_to_rgb888(raw: u16) -> [u8; 3] {
+    let expand = |value: u16| {
+        let value = (value & 0x1F) as u8;
+        (value << 3) | (value >> 2)
+    };
+    [expand(raw), expand(raw >> 5), expand(raw >> 10)]
+}
+
+/// Reassembles SGB command packets from the pulses a game writes to P1.
+#[derive(Debug, Clone)]
+pub struct SgbPacketDecoder {
+    receiving: bool,
+    armed: bool,
+    bit_index: usize,
+    packet: [u8; SGB_PACKET_BYTES],
+    data: Vec<u8>,
+    packets_remaining: usize,
+    commands: VecDeque<SgbCommand>,
+}
+
+impl Default for SgbPacketDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SgbPacketDecoder {
+    pub fn new() -> Self {
+        Self {
+            receiving: false,
+            armed: false,
+            bit_index: 0,
+            packet: [0; SGB_PACKET_BYTES],
+            data: Vec::new(),
+            packets_remaining: 0,
+            commands: VecDeque::new(),
+        }
+    }
+
+    pub fn write_p1(&mut self, value: u8) {
+        match value & P1_SELECT_MASK {
+            P1_RESET => {
+                self.receiving = true;
+                self.armed = false;
+                self.bit_index = 0;
+                self.packet = [0; SGB_PACKET_BYTES];
+            }
+            P1_IDLE => self.armed = true,
+            select @ (P1_BIT_ONE | P1_BIT_ZERO) if self.receiving && self.armed => {
+                self.armed = false;
+                self.push_bit(select == P1_BIT_ONE);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn take_command(&mut self) -> Option<SgbCommand> {
+        self.commands.pop_front()
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        if self.bit_index == PACKET_BITS {
+            // The stop bit must be zero; anything else aborts the transfer.
+            self.receiving = false;
+            if bit {
+                self.data.clear();
+                self.packets_remaining = 0;
+            } else {
+                self.finish_packet();
+            }
+            return;
+        }
+        if bit {
+            self.packet[self.bit_index / 8] |= 1 << (self.bit_index % 8);
+        }
+        self.bit_index += 1;
+    }
+
+    fn finish_packet(&mut self) {
+        if self.packets_remaining == 0 {
+            self.data.clear();
+            self.packets_remaining = usize::from(self.packet[0] & 0x07).max(1);
+        }
+        self.data.extend_from_slice(&self.packet);
+        self.packets_remaining -= 1;
+        if self.packets_remaining == 0 {
+            let id = self.data[0] >> 3;
+            self.commands.push_back(SgbCommand {
+                id,
+                data: std::mem::take(&mut self.data),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SGB_MLT_REQ, SGB_PACKET_BYTES, SGB_PAL01, SgbPacketDecoder};
+
+    fn send_packet(decoder: &mut SgbPacketDecoder, packet: &[u8; SGB_PACKET_BYTES]) {
+        decoder.write_p1(0x00);
+        decoder.write_p1(0x30);
+        for byte in packet {
+            for bit in 0..8 {
+                let value = if byte & (1 << bit) != 0 { 0x10 } else { 0x20 };
+                decoder.write_p1(value);
+                decoder.write_p1(0x30);
+            }
+        }
+        decoder.write_p1(0x20);
+        decoder.write_p1(0x30);
+    }
+
+    #[test]
+    fn decodes_mlt_req_packet() {
+        let mut decoder = SgbPacketDecoder::new();
+        let mut packet = [0; SGB_PACKET_BYTES];
+        packet[0] = (SGB_MLT_REQ << 3) | 0x01;
+        packet[1] = 0x01;
+
+        send_packet(&mut decoder, &packet);
+
+        let command = decoder.take_command().expect("command");
+        assert_eq!(command.id, SGB_MLT_REQ);
+        assert_eq!(command.data, packet.to_vec());
+        assert!(decoder.take_command().is_none());
+    }
+
+    #[test]
+    fn decodes_pal01_palette() {
+        let mut decoder = SgbPacketDecoder::new();
+        let mut packet = [0; SGB_PACKET_BYTES];
+        packet[0] = (SGB_PAL01 << 3) | 0x01;
+        packet[1..3].copy_from_slice(&0x7FFFu16.to_le_bytes());
+        packet[3..5].copy_from_slice(&0x001Fu16.to_le_bytes());
+        packet[5..7].copy_from_slice(&0x03E0u16.to_le_bytes());
+        packet[7..9].copy_from_slice(&0x0000u16.to_le_bytes());
+
+        send_packet(&mut decoder, &packet);
+
+        let command = decoder.take_command().expect("command");
+        assert_eq!(
+            command.palette(),
+            Some([
+                [0xFF, 0xFF, 0xFF],
+                [0xFF, 0x00, 0x00],
+                [0x00, 0xFF, 0x00],
+                [0x00, 0x00, 0x00],
+            ])
+        );
+    }
+
+    #[test]
+    fn joypad_polling_is_ignored() {
+        let mut decoder = SgbPacketDecoder::new();
+        for _ in 0..200 {
+            decoder.write_p1(0x20);
+            decoder.write_p1(0x10);
+            decoder.write_p1(0x30);
+        }
+        assert!(decoder.take_command().is_none());
+    }
+
+    #[test]
+    fn multi_packet_command_waits_for_all_packets() {
+        let mut decoder = SgbPacketDecoder::new();
+        let mut first = [0; SGB_PACKET_BYTES];
+        first[0] = (0x04 << 3) | 0x02;
+        let second = [0xAA; SGB_PACKET_BYTES];
+
+        send_packet(&mut decoder, &first);
+        assert!(decoder.take_command().is_none());
+        send_packet(&mut decoder, &second);
+
+        let command = decoder.take_command().expect("command");
+        assert_eq!(command.id, 0x04);
+        assert_eq!(command.data.len(), SGB_PACKET_BYTES * 2);
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use super::{Bus, Cpu};
+
+/// Logs CPU state before each instruction, one line per instruction in the Gameboy Doctor
+/// format, so runs can be diffed against a reference emulator.
+pub struct TraceLog {
+    writer: Box<dyn Write + Send>,
+}
+
+impl fmt::Debug for TraceLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceLog").finish_non_exhaustive()
+    }
+}
+
+impl TraceLog {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self { writer }
+    }
+
+    pub fn record(&mut self, cpu: &Cpu, bus: &Bus) -> io::Result<()> {
+        writeln!(self.writer, "{}", trace_line(cpu, bus))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+pub fn trace_line(cpu: &Cpu, bus: &Bus) -> String {
+    let regs = cpu.regs();
+    let pc = cpu.pc();
+    let pcmem = [0u16, 1, 2, 3].map(|offset| bus.read8(pc.wrapping_add(offset)));
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} \
+         PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        regs.a(),
+        regs.f(),
+        regs.b(),
+        regs.c(),
+        regs.d(),
+        regs.e(),
+        regs.h(),
+        regs.l(),
+        cpu.sp(),
+        pc,
+        pcmem[0],
+        pcmem[1],
+        pcmem[2],
+        pcmem[3],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trace_line;
+    use crate::domain::cartridge::ROM_BANK_SIZE;
+    use crate::domain::{Bus, Cartridge, Cpu};
+
+    #[test]
+    fn trace_line_matches_gameboy_doctor_format() {
+        let mut rom = vec![0; ROM_BANK_SIZE * 2];
+        rom[0x0100] = 0x00;
+        rom[0x0101] = 0xC3;
+        rom[0x0102] = 0x50;
+        rom[0x0103] = 0x01;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let bus = Bus::new(cartridge).expect("bus");
+        let mut cpu = Cpu::new();
+        cpu.apply_post_boot_state();
+
+        assert_eq!(
+            trace_line(&cpu, &bus),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01"
+        );
+    }
+}
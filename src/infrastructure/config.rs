@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::domain::RomHeader;
+use crate::infrastructure::fs::write_atomic;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    /// Palette for the ROM tile preview: `Splash` (default) or a game palette name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub splash_palette: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shader: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_skip: Option<u32>,
+    /// Pixel width over height, e.g. 1.1 for a wider handheld look. Defaults to 1.0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixel_aspect: Option<f32>,
+    /// Bars in the audio visualizer, 1 to 160. Defaults to 8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visualizer_bars: Option<usize>,
+    /// How visualizer bars are spaced in frequency: "log" (default) or "linear".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visualizer_scale: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colorize: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autofire: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autofire_rate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opcode_policy: Option<String>,
+    /// How the mixer limits loud samples: `hard` (default) or `soft`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_clip: Option<String>,
+    /// `full` (default), or `4bit` to quantize output like the DAC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_depth: Option<String>,
+    /// Shows the header logo before the game when no boot ROM is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_animation: Option<bool>,
+    /// Paces frames off the audio device's clock instead of the system clock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_sync: Option<bool>,
+    /// Left stick travel ignored around the center, 0 to 0.9. Defaults to 0.15.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stick_deadzone: Option<f32>,
+    /// Overrides `stick_deadzone` for the horizontal axis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stick_deadzone_x: Option<f32>,
+    /// Overrides `stick_deadzone` for the vertical axis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stick_deadzone_y: Option<f32>,
+    /// Exponent applied to stick travel past the deadzone; under 1 presses directions
+    /// with less deflection. Defaults to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stick_curve: Option<f32>,
+    /// Screenshots capture the whole display, overlay and visualizer included, instead
+    /// of the bare game frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screenshot_overlay: Option<bool>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub key_bindings: BTreeMap<String, String>,
+    /// Mixer gain per sound channel (`pulse1`, `pulse2`, `wave`, `noise`), 0.0 to 1.0.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub channel_gain: BTreeMap<String, f32>,
+}
+
+impl GameConfig {
+    pub fn new() -> Self {
+        Self {
+            palette: None,
+            splash_palette: None,
+            shader: None,
+            speed: None,
+            frame_skip: None,
+            pixel_aspect: None,
+            visualizer_bars: None,
+            visualizer_scale: None,
+            colorize: None,
+            socd: None,
+            autofire: None,
+            autofire_rate: None,
+            opcode_policy: None,
+            audio_clip: None,
+            audio_depth: None,
+            boot_animation: None,
+            audio_sync: None,
+            stick_deadzone: None,
+            stick_deadzone_x: None,
+            stick_deadzone_y: None,
+            stick_curve: None,
+            screenshot_overlay: None,
+            key_bindings: BTreeMap::new(),
+            channel_gain: BTreeMap::new(),
+        }
+    }
+
+    /// Returns `self` with every key set in `overrides` replacing the local value.
+    pub fn merged_with(&self, overrides: &GameConfig) -> GameConfig {
+        let mut key_bindings = self.key_bindings.clone();
+        for (action, key) in &overrides.key_bindings {
+            key_bindings.insert(action.clone(), key.clone());
+        }
+        let mut channel_gain = self.channel_gain.clone();
+        for (channel, gain) in &overrides.channel_gain {
+            channel_gain.insert(channel.clone(), *gain);
+        }
+        GameConfig {
+            palette: overrides.palette.clone().or_else(|| self.palette.clone()),
+            splash_palette: overrides
+                .splash_palette
+                .clone()
+                .or_else(|| self.splash_palette.clone()),
+            shader: overrides.shader.clone().or_else(|| self.shader.clone()),
+            speed: overrides.speed.or(self.speed),
+            frame_skip: overrides.frame_skip.or(self.frame_skip),
+            pixel_aspect: overrides.pixel_aspect.or(self.pixel_aspect),
+            visualizer_bars: overrides.visualizer_bars.or(self.visualizer_bars),
+            visualizer_scale: overrides
+                .visualizer_scale
+                .clone()
+                .or_else(|| self.visualizer_scale.clone()),
+            colorize: overrides.colorize.or(self.colorize),
+            socd: overrides.socd.clone().or_else(|| self.socd.clone()),
+            autofire: overrides.autofire.clone().or_else(|| self.autofire.clone()),
+            autofire_rate: overrides.autofire_rate.or(self.autofire_rate),
+            opcode_policy: overrides
+                .opcode_policy
+                .clone()
+                .or_else(|| self.opcode_policy.clone()),
+            audio_clip: overrides
+                .audio_clip
+                .clone()
+                .or_else(|| self.audio_clip.clone()),
+            audio_depth: overrides
+                .audio_depth
+                .clone()
+                .or_else(|| self.audio_depth.clone()),
+            boot_animation: overrides.boot_animation.or(self.boot_animation),
+            audio_sync: overrides.audio_sync.or(self.audio_sync),
+            stick_deadzone: overrides.stick_deadzone.or(self.stick_deadzone),
+            stick_deadzone_x: overrides.stick_deadzone_x.or(self.stick_deadzone_x),
+            stick_deadzone_y: overrides.stick_deadzone_y.or(self.stick_deadzone_y),
+            stick_curve: overrides.stick_curve.or(self.stick_curve),
+            screenshot_overlay: overrides.screenshot_overlay.or(self.screenshot_overlay),
+            key_bindings,
+            channel_gain,
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings for the app as a whole, which no game's config can override. Kept in their
+/// own file beside the global game config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlobalSettings {
+    /// Boot ROM to run when `--boot-rom` isn't given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_rom: Option<String>,
+    /// Whether Escape toggles the menu (`toggle`, default) or shows it while held (`hold`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu_mode: Option<String>,
+    /// What a ROM loaded from the menu waits for before it sees input: `off`, `any` or `start`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_gate: Option<String>,
+    /// `fifo` (vsync), `mailbox` or `immediate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub present_mode: Option<String>,
+    /// `header` runs ROMs as their CGB flag says; `extension` lets `.gbc` and `.gb` decide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension_policy: Option<String>,
+    /// What to reopen at launch: `off`, `rom` (default) or `state`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_resume: Option<String>,
+}
+
+impl GlobalSettings {
+    pub fn new() -> Self {
+        Self {
+            boot_rom: None,
+            menu_mode: None,
+            load_gate: None,
+            present_mode: None,
+            extension_policy: None,
+            auto_resume: None,
+        }
+    }
+}
+
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Decode(toml::de::Error),
+    Encode(toml::ser::Error),
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Decode(err)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Encode(err)
+    }
+}
+
+pub fn load_config(path: impl AsRef<Path>) -> Result<Option<GameConfig>, ConfigError> {
+    read_toml(path.as_ref())
+}
+
+pub fn save_config(path: impl AsRef<Path>, config: &GameConfig) -> Result<(), ConfigError> {
+    write_toml(path.as_ref(), config)
+}
+
+pub fn load_settings(path: impl AsRef<Path>) -> Result<Option<GlobalSettings>, ConfigError> {
+    read_toml(path.as_ref())
+}
+
+pub fn save_settings(path: impl AsRef<Path>, settings: &GlobalSettings) -> Result<(), ConfigError> {
+    write_toml(path.as_ref(), settings)
+}
+
+fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, ConfigError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(Some(toml::from_str(&text)?))
+}
+
+fn write_toml<T: Serialize>(path: &Path, value: &T) -> Result<(), ConfigError> {
+    let text = toml::to_string(value)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    write_atomic(path, text.as_bytes())?;
+    Ok(())
+}
+
+pub fn default_config_root() -> PathBuf {
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join("config")
+}
+
+pub fn global_config_path(root: &Path) -> PathBuf {
+    root.join("craterboy.toml")
+}
+
+pub fn settings_path(root: &Path) -> PathBuf {
+    root.join("settings.toml")
+}
+
+pub fn game_config_path(root: &Path, header: &RomHeader) -> PathBuf {
+    let name = format!(
+        "{}_{:04x}.toml",
+        header.title_slug(),
+        header.global_checksum
+    );
+    root.join("games").join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        GameConfig, GlobalSettings, game_config_path, load_config, load_settings, save_config,
+        save_settings, settings_path,
+    };
+    use crate::domain::Cartridge;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn unique_config_root() -> std::path::PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dirname = format!("craterboy_config_{}_{}", std::process::id(), id);
+        std::env::temp_dir().join(dirname)
+    }
+
+    #[test]
+    fn game_config_overrides_global_and_falls_back() {
+        let mut global = GameConfig::new();
+        global.palette = Some("DMG".to_string());
+        global.shader = Some("Smooth".to_string());
+        global.speed = Some(1.0);
+        global
+            .key_bindings
+            .insert("a".to_string(), "KeyZ".to_string());
+        global
+            .key_bindings
+            .insert("b".to_string(), "KeyX".to_string());
+
+        let mut game = GameConfig::new();
+        game.palette = Some("Amber".to_string());
+        game.key_bindings
+            .insert("a".to_string(), "KeyK".to_string());
+
+        let merged = global.merged_with(&game);
+        assert_eq!(merged.palette.as_deref(), Some("Amber"));
+        assert_eq!(merged.shader.as_deref(), Some("Smooth"));
+        assert_eq!(merged.speed, Some(1.0));
+        assert_eq!(
+            merged.key_bindings.get("a").map(String::as_str),
+            Some("KeyK")
+        );
+        assert_eq!(
+            merged.key_bindings.get("b").map(String::as_str),
+            Some("KeyX")
+        );
+    }
+
+    #[test]
+    fn game_config_roundtrip_keyed_by_header() {
+        let root = unique_config_root();
+        let mut rom = vec![0; 0x0150];
+        rom[0x0134..0x0139].copy_from_slice(b"TEST!");
+        rom[0x014E] = 0x12;
+        rom[0x014F] = 0x34;
+        let cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let path = game_config_path(&root, &cartridge.header);
+        assert!(path.ends_with("games/TEST_1234.toml"));
+
+        let mut config = GameConfig::new();
+        config.shader = Some("Toon".to_string());
+        config.speed = Some(2.0);
+        save_config(&path, &config).expect("save");
+
+        let loaded = load_config(&path).expect("load");
+        assert_eq!(loaded, Some(config));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn global_settings_roundtrip_apart_from_game_config() {
+        let root = unique_config_root();
+        let path = settings_path(&root);
+        let mut settings = GlobalSettings::new();
+        settings.menu_mode = Some("hold".to_string());
+        settings.auto_resume = Some("state".to_string());
+        save_settings(&path, &settings).expect("save");
+
+        assert_eq!(load_settings(&path).expect("load"), Some(settings));
+        // A game config naming a global setting doesn't pick it up.
+        std::fs::write(&path, "menu_mode = \"hold\"\nshader = \"Toon\"\n").expect("write");
+        let config = load_config(&path).expect("load").expect("config");
+        assert_eq!(config.shader.as_deref(), Some("Toon"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn missing_config_is_none() {
+        let root = unique_config_root();
+        let loaded = load_config(root.join("craterboy.toml")).expect("load");
+        assert!(loaded.is_none());
+    }
+}
@@ -0,0 +1,27 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes `data` to a temporary sibling and renames it over `path`, so a crash
+/// mid-write leaves the old file intact.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut temp_path = path.to_path_buf();
+    let unique = format!(
+        "tmp{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    );
+    if let Some(ext) = path.extension() {
+        let mut ext = ext.to_os_string();
+        ext.push(".");
+        ext.push(unique);
+        temp_path.set_extension(ext);
+    } else {
+        temp_path.set_extension(unique);
+    }
+
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
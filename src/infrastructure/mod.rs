@@ -1,2 +1,4 @@
+pub mod config;
+mod fs;
 pub mod persistence;
 pub mod rom_loader;
@@ -1,8 +1,14 @@
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-
-use crate::domain::Cartridge;
-use crate::infrastructure::rom_loader::{RomSaveError, save_battery_ram_with_root};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::domain::{
+    Cartridge, Cpu, Emulator, MemoryRegion, Movie, MovieError, SystemClock, TimeSource,
+    compute_global_checksum,
+};
+use crate::infrastructure::fs::write_atomic;
+use crate::infrastructure::rom_loader::{
+    RomSaveError, save_battery_data_with_root, save_battery_ram_with_root,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
@@ -32,23 +38,55 @@ impl SaveManager {
         }
     }
 
-    pub fn maybe_flush(
+    pub fn maybe_flush(&mut self, cartridge: &mut Cartridge) -> Result<bool, RomSaveError> {
+        self.maybe_flush_at(Instant::now(), cartridge)
+    }
+
+    pub fn maybe_flush_at(
         &mut self,
-        path: impl AsRef<Path>,
+        now: Instant,
         cartridge: &mut Cartridge,
     ) -> Result<bool, RomSaveError> {
-        self.maybe_flush_at(Instant::now(), path, cartridge)
+        if !self.flush_due(now, cartridge) {
+            return Ok(false);
+        }
+
+        save_battery_ram_with_root(self.save_root.as_deref(), cartridge)?;
+        cartridge.clear_ram_dirty();
+        self.last_dirty_at = None;
+        Ok(true)
     }
 
-    pub fn maybe_flush_at(
+    /// Like `maybe_flush_at`, but writes the whole `.sav` from `Emulator::export_save`, so
+    /// an MBC3 game keeps its clock block.
+    pub fn maybe_flush_emulator_at(
         &mut self,
         now: Instant,
-        path: impl AsRef<Path>,
-        cartridge: &mut Cartridge,
+        emulator: &mut Emulator,
     ) -> Result<bool, RomSaveError> {
+        let Some(cartridge) = emulator.cartridge() else {
+            return Ok(false);
+        };
+        if !self.flush_due(now, cartridge) {
+            return Ok(false);
+        }
+        let Ok(data) = emulator.export_save() else {
+            return Ok(false);
+        };
+
+        save_battery_data_with_root(self.save_root.as_deref(), cartridge, &data)?;
+        if let Some(cartridge) = emulator.cartridge_mut() {
+            cartridge.clear_ram_dirty();
+        }
+        self.last_dirty_at = None;
+        Ok(true)
+    }
+
+    /// Whether the RAM has been dirty and untouched for the inactivity window.
+    fn flush_due(&mut self, now: Instant, cartridge: &Cartridge) -> bool {
         if !cartridge.is_ram_dirty() {
             self.last_dirty_at = None;
-            return Ok(false);
+            return false;
         }
 
         let generation = cartridge.ram_dirty_generation();
@@ -57,28 +95,15 @@ impl SaveManager {
             self.last_dirty_at = Some(now);
         }
 
-        let should_flush = self
-            .last_dirty_at
-            .is_some_and(|dirty_at| now.duration_since(dirty_at) >= self.inactivity);
-        if !should_flush {
-            return Ok(false);
-        }
-
-        save_battery_ram_with_root(path, self.save_root.as_deref(), cartridge)?;
-        cartridge.clear_ram_dirty();
-        self.last_dirty_at = None;
-        Ok(true)
+        self.last_dirty_at
+            .is_some_and(|dirty_at| now.duration_since(dirty_at) >= self.inactivity)
     }
 
-    pub fn flush_now(
-        &mut self,
-        path: impl AsRef<Path>,
-        cartridge: &mut Cartridge,
-    ) -> Result<bool, RomSaveError> {
+    pub fn flush_now(&mut self, cartridge: &mut Cartridge) -> Result<bool, RomSaveError> {
         if !cartridge.is_ram_dirty() {
             return Ok(false);
         }
-        save_battery_ram_with_root(path, self.save_root.as_deref(), cartridge)?;
+        save_battery_ram_with_root(self.save_root.as_deref(), cartridge)?;
         cartridge.clear_ram_dirty();
         self.last_dirty_at = None;
         Ok(true)
@@ -100,7 +125,17 @@ impl AutoResumeMetadata {
     }
 
     pub fn with_save_root(rom_path: impl Into<PathBuf>, save_root: Option<PathBuf>) -> Self {
-        let saved_at_unix = SystemTime::now()
+        Self::stamped_by(rom_path, save_root, &SystemClock)
+    }
+
+    /// Like `with_save_root`, taking `saved_at_unix` from `clock`.
+    pub fn stamped_by(
+        rom_path: impl Into<PathBuf>,
+        save_root: Option<PathBuf>,
+        clock: &dyn TimeSource,
+    ) -> Self {
+        let saved_at_unix = clock
+            .now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
@@ -113,10 +148,68 @@ impl AutoResumeMetadata {
     }
 }
 
+/// What to reopen at launch when no ROM is given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResumeMode {
+    /// Start at the menu.
+    Off,
+    /// Reload the last ROM from power-on.
+    #[default]
+    Rom,
+    /// Reload the last ROM and the state it was left in at exit.
+    RomAndState,
+}
+
+impl ResumeMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Rom => "rom",
+            Self::RomAndState => "state",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "rom" => Some(Self::Rom),
+            "state" => Some(Self::RomAndState),
+            _ => None,
+        }
+    }
+
+    /// What to load from the stored resume entry, if anything.
+    pub fn plan(self, metadata: Option<AutoResumeMetadata>) -> Option<ResumePlan> {
+        let metadata = metadata?;
+        let state_path = match self {
+            Self::Off => return None,
+            Self::Rom => None,
+            Self::RomAndState => metadata.state_path,
+        };
+        Some(ResumePlan {
+            rom_path: metadata.rom_path,
+            save_root: metadata.save_root,
+            state_path,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumePlan {
+    pub rom_path: PathBuf,
+    pub save_root: Option<PathBuf>,
+    pub state_path: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub enum ResumeError {
     Io(std::io::Error),
     Codec(Box<bincode::ErrorKind>),
+    /// The resume state was saved from a different ROM than the one loaded.
+    RomMismatch {
+        expected: u16,
+        actual: u16,
+    },
 }
 
 impl From<std::io::Error> for ResumeError {
@@ -131,6 +224,88 @@ impl From<Box<bincode::ErrorKind>> for ResumeError {
     }
 }
 
+/// The CPU registers and RAM of a running game, saved at exit so `ResumeMode::RomAndState`
+/// can pick up where it left off. Hardware registers restart from their post-boot values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub rom_checksum: u16,
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub pc: u16,
+    pub sp: u16,
+    pub ime: bool,
+    pub wram: Vec<u8>,
+    pub hram: Vec<u8>,
+    pub vram: Vec<u8>,
+    pub oam: Vec<u8>,
+}
+
+impl ResumeState {
+    /// None with no cartridge loaded.
+    pub fn capture(emulator: &Emulator) -> Option<Self> {
+        let cartridge = emulator.cartridge()?;
+        let cpu = emulator.cpu();
+        let regs = cpu.regs();
+        Some(Self {
+            rom_checksum: rom_checksum(cartridge),
+            af: regs.af(),
+            bc: regs.bc(),
+            de: regs.de(),
+            hl: regs.hl(),
+            pc: cpu.pc(),
+            sp: cpu.sp(),
+            ime: cpu.ime(),
+            wram: emulator.memory_snapshot(MemoryRegion::Wram),
+            hram: emulator.memory_snapshot(MemoryRegion::Hram),
+            vram: emulator.memory_snapshot(MemoryRegion::Vram),
+            oam: emulator.memory_snapshot(MemoryRegion::Oam),
+        })
+    }
+
+    /// Puts the cartridge `emulator` has loaded back in this state. Fails without
+    /// touching it if the state was saved from another ROM.
+    pub fn restore(&self, emulator: &mut Emulator) -> Result<(), ResumeError> {
+        let actual = emulator.cartridge().map_or(0, rom_checksum);
+        if actual != self.rom_checksum {
+            return Err(ResumeError::RomMismatch {
+                expected: self.rom_checksum,
+                actual,
+            });
+        }
+        let mut cpu = Cpu::new();
+        let regs = cpu.regs_mut();
+        regs.set_af(self.af);
+        regs.set_bc(self.bc);
+        regs.set_de(self.de);
+        regs.set_hl(self.hl);
+        cpu.set_pc(self.pc);
+        cpu.set_sp(self.sp);
+        cpu.set_ime(self.ime);
+        emulator.restore_state_parts(cpu, &self.wram, &self.hram);
+        // Both regions were captured whole, so they fit.
+        let _ = emulator.load_vram(&self.vram);
+        let _ = emulator.load_oam(&self.oam);
+        Ok(())
+    }
+}
+
+fn rom_checksum(cartridge: &Cartridge) -> u16 {
+    compute_global_checksum(&cartridge.bytes).unwrap_or(cartridge.header.global_checksum)
+}
+
+pub fn save_resume_state(path: impl AsRef<Path>, state: &ResumeState) -> Result<(), ResumeError> {
+    let bytes = bincode::serialize(state)?;
+    write_atomic(path.as_ref(), &bytes)?;
+    Ok(())
+}
+
+pub fn load_resume_state(path: impl AsRef<Path>) -> Result<ResumeState, ResumeError> {
+    let bytes = std::fs::read(path)?;
+    Ok(bincode::deserialize(&bytes)?)
+}
+
 pub fn save_last_session(
     path: impl AsRef<Path>,
     metadata: &AutoResumeMetadata,
@@ -153,43 +328,57 @@ pub fn load_last_session(
     Ok(Some(metadata))
 }
 
+#[derive(Debug)]
+pub enum MovieFileError {
+    Io(std::io::Error),
+    Format(MovieError),
+}
+
+impl From<std::io::Error> for MovieFileError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<MovieError> for MovieFileError {
+    fn from(err: MovieError) -> Self {
+        Self::Format(err)
+    }
+}
+
+pub fn save_movie(path: impl AsRef<Path>, movie: &Movie) -> Result<(), MovieFileError> {
+    write_atomic(path.as_ref(), &movie.encode())?;
+    Ok(())
+}
+
+pub fn load_movie(path: impl AsRef<Path>) -> Result<Movie, MovieFileError> {
+    let bytes = std::fs::read(path)?;
+    Ok(Movie::decode(&bytes)?)
+}
+
 pub fn default_resume_path() -> PathBuf {
     std::env::current_dir()
         .unwrap_or_else(|_| PathBuf::from("."))
         .join("craterboy_last_session.bin")
 }
 
-fn write_atomic(path: &Path, data: &[u8]) -> Result<(), ResumeError> {
-    let mut temp_path = path.to_path_buf();
-    let unique = format!(
-        "tmp{}",
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos()
-    );
-    if let Some(ext) = path.extension() {
-        let mut ext = ext.to_os_string();
-        ext.push(".");
-        ext.push(unique);
-        temp_path.set_extension(ext);
-    } else {
-        temp_path.set_extension(unique);
-    }
-
-    std::fs::write(&temp_path, data)?;
-    std::fs::rename(&temp_path, path)?;
-    Ok(())
+/// Where the state for `ResumeMode::RomAndState` is kept, beside the resume entry.
+pub fn default_resume_state_path() -> PathBuf {
+    default_resume_path().with_extension("state")
 }
 
 #[cfg(test)]
 mod tests {
     use super::SaveManager;
-    use super::{AutoResumeMetadata, load_last_session, save_last_session};
-    use crate::domain::Cartridge;
-    use crate::infrastructure::rom_loader::save_path_for_rom;
+    use super::{
+        AutoResumeMetadata, ResumeError, ResumeMode, ResumePlan, ResumeState, load_last_session,
+        load_resume_state, save_last_session, save_resume_state,
+    };
+    use crate::domain::{Cartridge, Cpu, Emulator, MemoryRegion, MockClock, RTC_FOOTER_SIZE};
+    use crate::infrastructure::rom_loader::save_path_for_cartridge;
+    use std::sync::Arc;
     use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::time::{Duration, Instant};
+    use std::time::{Duration, Instant, UNIX_EPOCH};
 
     static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -207,7 +396,6 @@ mod tests {
 
     #[test]
     fn flushes_after_inactivity_and_clears_dirty() {
-        let rom_path = unique_rom_path();
         let save_root = unique_rom_path().with_extension("saves");
         let mut rom = vec![0; 0x0150];
         rom[0x0147] = 0x09;
@@ -219,18 +407,18 @@ mod tests {
         let start = Instant::now();
         assert!(
             !manager
-                .maybe_flush_at(start, &rom_path, &mut cartridge)
+                .maybe_flush_at(start, &mut cartridge)
                 .expect("maybe flush")
         );
 
         assert!(
             manager
-                .maybe_flush_at(start + Duration::from_secs(6), &rom_path, &mut cartridge)
+                .maybe_flush_at(start + Duration::from_secs(6), &mut cartridge)
                 .expect("maybe flush")
         );
         assert!(!cartridge.is_ram_dirty());
 
-        let save_path = save_path_for_rom(&rom_path, Some(&save_root));
+        let save_path = save_path_for_cartridge(&cartridge, Some(&save_root));
         let saved = std::fs::read(&save_path).expect("save read");
         assert_eq!(saved[0], 0x5A);
 
@@ -238,6 +426,44 @@ mod tests {
         let _ = std::fs::remove_dir_all(&save_root);
     }
 
+    #[test]
+    fn emulator_flush_keeps_the_rtc_block() {
+        let save_root = unique_rom_path().with_extension("saves");
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        let mut emulator = Emulator::with_time_source(Arc::new(MockClock::new(UNIX_EPOCH)));
+        emulator.load_rom_bytes(&rom, None).expect("load rom");
+        emulator.cartridge_mut().expect("cartridge").ram_mut()[0] = 0x5A;
+        emulator
+            .cartridge_mut()
+            .expect("cartridge")
+            .mark_ram_dirty();
+
+        let mut manager = SaveManager::with_save_root(Duration::from_secs(5), &save_root);
+        let start = Instant::now();
+        assert!(
+            !manager
+                .maybe_flush_emulator_at(start, &mut emulator)
+                .expect("maybe flush")
+        );
+        assert!(
+            manager
+                .maybe_flush_emulator_at(start + Duration::from_secs(6), &mut emulator)
+                .expect("maybe flush")
+        );
+        let cartridge = emulator.cartridge().expect("cartridge");
+        assert!(!cartridge.is_ram_dirty());
+
+        let save_path = save_path_for_cartridge(cartridge, Some(&save_root));
+        let saved = std::fs::read(&save_path).expect("save read");
+        assert_eq!(saved, emulator.export_save().expect("export save"));
+        assert_eq!(saved.len(), 0x2000 + RTC_FOOTER_SIZE);
+
+        let _ = std::fs::remove_file(&save_path);
+        let _ = std::fs::remove_dir_all(&save_root);
+    }
+
     #[test]
     fn auto_resume_roundtrip() {
         let meta_path = unique_meta_path();
@@ -250,6 +476,89 @@ mod tests {
         let _ = std::fs::remove_file(&meta_path);
     }
 
+    #[test]
+    fn auto_resume_timestamp_comes_from_clock() {
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        clock.advance(Duration::from_millis(2_500));
+
+        let metadata = AutoResumeMetadata::stamped_by("roms/tetris.gb", None, &clock);
+
+        assert_eq!(metadata.saved_at_unix, 1_700_000_002);
+    }
+
+    #[test]
+    fn resume_mode_decides_what_to_load() {
+        let mut metadata = AutoResumeMetadata::new("game.gb");
+        metadata.state_path = Some("game.state".into());
+
+        assert_eq!(ResumeMode::Off.plan(Some(metadata.clone())), None);
+        assert_eq!(
+            ResumeMode::Rom.plan(Some(metadata.clone())),
+            Some(ResumePlan {
+                rom_path: "game.gb".into(),
+                save_root: None,
+                state_path: None,
+            })
+        );
+        let plan = ResumeMode::RomAndState.plan(Some(metadata)).expect("plan");
+        assert_eq!(plan.state_path, Some("game.state".into()));
+        assert_eq!(ResumeMode::RomAndState.plan(None), None);
+        assert_eq!(ResumeMode::from_name("ROM"), Some(ResumeMode::Rom));
+        assert_eq!(
+            ResumeMode::from_name("state"),
+            Some(ResumeMode::RomAndState)
+        );
+    }
+
+    #[test]
+    fn resume_state_restores_cpu_and_ram_for_the_same_rom() {
+        let state_path = unique_meta_path().with_extension("state");
+        let rom = vec![0; 0x8000];
+        let cartridge = Cartridge::from_bytes(rom.clone()).expect("cartridge");
+        let mut cpu = Cpu::new();
+        cpu.regs_mut().set_af(0x42B0);
+        cpu.regs_mut().set_hl(0xC123);
+        cpu.set_pc(0x0150);
+        cpu.set_sp(0xDFF0);
+        let wram = [0x5A; 0x20];
+        let hram = [0x77; 0x08];
+        let mut emulator =
+            Emulator::from_state_parts(cartridge.clone(), cpu, &wram, &hram).expect("emulator");
+        emulator.load_vram(&[0x3C; 0x10]).expect("vram");
+
+        let state = ResumeState::capture(&emulator).expect("state");
+        save_resume_state(&state_path, &state).expect("save");
+        let state = load_resume_state(&state_path).expect("load");
+        let mut resumed = Emulator::new();
+        resumed.load_cartridge(cartridge).expect("load");
+        state.restore(&mut resumed).expect("restore");
+
+        assert_eq!(resumed.cpu().regs().af(), 0x42B0);
+        assert_eq!(resumed.cpu().regs().hl(), 0xC123);
+        assert_eq!(resumed.cpu().pc(), 0x0150);
+        assert_eq!(resumed.cpu().sp(), 0xDFF0);
+        for region in [MemoryRegion::Wram, MemoryRegion::Hram, MemoryRegion::Vram] {
+            assert_eq!(
+                resumed.memory_snapshot(region),
+                emulator.memory_snapshot(region)
+            );
+        }
+
+        let mut other_rom = rom;
+        other_rom[0x0200] = 0x42;
+        let mut other = Emulator::new();
+        other
+            .load_cartridge(Cartridge::from_bytes(other_rom).expect("cartridge"))
+            .expect("load");
+        assert!(matches!(
+            state.restore(&mut other),
+            Err(ResumeError::RomMismatch { .. })
+        ));
+        assert_ne!(other.cpu().pc(), 0x0150);
+
+        let _ = std::fs::remove_file(&state_path);
+    }
+
     #[test]
     fn auto_resume_missing_is_none() {
         let meta_path = unique_meta_path();
@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::domain::{Cartridge, RomHeaderError};
+use crate::domain::{Cartridge, CgbFlag, RomHeaderError};
+use crate::infrastructure::fs::write_atomic;
 
 #[derive(Debug)]
 pub enum RomLoadError {
@@ -22,6 +22,60 @@ impl From<RomHeaderError> for RomLoadError {
     }
 }
 
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+pub const CGB_BOOT_ROM_SIZE: usize = 0x800;
+/// CGB dumps that keep the unused cartridge-header gap at 0x100-0x1FF.
+pub const CGB_BOOT_ROM_SIZE_WITH_GAP: usize = 0x900;
+
+/// Whether `len` matches a known boot ROM dump.
+pub fn is_valid_boot_rom_size(len: usize) -> bool {
+    matches!(
+        len,
+        DMG_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE_WITH_GAP
+    )
+}
+
+/// Whether a ROM's file extension may override the CGB flag in its header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtensionPolicy {
+    /// Run the ROM as its header says, whatever the file is called.
+    #[default]
+    Header,
+    /// `.gbc` runs in CGB mode and `.gb` in DMG mode, for mislabeled dumps.
+    Extension,
+}
+
+impl ExtensionPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Header => "header",
+            Self::Extension => "extension",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "header" => Some(Self::Header),
+            "extension" => Some(Self::Extension),
+            _ => None,
+        }
+    }
+
+    /// The CGB mode `path`'s extension asks for, if this policy lets it ask. A
+    /// CGB-only header always wins, since such a game can't run on a DMG.
+    pub fn cgb_hint(self, path: &Path, cgb_flag: CgbFlag) -> Option<bool> {
+        if self == Self::Header || cgb_flag == CgbFlag::CgbOnly {
+            return None;
+        }
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gbc" => Some(true),
+            "gb" => Some(false),
+            _ => None,
+        }
+    }
+}
+
 pub fn load_rom(path: impl AsRef<Path>) -> Result<Cartridge, RomLoadError> {
     load_rom_with_save_root(path, None)
 }
@@ -29,16 +83,30 @@ pub fn load_rom(path: impl AsRef<Path>) -> Result<Cartridge, RomLoadError> {
 pub fn load_rom_with_save_root(
     path: impl AsRef<Path>,
     save_root: Option<&Path>,
+) -> Result<Cartridge, RomLoadError> {
+    load_rom_with_policy(path, save_root, ExtensionPolicy::default())
+}
+
+pub fn load_rom_with_policy(
+    path: impl AsRef<Path>,
+    save_root: Option<&Path>,
+    policy: ExtensionPolicy,
 ) -> Result<Cartridge, RomLoadError> {
     let path = path.as_ref();
     let bytes = std::fs::read(path)?;
     let mut cartridge = Cartridge::from_bytes(bytes)?;
+    cartridge.set_cgb_hint(policy.cgb_hint(path, cartridge.header.cgb_flag));
 
     if cartridge.has_battery() && cartridge.has_ram() {
-        let save_path = save_path_for_rom(path, save_root);
-        if save_path.exists() {
-            let ram = std::fs::read(&save_path).map_err(RomLoadError::SaveIo)?;
-            cartridge.load_ram(&ram);
+        let save_path = [
+            save_path_for_cartridge(&cartridge, save_root),
+            legacy_save_path_for_rom(path, save_root),
+        ]
+        .into_iter()
+        .find(|candidate| candidate.exists());
+        if let Some(save_path) = save_path {
+            let data = std::fs::read(&save_path).map_err(RomLoadError::SaveIo)?;
+            cartridge.load_save(&data);
         }
     }
 
@@ -56,27 +124,52 @@ impl From<std::io::Error> for RomSaveError {
     }
 }
 
-pub fn save_battery_ram(path: impl AsRef<Path>, cartridge: &Cartridge) -> Result<(), RomSaveError> {
-    save_battery_ram_with_root(path, None, cartridge)
+pub fn save_battery_ram(cartridge: &Cartridge) -> Result<(), RomSaveError> {
+    save_battery_ram_with_root(None, cartridge)
 }
 
 pub fn save_battery_ram_with_root(
-    path: impl AsRef<Path>,
     save_root: Option<&Path>,
     cartridge: &Cartridge,
+) -> Result<(), RomSaveError> {
+    save_battery_data_with_root(save_root, cartridge, cartridge.ram())
+}
+
+/// Writes a whole `.sav` for `cartridge`, such as `Emulator::export_save` produces.
+pub fn save_battery_data_with_root(
+    save_root: Option<&Path>,
+    cartridge: &Cartridge,
+    data: &[u8],
 ) -> Result<(), RomSaveError> {
     if !cartridge.has_battery() || !cartridge.has_ram() {
         return Ok(());
     }
-    let save_path = save_path_for_rom(path.as_ref(), save_root);
+    let save_path = save_path_for_cartridge(cartridge, save_root);
     if let Some(parent) = save_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    write_atomic(&save_path, cartridge.ram())?;
+    write_atomic(&save_path, data)?;
     Ok(())
 }
 
-pub(crate) fn save_path_for_rom(path: &Path, save_root: Option<&Path>) -> PathBuf {
+/// Keyed on the title and the global checksum, so revisions and hacks sharing a title
+/// keep separate saves. An untitled ROM's slug is already the checksum.
+pub(crate) fn save_path_for_cartridge(cartridge: &Cartridge, save_root: Option<&Path>) -> PathBuf {
+    let root = save_root
+        .map(PathBuf::from)
+        .unwrap_or_else(default_save_root);
+    let slug = cartridge.title_slug();
+    let checksum = format!("{:04x}", cartridge.header.global_checksum);
+    let name = if slug == checksum {
+        slug
+    } else {
+        format!("{slug}_{checksum}")
+    };
+    root.join(name).join("ram.sav")
+}
+
+/// Save location used before saves were keyed by title; still read so old saves load.
+fn legacy_save_path_for_rom(path: &Path, save_root: Option<&Path>) -> PathBuf {
     let root = save_root
         .map(PathBuf::from)
         .unwrap_or_else(default_save_root);
@@ -92,34 +185,18 @@ fn default_save_root() -> PathBuf {
     PathBuf::from("saves")
 }
 
-fn write_atomic(path: &Path, data: &[u8]) -> Result<(), RomSaveError> {
-    let mut temp_path = path.to_path_buf();
-    let unique = format!(
-        "tmp{}",
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos()
-    );
-    if let Some(ext) = path.extension() {
-        let mut ext = ext.to_os_string();
-        ext.push(".");
-        ext.push(unique);
-        temp_path.set_extension(ext);
-    } else {
-        temp_path.set_extension(unique);
-    }
-
-    std::fs::write(&temp_path, data)?;
-    std::fs::rename(&temp_path, path)?;
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{load_rom_with_save_root, save_battery_ram_with_root, save_path_for_rom};
-    use crate::domain::Cartridge;
+    use super::{
+        CGB_BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE_WITH_GAP, DMG_BOOT_ROM_SIZE, ExtensionPolicy,
+        is_valid_boot_rom_size, legacy_save_path_for_rom, load_rom_with_policy,
+        load_rom_with_save_root, save_battery_data_with_root, save_battery_ram_with_root,
+        save_path_for_cartridge,
+    };
+    use crate::domain::{Cartridge, Emulator, MockClock, RtcFooter};
+    use std::sync::Arc;
     use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, UNIX_EPOCH};
 
     static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -137,17 +214,64 @@ mod tests {
         unique_path("craterboy_rom").with_extension("gb")
     }
 
+    #[test]
+    fn boot_rom_size_accepts_dmg_and_cgb_dumps() {
+        assert!(is_valid_boot_rom_size(DMG_BOOT_ROM_SIZE));
+        assert!(is_valid_boot_rom_size(CGB_BOOT_ROM_SIZE));
+        assert!(is_valid_boot_rom_size(CGB_BOOT_ROM_SIZE_WITH_GAP));
+        assert!(!is_valid_boot_rom_size(0));
+        assert!(!is_valid_boot_rom_size(0x8000));
+        assert!(!is_valid_boot_rom_size(DMG_BOOT_ROM_SIZE - 1));
+    }
+
+    #[test]
+    fn gbc_extension_selects_cgb_under_the_extension_policy() {
+        let rom_path = unique_path("craterboy_rom").with_extension("gbc");
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x00; // DMG only
+        std::fs::write(&rom_path, &rom).expect("rom write");
+
+        let header_only =
+            load_rom_with_policy(&rom_path, None, ExtensionPolicy::Header).expect("load");
+        assert!(!header_only.runs_as_cgb());
+        let hinted =
+            load_rom_with_policy(&rom_path, None, ExtensionPolicy::Extension).expect("load");
+        assert!(hinted.runs_as_cgb());
+        assert!(!hinted.is_cgb());
+        let bus = crate::domain::Bus::new(hinted).expect("bus");
+        assert!(bus.is_cgb());
+
+        let dmg_path = rom_path.with_extension("gb");
+        rom[0x0143] = 0x80; // CGB supported
+        std::fs::write(&dmg_path, &rom).expect("rom write");
+        let hinted =
+            load_rom_with_policy(&dmg_path, None, ExtensionPolicy::Extension).expect("load");
+        assert!(!hinted.runs_as_cgb());
+
+        rom[0x0143] = 0xC0; // CGB only
+        std::fs::write(&dmg_path, &rom).expect("rom write");
+        let cgb_only =
+            load_rom_with_policy(&dmg_path, None, ExtensionPolicy::Extension).expect("load");
+        assert!(cgb_only.runs_as_cgb());
+
+        let _ = std::fs::remove_file(&rom_path);
+        let _ = std::fs::remove_file(&dmg_path);
+    }
+
     #[test]
     fn load_rom_reads_existing_save() {
         let rom_path = unique_rom_path();
         let save_root = unique_save_root();
-        let save_path = save_path_for_rom(&rom_path, Some(&save_root));
-        if let Some(parent) = save_path.parent() {
-            std::fs::create_dir_all(parent).expect("save dir");
-        }
         let mut rom = vec![0; 0x0150];
         rom[0x0147] = 0x09;
         rom[0x0149] = 0x02;
+        let save_path = save_path_for_cartridge(
+            &Cartridge::from_bytes(rom.clone()).expect("cartridge"),
+            Some(&save_root),
+        );
+        if let Some(parent) = save_path.parent() {
+            std::fs::create_dir_all(parent).expect("save dir");
+        }
 
         std::fs::write(&rom_path, &rom).expect("rom write");
         std::fs::write(&save_path, vec![0xAA; 0x2000]).expect("save write");
@@ -172,9 +296,9 @@ mod tests {
         let save_root = unique_save_root();
         let mut cartridge = Cartridge::from_bytes(rom).expect("cartridge");
         cartridge.ext_ram.fill(0x5A);
-        save_battery_ram_with_root(&rom_path, Some(&save_root), &cartridge).expect("save");
+        save_battery_ram_with_root(Some(&save_root), &cartridge).expect("save");
 
-        let save_path = save_path_for_rom(&rom_path, Some(&save_root));
+        let save_path = save_path_for_cartridge(&cartridge, Some(&save_root));
         let saved = std::fs::read(&save_path).expect("save read");
         assert_eq!(saved.len(), 0x2000);
         assert_eq!(saved[0], 0x5A);
@@ -193,15 +317,15 @@ mod tests {
         std::fs::write(&rom_path, &rom).expect("rom write");
 
         let save_root = unique_save_root();
-        let save_path = save_path_for_rom(&rom_path, Some(&save_root));
+        let mut cartridge = Cartridge::from_bytes(rom).expect("cartridge");
+        let save_path = save_path_for_cartridge(&cartridge, Some(&save_root));
         if let Some(parent) = save_path.parent() {
             std::fs::create_dir_all(parent).expect("save dir");
         }
         std::fs::write(&save_path, vec![0x11; 0x2000]).expect("save write");
 
-        let mut cartridge = Cartridge::from_bytes(rom).expect("cartridge");
         cartridge.ext_ram.fill(0x22);
-        save_battery_ram_with_root(&rom_path, Some(&save_root), &cartridge).expect("save");
+        save_battery_ram_with_root(Some(&save_root), &cartridge).expect("save");
 
         let saved = std::fs::read(&save_path).expect("save read");
         assert_eq!(saved[0], 0x22);
@@ -210,4 +334,95 @@ mod tests {
         let _ = std::fs::remove_file(&save_path);
         let _ = std::fs::remove_dir_all(&save_root);
     }
+
+    #[test]
+    fn saves_for_same_titled_roms_stay_apart() {
+        let mut rom = vec![0; 0x0150];
+        rom[0x0134..0x0138].copy_from_slice(b"GAME");
+        rom[0x0147] = 0x09;
+        rom[0x0149] = 0x02;
+        let original = Cartridge::from_bytes(rom.clone()).expect("cartridge");
+        rom[0x014F] = 0x01; // another revision's global checksum
+        let revision = Cartridge::from_bytes(rom).expect("cartridge");
+
+        let root = std::path::Path::new("saves");
+        let save_path = save_path_for_cartridge(&original, Some(root));
+        assert_ne!(save_path, save_path_for_cartridge(&revision, Some(root)));
+        assert_eq!(save_path, root.join("GAME_0000").join("ram.sav"));
+
+        let mut untitled = vec![0; 0x0150];
+        untitled[0x0147] = 0x09;
+        untitled[0x014E] = 0xBE;
+        untitled[0x014F] = 0xEF;
+        let untitled = Cartridge::from_bytes(untitled).expect("cartridge");
+        assert_eq!(
+            save_path_for_cartridge(&untitled, Some(root)),
+            root.join("beef").join("ram.sav")
+        );
+    }
+
+    #[test]
+    fn battery_save_carries_the_rtc_through_load_and_write() {
+        let rom_path = unique_rom_path();
+        let save_root = unique_save_root();
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x0149] = 0x02;
+        std::fs::write(&rom_path, &rom).expect("rom write");
+
+        let mut data = vec![0x5A; 0x2000];
+        let footer = RtcFooter {
+            registers: [10, 20, 3, 0, 0],
+            latched: [10, 20, 3, 0, 0],
+            timestamp: 1_700_000_000,
+        };
+        data.extend_from_slice(&footer.encode());
+        let save_path = save_path_for_cartridge(
+            &Cartridge::from_bytes(rom).expect("cartridge"),
+            Some(&save_root),
+        );
+        std::fs::create_dir_all(save_path.parent().expect("parent")).expect("save dir");
+        std::fs::write(&save_path, &data).expect("save write");
+
+        let cartridge = load_rom_with_save_root(&rom_path, Some(&save_root)).expect("load");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_090);
+        let mut emulator = Emulator::with_time_source(Arc::new(MockClock::new(now)));
+        emulator.load_cartridge(cartridge).expect("load cartridge");
+        let snapshot = emulator.rtc_snapshot().expect("rtc snapshot");
+        assert_eq!(snapshot.registers, [40, 21, 3, 0, 0]);
+
+        let exported = emulator.export_save().expect("export save");
+        let cartridge = emulator.cartridge().expect("cartridge");
+        save_battery_data_with_root(Some(&save_root), cartridge, &exported).expect("save");
+        let saved = std::fs::read(&save_path).expect("save read");
+        assert_eq!(saved[0], 0x5A);
+        let saved_footer = RtcFooter::decode(&saved[0x2000..]).expect("rtc footer");
+        assert_eq!(saved_footer.registers, [40, 21, 3, 0, 0]);
+        assert_eq!(saved_footer.timestamp, 1_700_000_090);
+
+        let _ = std::fs::remove_file(&rom_path);
+        let _ = std::fs::remove_dir_all(&save_root);
+    }
+
+    #[test]
+    fn load_rom_falls_back_to_legacy_save_path() {
+        let rom_path = unique_rom_path();
+        let save_root = unique_save_root();
+        let save_path = legacy_save_path_for_rom(&rom_path, Some(&save_root));
+        if let Some(parent) = save_path.parent() {
+            std::fs::create_dir_all(parent).expect("save dir");
+        }
+        let mut rom = vec![0; 0x0150];
+        rom[0x0147] = 0x09;
+        rom[0x0149] = 0x02;
+
+        std::fs::write(&rom_path, &rom).expect("rom write");
+        std::fs::write(&save_path, vec![0xBB; 0x2000]).expect("save write");
+
+        let cartridge = load_rom_with_save_root(&rom_path, Some(&save_root)).expect("load");
+        assert_eq!(cartridge.ext_ram[0], 0xBB);
+
+        let _ = std::fs::remove_file(&rom_path);
+        let _ = std::fs::remove_dir_all(&save_root);
+    }
 }
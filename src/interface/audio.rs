@@ -1,12 +1,13 @@
 use std::collections::VecDeque;
 use std::f32::consts::PI;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
 
-use crate::domain::Emulator;
+use crate::domain::{Emulator, goertzel};
 
 const DEFAULT_SAMPLE_RATE: u32 = 48_000;
 const DEFAULT_VOLUME: f32 = 0.3;
@@ -16,6 +17,56 @@ const MIN_BUFFER_FRAMES: usize = 256;
 const VISUALIZER_SAMPLE_WINDOW: usize = 512;
 const VISUALIZER_MIN_FREQ: f32 = 80.0;
 const VISUALIZER_MAX_FREQ: f32 = 8_000.0;
+/// Largest share the audio clock may stretch or shrink a frame, so a stalled or paused
+/// device can't drag the emulator far off 59.7 fps.
+const MAX_PACE_ADJUST: f64 = 0.05;
+
+/// How the visualizer spreads its bars between the lowest and highest frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpectrumScale {
+    /// Each bar a fixed ratio above the last, like a piano keyboard.
+    #[default]
+    Log,
+    /// Each bar a fixed number of hertz above the last, leaving most bars to the treble.
+    Linear,
+}
+
+impl SpectrumScale {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Log => "log",
+            Self::Linear => "linear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "log" => Some(Self::Log),
+            "linear" => Some(Self::Linear),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Log => Self::Linear,
+            Self::Linear => Self::Log,
+        }
+    }
+
+    /// Center frequency of bar `index` out of `bands`, from `min` up to `max`.
+    fn band_frequency(self, index: usize, bands: usize, min: f32, max: f32) -> f32 {
+        let t = if bands == 1 {
+            0.0
+        } else {
+            index as f32 / (bands - 1) as f32
+        };
+        match self {
+            Self::Log => min * (max / min).max(1.0).powf(t),
+            Self::Linear => min + (max - min).max(0.0) * t,
+        }
+    }
+}
 
 pub struct AudioOutput {
     stream: Option<OutputStream>,
@@ -27,6 +78,8 @@ pub struct AudioOutput {
     max_buffer_frames: usize,
     samples: Arc<Mutex<VecDeque<[i16; 2]>>>,
     visualizer_samples: Arc<Mutex<VecDeque<i16>>>,
+    underruns: Arc<AtomicU64>,
+    consumed: Arc<AtomicU64>,
 }
 
 impl AudioOutput {
@@ -43,6 +96,8 @@ impl AudioOutput {
             max_buffer_frames: max_buffer_frames.max(MIN_BUFFER_FRAMES),
             samples: Arc::new(Mutex::new(VecDeque::new())),
             visualizer_samples: Arc::new(Mutex::new(VecDeque::new())),
+            underruns: Arc::new(AtomicU64::new(0)),
+            consumed: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -67,7 +122,12 @@ impl AudioOutput {
         let (stream, stream_handle) = OutputStream::try_default().ok().unwrap();
         let sink = Sink::try_new(&stream_handle).unwrap();
         sink.set_volume(DEFAULT_VOLUME);
-        sink.append(RingSource::new(self.samples.clone(), sample_rate));
+        sink.append(RingSource::new(
+            self.samples.clone(),
+            self.underruns.clone(),
+            self.consumed.clone(),
+            sample_rate,
+        ));
         sink.play();
 
         self.sample_rate = sample_rate;
@@ -76,9 +136,12 @@ impl AudioOutput {
         self.max_buffer_frames = buffer_frames_for_ms(sample_rate, MAX_BUFFER_MS)
             .max(self.target_buffer_frames * 2)
             .max(MIN_BUFFER_FRAMES);
+        // Anything the APU holds past the sink's budget would only be dropped here later.
+        emulator.apu_set_max_queue(self.max_buffer_frames);
 
         self.samples.lock().unwrap().clear();
         self.visualizer_samples.lock().unwrap().clear();
+        self.underruns.store(0, Ordering::Relaxed);
         self.stream = Some(stream);
         self.stream_handle = Some(stream_handle);
         *self.sink.lock().unwrap() = Some(sink);
@@ -129,15 +192,7 @@ impl AudioOutput {
         }
     }
 
-    pub fn visualizer_bars(&self, bands: usize) -> Vec<f32> {
-        if bands == 0 {
-            return Vec::new();
-        }
-        let sample_rate = self.sample_rate as f32;
-        if sample_rate <= 0.0 {
-            return vec![0.0; bands];
-        }
-
+    pub fn visualizer_bars(&self, bands: usize, scale: SpectrumScale) -> Vec<f32> {
         let samples: Vec<f32> = {
             let viz = self.visualizer_samples.lock().unwrap();
             if viz.len() < VISUALIZER_SAMPLE_WINDOW {
@@ -145,35 +200,7 @@ impl AudioOutput {
             }
             viz.iter().map(|&s| s as f32 / 32768.0).collect()
         };
-
-        let windowed: Vec<f32> = samples
-            .iter()
-            .enumerate()
-            .map(|(i, s)| {
-                let t = i as f32 / (samples.len().saturating_sub(1) as f32);
-                let w = 0.5 - 0.5 * (2.0 * PI * t).cos();
-                s * w
-            })
-            .collect();
-
-        let min_freq = VISUALIZER_MIN_FREQ;
-        let max_freq = VISUALIZER_MAX_FREQ.min(sample_rate * 0.45);
-        let mut bars = Vec::with_capacity(bands);
-        let span = (max_freq / min_freq).max(1.0);
-        for i in 0..bands {
-            let t = if bands == 1 {
-                0.0
-            } else {
-                i as f32 / (bands - 1) as f32
-            };
-            let freq = min_freq * span.powf(t);
-            let power = goertzel(&windowed, freq, sample_rate);
-            let amp = (power.sqrt() * 8.0 / windowed.len() as f32)
-                .min(1.0)
-                .powf(0.6);
-            bars.push(amp);
-        }
-        bars
+        spectrum_bars(&samples, self.sample_rate as f32, bands, scale)
     }
 
     pub fn stop(&mut self) {
@@ -185,13 +212,45 @@ impl AudioOutput {
         self.stream_handle = None;
     }
 
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+        self.visualizer_samples.lock().unwrap().clear();
+    }
+
+    /// Frames the output had to fill with silence since the last call.
+    pub fn underrun_count(&self) -> u64 {
+        self.underruns.swap(0, Ordering::Relaxed)
+    }
+
+    /// Stereo frames the device has pulled, silence included, since the output was created.
+    pub fn consumed_frames(&self) -> u64 {
+        self.consumed.load(Ordering::Relaxed)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
     pub fn is_playing(&self) -> bool {
         self.sink
             .lock()
             .unwrap()
             .as_ref()
-            .map(|s| !s.empty())
-            .unwrap_or(false)
+            .is_some_and(|s| is_audible(!s.empty(), s.is_paused()))
+    }
+
+    /// Holds playback where it is, keeping queued samples for [`AudioOutput::resume`]
+    /// instead of letting the sink drain them into an underrun.
+    pub fn pause(&self) {
+        if let Some(sink) = self.sink.lock().unwrap().as_ref() {
+            sink.pause();
+        }
+    }
+
+    pub fn resume(&self) {
+        if let Some(sink) = self.sink.lock().unwrap().as_ref() {
+            sink.play();
+        }
     }
 
     pub fn set_volume(&self, volume: f32) {
@@ -203,6 +262,8 @@ impl AudioOutput {
 
 struct RingSource {
     samples: Arc<Mutex<VecDeque<[i16; 2]>>>,
+    underruns: Arc<AtomicU64>,
+    consumed: Arc<AtomicU64>,
     sample_rate: u32,
     last_frame: [i16; 2],
     pending_frame: Option<[i16; 2]>,
@@ -210,9 +271,16 @@ struct RingSource {
 }
 
 impl RingSource {
-    fn new(samples: Arc<Mutex<VecDeque<[i16; 2]>>>, sample_rate: u32) -> Self {
+    fn new(
+        samples: Arc<Mutex<VecDeque<[i16; 2]>>>,
+        underruns: Arc<AtomicU64>,
+        consumed: Arc<AtomicU64>,
+        sample_rate: u32,
+    ) -> Self {
         Self {
             samples,
+            underruns,
+            consumed,
             sample_rate,
             last_frame: [0, 0],
             pending_frame: None,
@@ -237,6 +305,7 @@ impl Iterator for RingSource {
             return Some(sample);
         }
 
+        self.consumed.fetch_add(1, Ordering::Relaxed);
         let frame = self.samples.lock().unwrap().pop_front();
         match frame {
             Some(frame) => {
@@ -246,6 +315,7 @@ impl Iterator for RingSource {
                 Some(frame[0])
             }
             None => {
+                self.underruns.fetch_add(1, Ordering::Relaxed);
                 let frame = self.last_frame;
                 self.pending_frame = Some(frame);
                 self.pending_index = 1;
@@ -279,26 +349,65 @@ impl Default for AudioOutput {
     }
 }
 
-fn goertzel(samples: &[f32], freq: f32, sample_rate: f32) -> f32 {
-    if samples.is_empty() || freq <= 0.0 || sample_rate <= 0.0 {
-        return 0.0;
-    }
-    let n = samples.len() as f32;
-    let k = (0.5 + (n * freq / sample_rate)).floor();
-    let w = 2.0 * PI * k / n;
-    let cosine = w.cos();
-    let sine = w.sin();
-    let coeff = 2.0 * cosine;
-    let mut q1 = 0.0;
-    let mut q2 = 0.0;
-    for &sample in samples {
-        let q0 = coeff * q1 - q2 + sample;
-        q2 = q1;
-        q1 = q0;
-    }
-    let real = q1 - q2 * cosine;
-    let imag = q2 * sine;
-    real * real + imag * imag
+fn is_audible(has_source: bool, paused: bool) -> bool {
+    has_source && !paused
+}
+
+/// Levels from 0 to 1 for `bands` frequencies across the visualizer range, spaced as
+/// `scale` says.
+fn spectrum_bars(
+    samples: &[f32],
+    sample_rate: f32,
+    bands: usize,
+    scale: SpectrumScale,
+) -> Vec<f32> {
+    if bands == 0 {
+        return Vec::new();
+    }
+    if sample_rate <= 0.0 {
+        return vec![0.0; bands];
+    }
+
+    let windowed: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let t = i as f32 / (samples.len().saturating_sub(1) as f32);
+            let w = 0.5 - 0.5 * (2.0 * PI * t).cos();
+            s * w
+        })
+        .collect();
+
+    let min_freq = VISUALIZER_MIN_FREQ;
+    let max_freq = VISUALIZER_MAX_FREQ.min(sample_rate * 0.45);
+    let mut bars = Vec::with_capacity(bands);
+    for i in 0..bands {
+        let freq = scale.band_frequency(i, bands, min_freq, max_freq);
+        let power = goertzel(&windowed, freq, sample_rate);
+        let amp = (power.sqrt() * 8.0 / windowed.len() as f32)
+            .min(1.0)
+            .powf(0.6);
+        bars.push(amp);
+    }
+    bars
+}
+
+/// Wall time one emulated frame should take for the emulator to produce samples exactly
+/// as fast as the device drained `consumed` frames over `elapsed`. A device running
+/// slower than `sample_rate` stretches the frame, a faster one shrinks it.
+pub fn paced_frame_interval(
+    nominal: Duration,
+    sample_rate: u32,
+    consumed: u64,
+    elapsed: Duration,
+) -> Duration {
+    if sample_rate == 0 || consumed == 0 || elapsed.is_zero() {
+        return nominal;
+    }
+    let device_rate = consumed as f64 / elapsed.as_secs_f64();
+    let scale =
+        (sample_rate as f64 / device_rate).clamp(1.0 - MAX_PACE_ADJUST, 1.0 + MAX_PACE_ADJUST);
+    nominal.mul_f64(scale)
 }
 
 fn buffer_frames_for_ms(sample_rate: u32, ms: u32) -> usize {
@@ -307,3 +416,97 @@ fn buffer_frames_for_ms(sample_rate: u32, ms: u32) -> usize {
     }
     ((sample_rate as u64 * ms as u64) / 1_000) as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{RingSource, SpectrumScale, is_audible, paced_frame_interval, spectrum_bars};
+
+    #[test]
+    fn paused_output_is_not_playing() {
+        assert!(is_audible(true, false));
+        assert!(!is_audible(true, true));
+        assert!(!is_audible(false, false));
+    }
+
+    #[test]
+    fn spectrum_has_one_level_per_requested_bar() {
+        let tone: Vec<f32> = (0..2048)
+            .map(|i| (i as f32 * 2.0 * std::f32::consts::PI * 440.0 / 48_000.0).sin())
+            .collect();
+        for bands in [1, 2, 8, 32, 160] {
+            let bars = spectrum_bars(&tone, 48_000.0, bands, SpectrumScale::Log);
+            assert_eq!(bars.len(), bands);
+            assert!(bars.iter().all(|level| (0.0..=1.0).contains(level)));
+        }
+        assert!(spectrum_bars(&tone, 48_000.0, 0, SpectrumScale::Log).is_empty());
+        assert_eq!(
+            spectrum_bars(&tone, 0.0, 4, SpectrumScale::Linear),
+            vec![0.0; 4]
+        );
+    }
+
+    #[test]
+    fn band_frequencies_follow_the_scale() {
+        let centers = |scale: SpectrumScale| -> Vec<f32> {
+            (0..5)
+                .map(|i| scale.band_frequency(i, 5, 100.0, 1_600.0))
+                .collect()
+        };
+
+        let linear = centers(SpectrumScale::Linear);
+        assert_eq!(linear, [100.0, 475.0, 850.0, 1_225.0, 1_600.0]);
+
+        let log = centers(SpectrumScale::Log);
+        for pair in log.windows(2) {
+            assert!((pair[1] / pair[0] - 2.0).abs() < 1e-4, "{log:?}");
+        }
+        assert_eq!(
+            SpectrumScale::from_name("Linear"),
+            Some(SpectrumScale::Linear)
+        );
+        assert_eq!(SpectrumScale::Log.next(), SpectrumScale::Linear);
+    }
+
+    #[test]
+    fn ring_source_counts_underruns() {
+        let samples = Arc::new(Mutex::new(VecDeque::from([[1, 2], [3, 4]])));
+        let underruns = Arc::new(AtomicU64::new(0));
+        let consumed = Arc::new(AtomicU64::new(0));
+        let mut source = RingSource::new(samples, underruns.clone(), consumed.clone(), 48_000);
+
+        let queued: Vec<i16> = source.by_ref().take(4).collect();
+        assert_eq!(queued, [1, 2, 3, 4]);
+        assert_eq!(underruns.load(Ordering::Relaxed), 0);
+
+        let silence: Vec<i16> = source.by_ref().take(4).collect();
+        assert_eq!(silence, [3, 4, 3, 4]);
+        assert_eq!(underruns.load(Ordering::Relaxed), 2);
+        assert_eq!(consumed.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn frame_pacing_follows_the_device_clock() {
+        let nominal = Duration::from_micros(16_742);
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            paced_frame_interval(nominal, 48_000, 48_000, second),
+            nominal
+        );
+        // A device draining 1% slow should slow the emulator by 1% too.
+        let slow = paced_frame_interval(nominal, 48_000, 47_520, second);
+        assert!((slow.as_secs_f64() / nominal.as_secs_f64() - 1.0 / 0.99).abs() < 1e-6);
+        let fast = paced_frame_interval(nominal, 48_000, 48_480, second);
+        assert!(fast < nominal);
+        // A stalled device is clamped instead of freezing the emulator.
+        assert_eq!(
+            paced_frame_interval(nominal, 48_000, 100, second),
+            nominal.mul_f64(1.05)
+        );
+        assert_eq!(paced_frame_interval(nominal, 48_000, 0, second), nominal);
+    }
+}
@@ -1,11 +1,52 @@
 use crate::application::app;
 use crate::domain::{
-    Cartridge, CartridgeType, CgbFlag, Destination, Licensee, RamSize, RomHeader, RomSize, SgbFlag,
+    Cartridge, CartridgeType, CgbFlag, Destination, DumpSize, Emulator, FrameError, Licensee,
+    MbcError, Movie, MovieError, MovieFrame, RamSize, RomHeader, RomSize, SgbFlag,
     compute_global_checksum, compute_header_checksum, nintendo_logo_matches,
 };
 use crate::infrastructure::rom_loader::RomLoadError;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+#[derive(Debug)]
+pub enum HeadlessError {
+    Load(MbcError),
+    Frame(FrameError),
+    Io(std::io::Error),
+    Movie(MovieError),
+}
+
+/// Where `--record-inputs` writes, or `--replay-inputs` reads, a headless run's input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputMovie {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl From<MbcError> for HeadlessError {
+    fn from(err: MbcError) -> Self {
+        Self::Load(err)
+    }
+}
+
+impl From<FrameError> for HeadlessError {
+    fn from(err: FrameError) -> Self {
+        Self::Frame(err)
+    }
+}
+
+impl From<std::io::Error> for HeadlessError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<MovieError> for HeadlessError {
+    fn from(err: MovieError) -> Self {
+        Self::Movie(err)
+    }
+}
+
 pub fn run() {
     let mut args = std::env::args();
     let program = args.next().unwrap_or_else(|| "craterboy".to_string());
@@ -22,6 +63,16 @@ pub fn run() {
             "-v" | "--verbose" => {
                 verbose = true;
             }
+            "--mappers" => {
+                print_mappers();
+                return;
+            }
+            "--apu-selftest" => {
+                if !run_apu_selftest() {
+                    std::process::exit(1);
+                }
+                return;
+            }
             "--save-root" => {
                 let Some(root) = args.next() else {
                     print_usage(&program);
@@ -45,13 +96,13 @@ pub fn run() {
 
     let path = match path {
         Some(path) => path,
-        None => match app::load_auto_resume_path() {
-            Ok(Some((resume_path, resume_root))) => {
+        None => match app::load_resume_plan() {
+            Ok(Some(plan)) => {
                 if save_root.is_none() {
-                    save_root = resume_root;
+                    save_root = plan.save_root;
                 }
-                println!("Auto-resume: {}", resume_path.display());
-                resume_path
+                println!("Auto-resume: {}", plan.rom_path.display());
+                plan.rom_path
             }
             Ok(None) => {
                 print_usage(&program);
@@ -79,6 +130,143 @@ pub fn run() {
     }
 }
 
+/// Loads the ROM at `path` and runs it headless for `frames` frames, for scripts and CI.
+/// A replayed movie runs for its own length unless `frames` is given.
+pub fn run_rom(path: &Path, frames: Option<u32>, movie: Option<InputMovie>) {
+    let cartridge = match app::load_rom(path) {
+        Ok(cartridge) => cartridge,
+        Err(err) => {
+            report_load_error(path, err);
+            std::process::exit(1);
+        }
+    };
+    let out = &mut std::io::stdout().lock();
+    let result = match movie {
+        None => run_headless(cartridge, frames.unwrap_or(0), out),
+        Some(InputMovie::Record(movie_path)) => {
+            let frames = frames.unwrap_or(0);
+            // Nothing presses buttons in a headless run, so the movie records them released.
+            let input = |_| MovieFrame::RELEASED;
+            run_headless_record(cartridge, frames, input, out).map(|(hash, movie)| {
+                if let Err(err) = app::save_movie(&movie_path, &movie) {
+                    eprintln!(
+                        "Failed to write movie '{}': {:?}",
+                        movie_path.display(),
+                        err
+                    );
+                    std::process::exit(1);
+                }
+                hash
+            })
+        }
+        Some(InputMovie::Replay(movie_path)) => {
+            let movie = match app::load_movie(&movie_path) {
+                Ok(movie) => movie,
+                Err(err) => {
+                    eprintln!("Failed to read movie '{}': {:?}", movie_path.display(), err);
+                    std::process::exit(1);
+                }
+            };
+            let frames = frames.unwrap_or(movie.len() as u32);
+            run_headless_replay(cartridge, &movie, frames, out)
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to run ROM '{}': {:?}", path.display(), err);
+        std::process::exit(1);
+    }
+}
+
+/// Runs `cartridge` without a display for `frames` frames, then prints the final CPU state
+/// and a hash of the last frame. Returns the hash.
+pub fn run_headless(
+    cartridge: Cartridge,
+    frames: u32,
+    out: &mut impl Write,
+) -> Result<u64, HeadlessError> {
+    run_headless_until(cartridge, frames, |_| false, out)
+}
+
+/// Like [`run_headless`], but stops early once `done` returns true after a frame.
+pub fn run_headless_until(
+    cartridge: Cartridge,
+    frames: u32,
+    done: impl FnMut(&Emulator) -> bool,
+    out: &mut impl Write,
+) -> Result<u64, HeadlessError> {
+    run_frames(cartridge, frames, |_, _| {}, done, out)
+}
+
+/// Like [`run_headless`], pressing what `input` returns for each frame and returning
+/// the input seen on each frame as a movie.
+pub fn run_headless_record(
+    cartridge: Cartridge,
+    frames: u32,
+    mut input: impl FnMut(u32) -> MovieFrame,
+    out: &mut impl Write,
+) -> Result<(u64, Movie), HeadlessError> {
+    let mut movie = Movie::new(&cartridge);
+    let hash = run_frames(
+        cartridge,
+        frames,
+        |emulator, frame| {
+            emulator.apply_movie_frame(input(frame));
+            movie.push(emulator.movie_frame());
+        },
+        |_| false,
+        out,
+    )?;
+    Ok((hash, movie))
+}
+
+/// Like [`run_headless`], driving the joypad from `movie`, which must match the ROM.
+pub fn run_headless_replay(
+    cartridge: Cartridge,
+    movie: &Movie,
+    frames: u32,
+    out: &mut impl Write,
+) -> Result<u64, HeadlessError> {
+    movie.check_cartridge(&cartridge)?;
+    run_frames(
+        cartridge,
+        frames,
+        |emulator, frame| emulator.apply_movie_frame(movie.frame(frame as usize)),
+        |_| false,
+        out,
+    )
+}
+
+fn run_frames(
+    cartridge: Cartridge,
+    frames: u32,
+    mut before_frame: impl FnMut(&mut Emulator, u32),
+    mut done: impl FnMut(&Emulator) -> bool,
+    out: &mut impl Write,
+) -> Result<u64, HeadlessError> {
+    let mut emulator = Emulator::new();
+    emulator.load_cartridge(cartridge)?;
+
+    let mut frames_run: u32 = 0;
+    let mut cycles: u64 = 0;
+    while frames_run < frames {
+        before_frame(&mut emulator, frames_run);
+        cycles += u64::from(emulator.step_frame()?);
+        frames_run += 1;
+        if done(&emulator) {
+            break;
+        }
+    }
+
+    let hash = fnv1a64(emulator.framebuffer().present());
+    writeln!(out, "Frames: {}", frames_run)?;
+    writeln!(out, "Cycles: {}", cycles)?;
+    if let Some(state) = emulator.cpu_state() {
+        writeln!(out, "CPU: {}", state)?;
+    }
+    writeln!(out, "Frame Hash (fnv1a64): {:016X}", hash)?;
+    Ok(hash)
+}
+
 fn print_report(path: &Path, cartridge: &Cartridge, verbose: bool) {
     println!("ROM: {}", path.display());
     println!(
@@ -95,6 +283,32 @@ fn print_report(path: &Path, cartridge: &Cartridge, verbose: bool) {
     }
 }
 
+/// Checks the APU's pulse timing against a reference tone. Returns whether it passed.
+fn run_apu_selftest() -> bool {
+    let report = Emulator::apu_selftest();
+    println!(
+        "APU Pulse Pitch: {} (expected {:.1} Hz, measured {:.1} Hz)",
+        if report.passed() { "OK" } else { "FAIL" },
+        report.expected_hz,
+        report.measured_hz
+    );
+    report.passed()
+}
+
+fn print_mappers() {
+    let (supported, unsupported): (Vec<_>, Vec<_>) = CartridgeType::KNOWN
+        .into_iter()
+        .partition(|cartridge_type| cartridge_type.is_supported());
+    println!("Supported:");
+    for cartridge_type in supported {
+        println!("- {}", cartridge_type_label(cartridge_type));
+    }
+    println!("Unsupported:");
+    for cartridge_type in unsupported {
+        println!("- {}", cartridge_type_label(cartridge_type));
+    }
+}
+
 fn print_header(header: &RomHeader) {
     println!("Title: {}", header.title);
     println!("CGB: {}", cgb_flag_label(header.cgb_flag));
@@ -103,6 +317,14 @@ fn print_header(header: &RomHeader) {
         "Cartridge Type: {}",
         cartridge_type_label(header.cartridge_type)
     );
+    println!(
+        "Mapper Supported: {}",
+        if header.cartridge_type.is_supported() {
+            "Yes"
+        } else {
+            "No"
+        }
+    );
     println!("ROM Size: {}", rom_size_label(header.rom_size));
     println!("RAM Size: {}", ram_size_label(header.ram_size));
     println!("Destination: {}", destination_label(header.destination));
@@ -197,29 +419,44 @@ fn print_checks(cartridge: &Cartridge) {
         }
     }
 
-    let expected_size = cartridge.header.rom_size.bytes();
-    match expected_size {
-        Some(expected) => {
-            if expected == cartridge.bytes.len() {
-                println!(
-                    "ROM Size Check: OK (expected {} bytes, file has {} bytes)",
-                    expected,
-                    cartridge.bytes.len()
-                );
-            } else {
-                println!(
-                    "ROM Size Check: FAIL (expected {} bytes, file has {} bytes)",
-                    expected,
-                    cartridge.bytes.len()
-                );
-                warnings.push(format!(
-                    "ROM size mismatch: header expects {} bytes, file has {} bytes",
-                    expected,
-                    cartridge.bytes.len()
-                ));
-            }
+    match cartridge.header.rom_size.check_dump(&cartridge.bytes) {
+        DumpSize::Exact => {
+            println!(
+                "ROM Size Check: OK ({} bytes, as the header declares)",
+                cartridge.bytes.len()
+            );
         }
-        None => {
+        DumpSize::Trimmed { expected, actual } => {
+            println!(
+                "ROM Size Check: Trimmed (expected {} bytes, file has {} bytes)",
+                expected, actual
+            );
+            warnings.push(format!(
+                "ROM appears trimmed: header expects {} bytes, file has {} bytes",
+                expected, actual
+            ));
+        }
+        DumpSize::Overdumped {
+            expected,
+            actual,
+            padding,
+        } => {
+            let extra = match padding {
+                Some(fill) => format!("0x{:02X} padding", fill),
+                None => "data".to_string(),
+            };
+            println!(
+                "ROM Size Check: Overdumped (expected {} bytes, file has {} bytes; extra bytes are {})",
+                expected, actual, extra
+            );
+            warnings.push(format!(
+                "ROM appears overdumped: header expects {} bytes, file has {} extra bytes of {}",
+                expected,
+                actual - expected,
+                extra
+            ));
+        }
+        DumpSize::Unknown => {
             println!(
                 "ROM Size Check: Unknown (code 0x{:02X})",
                 cartridge.header.rom_size.code()
@@ -245,6 +482,10 @@ fn print_checks(cartridge: &Cartridge) {
         ));
     }
 
+    if let Some(warning) = cartridge.header.compatibility_warning() {
+        warnings.push(warning.to_string());
+    }
+
     if !warnings.is_empty() {
         println!("Warnings:");
         for warning in warnings {
@@ -293,8 +534,8 @@ fn licensee_label(licensee: &Licensee) -> String {
 
 fn print_usage(program: &str) {
     eprintln!(
-        "Usage: {} [--verbose] [--save-root <path>] <rom-path>",
-        program
+        "Usage: {} [--verbose] [--save-root <path>] <rom-path>\n       {} --frames <n> [--record-inputs <file.gbm>] <rom-path>\n       {} [--frames <n>] --replay-inputs <file.gbm> <rom-path>\n       {} --mappers\n       {} --apu-selftest",
+        program, program, program, program, program
     );
 }
 
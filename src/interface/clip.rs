@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::path::Path;
+
+use gif::{Encoder, EncodingError, Frame, Repeat};
+
+use crate::domain::{FRAME_HEIGHT, FRAME_INTERVAL_NS, FRAME_SIZE, FRAME_WIDTH};
+
+/// GIF delays are whole centiseconds and most viewers slow anything under 2 down to 10,
+/// so clips play at 50 fps and drop the emulated frames that fall in between.
+const CLIP_FRAME_DELAY_CS: u16 = 2;
+const CLIP_FRAME_NS: u64 = CLIP_FRAME_DELAY_CS as u64 * 10_000_000;
+/// Ten seconds of clip, about 35 MB of frames.
+pub const MAX_CLIP_FRAMES: usize = 500;
+const QUANTIZE_SPEED: i32 = 10;
+
+/// Emulator frames kept for an animated GIF, timed to play back at game speed.
+#[derive(Debug, Clone)]
+pub struct ClipRecorder {
+    frames: Vec<Vec<u8>>,
+    /// Emulated frames offered so far, kept or not.
+    offered: u64,
+}
+
+impl ClipRecorder {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            offered: 0,
+        }
+    }
+
+    /// Offers one emulated RGB frame. Returns false once the clip is full.
+    pub fn push(&mut self, rgb: &[u8]) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        let slot = self.offered * FRAME_INTERVAL_NS / CLIP_FRAME_NS;
+        self.offered += 1;
+        if slot >= self.frames.len() as u64 && rgb.len() == FRAME_SIZE {
+            self.frames.push(rgb.to_vec());
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frames.len() >= MAX_CLIP_FRAMES
+    }
+
+    /// Writes a looping GIF, quantizing each frame to its own palette.
+    pub fn encode<W: Write>(&self, out: W) -> Result<(), EncodingError> {
+        let mut encoder = Encoder::new(out, FRAME_WIDTH as u16, FRAME_HEIGHT as u16, &[])?;
+        encoder.set_repeat(Repeat::Infinite)?;
+        for rgb in &self.frames {
+            let mut frame =
+                Frame::from_rgb_speed(FRAME_WIDTH as u16, FRAME_HEIGHT as u16, rgb, QUANTIZE_SPEED);
+            frame.delay = CLIP_FRAME_DELAY_CS;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), EncodingError> {
+        let file = std::fs::File::create(path)?;
+        self.encode(std::io::BufWriter::new(file))
+    }
+}
+
+impl Default for ClipRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CLIP_FRAME_DELAY_CS, ClipRecorder};
+    use crate::domain::{FRAME_HEIGHT, FRAME_SIZE, FRAME_WIDTH};
+
+    #[test]
+    fn clip_encodes_frames_at_game_speed() {
+        let mut clip = ClipRecorder::new();
+        for shade in 0..6u8 {
+            assert!(clip.push(&vec![shade * 40; FRAME_SIZE]));
+        }
+        // Six frames at 59.7 fps span five 20 ms GIF frames.
+        assert_eq!(clip.len(), 5);
+
+        let mut bytes = Vec::new();
+        clip.encode(&mut bytes).expect("encode");
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes.as_slice()).expect("decode");
+        assert_eq!(
+            (decoder.width() as usize, decoder.height() as usize),
+            (FRAME_WIDTH, FRAME_HEIGHT)
+        );
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().expect("frame") {
+            assert_eq!(frame.delay, CLIP_FRAME_DELAY_CS);
+            assert_eq!(frame.buffer.len(), FRAME_WIDTH * FRAME_HEIGHT * 4);
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+    }
+}
@@ -1,6 +1,9 @@
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use ab_glyph::{Font, FontArc, PxScale, ScaleFont, point};
 
@@ -8,7 +11,7 @@ use rfd::FileDialog;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::window::{Fullscreen, WindowBuilder};
 
 const EFFECT_SMOOTHING_STRENGTH: f32 = 0.2;
@@ -16,30 +19,71 @@ const EFFECT_OUTLINE_STRENGTH: f32 = 0.8;
 
 use crate::application::app;
 use crate::domain::{
-    Cartridge, Emulator, FRAME_HEIGHT, FRAME_INTERVAL_NS, FRAME_SIZE, FRAME_WIDTH,
+    Autofire, Button, Cartridge, ChannelId, ClipMode, DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP,
+    Dpad, Emulator, FRAME_CYCLES, FRAME_HEIGHT, FRAME_INTERVAL_NS, FRAME_SIZE, FRAME_WIDTH,
+    FrameError, Framebuffer, LAYER_BG, LAYER_SPRITES, LAYER_WINDOW, Movie, OpcodePolicy,
+    RamBankSelection, RomHeader, SampleDepth, SocdMode, SpriteInfo, TILE_MAP_SIZE, TILE_MAP_WIDTH,
+    dmg_lcd_filter, rgb_to_rgba,
 };
+use crate::infrastructure::config::GameConfig;
+use crate::infrastructure::persistence::SaveManager;
 use crate::infrastructure::rom_loader::RomLoadError;
+use crate::interface::cli::InputMovie;
+use crate::interface::clip::ClipRecorder;
 use crate::interface::menu::{MenuAction, MenuOverlay};
 
 #[cfg(feature = "audio")]
-use crate::interface::audio::AudioOutput;
+use crate::interface::audio::{AudioOutput, SpectrumScale, paced_frame_interval};
 
 #[cfg(feature = "gamepad")]
-use gilrs::{Gamepad, Gilrs};
+use gilrs::{EventType, Gamepad, GamepadId, Gilrs};
 
 const FRAME_WIDTH_U32: u32 = FRAME_WIDTH as u32;
+#[cfg(feature = "gamepad")]
+const DEFAULT_STICK_DEADZONE: f32 = 0.15;
+/// Shaped stick deflection that presses a direction, and the lower one that releases it.
+#[cfg(feature = "gamepad")]
+const STICK_PRESS: f32 = 0.25;
+#[cfg(feature = "gamepad")]
+const STICK_RELEASE: f32 = 0.1;
+const TILE_MAP_WIDTH_U32: u32 = TILE_MAP_WIDTH as u32;
 const VISUALIZER_HEIGHT: usize = 32;
 const DISPLAY_HEIGHT: usize = FRAME_HEIGHT + VISUALIZER_HEIGHT;
 const DISPLAY_HEIGHT_U32: u32 = DISPLAY_HEIGHT as u32;
-const VISUALIZER_BARS: usize = 8;
+const DEFAULT_VISUALIZER_BARS: usize = 8;
 const VISUALIZER_BG: [u8; 3] = [0x06, 0x08, 0x0B];
 const VISUALIZER_GREEN: [u8; 3] = [0x24, 0xD1, 0x4C];
 const VISUALIZER_YELLOW: [u8; 3] = [0xF2, 0xC9, 0x4C];
 const VISUALIZER_RED: [u8; 3] = [0xE8, 0x4B, 0x4B];
+const VISUALIZER_PEAK: [u8; 3] = [0xF4, 0xF6, 0xF8];
+/// Share of its height a peak marker keeps each update; the bars themselves keep 0.88.
+const PEAK_DECAY: f32 = 0.97;
 const TILE_SIZE: usize = 8;
 const TILE_BYTES: usize = 16;
 const TILE_DATA_OFFSET: usize = 0x0000;
 const DEFAULT_PALETTE_INDEX: usize = 0;
+const OVERLAY_FPS: u32 = 0x01;
+const OVERLAY_FRAME: u32 = 0x02;
+const OVERLAY_TARGET: u32 = 0x04;
+const OVERLAY_PALETTE: u32 = 0x08;
+const OVERLAY_SHADER: u32 = 0x10;
+const OVERLAY_OTHER: u32 = 0x20;
+const OVERLAY_ALL: u32 =
+    OVERLAY_FPS | OVERLAY_FRAME | OVERLAY_TARGET | OVERLAY_PALETTE | OVERLAY_SHADER | OVERLAY_OTHER;
+/// Metric sets cycled with Shift+F1: everything, FPS only, then frame timing.
+const OVERLAY_PRESETS: [u32; 3] = [
+    OVERLAY_ALL,
+    OVERLAY_FPS,
+    OVERLAY_FPS | OVERLAY_FRAME | OVERLAY_TARGET,
+];
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+/// Limits for the `pixel_aspect` setting, pixel width over pixel height.
+const MIN_PIXEL_ASPECT: f32 = 0.5;
+const MAX_PIXEL_ASPECT: f32 = 2.0;
+const MAX_FRAME_SKIP: u32 = 9;
+/// How long battery RAM must sit unchanged before it's written out mid-session.
+const BATTERY_FLUSH_DELAY: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Clone, Copy)]
 struct PaletteDefinition {
@@ -86,7 +130,321 @@ const PALETTES: [PaletteDefinition; 4] = [
     },
 ];
 
-#[derive(Debug, Clone, Copy)]
+/// Colors for the ROM tile preview shown before a game runs, kept apart from the game
+/// palettes so the preview never passes for gameplay.
+const SPLASH_PALETTE: PaletteDefinition = PaletteDefinition {
+    name: "Splash",
+    colors: [
+        [0xF0, 0xE8, 0xF8],
+        [0xB0, 0x98, 0xD0],
+        [0x60, 0x48, 0x88],
+        [0x20, 0x14, 0x38],
+    ],
+};
+
+/// The `splash_palette` setting: `Splash` or any game palette by name.
+fn splash_palette_by_name(name: Option<&str>) -> [[u8; 3]; 4] {
+    name.and_then(palette_index_by_name)
+        .map_or(SPLASH_PALETTE.colors, |index| PALETTES[index].colors)
+}
+
+/// `index` moved by `delta` through a list of `len` entries, wrapping at both ends.
+fn cycled_index(index: usize, delta: isize, len: usize) -> usize {
+    (index as isize + delta).rem_euclid(len as isize) as usize
+}
+
+fn palette_index_by_name(name: &str) -> Option<usize> {
+    PALETTES
+        .iter()
+        .position(|palette| palette.name.eq_ignore_ascii_case(name))
+}
+
+/// How Escape shows the menu: each press flips it, or it stays up only while held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuMode {
+    Toggle,
+    Hold,
+}
+
+impl MenuMode {
+    fn next(self) -> Self {
+        match self {
+            Self::Toggle => Self::Hold,
+            Self::Hold => Self::Toggle,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Toggle, Self::Hold]
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Toggle => "Toggle",
+            Self::Hold => "Hold",
+        }
+    }
+
+    /// Menu visibility after Escape is pressed or released.
+    fn visibility_after(self, visible: bool, pressed: bool) -> bool {
+        match self {
+            Self::Toggle if pressed => !visible,
+            Self::Toggle => visible,
+            Self::Hold => pressed,
+        }
+    }
+}
+
+fn load_menu_mode() -> MenuMode {
+    match app::menu_mode_preference() {
+        Ok(name) => name
+            .as_deref()
+            .and_then(MenuMode::from_name)
+            .unwrap_or(MenuMode::Toggle),
+        Err(err) => {
+            eprintln!("Failed to load menu mode setting: {:?}", err);
+            MenuMode::Toggle
+        }
+    }
+}
+
+/// What a ROM loaded from the menu waits for before the game sees any input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum LoadGate {
+    #[default]
+    Off,
+    AnyButton,
+    Start,
+}
+
+impl LoadGate {
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Off, Self::AnyButton, Self::Start]
+            .into_iter()
+            .find(|gate| gate.name().eq_ignore_ascii_case(name))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::AnyButton => "Any",
+            Self::Start => "Start",
+        }
+    }
+
+    /// Whether newly pressed `lines` open the gate.
+    fn opened_by(self, lines: u8) -> bool {
+        match self {
+            Self::Off => true,
+            Self::AnyButton => lines != 0,
+            Self::Start => lines & LINE_START != 0,
+        }
+    }
+}
+
+fn load_gate_setting() -> LoadGate {
+    match app::load_gate_preference() {
+        Ok(Some(name)) => LoadGate::from_name(&name).unwrap_or_else(|| {
+            eprintln!("Ignoring unknown load gate '{}'", name);
+            LoadGate::Off
+        }),
+        Ok(None) => LoadGate::Off,
+        Err(err) => {
+            eprintln!("Failed to load load gate setting: {:?}", err);
+            LoadGate::Off
+        }
+    }
+}
+
+/// Debug view that replaces the screen with a whole 256x256 tile map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TileMapView {
+    Background,
+    Window,
+}
+
+impl TileMapView {
+    /// Off, then the background map, then the window map.
+    fn after(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(Self::Background),
+            Some(Self::Background) => Some(Self::Window),
+            Some(Self::Window) => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Background => "BG",
+            Self::Window => "Window",
+        }
+    }
+}
+
+/// What a screenshot captures: the emulator frame alone, or the display as shown with
+/// the overlay, sprite boxes and visualizer drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screenshot {
+    Clean,
+    Full,
+}
+
+impl Screenshot {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Clean => "Clean",
+            Self::Full => "Full",
+        }
+    }
+}
+
+/// RGBA pixels, width and height for `kind`: `frame` is the emulator's RGB frame and
+/// `padded` the composited upload buffer.
+fn capture_screenshot(
+    kind: Screenshot,
+    frame: &[u8],
+    padded: &[u8],
+    bytes_per_row: u32,
+) -> (Vec<u8>, u32, u32) {
+    match kind {
+        Screenshot::Clean => {
+            let mut rgba = vec![0; frame.len() / 3 * 4];
+            rgb_to_rgba(frame, &mut rgba);
+            (rgba, FRAME_WIDTH_U32, FRAME_HEIGHT as u32)
+        }
+        Screenshot::Full => {
+            let rgba = unpad_rows(padded, bytes_per_row, FRAME_WIDTH_U32, DISPLAY_HEIGHT_U32);
+            (rgba, FRAME_WIDTH_U32, DISPLAY_HEIGHT_U32)
+        }
+    }
+}
+
+/// Row stride wgpu accepts for copying `width` RGBA pixels to or from a buffer.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Packs `height` RGBA rows spaced `bytes_per_row` apart into tight `width * 4` rows.
+fn unpad_rows(padded: &[u8], bytes_per_row: u32, width: u32, height: u32) -> Vec<u8> {
+    let row = width as usize * 4;
+    padded
+        .chunks(bytes_per_row as usize)
+        .take(height as usize)
+        .flat_map(|line| &line[..row])
+        .copied()
+        .collect()
+}
+
+/// `<rom name>-<unix ms>.<extension>` in the working directory.
+fn capture_path(rom_path: Option<&Path>, extension: &str) -> PathBuf {
+    let stem = rom_path
+        .and_then(Path::file_stem)
+        .map_or_else(|| "craterboy".into(), |stem| stem.to_string_lossy());
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join(format!("{stem}-{stamp}.{extension}"))
+}
+
+fn write_png(path: &Path, rgba: &[u8], width: u32, height: u32) -> Result<(), png::EncodingError> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)
+}
+
+/// How frames reach the screen: `Fifo` waits for vsync, `Mailbox` replaces a queued
+/// frame without tearing, and `Immediate` shows frames at once and may tear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl PresentMode {
+    const ALL: [Self; 3] = [Self::Fifo, Self::Mailbox, Self::Immediate];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    fn from_wgpu(mode: wgpu::PresentMode) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.wgpu() == mode)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fifo => "Fifo",
+            Self::Mailbox => "Mailbox",
+            Self::Immediate => "Immediate",
+        }
+    }
+
+    fn wgpu(self) -> wgpu::PresentMode {
+        match self {
+            Self::Fifo => wgpu::PresentMode::Fifo,
+            Self::Mailbox => wgpu::PresentMode::Mailbox,
+            Self::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Fifo => Self::Mailbox,
+            Self::Mailbox => Self::Immediate,
+            Self::Immediate => Self::Fifo,
+        }
+    }
+}
+
+/// The requested mode if the surface supports it, else Fifo, which every surface must
+/// support. With no request, the surface's preferred mode.
+fn select_present_mode(
+    requested: Option<PresentMode>,
+    available: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    let fallback = available
+        .first()
+        .copied()
+        .unwrap_or(wgpu::PresentMode::Fifo);
+    match requested {
+        Some(mode) if available.contains(&mode.wgpu()) => mode.wgpu(),
+        Some(_) if available.contains(&wgpu::PresentMode::Fifo) => wgpu::PresentMode::Fifo,
+        _ => fallback,
+    }
+}
+
+/// The `--present-mode` choice, else the saved preference.
+fn load_present_mode(requested: Option<String>) -> Option<PresentMode> {
+    let name = match requested {
+        Some(name) => name,
+        None => match app::present_mode_preference() {
+            Ok(name) => name?,
+            Err(err) => {
+                eprintln!("Failed to load present mode setting: {:?}", err);
+                return None;
+            }
+        },
+    };
+    let mode = PresentMode::from_name(&name);
+    if mode.is_none() {
+        eprintln!("Ignoring unknown present mode '{}'", name);
+    }
+    mode
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ShaderEffect {
     Nearest,
     Smooth,
@@ -104,6 +462,12 @@ impl ShaderEffect {
         }
     }
 
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Nearest, Self::Smooth, Self::Toon, Self::SmoothToon]
+            .into_iter()
+            .find(|effect| effect.name().eq_ignore_ascii_case(name))
+    }
+
     fn name(self) -> &'static str {
         match self {
             Self::Nearest => "Nearest",
@@ -123,6 +487,66 @@ impl ShaderEffect {
     }
 }
 
+/// The settings the in-game controls change, as a config sets them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GameSettings {
+    palette_index: usize,
+    effect: ShaderEffect,
+    speed: f32,
+    colorize: bool,
+    socd: SocdMode,
+    autofire: Autofire,
+}
+
+impl GameSettings {
+    fn from_config(config: &GameConfig) -> Self {
+        let mut autofire = Autofire::new();
+        if let Some(label) = config.autofire.as_deref()
+            && !autofire.set_label(label)
+        {
+            eprintln!("Ignoring autofire setting '{}'", label);
+        }
+        if let Some(rate) = config.autofire_rate {
+            autofire.rate = rate.max(1);
+        }
+        Self {
+            palette_index: config
+                .palette
+                .as_deref()
+                .and_then(palette_index_by_name)
+                .unwrap_or(DEFAULT_PALETTE_INDEX),
+            effect: config
+                .shader
+                .as_deref()
+                .and_then(ShaderEffect::from_name)
+                .unwrap_or(ShaderEffect::Nearest),
+            speed: config.speed.unwrap_or(1.0).clamp(MIN_SPEED, MAX_SPEED),
+            colorize: config.colorize.unwrap_or(false),
+            socd: config
+                .socd
+                .as_deref()
+                .and_then(SocdMode::from_name)
+                .unwrap_or_default(),
+            autofire,
+        }
+    }
+
+    /// Sets each of these fields in `game` where it differs from `defaults`, and clears
+    /// it where it matches.
+    fn write_overrides(&self, defaults: &GameSettings, game: &mut GameConfig) {
+        game.palette = (self.palette_index != defaults.palette_index)
+            .then(|| PALETTES[self.palette_index].name.to_string());
+        game.shader = (self.effect != defaults.effect).then(|| self.effect.name().to_string());
+        game.speed = (self.speed != defaults.speed).then_some(self.speed);
+        game.colorize = (self.colorize != defaults.colorize).then_some(self.colorize);
+        game.socd = (self.socd != defaults.socd).then(|| self.socd.name().to_string());
+        game.autofire = (self.autofire.label() != defaults.autofire.label())
+            .then(|| self.autofire.label().to_string());
+        game.autofire_rate =
+            (self.autofire.rate != defaults.autofire.rate).then_some(self.autofire.rate);
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 struct EffectUniform {
@@ -159,12 +583,37 @@ impl EffectUniform {
     }
 }
 
-pub fn run(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
-    pollster::block_on(run_async(rom_path, boot_rom_path));
+/// Opens the window. With `threaded`, emulation runs on its own thread and the window
+/// shows the newest frame it has produced. `movie` records the session's input, written
+/// on exit, or replays a recording in place of the keyboard.
+pub fn run(
+    rom_path: Option<PathBuf>,
+    boot_rom_path: Option<PathBuf>,
+    boot_timing: bool,
+    threaded: bool,
+    movie: Option<InputMovie>,
+    present_mode: Option<String>,
+) {
+    pollster::block_on(run_async(
+        rom_path,
+        boot_rom_path,
+        boot_timing,
+        threaded,
+        movie,
+        present_mode,
+    ));
 }
 
-async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
-    let (cartridge, loaded_path) = load_rom_cartridge(rom_path.clone());
+async fn run_async(
+    rom_path: Option<PathBuf>,
+    boot_rom_path: Option<PathBuf>,
+    boot_timing: bool,
+    threaded: bool,
+    movie: Option<InputMovie>,
+    present_mode: Option<String>,
+) {
+    let (cartridge, loaded_path, resume_state) = load_rom_cartridge(rom_path.clone());
+    let movie = movie.and_then(|movie| open_movie(movie, cartridge.as_ref()));
     let rom_bytes = cartridge.as_ref().map(|cart| cart.bytes.clone());
     let boot_rom = load_boot_rom(boot_rom_path);
     let event_loop = EventLoop::new().expect("event loop");
@@ -196,34 +645,64 @@ async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
         loaded_path.or(rom_path),
     )
     .await;
-    let frame_interval = Duration::from_nanos(FRAME_INTERVAL_NS);
-    let target_ms = frame_interval.as_secs_f64() * 1000.0;
+    if let Some(path) = resume_state {
+        state.restore_resume_state(&path);
+    }
+    if let Some(mode) = load_present_mode(present_mode) {
+        state.set_present_mode(mode);
+    }
+    if boot_timing {
+        state.enable_boot_timing();
+    }
+    if threaded && movie.is_some() {
+        eprintln!("--threaded is ignored while recording or replaying inputs");
+    } else if threaded {
+        state.start_emulation_thread();
+    }
+    state.movie = movie;
     let mut next_frame = Instant::now();
     let mut fps_last = Instant::now();
     let mut fps_frames: u32 = 0;
+    let mut display_frames: u32 = 0;
     let mut frame_time_last = Instant::now();
     state.set_overlay_metric("FPS", "0.0");
     state.set_overlay_metric("Frame", "0.0 ms");
-    state.set_overlay_metric("Target", format!("{:.3} ms", target_ms));
+    state.set_overlay_metric(
+        "Target",
+        format!("{:.3} ms", state.frame_interval().as_secs_f64() * 1000.0),
+    );
     state.set_overlay_metric("Palette", PALETTES[state.palette_index].name);
     state.set_overlay_metric("Shader", state.effect.name());
+    state.report_compatibility();
 
     let _ = event_loop.run(move |event, elwt| match event {
         Event::WindowEvent { event, window_id } if window_id == target_window_id => match event {
             WindowEvent::CloseRequested => {
                 #[cfg(feature = "audio")]
                 state.audio.stop();
+                state.finish_movie();
+                state.finish_clip();
+                state.wait_for_clip_writers();
+                state.save_battery();
+                state.save_resume_state();
                 elwt.exit();
             }
             WindowEvent::Resized(size) => state.resize(size),
+            WindowEvent::ModifiersChanged(modifiers) => state.modifiers = modifiers.state(),
             WindowEvent::KeyboardInput { event, .. } => {
                 if let PhysicalKey::Code(code) = event.physical_key {
                     let pressed = event.state == ElementState::Pressed;
                     if pressed && !event.repeat && code == KeyCode::F11 {
-                        toggle_borderless_fullscreen(&window);
+                        if state.modifiers.shift_key() {
+                            state.cycle_present_mode();
+                        } else {
+                            toggle_borderless_fullscreen(&window);
+                        }
                     }
-                    if pressed && !event.repeat && code == KeyCode::Escape {
-                        state.toggle_menu();
+                    if code == KeyCode::Escape {
+                        if !event.repeat {
+                            state.handle_menu_key(pressed);
+                        }
                         window.request_redraw();
                         return;
                     }
@@ -271,16 +750,24 @@ async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
                 if state.quit_requested {
                     #[cfg(feature = "audio")]
                     state.audio.stop();
+                    state.finish_movie();
+                    state.finish_clip();
+                    state.wait_for_clip_writers();
+                    state.save_battery();
+                    state.save_resume_state();
                     elwt.exit();
                     return;
                 }
                 state.update_frame();
-                match state.render() {
-                    Ok(()) => {}
-                    Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
-                    Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
-                    Err(wgpu::SurfaceError::Outdated) => {}
-                    Err(wgpu::SurfaceError::Timeout) => {}
+                if state.take_render_slot() {
+                    match state.render() {
+                        Ok(()) => {}
+                        Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
+                        Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+                        Err(wgpu::SurfaceError::Outdated) => {}
+                        Err(wgpu::SurfaceError::Timeout) => {}
+                    }
+                    display_frames = display_frames.saturating_add(1);
                 }
                 fps_frames = fps_frames.saturating_add(1);
 
@@ -296,7 +783,18 @@ async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
                 if elapsed >= Duration::from_secs(1) {
                     let fps = fps_frames as f64 / elapsed.as_secs_f64();
                     state.set_overlay_metric("FPS", format!("{:.1}", fps));
+                    let display_fps = display_frames as f64 / elapsed.as_secs_f64();
+                    state.set_overlay_metric("Display", format!("{:.1}", display_fps));
+                    #[cfg(feature = "audio")]
+                    state.set_overlay_metric("Underruns", state.audio.underrun_count().to_string());
+                    #[cfg(feature = "audio")]
+                    state.update_audio_clock(now);
+                    state.flush_battery(now);
+                    if let Some(dropped) = state.take_dropped_frames() {
+                        state.set_overlay_metric("Dropped", dropped.to_string());
+                    }
                     fps_frames = 0;
+                    display_frames = 0;
                     fps_last = now;
                 }
             }
@@ -304,6 +802,7 @@ async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
         },
         Event::AboutToWait => {
             let now = Instant::now();
+            let frame_interval = state.frame_interval();
             if now >= next_frame {
                 while next_frame <= now {
                     next_frame += frame_interval;
@@ -316,38 +815,208 @@ async fn run_async(rom_path: Option<PathBuf>, boot_rom_path: Option<PathBuf>) {
     });
 }
 
-fn load_rom_cartridge(path: Option<PathBuf>) -> (Option<Cartridge>, Option<PathBuf>) {
+/// A one-slot frame channel. Publishing replaces any frame the consumer hasn't taken yet,
+/// so a slow reader skips stale frames instead of falling behind.
+#[derive(Debug)]
+struct LatestFrame<T> {
+    slot: Mutex<Option<T>>,
+    dropped: AtomicU64,
+}
+
+impl<T> LatestFrame<T> {
+    fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn publish(&self, frame: T) {
+        if self.slot.lock().unwrap().replace(frame).is_some() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn take(&self) -> Option<T> {
+        self.slot.lock().unwrap().take()
+    }
+
+    /// Frames overwritten before they were taken, since the last call.
+    fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Runs `step_frame` on a worker thread at the target frame rate, publishing each
+/// completed frame. The window thread still applies input and settings through the
+/// shared emulator's lock.
+struct EmulationThread {
+    frames: Arc<LatestFrame<Vec<u8>>>,
+    running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    interval_ns: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulationThread {
+    fn spawn(emulator: Arc<Mutex<Emulator>>, interval: Duration) -> Self {
+        let frames = Arc::new(LatestFrame::new());
+        let running = Arc::new(AtomicBool::new(true));
+        let paused = Arc::new(AtomicBool::new(false));
+        let interval_ns = Arc::new(AtomicU64::new(interval.as_nanos() as u64));
+        let handle = {
+            let frames = Arc::clone(&frames);
+            let running = Arc::clone(&running);
+            let paused = Arc::clone(&paused);
+            let interval_ns = Arc::clone(&interval_ns);
+            thread::spawn(move || {
+                let mut next_frame = Instant::now();
+                while running.load(Ordering::Relaxed) {
+                    if !paused.load(Ordering::Relaxed) {
+                        let mut emulator = emulator.lock().unwrap();
+                        // A failed step leaves its error in `frame_error` for the window
+                        // thread to report, and the worker idles until a reload or reset
+                        // clears it.
+                        if emulator.has_bus()
+                            && emulator.frame_error().is_none()
+                            && emulator.step_frame().is_ok()
+                            && let Some(frame) = emulator.take_new_frame()
+                        {
+                            frames.publish(frame.to_vec());
+                        }
+                    }
+                    next_frame += Duration::from_nanos(interval_ns.load(Ordering::Relaxed));
+                    let now = Instant::now();
+                    if next_frame > now {
+                        thread::sleep(next_frame - now);
+                    } else {
+                        next_frame = now;
+                    }
+                }
+            })
+        };
+        Self {
+            frames,
+            running,
+            paused,
+            interval_ns,
+            handle: Some(handle),
+        }
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    fn set_interval(&self, interval: Duration) {
+        self.interval_ns
+            .store(interval.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for EmulationThread {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The ROM to start with, its path, and the resume state to restore onto it, if any.
+fn load_rom_cartridge(
+    path: Option<PathBuf>,
+) -> (Option<Cartridge>, Option<PathBuf>, Option<PathBuf>) {
     let mut path = path;
+    let mut state_path = None;
     if path.is_none()
-        && let Ok(Some((resume_path, _))) = app::load_auto_resume_path()
+        && let Ok(Some(plan)) = app::load_resume_plan()
     {
-        path = Some(resume_path);
+        path = Some(plan.rom_path);
+        state_path = plan.state_path;
     }
 
     let Some(path) = path else {
-        return (None, None);
+        return (None, None, None);
     };
 
     match app::load_rom(&path) {
-        Ok(cartridge) => (Some(cartridge), Some(path)),
+        Ok(cartridge) => (Some(cartridge), Some(path), state_path),
         Err(err) => {
             report_rom_error(&path, err);
-            (None, None)
+            (None, None, None)
         }
     }
 }
 
-fn load_boot_rom(path: Option<PathBuf>) -> Option<Vec<u8>> {
-    let Some(path) = path else {
+/// Input being recorded to, or replayed from, a movie file.
+enum MovieSession {
+    Recording { movie: Movie, path: PathBuf },
+    Replaying { movie: Movie, frame: usize },
+}
+
+fn open_movie(movie: InputMovie, cartridge: Option<&Cartridge>) -> Option<MovieSession> {
+    let Some(cartridge) = cartridge else {
+        eprintln!("Input movies need a ROM to be loaded at startup");
         return None;
     };
-    match std::fs::read(&path) {
-        Ok(bytes) => Some(bytes),
+    match movie {
+        InputMovie::Record(path) => Some(MovieSession::Recording {
+            movie: Movie::new(cartridge),
+            path,
+        }),
+        InputMovie::Replay(path) => {
+            let movie = match app::load_movie(&path) {
+                Ok(movie) => movie,
+                Err(err) => {
+                    eprintln!("Failed to read movie '{}': {:?}", path.display(), err);
+                    return None;
+                }
+            };
+            if let Err(err) = movie.check_cartridge(cartridge) {
+                eprintln!(
+                    "Movie '{}' does not match the ROM: {:?}",
+                    path.display(),
+                    err
+                );
+                return None;
+            }
+            Some(MovieSession::Replaying { movie, frame: 0 })
+        }
+    }
+}
+
+/// Reads the boot ROM passed with `--boot-rom`, remembering it as the default, or falls
+/// back to the default from the global config.
+fn load_boot_rom(path: Option<PathBuf>) -> Option<Vec<u8>> {
+    let explicit = path.is_some();
+    let path = match path {
+        Some(path) => path,
+        None => match app::default_boot_rom_path() {
+            Ok(path) => path?,
+            Err(err) => {
+                eprintln!("Failed to load boot ROM setting: {:?}", err);
+                return None;
+            }
+        },
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
         Err(err) => {
             eprintln!("Failed to read boot ROM '{}': {}", path.display(), err);
-            None
+            return None;
         }
+    };
+    if !app::is_valid_boot_rom_size(bytes.len()) {
+        eprintln!(
+            "Boot ROM '{}' is {} bytes; expected 256 (DMG) or 2048 (CGB)",
+            path.display(),
+            bytes.len()
+        );
+    } else if explicit && let Err(err) = app::save_default_boot_rom_path(&path) {
+        eprintln!("Failed to save boot ROM setting: {:?}", err);
     }
+    Some(bytes)
 }
 
 fn report_rom_error(path: &PathBuf, err: RomLoadError) {
@@ -399,129 +1068,457 @@ struct State {
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
     size: PhysicalSize<u32>,
     texture: wgpu::Texture,
     _texture_view: wgpu::TextureView,
-    _texture_sampler: wgpu::Sampler,
+    texture_sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     pipeline: wgpu::RenderPipeline,
+    /// The blit pipeline drawing into an RGBA texture, for native-size captures.
+    capture_pipeline: wgpu::RenderPipeline,
+    map_texture: wgpu::Texture,
+    _map_texture_view: wgpu::TextureView,
+    map_bind_group: wgpu::BindGroup,
     menu_texture: wgpu::Texture,
     menu_texture_view: wgpu::TextureView,
     menu_texture_sampler: wgpu::Sampler,
     menu_bind_group: wgpu::BindGroup,
     menu_bind_group_layout: wgpu::BindGroupLayout,
     menu_pipeline: wgpu::RenderPipeline,
-    emulator: Emulator,
+    emulator: Arc<Mutex<Emulator>>,
+    worker: Option<EmulationThread>,
+    save_manager: SaveManager,
+    movie: Option<MovieSession>,
+    /// The frame being shown, copied from the emulator or the worker each redraw.
+    display: Vec<u8>,
     frame_index: u8,
     rom_bytes: Option<Vec<u8>>,
     rom_frame_ready: bool,
+    splash_palette: [[u8; 3]; 4],
+    pixel_aspect: f32,
     rom_path: Option<PathBuf>,
     boot_rom: Option<Vec<u8>>,
     input: InputState,
+    key_bindings: KeyBindings,
+    modifiers: ModifiersState,
     overlay: Overlay,
     palette_index: usize,
     effect: ShaderEffect,
+    colorize: bool,
+    lcd_filter: bool,
+    sprite_boxes: bool,
+    tile_map_view: Option<TileMapView>,
+    /// Taken on the next render, once the frame has been composited.
+    pending_screenshot: Option<Screenshot>,
+    /// Read the frame back from a native-size GPU texture on the next render.
+    pending_native_capture: bool,
+    /// The GIF clip being recorded, if any.
+    clip: Option<ClipRecorder>,
+    /// Threads still encoding finished clips.
+    clip_writers: Vec<JoinHandle<()>>,
+    socd: SocdMode,
+    autofire: Autofire,
+    input_frame: u64,
+    speed: f32,
+    frame_skip: u32,
+    redraw_index: u64,
+    rom_header: Option<RomHeader>,
+    game_config: GameConfig,
     effect_uniform: wgpu::Buffer,
     visualizer_levels: Vec<f32>,
+    /// The highest recent level of each bar, falling slower than the bar itself.
+    peak_levels: Vec<f32>,
     menu: MenuOverlay,
     menu_visible: bool,
+    menu_mode: MenuMode,
+    load_gate: LoadGate,
     menu_cursor: Option<slint::LogicalPosition>,
     quit_requested: bool,
+    /// The frame error last shown, so a stopped CPU is reported once per stop.
+    reported_frame_error: Option<FrameError>,
     #[cfg(feature = "audio")]
     audio: AudioOutput,
+    #[cfg(feature = "audio")]
+    audio_sync: bool,
+    #[cfg(feature = "audio")]
+    visualizer_scale: SpectrumScale,
+    /// Where the current audio clock window started: wall time and consumed frames.
+    #[cfg(feature = "audio")]
+    audio_clock_start: (Instant, u64),
+    /// Frames the audio device consumed over the last full window, and how long it took.
+    #[cfg(feature = "audio")]
+    audio_clock: Option<(u64, Duration)>,
     #[cfg(feature = "gamepad")]
     gilrs: Option<Gilrs>,
+    /// The gamepad picked with Ctrl+J; `None` follows whichever was used last.
+    #[cfg(feature = "gamepad")]
+    pinned_gamepad: Option<GamepadId>,
+    #[cfg(feature = "gamepad")]
+    last_active_gamepad: Option<GamepadId>,
+    /// Left stick response, X then Y.
+    #[cfg(feature = "gamepad")]
+    stick: [StickAxis; 2],
 }
 
+/// Held lines packed into one byte: buttons in the low nibble, d-pad in the high.
+const LINE_A: u8 = 0x01;
+const LINE_B: u8 = 0x02;
+const LINE_SELECT: u8 = 0x04;
+const LINE_START: u8 = 0x08;
+const DPAD_LINES: [u8; 4] = [DPAD_RIGHT, DPAD_LEFT, DPAD_UP, DPAD_DOWN];
+
+/// Buttons held on one input device.
 #[derive(Debug, Default, Clone, Copy)]
-struct InputState {
-    right: bool,
-    left: bool,
-    up: bool,
-    down: bool,
+struct HeldInput {
+    dpad: Dpad,
     a: bool,
     b: bool,
     select: bool,
     start: bool,
 }
 
-impl InputState {
-    fn handle_key(&mut self, code: KeyCode, pressed: bool) {
-        match code {
-            KeyCode::ArrowRight => self.right = pressed,
-            KeyCode::ArrowLeft => self.left = pressed,
-            KeyCode::ArrowUp => self.up = pressed,
-            KeyCode::ArrowDown => self.down = pressed,
-            KeyCode::KeyZ => self.a = pressed,
-            KeyCode::KeyX => self.b = pressed,
-            KeyCode::Enter => self.start = pressed,
-            KeyCode::ShiftLeft | KeyCode::ShiftRight => self.select = pressed,
-            _ => {}
+impl HeldInput {
+    fn lines(&self) -> u8 {
+        let dpad = !self.dpad.joyp_nibble(SocdMode::Passthrough) & 0x0F;
+        let mut lines = dpad << 4;
+        for (held, line) in [
+            (self.a, LINE_A),
+            (self.b, LINE_B),
+            (self.select, LINE_SELECT),
+            (self.start, LINE_START),
+        ] {
+            if held {
+                lines |= line;
+            }
         }
+        lines
     }
 
-    fn apply(&self, emulator: &mut Emulator) {
-        let mut dpad = 0x0F;
-        if self.right {
-            dpad &= !0x01;
-        }
-        if self.left {
-            dpad &= !0x02;
-        }
-        if self.up {
-            dpad &= !0x04;
-        }
-        if self.down {
-            dpad &= !0x08;
+    /// This input with everything outside `lines` released.
+    fn masked(&self, lines: u8) -> HeldInput {
+        let mut masked = *self;
+        for direction in DPAD_LINES {
+            if lines & (direction << 4) == 0 {
+                masked.dpad.set(direction, false);
+            }
         }
+        masked.a &= lines & LINE_A != 0;
+        masked.b &= lines & LINE_B != 0;
+        masked.select &= lines & LINE_SELECT != 0;
+        masked.start &= lines & LINE_START != 0;
+        masked
+    }
 
-        let mut buttons = 0x0F;
-        if self.a {
-            buttons &= !0x01;
-        }
-        if self.b {
-            buttons &= !0x02;
+    #[cfg(feature = "gamepad")]
+    fn merged(&self, other: &HeldInput) -> HeldInput {
+        HeldInput {
+            dpad: self.dpad.merged(&other.dpad),
+            a: self.a || other.a,
+            b: self.b || other.b,
+            select: self.select || other.select,
+            start: self.start || other.start,
         }
-        if self.select {
-            buttons &= !0x04;
+    }
+}
+
+/// Keeps menu input out of a freshly loaded game. Lines held when the ROM loads stay
+/// ignored until released, and an armed gate swallows everything up to its opening press.
+#[derive(Debug, Default, Clone, Copy)]
+struct LoadGuard {
+    /// Lines held since before the load or the gate opened.
+    stale: u8,
+    gate: LoadGate,
+}
+
+impl LoadGuard {
+    fn new(held: u8, gate: LoadGate) -> Self {
+        Self { stale: held, gate }
+    }
+
+    /// Follows the lines now held, opening the gate on the press it waits for.
+    fn update(&mut self, held: u8) {
+        self.stale &= held;
+        if self.gate == LoadGate::Off {
+            return;
         }
-        if self.start {
-            buttons &= !0x08;
+        let fresh = held & !self.stale;
+        if self.gate.opened_by(fresh) {
+            self.gate = LoadGate::Off;
         }
+        // Presses made while waiting, the opening one included, stay out of the game too.
+        self.stale |= fresh;
+    }
 
-        emulator.set_joyp_dpad(dpad);
-        emulator.set_joyp_buttons(buttons);
+    /// The held lines the game may see.
+    fn visible(&self, held: u8) -> u8 {
+        if self.gate == LoadGate::Off {
+            held & !self.stale
+        } else {
+            0
+        }
     }
+}
 
+/// Keyboard and gamepad input, kept apart so polling one never releases the other.
+#[derive(Debug, Default, Clone, Copy)]
+struct InputState {
+    keyboard: HeldInput,
     #[cfg(feature = "gamepad")]
-    fn handle_gamepad(&mut self, gamepad: &Gamepad, deadzone: f32) {
-        // Standard Xbox/PS controller mapping
-        // D-pad: Left stick
-        let axis_x = gamepad
-            .axis_data(gilrs::Axis::LeftStickX)
-            .map(|a| a.value())
-            .unwrap_or(0.0);
-        let axis_y = gamepad
-            .axis_data(gilrs::Axis::LeftStickY)
-            .map(|a| a.value())
-            .unwrap_or(0.0);
+    gamepad: HeldInput,
+    guard: LoadGuard,
+    /// Which stick directions are held, X then Y.
+    #[cfg(feature = "gamepad")]
+    stick: [AxisLatch; 2],
+}
+
+/// How one stick axis responds: travel inside `deadzone` is ignored and the rest is
+/// rescaled to 0..1 and raised to `curve`, so curves under 1 press directions sooner.
+#[cfg(feature = "gamepad")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StickAxis {
+    deadzone: f32,
+    curve: f32,
+}
+
+#[cfg(feature = "gamepad")]
+impl StickAxis {
+    fn new(deadzone: f32, curve: f32) -> Self {
+        Self {
+            deadzone: deadzone.clamp(0.0, 0.9),
+            curve: curve.clamp(0.25, 4.0),
+        }
+    }
+
+    fn shape(self, value: f32) -> f32 {
+        let travel = ((value.abs() - self.deadzone) / (1.0 - self.deadzone)).clamp(0.0, 1.0);
+        travel.powf(self.curve).copysign(value)
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl Default for StickAxis {
+    fn default() -> Self {
+        Self::new(DEFAULT_STICK_DEADZONE, 1.0)
+    }
+}
+
+/// The two digital directions of one stick axis. A direction presses at `STICK_PRESS`
+/// but only lets go below `STICK_RELEASE`, so a stick resting near the edge can't
+/// chatter.
+#[cfg(feature = "gamepad")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct AxisLatch {
+    negative: bool,
+    positive: bool,
+}
+
+#[cfg(feature = "gamepad")]
+impl AxisLatch {
+    fn update(&mut self, shaped: f32) {
+        let held = |was: bool, amount: f32| amount >= if was { STICK_RELEASE } else { STICK_PRESS };
+        self.negative = held(self.negative, -shaped);
+        self.positive = held(self.positive, shaped);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct KeyBindings {
+    keys: Vec<(KeyCode, Button)>,
+}
 
-        self.left = axis_x < -deadzone;
-        self.right = axis_x > deadzone;
-        self.up = axis_y < -deadzone;
-        self.down = axis_y > deadzone;
+impl KeyBindings {
+    fn new() -> Self {
+        Self {
+            keys: vec![
+                (KeyCode::ArrowRight, Button::Right),
+                (KeyCode::ArrowLeft, Button::Left),
+                (KeyCode::ArrowUp, Button::Up),
+                (KeyCode::ArrowDown, Button::Down),
+                (KeyCode::KeyZ, Button::A),
+                (KeyCode::KeyX, Button::B),
+                (KeyCode::Enter, Button::Start),
+                (KeyCode::ShiftLeft, Button::Select),
+                (KeyCode::ShiftRight, Button::Select),
+            ],
+        }
+    }
+
+    fn with_overrides(overrides: &BTreeMap<String, String>) -> Self {
+        let mut bindings = Self::new();
+        for (action, key) in overrides {
+            let (Some(button), Some(code)) = (Button::from_name(action), key_code_from_name(key))
+            else {
+                eprintln!("Ignoring key binding '{} = {}'", action, key);
+                continue;
+            };
+            bindings.keys.retain(|(_, bound)| *bound != button);
+            bindings.keys.push((code, button));
+        }
+        bindings
+    }
+
+    fn button_for(&self, code: KeyCode) -> Option<Button> {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == code)
+            .map(|(_, button)| *button)
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "KeyA" => KeyCode::KeyA,
+        "KeyB" => KeyCode::KeyB,
+        "KeyC" => KeyCode::KeyC,
+        "KeyD" => KeyCode::KeyD,
+        "KeyE" => KeyCode::KeyE,
+        "KeyF" => KeyCode::KeyF,
+        "KeyG" => KeyCode::KeyG,
+        "KeyH" => KeyCode::KeyH,
+        "KeyI" => KeyCode::KeyI,
+        "KeyJ" => KeyCode::KeyJ,
+        "KeyK" => KeyCode::KeyK,
+        "KeyL" => KeyCode::KeyL,
+        "KeyM" => KeyCode::KeyM,
+        "KeyN" => KeyCode::KeyN,
+        "KeyO" => KeyCode::KeyO,
+        "KeyP" => KeyCode::KeyP,
+        "KeyQ" => KeyCode::KeyQ,
+        "KeyR" => KeyCode::KeyR,
+        "KeyS" => KeyCode::KeyS,
+        "KeyT" => KeyCode::KeyT,
+        "KeyU" => KeyCode::KeyU,
+        "KeyV" => KeyCode::KeyV,
+        "KeyW" => KeyCode::KeyW,
+        "KeyX" => KeyCode::KeyX,
+        "KeyY" => KeyCode::KeyY,
+        "KeyZ" => KeyCode::KeyZ,
+        "Digit0" => KeyCode::Digit0,
+        "Digit1" => KeyCode::Digit1,
+        "Digit2" => KeyCode::Digit2,
+        "Digit3" => KeyCode::Digit3,
+        "Digit4" => KeyCode::Digit4,
+        "Digit5" => KeyCode::Digit5,
+        "Digit6" => KeyCode::Digit6,
+        "Digit7" => KeyCode::Digit7,
+        "Digit8" => KeyCode::Digit8,
+        "Digit9" => KeyCode::Digit9,
+        "ArrowUp" => KeyCode::ArrowUp,
+        "ArrowDown" => KeyCode::ArrowDown,
+        "ArrowLeft" => KeyCode::ArrowLeft,
+        "ArrowRight" => KeyCode::ArrowRight,
+        "Enter" => KeyCode::Enter,
+        "Space" => KeyCode::Space,
+        "Backspace" => KeyCode::Backspace,
+        "Tab" => KeyCode::Tab,
+        "ShiftLeft" => KeyCode::ShiftLeft,
+        "ShiftRight" => KeyCode::ShiftRight,
+        "ControlLeft" => KeyCode::ControlLeft,
+        "ControlRight" => KeyCode::ControlRight,
+        "AltLeft" => KeyCode::AltLeft,
+        "AltRight" => KeyCode::AltRight,
+        _ => return None,
+    };
+    Some(code)
+}
+
+impl InputState {
+    fn handle_key(&mut self, bindings: &KeyBindings, code: KeyCode, pressed: bool) {
+        let Some(button) = bindings.button_for(code) else {
+            return;
+        };
+        let keyboard = &mut self.keyboard;
+        match button {
+            Button::Right => keyboard.dpad.set(DPAD_RIGHT, pressed),
+            Button::Left => keyboard.dpad.set(DPAD_LEFT, pressed),
+            Button::Up => keyboard.dpad.set(DPAD_UP, pressed),
+            Button::Down => keyboard.dpad.set(DPAD_DOWN, pressed),
+            Button::A => keyboard.a = pressed,
+            Button::B => keyboard.b = pressed,
+            Button::Select => keyboard.select = pressed,
+            Button::Start => keyboard.start = pressed,
+        }
+        self.guard.update(self.held().lines());
+    }
+
+    /// Ignores whatever is held now until released, then waits for `gate` to open.
+    fn guard_load(&mut self, gate: LoadGate) {
+        self.guard = LoadGuard::new(self.held().lines(), gate);
+    }
+
+    fn held(&self) -> HeldInput {
+        #[cfg(feature = "gamepad")]
+        return self.keyboard.merged(&self.gamepad);
+        #[cfg(not(feature = "gamepad"))]
+        self.keyboard
+    }
+
+    fn apply(&self, emulator: &mut Emulator, socd: SocdMode, autofire: &Autofire, frame: u64) {
+        let held = self.held();
+        let held = held.masked(self.guard.visible(held.lines()));
+        let dpad = held.dpad.joyp_nibble(socd);
+        let pulse = autofire.pressed_on(frame);
+
+        let mut buttons = 0x0F;
+        if held.a && (!autofire.a || pulse) {
+            buttons &= !0x01;
+        }
+        if held.b && (!autofire.b || pulse) {
+            buttons &= !0x02;
+        }
+        if held.select {
+            buttons &= !0x04;
+        }
+        if held.start {
+            buttons &= !0x08;
+        }
+
+        emulator.set_joyp_dpad(dpad);
+        emulator.set_joyp_buttons(buttons);
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn handle_gamepad(&mut self, gamepad: &Gamepad, stick: [StickAxis; 2]) {
+        // Standard Xbox/PS controller mapping
+        // D-pad: Left stick
+        let axis_x = gamepad
+            .axis_data(gilrs::Axis::LeftStickX)
+            .map(|a| a.value())
+            .unwrap_or(0.0);
+        let axis_y = gamepad
+            .axis_data(gilrs::Axis::LeftStickY)
+            .map(|a| a.value())
+            .unwrap_or(0.0);
+
+        let [x, y] = &mut self.stick;
+        x.update(stick[0].shape(axis_x));
+        y.update(stick[1].shape(axis_y));
+        let held = &mut self.gamepad;
+        held.dpad.set(DPAD_LEFT, x.negative);
+        held.dpad.set(DPAD_RIGHT, x.positive);
+        held.dpad.set(DPAD_UP, y.negative);
+        held.dpad.set(DPAD_DOWN, y.positive);
 
         // A/B face buttons
-        self.a =
+        held.a =
             gamepad.is_pressed(gilrs::Button::South) || gamepad.is_pressed(gilrs::Button::East);
-        self.b =
+        held.b =
             gamepad.is_pressed(gilrs::Button::West) || gamepad.is_pressed(gilrs::Button::North);
 
         // Start/Select
-        self.start =
+        held.start =
             gamepad.is_pressed(gilrs::Button::Start) || gamepad.is_pressed(gilrs::Button::Mode);
-        self.select = gamepad.is_pressed(gilrs::Button::Select)
+        held.select = gamepad.is_pressed(gilrs::Button::Select)
             || gamepad.is_pressed(gilrs::Button::LeftTrigger);
+        self.guard.update(self.held().lines());
     }
 }
 
@@ -666,6 +1663,40 @@ impl State {
             ],
         });
 
+        let map_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("tile_map"),
+            size: wgpu::Extent3d {
+                width: TILE_MAP_WIDTH_U32,
+                height: TILE_MAP_WIDTH_U32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let map_texture_view = map_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let map_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tile_map_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&map_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: effect_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("blit_shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shader_blit.wgsl").into()),
@@ -699,6 +1730,28 @@ impl State {
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         });
+        let capture_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("capture_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
 
         let menu_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -798,6 +1851,7 @@ impl State {
         });
 
         let mut emulator = Emulator::new();
+        let rom_header = cartridge.as_ref().map(|cart| cart.header.clone());
         if let Some(cartridge) = cartridge
             && let Err(err) = emulator.load_cartridge_with_boot_rom(cartridge, boot_rom.clone())
         {
@@ -823,44 +1877,94 @@ impl State {
         #[cfg(feature = "gamepad")]
         let gilrs = Gilrs::new().ok();
 
-        Self {
+        let mut state = Self {
             surface,
             device,
             queue,
             config,
+            present_modes: surface_caps.present_modes.clone(),
             size,
             texture,
             _texture_view: texture_view,
-            _texture_sampler: texture_sampler,
+            texture_sampler,
+            bind_group_layout,
             bind_group,
             pipeline,
+            capture_pipeline,
+            map_texture,
+            _map_texture_view: map_texture_view,
+            map_bind_group,
             menu_texture,
             menu_texture_view,
             menu_texture_sampler,
             menu_bind_group,
             menu_bind_group_layout,
             menu_pipeline,
-            emulator,
+            emulator: Arc::new(Mutex::new(emulator)),
+            worker: None,
+            save_manager: SaveManager::new(BATTERY_FLUSH_DELAY),
+            movie: None,
+            display: vec![0; FRAME_SIZE],
             frame_index: 0,
             rom_bytes,
             rom_frame_ready: false,
+            splash_palette: SPLASH_PALETTE.colors,
+            pixel_aspect: 1.0,
             rom_path,
             boot_rom,
             input: InputState::default(),
+            key_bindings: KeyBindings::new(),
+            modifiers: ModifiersState::empty(),
             overlay: Overlay::new(),
             palette_index,
             effect,
+            colorize: false,
+            lcd_filter: false,
+            sprite_boxes: false,
+            tile_map_view: None,
+            pending_screenshot: None,
+            pending_native_capture: false,
+            clip: None,
+            clip_writers: Vec::new(),
+            socd: SocdMode::default(),
+            autofire: Autofire::new(),
+            input_frame: 0,
+            speed: 1.0,
+            frame_skip: 0,
+            redraw_index: 0,
+            rom_header,
+            game_config: GameConfig::new(),
             effect_uniform: effect_buffer,
-            visualizer_levels: vec![0.0; VISUALIZER_BARS],
+            visualizer_levels: vec![0.0; DEFAULT_VISUALIZER_BARS],
+            peak_levels: vec![0.0; DEFAULT_VISUALIZER_BARS],
             menu,
             menu_visible: !has_bus,
+            menu_mode: load_menu_mode(),
+            load_gate: load_gate_setting(),
             menu_cursor: None,
             quit_requested: false,
+            reported_frame_error: None,
             #[cfg(feature = "audio")]
             audio,
+            #[cfg(feature = "audio")]
+            audio_sync: false,
+            #[cfg(feature = "audio")]
+            visualizer_scale: SpectrumScale::default(),
+            #[cfg(feature = "audio")]
+            audio_clock_start: (Instant::now(), 0),
+            #[cfg(feature = "audio")]
+            audio_clock: None,
             #[cfg(feature = "gamepad")]
             gilrs,
-        }
+            #[cfg(feature = "gamepad")]
+            pinned_gamepad: None,
+            #[cfg(feature = "gamepad")]
+            last_active_gamepad: None,
+            #[cfg(feature = "gamepad")]
+            stick: [StickAxis::default(); 2],
+        };
+        state.apply_game_config();
+        state
     }
 
     fn resize(&mut self, size: PhysicalSize<u32>) {
@@ -875,36 +1979,457 @@ impl State {
         self.menu.resize(size.width as usize, size.height as usize);
     }
 
+    /// Switches to the next present mode the surface supports and saves the choice.
+    fn cycle_present_mode(&mut self) {
+        let current = PresentMode::from_wgpu(self.config.present_mode).unwrap_or(PresentMode::Fifo);
+        let mut next = current.next();
+        while next != current && !self.present_modes.contains(&next.wgpu()) {
+            next = next.next();
+        }
+        self.set_present_mode(next);
+        if let Err(err) = app::save_present_mode_preference(next.name()) {
+            eprintln!("Failed to save present mode setting: {:?}", err);
+        }
+    }
+
+    /// Reconfigures the surface with `mode`, or Fifo if the surface doesn't support it.
+    fn set_present_mode(&mut self, mode: PresentMode) {
+        self.config.present_mode = select_present_mode(Some(mode), &self.present_modes);
+        self.surface.configure(&self.device, &self.config);
+        let active = PresentMode::from_wgpu(self.config.present_mode).unwrap_or(mode);
+        self.set_overlay_metric("Present", active.name());
+    }
+
+    fn emulator(&self) -> MutexGuard<'_, Emulator> {
+        self.emulator.lock().unwrap()
+    }
+
+    fn start_emulation_thread(&mut self) {
+        let emulator = Arc::clone(&self.emulator);
+        self.worker = Some(EmulationThread::spawn(emulator, self.frame_interval()));
+    }
+
+    fn replaying_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieSession::Replaying { .. }))
+    }
+
+    /// Saves the frame `kind` asks for as a PNG in the working directory.
+    fn save_screenshot(&mut self, kind: Screenshot, padded: &[u8], bytes_per_row: u32) {
+        let (rgba, width, height) = capture_screenshot(kind, &self.display, padded, bytes_per_row);
+        let path = capture_path(self.rom_path.as_deref(), "png");
+        match write_png(&path, &rgba, width, height) {
+            Ok(()) => {
+                println!("Saved screenshot to {}", path.display());
+                self.set_overlay_metric("Screenshot", kind.name());
+            }
+            Err(err) => eprintln!("Failed to write screenshot '{}': {:?}", path.display(), err),
+        }
+    }
+
+    /// Draws the emulator frame through the blit pipeline into an unscaled 160x144 target,
+    /// with nearest sampling and no effect, and saves what comes back, for comparing
+    /// against reference PNGs byte for byte.
+    fn save_native_capture(&mut self) {
+        let bytes_per_row = padded_bytes_per_row(FRAME_WIDTH_U32);
+        let size = wgpu::Extent3d {
+            width: FRAME_WIDTH_U32,
+            height: FRAME_HEIGHT as u32,
+            depth_or_array_layers: 1,
+        };
+        let texture_descriptor = |label, usage| wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage,
+            view_formats: &[],
+        };
+        let source = self.device.create_texture(&texture_descriptor(
+            "native_capture_source",
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        ));
+        let target = self.device.create_texture(&texture_descriptor(
+            "native_capture_target",
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        ));
+        let mut upload = vec![0u8; bytes_per_row as usize * FRAME_HEIGHT];
+        pack_rgba_rows(
+            &self.display,
+            FRAME_WIDTH,
+            bytes_per_row as usize,
+            &mut upload,
+        );
+        let layout = wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(size.height),
+        };
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &source,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &upload,
+            layout,
+            size,
+        );
+
+        let effect_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("native_capture_effect"),
+            size: std::mem::size_of::<EffectUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: true,
+        });
+        effect_buffer
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(EffectUniform::new(ShaderEffect::Nearest).as_bytes());
+        effect_buffer.unmap();
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("native_capture_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: effect_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("native_capture_readback"),
+            size: upload.len() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("native_capture_encoder"),
+            });
+        {
+            let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("native_capture_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.capture_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout,
+            },
+            size,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        // Blocks this redraw until the GPU is done, a frame or so; fine for a one-off
+        // debug capture, so the readback isn't deferred to a later redraw.
+        self.device.poll(wgpu::Maintain::Wait);
+        if let Err(err) = receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError)) {
+            eprintln!("Failed to read back native capture: {:?}", err);
+            return;
+        }
+        let rgba = unpad_rows(
+            &slice.get_mapped_range(),
+            bytes_per_row,
+            size.width,
+            size.height,
+        );
+        buffer.unmap();
+
+        let path = capture_path(self.rom_path.as_deref(), "native.png");
+        match write_png(&path, &rgba, size.width, size.height) {
+            Ok(()) => {
+                println!("Saved native capture to {}", path.display());
+                self.set_overlay_metric("Screenshot", "Native");
+            }
+            Err(err) => eprintln!("Failed to write capture '{}': {:?}", path.display(), err),
+        }
+    }
+
+    /// Drains gamepad events, noting which pad was touched last, and reads the chosen one.
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepad(&mut self) {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return;
+        };
+        while let Some(event) = gilrs.next_event() {
+            let active = match event.event {
+                EventType::ButtonPressed(..) => true,
+                EventType::AxisChanged(_, value, _) => value.abs() >= 0.5,
+                _ => false,
+            };
+            if active {
+                self.last_active_gamepad = Some(event.id);
+            }
+        }
+        let connected: Vec<GamepadId> = gilrs.gamepads().map(|(id, _)| id).collect();
+        if let Some(id) = select_gamepad(self.pinned_gamepad, self.last_active_gamepad, &connected)
+        {
+            self.input.handle_gamepad(&gilrs.gamepad(id), self.stick);
+        }
+    }
+
+    /// Pins the next connected gamepad, going back to following the last used one after
+    /// the final pad.
+    #[cfg(feature = "gamepad")]
+    fn cycle_gamepad(&mut self) {
+        let Some(gilrs) = self.gilrs.as_ref() else {
+            return;
+        };
+        let connected: Vec<GamepadId> = gilrs.gamepads().map(|(id, _)| id).collect();
+        self.pinned_gamepad = next_pinned_gamepad(self.pinned_gamepad, &connected);
+        let label = match self.pinned_gamepad {
+            Some(id) => gilrs.gamepad(id).name().to_string(),
+            None => "Auto".to_string(),
+        };
+        self.set_overlay_metric("Pad", label);
+    }
+
+    fn toggle_clip_recording(&mut self) {
+        if self.clip.is_some() {
+            self.finish_clip();
+        } else if self.emulator().has_bus() {
+            self.clip = Some(ClipRecorder::new());
+            self.set_overlay_metric("Clip", "REC");
+        }
+    }
+
+    /// Stops recording and writes the clip on a background thread, since quantizing
+    /// hundreds of frames takes far longer than a tick; called on Ctrl+G or once the
+    /// clip is full.
+    fn finish_clip(&mut self) {
+        let Some(clip) = self.clip.take() else {
+            return;
+        };
+        self.set_overlay_metric("Clip", "Off");
+        if clip.is_empty() {
+            return;
+        }
+        let path = capture_path(self.rom_path.as_deref(), "gif");
+        self.clip_writers.retain(|writer| !writer.is_finished());
+        self.clip_writers
+            .push(thread::spawn(move || match clip.save(&path) {
+                Ok(()) => println!("Saved {} frame clip to {}", clip.len(), path.display()),
+                Err(err) => eprintln!("Failed to write clip '{}': {:?}", path.display(), err),
+            }));
+    }
+
+    /// Blocks until every clip still being encoded is on disk.
+    fn wait_for_clip_writers(&mut self) {
+        for writer in self.clip_writers.drain(..) {
+            let _ = writer.join();
+        }
+    }
+
+    /// Writes out a recording in progress.
+    fn finish_movie(&mut self) {
+        if let Some(MovieSession::Recording { movie, path }) = self.movie.take()
+            && let Err(err) = app::save_movie(&path, &movie)
+        {
+            eprintln!("Failed to write movie '{}': {:?}", path.display(), err);
+        }
+    }
+
+    /// Writes the game's battery save, clock included, before it's unloaded.
+    fn save_battery(&self) {
+        if let Err(err) = app::save_battery(&self.emulator(), None) {
+            eprintln!("Failed to write battery save: {:?}", err);
+        }
+    }
+
+    /// Writes the battery save once the game has stopped touching its RAM for a moment,
+    /// so a crash doesn't lose a whole session.
+    fn flush_battery(&mut self, now: Instant) {
+        let mut emulator = self.emulator.lock().unwrap();
+        if let Err(err) = self
+            .save_manager
+            .maybe_flush_emulator_at(now, &mut emulator)
+        {
+            eprintln!("Failed to write battery save: {:?}", err);
+        }
+    }
+
+    /// Leaves the running game's state for the next launch, under `auto_resume = "state"`.
+    fn save_resume_state(&self) {
+        let Some(path) = self.rom_path.clone() else {
+            return;
+        };
+        if let Err(err) = app::save_resume_state(&self.emulator(), path) {
+            eprintln!("Failed to write resume state: {:?}", err);
+        }
+    }
+
+    fn restore_resume_state(&self, path: &Path) {
+        if let Err(err) = app::restore_resume_state(&mut self.emulator(), path) {
+            eprintln!(
+                "Failed to restore resume state '{}': {:?}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    fn take_dropped_frames(&self) -> Option<u64> {
+        self.worker
+            .as_ref()
+            .map(|worker| worker.frames.take_dropped())
+    }
+
     fn update_frame(&mut self) {
+        if let Some(worker) = &self.worker {
+            worker.set_paused(self.menu_visible);
+            worker.set_interval(self.frame_interval());
+        }
         if self.menu_visible {
             self.menu.update_timers();
         } else {
-            // Poll gamepad input
             #[cfg(feature = "gamepad")]
-            if let Some(ref gilrs) = self.gilrs
-                && let Some((_id, gamepad)) = gilrs.gamepads().next()
+            self.poll_gamepad();
+
+            let shared = Arc::clone(&self.emulator);
+            let mut emulator = shared.lock().unwrap();
+            self.input
+                .apply(&mut emulator, self.socd, &self.autofire, self.input_frame);
+            match &mut self.movie {
+                Some(MovieSession::Replaying { movie, frame }) => {
+                    emulator.apply_movie_frame(movie.frame(*frame));
+                    *frame += 1;
+                }
+                Some(MovieSession::Recording { movie, .. }) => movie.push(emulator.movie_frame()),
+                None => {}
+            }
+            // The worker steps frames on its own schedule in threaded mode.
+            if self.worker.is_some() || emulator.step_frame().is_ok() {
+                self.set_overlay_metric(
+                    "Cycles",
+                    format!("{}/{}", emulator.last_frame_cycles(), FRAME_CYCLES),
+                );
+            }
+            let (enable, flag, ime) = emulator.interrupt_state();
+            self.set_overlay_metric(
+                "IRQ",
+                format!(
+                    "IE {:02X} IF {:02X} IME {}",
+                    enable,
+                    flag,
+                    if ime { "on" } else { "off" }
+                ),
+            );
+            self.input_frame = self.input_frame.wrapping_add(1);
+            // A clip only takes frames the emulator has finished, not one per tick. The
+            // worker can't finish another while we hold the lock, so the emulator's
+            // framebuffer is the frame just taken.
+            let new_frame = match &self.worker {
+                Some(worker) => match worker.frames.take() {
+                    Some(frame) => {
+                        self.display = frame;
+                        true
+                    }
+                    None => false,
+                },
+                None => emulator.take_new_frame().is_some(),
+            };
+            if new_frame
+                && let Some(clip) = &mut self.clip
+                && !clip.push(emulator.framebuffer().present())
             {
-                self.input.handle_gamepad(&gamepad, 0.15);
+                self.finish_clip();
+            }
+            let frame_error = emulator.frame_error();
+            while let Some(event) = emulator.take_unsupported_opcode() {
+                self.set_overlay_metric("Opcode", format!("{:#04x}", event.opcode));
+                // The opcode that stopped the CPU is reported as the frame error below.
+                if frame_error == Some(FrameError::UnsupportedOpcode(event)) {
+                    continue;
+                }
+                let message = event.message();
+                eprintln!("{}", message);
+                self.menu.set_status(message);
+            }
+            if frame_error != self.reported_frame_error {
+                self.reported_frame_error = frame_error;
+                match frame_error {
+                    Some(err) => {
+                        let message = err.message();
+                        eprintln!("{}", message);
+                        self.set_overlay_metric("CPU", "Stopped");
+                        self.menu.set_status(message);
+                    }
+                    None => self.set_overlay_metric("CPU", "Running"),
+                }
             }
-
-            self.input.apply(&mut self.emulator);
-            let _ = self.emulator.step_frame();
             #[cfg(feature = "audio")]
-            self.audio.enqueue_emulator_samples(&mut self.emulator);
+            self.audio.enqueue_emulator_samples(&mut emulator);
         }
         self.apply_menu_actions();
         self.update_visualizer();
-        if self.emulator.has_bus() {
+        let (rom_bank, ram_bank, ram_bank_count) = {
+            let emulator = self.emulator();
+            (
+                emulator.current_rom_bank(),
+                emulator.current_ram_bank(),
+                emulator.ram_bank_count(),
+            )
+        };
+        if let Some(bank) = rom_bank {
+            self.set_overlay_metric("Bank", bank.to_string());
+        }
+        if let Some(selection) = ram_bank {
+            let value = match selection {
+                RamBankSelection::Bank(bank) => format!("{}/{}", bank, ram_bank_count),
+                RamBankSelection::Rtc(_) | RamBankSelection::Camera => selection.label(),
+            };
+            self.set_overlay_metric("RAM", value);
+        }
+        if self.emulator().has_bus() {
             return;
         }
         if let Some(rom) = self.rom_bytes.as_deref() {
             if !self.rom_frame_ready {
-                let palette = PALETTES[self.palette_index].colors;
-                Self::render_rom_tiles(
-                    self.emulator.framebuffer_mut().as_mut_slice(),
-                    rom,
-                    palette,
-                );
+                let palette = self.splash_palette;
+                let mut emulator = self.emulator();
+                Self::render_rom_tiles(emulator.framebuffer_mut(), rom, palette);
+                emulator.framebuffer_mut().swap();
+                drop(emulator);
                 self.rom_frame_ready = true;
             }
             return;
@@ -913,7 +2438,8 @@ impl State {
         self.frame_index = self.frame_index.wrapping_add(1);
         let width = FRAME_WIDTH;
         let height = FRAME_HEIGHT;
-        let pixels = self.emulator.framebuffer_mut().as_mut_slice();
+        let mut emulator = self.emulator();
+        let pixels = emulator.framebuffer_mut().as_mut_slice();
         for y in 0..height {
             for x in 0..width {
                 let idx = (y * width + x) * 3;
@@ -925,31 +2451,145 @@ impl State {
                 pixels[idx + 2] = b;
             }
         }
+        emulator.framebuffer_mut().swap();
     }
 
     fn handle_key(&mut self, code: KeyCode, pressed: bool, repeated: bool) {
         if pressed && !repeated && code == KeyCode::F1 {
-            self.overlay.toggle();
+            if self.modifiers.shift_key() {
+                self.overlay.cycle_metrics();
+            } else {
+                self.overlay.toggle();
+            }
         }
         if pressed && !repeated && code == KeyCode::F2 {
-            self.cycle_palette(1);
+            self.cycle_palette(if self.modifiers.shift_key() { -1 } else { 1 });
         }
         if pressed && !repeated && code == KeyCode::F3 {
             self.cycle_shader();
         }
-        self.input.handle_key(code, pressed);
-        if !self.menu_visible {
-            self.input.apply(&mut self.emulator);
+        if pressed && !repeated && code == KeyCode::F4 {
+            self.set_colorize(!self.colorize);
+        }
+        if pressed && !repeated && code == KeyCode::F5 {
+            self.lcd_filter = !self.lcd_filter;
+            self.set_overlay_metric("LCD", if self.lcd_filter { "On" } else { "Off" });
+        }
+        if pressed && !repeated && code == KeyCode::F6 {
+            self.socd = self.socd.next();
+            self.set_overlay_metric("SOCD", self.socd.name());
+        }
+        if pressed && !repeated && code == KeyCode::F7 {
+            self.autofire.cycle();
+            self.set_overlay_metric("Turbo", self.autofire.label());
+        }
+        if pressed && !repeated && code == KeyCode::F8 {
+            if self.modifiers.shift_key() {
+                self.tile_map_view = TileMapView::after(self.tile_map_view);
+                let name = self.tile_map_view.map_or("Off", TileMapView::name);
+                self.set_overlay_metric("Map", name);
+            } else {
+                self.toggle_layer(LAYER_BG, "BG");
+            }
+        }
+        if pressed && !repeated && code == KeyCode::F9 {
+            self.toggle_layer(LAYER_WINDOW, "Window");
+        }
+        if pressed && !repeated && code == KeyCode::F10 {
+            self.toggle_layer(LAYER_SPRITES, "Sprites");
+        }
+        if pressed && !repeated && code == KeyCode::F12 {
+            self.sprite_boxes = !self.sprite_boxes;
+            self.set_overlay_metric("OAM", if self.sprite_boxes { "On" } else { "Off" });
+        }
+        if pressed && !repeated && code == KeyCode::PrintScreen && self.modifiers.control_key() {
+            self.pending_native_capture = true;
+        } else if pressed && !repeated && code == KeyCode::PrintScreen {
+            // Shift takes the kind the config doesn't pick.
+            let full = self.game_config.screenshot_overlay.unwrap_or(false);
+            self.pending_screenshot = Some(if full != self.modifiers.shift_key() {
+                Screenshot::Full
+            } else {
+                Screenshot::Clean
+            });
+        }
+        if pressed && !repeated && code == KeyCode::KeyG && self.modifiers.control_key() {
+            self.toggle_clip_recording();
+            return;
+        }
+        #[cfg(feature = "audio")]
+        if pressed && !repeated && code == KeyCode::KeyV && self.modifiers.control_key() {
+            self.visualizer_scale = self.visualizer_scale.next();
+            self.set_overlay_metric("Spectrum", self.visualizer_scale.name());
+            return;
+        }
+        #[cfg(feature = "gamepad")]
+        if pressed && !repeated && code == KeyCode::KeyJ && self.modifiers.control_key() {
+            self.cycle_gamepad();
+            return;
+        }
+        if pressed && !repeated && code == KeyCode::KeyR && self.modifiers.control_key() {
+            if self.modifiers.shift_key() {
+                self.power_cycle_emulator();
+            } else {
+                self.soft_reset_emulator();
+            }
+            return;
+        }
+        self.input.handle_key(&self.key_bindings, code, pressed);
+        // A replay owns the joypad; update_frame applies its input before each frame.
+        if !self.menu_visible && !self.replaying_movie() {
+            self.input.apply(
+                &mut self.emulator(),
+                self.socd,
+                &self.autofire,
+                self.input_frame,
+            );
+        }
+    }
+
+    fn soft_reset_emulator(&mut self) {
+        self.emulator().soft_reset();
+        self.after_reset();
+    }
+
+    fn power_cycle_emulator(&mut self) {
+        self.emulator().power_cycle();
+        self.after_reset();
+    }
+
+    fn after_reset(&mut self) {
+        self.input = InputState::default();
+        #[cfg(feature = "audio")]
+        self.audio.clear();
+        if self.menu_visible && self.emulator().has_bus() {
+            self.close_menu();
         }
     }
 
     fn update_input_state(&mut self, code: KeyCode, pressed: bool) {
-        self.input.handle_key(code, pressed);
+        self.input.handle_key(&self.key_bindings, code, pressed);
+    }
+
+    /// Escape press or release. Shift+Escape switches between toggle and hold modes.
+    fn handle_menu_key(&mut self, pressed: bool) {
+        if pressed && self.modifiers.shift_key() {
+            self.menu_mode = self.menu_mode.next();
+            self.set_overlay_metric("Menu", self.menu_mode.name());
+            if let Err(err) = app::save_menu_mode_preference(self.menu_mode.name()) {
+                eprintln!("Failed to save menu mode setting: {:?}", err);
+            }
+            return;
+        }
+        if self.menu_mode.visibility_after(self.menu_visible, pressed) != self.menu_visible {
+            self.toggle_menu();
+        }
     }
 
     fn toggle_menu(&mut self) {
         self.menu_visible = !self.menu_visible;
-        self.menu.set_has_rom(self.emulator.has_bus());
+        let has_rom = self.emulator().has_bus();
+        self.menu.set_has_rom(has_rom);
         if let Some(ref path) = self.rom_path {
             self.menu.set_rom_path(path.to_string_lossy().to_string());
         }
@@ -958,6 +2598,19 @@ impl State {
         if !self.menu_visible {
             self.menu_cursor = None;
         }
+        #[cfg(feature = "audio")]
+        if self.menu_visible {
+            self.audio.pause();
+        } else {
+            self.audio.resume();
+        }
+    }
+
+    fn close_menu(&mut self) {
+        self.menu_visible = false;
+        self.menu_cursor = None;
+        #[cfg(feature = "audio")]
+        self.audio.resume();
     }
 
     fn apply_menu_actions(&mut self) {
@@ -966,11 +2619,13 @@ impl State {
             match action {
                 MenuAction::LoadRom(path) => self.handle_menu_load(path),
                 MenuAction::Resume => {
-                    if self.emulator.has_bus() {
-                        self.menu_visible = false;
-                        self.menu_cursor = None;
+                    if self.emulator().has_bus() {
+                        self.close_menu();
                     }
                 }
+                MenuAction::SoftReset => self.soft_reset_emulator(),
+                MenuAction::PowerCycle => self.power_cycle_emulator(),
+                MenuAction::SaveGameConfig => self.save_game_config(),
                 MenuAction::Quit => {
                     self.quit_requested = true;
                 }
@@ -997,26 +2652,39 @@ impl State {
             return;
         }
         let path = PathBuf::from(trimmed);
+        // Written first so reloading the same game reads its latest save.
+        self.save_battery();
         match app::load_rom(&path) {
+            Ok(cartridge) if !cartridge.header.cartridge_type.is_supported() => {
+                self.menu.set_status(format!(
+                    "Unsupported cartridge type: {}",
+                    cartridge.header.cartridge_type.description()
+                ));
+            }
             Ok(cartridge) => {
                 let bytes = cartridge.bytes.clone();
-                if let Err(err) = self
-                    .emulator
-                    .load_cartridge_with_boot_rom(cartridge, self.boot_rom.clone())
-                {
+                let header = cartridge.header.clone();
+                let loaded = self
+                    .emulator()
+                    .load_cartridge_with_boot_rom(cartridge, self.boot_rom.clone());
+                if let Err(err) = loaded {
                     self.menu.set_status(format!("Failed to init ROM: {err:?}"));
                     return;
                 }
-                self.emulator
-                    .set_palette(PALETTES[self.palette_index].colors);
                 self.rom_bytes = Some(bytes);
-                self.rom_frame_ready = false;
+                self.rom_header = Some(header);
+                self.save_manager = SaveManager::new(BATTERY_FLUSH_DELAY);
+                self.apply_game_config();
                 self.rom_path = Some(path.clone());
                 let _ = app::save_auto_resume_for(path, None);
                 self.menu.set_has_rom(true);
                 self.menu.set_status("");
-                self.menu_visible = false;
-                self.menu_cursor = None;
+                self.report_compatibility();
+                // The gamepad isn't polled under the menu, so catch up before guarding.
+                #[cfg(feature = "gamepad")]
+                self.poll_gamepad();
+                self.input.guard_load(self.load_gate);
+                self.close_menu();
             }
             Err(err) => {
                 self.menu.set_status(menu_error_message(&path, err));
@@ -1024,19 +2692,197 @@ impl State {
         }
     }
 
+    fn apply_game_config(&mut self) {
+        let config = match self.rom_header.as_ref().map(app::load_game_config) {
+            Some(Ok(config)) => config,
+            Some(Err(err)) => {
+                eprintln!("Failed to load game config: {:?}", err);
+                GameConfig::new()
+            }
+            None => GameConfig::new(),
+        };
+        let settings = GameSettings::from_config(&config);
+        self.palette_index = settings.palette_index;
+        self.emulator()
+            .set_palette(PALETTES[self.palette_index].colors);
+        self.splash_palette = splash_palette_by_name(config.splash_palette.as_deref());
+        self.rom_frame_ready = false;
+        self.effect = settings.effect;
+        self.update_effect_uniform();
+        self.speed = settings.speed;
+        self.pixel_aspect = config
+            .pixel_aspect
+            .unwrap_or(1.0)
+            .clamp(MIN_PIXEL_ASPECT, MAX_PIXEL_ASPECT);
+        self.frame_skip = config.frame_skip.unwrap_or(0).min(MAX_FRAME_SKIP);
+        // Each bar needs at least a pixel column.
+        let bars = config
+            .visualizer_bars
+            .unwrap_or(DEFAULT_VISUALIZER_BARS)
+            .clamp(1, FRAME_WIDTH);
+        if bars != self.visualizer_levels.len() {
+            self.visualizer_levels = vec![0.0; bars];
+            self.peak_levels = vec![0.0; bars];
+        }
+        self.set_overlay_metric("Skip", self.frame_skip.to_string());
+        self.set_colorize(settings.colorize);
+        self.socd = settings.socd;
+        self.set_overlay_metric("SOCD", self.socd.name());
+        self.autofire = settings.autofire;
+        self.set_overlay_metric("Turbo", self.autofire.label());
+        let opcode_policy = config
+            .opcode_policy
+            .as_deref()
+            .and_then(OpcodePolicy::from_name)
+            .unwrap_or_default();
+        self.emulator().set_opcode_policy(opcode_policy);
+        let clip_mode = config
+            .audio_clip
+            .as_deref()
+            .and_then(ClipMode::from_name)
+            .unwrap_or_default();
+        self.emulator().apu_set_clip_mode(clip_mode);
+        let sample_depth = config
+            .audio_depth
+            .as_deref()
+            .and_then(SampleDepth::from_name)
+            .unwrap_or_default();
+        self.emulator().apu_set_sample_depth(sample_depth);
+        {
+            let mut emulator = self.emulator();
+            for channel in ChannelId::ALL {
+                emulator.apu_set_channel_gain(channel, 1.0);
+            }
+            for (name, gain) in &config.channel_gain {
+                match ChannelId::from_name(name) {
+                    Some(channel) => emulator.apu_set_channel_gain(channel, *gain),
+                    None => eprintln!("Ignoring gain for unknown channel '{}'", name),
+                }
+            }
+        }
+        if config.boot_animation.unwrap_or(false) {
+            self.emulator().start_boot_animation();
+        }
+        #[cfg(feature = "audio")]
+        {
+            self.audio_sync = config.audio_sync.unwrap_or(false);
+            self.visualizer_scale = config
+                .visualizer_scale
+                .as_deref()
+                .and_then(SpectrumScale::from_name)
+                .unwrap_or_default();
+        }
+        #[cfg(feature = "gamepad")]
+        {
+            let curve = config.stick_curve.unwrap_or(1.0);
+            let deadzone = config.stick_deadzone.unwrap_or(DEFAULT_STICK_DEADZONE);
+            self.stick = [
+                StickAxis::new(config.stick_deadzone_x.unwrap_or(deadzone), curve),
+                StickAxis::new(config.stick_deadzone_y.unwrap_or(deadzone), curve),
+            ];
+        }
+        self.key_bindings = KeyBindings::with_overrides(&config.key_bindings);
+        self.game_config = config;
+        self.set_overlay_metric("Palette", PALETTES[self.palette_index].name);
+        self.set_overlay_metric("Shader", self.effect.name());
+        self.set_overlay_metric(
+            "Target",
+            format!("{:.3} ms", self.frame_interval().as_secs_f64() * 1000.0),
+        );
+    }
+
+    fn save_game_config(&mut self) {
+        let Some(ref header) = self.rom_header else {
+            self.menu.set_status("Load a ROM to save game settings.");
+            return;
+        };
+        let (global, mut game) = match app::load_config_layers(header) {
+            Ok(layers) => layers,
+            Err(err) => {
+                self.menu
+                    .set_status(format!("Failed to load game settings: {err:?}"));
+                return;
+            }
+        };
+        // Only what differs from the global settings goes in the game's file, so later
+        // global changes still reach this game.
+        let defaults = GameSettings::from_config(&global);
+        let current = GameSettings {
+            palette_index: self.palette_index,
+            effect: self.effect,
+            speed: self.speed,
+            colorize: self.colorize,
+            socd: self.socd,
+            autofire: self.autofire,
+        };
+        current.write_overrides(&defaults, &mut game);
+        match app::save_game_config(header, &game) {
+            Ok(()) => {
+                self.game_config = global.merged_with(&game);
+                self.menu.set_status("Saved settings for this game.");
+            }
+            Err(err) => {
+                self.menu
+                    .set_status(format!("Failed to save game settings: {err:?}"));
+            }
+        }
+    }
+
+    fn frame_interval(&self) -> Duration {
+        let nominal =
+            Duration::from_secs_f64(FRAME_INTERVAL_NS as f64 / 1_000_000_000.0 / self.speed as f64);
+        #[cfg(feature = "audio")]
+        if self.audio_sync
+            && let Some((consumed, elapsed)) = self.audio_clock
+        {
+            return paced_frame_interval(nominal, self.audio.sample_rate(), consumed, elapsed);
+        }
+        nominal
+    }
+
+    /// Closes the audio clock window and measures how fast the device drained samples.
+    /// Windows where the menu held playback are dropped, since the device sat idle.
+    #[cfg(feature = "audio")]
+    fn update_audio_clock(&mut self, now: Instant) {
+        let consumed = self.audio.consumed_frames();
+        let (started, start_consumed) = self.audio_clock_start;
+        if !self.menu_visible && self.audio.is_playing() {
+            self.audio_clock = Some((
+                consumed.saturating_sub(start_consumed),
+                now.duration_since(started),
+            ));
+        }
+        self.audio_clock_start = (now, consumed);
+    }
+
     fn set_overlay_metric(&mut self, label: &str, value: impl Into<String>) {
         self.overlay.set_metric(label, value);
     }
 
+    fn report_compatibility(&mut self) {
+        let Some(header) = self.rom_header.as_ref() else {
+            return;
+        };
+        match header.compatibility_warning() {
+            Some(warning) => {
+                eprintln!("{}", warning);
+                self.menu.set_status(warning);
+                self.set_overlay_metric("Compat", "CGB only");
+            }
+            None => self.set_overlay_metric("Compat", "OK"),
+        }
+    }
+
     fn update_visualizer(&mut self) {
         let target = {
             #[cfg(feature = "audio")]
             {
-                self.audio.visualizer_bars(VISUALIZER_BARS)
+                self.audio
+                    .visualizer_bars(self.visualizer_levels.len(), self.visualizer_scale)
             }
             #[cfg(not(feature = "audio"))]
             {
-                vec![0.0; VISUALIZER_BARS]
+                vec![0.0f32; self.visualizer_levels.len()]
             }
         };
         for (level, target) in self.visualizer_levels.iter_mut().zip(target.iter()) {
@@ -1047,6 +2893,7 @@ impl State {
                 *level *= 0.88;
             }
         }
+        hold_peaks(&mut self.peak_levels, &self.visualizer_levels);
     }
 
     fn update_effect_uniform(&self) {
@@ -1062,24 +2909,38 @@ impl State {
     }
 
     fn cycle_palette(&mut self, delta: isize) {
-        let len = PALETTES.len() as isize;
-        let next = (self.palette_index as isize + delta + len) % len;
-        self.palette_index = next as usize;
+        self.palette_index = cycled_index(self.palette_index, delta, PALETTES.len());
         let palette = PALETTES[self.palette_index].colors;
-        self.emulator.set_palette(palette);
+        self.emulator().set_palette(palette);
         self.rom_frame_ready = false;
         self.set_overlay_metric("Palette", PALETTES[self.palette_index].name);
     }
 
-    fn render_rom_tiles(framebuffer: &mut [u8], rom: &[u8], palette: [[u8; 3]; 4]) {
+    fn enable_boot_timing(&mut self) {
+        let mut emulator = self.emulator();
+        emulator.set_boot_timing(true);
+        if self.boot_rom.is_none() {
+            emulator.soft_reset();
+        }
+    }
+
+    fn toggle_layer(&mut self, layer: u8, name: &str) {
+        let enabled = !self.emulator().layer_enabled(layer);
+        self.emulator().set_layer_enabled(layer, enabled);
+        self.set_overlay_metric(name, if enabled { "On" } else { "Off" });
+    }
+
+    fn set_colorize(&mut self, enabled: bool) {
+        self.colorize = enabled;
+        self.emulator().set_colorization(enabled);
+        self.set_overlay_metric("Colorize", if enabled { "On" } else { "Off" });
+    }
+
+    fn render_rom_tiles(framebuffer: &mut Framebuffer, rom: &[u8], palette: [[u8; 3]; 4]) {
         let width = FRAME_WIDTH;
         let height = FRAME_HEIGHT;
-        let bg = palette[0];
-        for idx in (0..framebuffer.len()).step_by(3) {
-            framebuffer[idx] = bg[0];
-            framebuffer[idx + 1] = bg[1];
-            framebuffer[idx + 2] = bg[2];
-        }
+        framebuffer.fill(palette[0]);
+        let framebuffer = framebuffer.as_mut_slice();
 
         let tiles_per_row = width / TILE_SIZE;
         let tiles_per_col = height / TILE_SIZE;
@@ -1123,30 +2984,17 @@ impl State {
     }
 
     fn compute_viewport(&self) -> Viewport {
-        let window_w = self.size.width;
-        let window_h = self.size.height;
-        if window_w == 0 || window_h == 0 {
-            return Viewport::full(window_w, window_h);
-        }
-
-        let max_scale_w = window_w / FRAME_WIDTH_U32;
-        let max_scale_h = window_h / DISPLAY_HEIGHT_U32;
-        let scale = max_scale_w.min(max_scale_h).max(1);
-        let target_w = FRAME_WIDTH_U32 * scale;
-        let target_h = DISPLAY_HEIGHT_U32 * scale;
-        let x = window_w.saturating_sub(target_w) / 2;
-        let y = window_h.saturating_sub(target_h) / 2;
+        Viewport::fit(
+            self.size,
+            FRAME_WIDTH_U32,
+            DISPLAY_HEIGHT_U32,
+            self.pixel_aspect,
+        )
+    }
 
-        Viewport {
-            x: x as f32,
-            y: y as f32,
-            width: target_w as f32,
-            height: target_h as f32,
-            scissor_x: x,
-            scissor_y: y,
-            scissor_width: target_w,
-            scissor_height: target_h,
-        }
+    /// A `width` by `height` image with square pixels, such as the tile map view.
+    fn compute_viewport_for(&self, width: u32, height: u32) -> Viewport {
+        Viewport::fit(self.size, width, height, 1.0)
     }
 
     fn resize_menu_resources(&mut self) {
@@ -1185,13 +3033,91 @@ impl State {
         });
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let (mut padded, bytes_per_row) = prepare_framebuffer_upload(
-            self.emulator.framebuffer().as_slice(),
-            &self.visualizer_levels,
+    /// Whether this redraw should reach the GPU. Emulation runs every frame either way;
+    /// frame skip only drops the upload and draw, and never while the menu is open.
+    fn take_render_slot(&mut self) -> bool {
+        let index = self.redraw_index;
+        self.redraw_index = self.redraw_index.wrapping_add(1);
+        self.menu_visible || should_render_frame(index, self.frame_skip)
+    }
+
+    /// Picks up the newest frame: in threaded mode `update_frame` has already taken the
+    /// worker's, else this copies the emulator's own.
+    fn refresh_display(&mut self) {
+        let shared = Arc::clone(&self.emulator);
+        let emulator = shared.lock().unwrap();
+        if self.worker.is_none() || !emulator.has_bus() {
+            self.display
+                .copy_from_slice(emulator.framebuffer().present());
+        }
+    }
+
+    fn upload_tile_map(&mut self, view: TileMapView) {
+        let mut map = vec![0; TILE_MAP_SIZE];
+        let scroll = {
+            let emulator = self.emulator();
+            let rendered = match view {
+                TileMapView::Background => emulator.render_bg_map(&mut map),
+                TileMapView::Window => emulator.render_window_map(&mut map),
+            };
+            if let Err(err) = rendered {
+                eprintln!("Failed to render tile map: {:?}", err);
+                return;
+            }
+            // The window isn't scrolled, so only the background map gets a viewport.
+            emulator
+                .bg_scroll()
+                .filter(|_| view == TileMapView::Background)
+        };
+        let (padded, bytes_per_row) = prepare_tile_map_upload(&map, scroll);
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.map_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &padded,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(TILE_MAP_WIDTH_U32),
+            },
+            wgpu::Extent3d {
+                width: TILE_MAP_WIDTH_U32,
+                height: TILE_MAP_WIDTH_U32,
+                depth_or_array_layers: 1,
+            },
         );
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.refresh_display();
+        let (mut padded, bytes_per_row) = if self.lcd_filter {
+            let mut frame = self.display.clone();
+            dmg_lcd_filter(&mut frame);
+            prepare_framebuffer_upload(&frame, &self.visualizer_levels, &self.peak_levels)
+        } else {
+            prepare_framebuffer_upload(&self.display, &self.visualizer_levels, &self.peak_levels)
+        };
+        if self.sprite_boxes {
+            let sprites = self.emulator().sprite_entries();
+            draw_sprite_boxes(&mut padded, bytes_per_row, &sprites);
+        }
         self.overlay
             .draw(&mut padded, bytes_per_row, FRAME_WIDTH, FRAME_HEIGHT);
+        if self.clip.is_some() {
+            draw_rec_indicator(&mut padded, bytes_per_row);
+        }
+        if let Some(kind) = self.pending_screenshot.take() {
+            self.save_screenshot(kind, &padded, bytes_per_row);
+        }
+        if std::mem::take(&mut self.pending_native_capture) {
+            self.save_native_capture();
+        }
+        if let Some(view) = self.tile_map_view {
+            self.upload_tile_map(view);
+        }
 
         self.queue.write_texture(
             wgpu::ImageCopyTexture {
@@ -1251,7 +3177,10 @@ impl State {
             });
 
         {
-            let viewport = self.compute_viewport();
+            let viewport = match self.tile_map_view {
+                Some(_) => self.compute_viewport_for(TILE_MAP_WIDTH_U32, TILE_MAP_WIDTH_U32),
+                None => self.compute_viewport(),
+            };
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("render_pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -1286,7 +3215,11 @@ impl State {
                 viewport.scissor_height,
             );
             render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            let bind_group = match self.tile_map_view {
+                Some(_) => &self.map_bind_group,
+                None => &self.bind_group,
+            };
+            render_pass.set_bind_group(0, bind_group, &[]);
             render_pass.draw(0..3, 0..1);
 
             if self.menu_visible {
@@ -1399,7 +3332,39 @@ impl State {
     }
 }
 
-fn prepare_framebuffer_upload(frame: &[u8], bars: &[f32]) -> (Vec<u8>, u32) {
+/// The gamepad that drives input: the pinned one while it's connected, else the one used
+/// last, else the first connected.
+#[cfg(feature = "gamepad")]
+fn select_gamepad<T: Copy + PartialEq>(
+    pinned: Option<T>,
+    last_active: Option<T>,
+    connected: &[T],
+) -> Option<T> {
+    pinned
+        .filter(|id| connected.contains(id))
+        .or_else(|| last_active.filter(|id| connected.contains(id)))
+        .or_else(|| connected.first().copied())
+}
+
+/// The pin after `pinned` in `connected`, with `None` (follow the last used pad) after
+/// the final one.
+#[cfg(feature = "gamepad")]
+fn next_pinned_gamepad<T: Copy + PartialEq>(pinned: Option<T>, connected: &[T]) -> Option<T> {
+    match pinned.and_then(|id| connected.iter().position(|&other| other == id)) {
+        Some(index) => connected.get(index + 1).copied(),
+        None => connected.first().copied(),
+    }
+}
+
+/// Raises each peak to its bar at once, otherwise lets it fall by `PEAK_DECAY`, never
+/// below the bar.
+fn hold_peaks(peaks: &mut [f32], levels: &[f32]) {
+    for (peak, &level) in peaks.iter_mut().zip(levels) {
+        *peak = (*peak * PEAK_DECAY).max(level);
+    }
+}
+
+fn prepare_framebuffer_upload(frame: &[u8], bars: &[f32], peaks: &[f32]) -> (Vec<u8>, u32) {
     let width = FRAME_WIDTH;
     let height = DISPLAY_HEIGHT;
     if frame.len() != FRAME_SIZE {
@@ -1409,32 +3374,21 @@ fn prepare_framebuffer_upload(frame: &[u8], bars: &[f32]) -> (Vec<u8>, u32) {
     let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
     let padded = unpadded.div_ceil(align) * align;
     let mut data = vec![0u8; padded * height];
-    for y in 0..height {
-        let dst = y * padded;
-        for x in 0..width {
-            let dst_px = dst + x * 4;
-            data[dst_px] = VISUALIZER_BG[0];
-            data[dst_px + 1] = VISUALIZER_BG[1];
-            data[dst_px + 2] = VISUALIZER_BG[2];
-            data[dst_px + 3] = 0xFF;
-        }
+    let (screen, visualizer) = data.split_at_mut(padded * FRAME_HEIGHT);
+    pack_rgba_rows(frame, width, padded, screen);
+    let mut background = vec![0u8; padded];
+    for pixel in background[..unpadded].chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[VISUALIZER_BG[0], VISUALIZER_BG[1], VISUALIZER_BG[2], 0xFF]);
     }
-    for y in 0..FRAME_HEIGHT {
-        let src = y * width * 3;
-        let dst = y * padded;
-        for x in 0..width {
-            let src_px = src + x * 3;
-            let dst_px = dst + x * 4;
-            data[dst_px] = frame[src_px];
-            data[dst_px + 1] = frame[src_px + 1];
-            data[dst_px + 2] = frame[src_px + 2];
-            data[dst_px + 3] = 0xFF;
-        }
+    for row in visualizer.chunks_exact_mut(padded) {
+        row.copy_from_slice(&background);
     }
 
     if !bars.is_empty() {
         let bar_count = bars.len().min(width);
         let bar_width = width / bar_count;
+        // One-pixel bars have no room for the gap column between them.
+        let gap = usize::from(bar_width > 1);
         let bar_area_height = VISUALIZER_HEIGHT.saturating_sub(2).max(1);
         let y_base = FRAME_HEIGHT + VISUALIZER_HEIGHT - 1;
         for (i, level) in bars.iter().take(bar_count).enumerate() {
@@ -1445,7 +3399,7 @@ fn prepare_framebuffer_upload(frame: &[u8], bars: &[f32]) -> (Vec<u8>, u32) {
             }
             let x_start = i * bar_width;
             let x_end = (i + 1) * bar_width;
-            let x_bar_end = x_end.saturating_sub(1);
+            let x_bar_end = x_end - gap;
             for y in 0..bar_height {
                 let y_pos = y_base.saturating_sub(y + 1);
                 let t = y as f32 / bar_area_height as f32;
@@ -1483,6 +3437,63 @@ fn prepare_framebuffer_upload(frame: &[u8], bars: &[f32]) -> (Vec<u8>, u32) {
                 }
             }
         }
+        for (i, peak) in peaks.iter().take(bar_count).enumerate() {
+            let peak_height = (peak.clamp(0.0, 1.0) * bar_area_height as f32).round() as usize;
+            if peak_height == 0 || bar_width == 0 {
+                continue;
+            }
+            let dst = y_base.saturating_sub(peak_height) * padded;
+            for x in i * bar_width..(i + 1) * bar_width - gap {
+                let dst_px = dst + x * 4;
+                data[dst_px..dst_px + 3].copy_from_slice(&VISUALIZER_PEAK);
+                data[dst_px + 3] = 0xFF;
+            }
+        }
+    }
+    (data, padded as u32)
+}
+
+/// Expands `rgb` rows of `width` pixels into `out` as RGBA rows `padded` bytes apart.
+/// When no padding is needed the rows are contiguous and convert in a single pass.
+fn pack_rgba_rows(rgb: &[u8], width: usize, padded: usize, out: &mut [u8]) {
+    let unpadded = width * 4;
+    if padded == unpadded {
+        rgb_to_rgba(rgb, out);
+        return;
+    }
+    for (src, dst) in rgb
+        .chunks_exact(width * 3)
+        .zip(out.chunks_exact_mut(padded))
+    {
+        rgb_to_rgba(src, &mut dst[..unpadded]);
+    }
+}
+
+/// Converts a tile map to padded RGBA and outlines the visible frame at `scroll` (SCX,
+/// SCY), wrapping around the map edges like the PPU does.
+fn prepare_tile_map_upload(map: &[u8], scroll: Option<(u8, u8)>) -> (Vec<u8>, u32) {
+    let width = TILE_MAP_WIDTH;
+    let unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    let padded = unpadded.div_ceil(align) * align;
+    let mut data = vec![0u8; padded * width];
+    let pixels = map.len().min(width * width * 3);
+    pack_rgba_rows(&map[..pixels], width, padded, &mut data);
+    if let Some((scx, scy)) = scroll {
+        let color = [0xFF, 0x40, 0x40];
+        let mut mark = |x: usize, y: usize| {
+            let dst = (y % width) * padded + (x % width) * 4;
+            data[dst..dst + 3].copy_from_slice(&color);
+        };
+        let (left, top) = (scx as usize, scy as usize);
+        for x in 0..FRAME_WIDTH {
+            mark(left + x, top);
+            mark(left + x, top + FRAME_HEIGHT - 1);
+        }
+        for y in 0..FRAME_HEIGHT {
+            mark(left, top + y);
+            mark(left + FRAME_WIDTH - 1, top + y);
+        }
     }
     (data, padded as u32)
 }
@@ -1530,6 +3541,41 @@ impl Viewport {
             scissor_height: height,
         }
     }
+
+    /// Largest integer scale of a `width` by `height` image that fits `window`, centered,
+    /// with each pixel stretched to `pixel_aspect` (width over height) first. A window too
+    /// small for even one scale gets the image shrunk to fit, as wgpu rejects a scissor
+    /// rect past the render target.
+    fn fit(window: PhysicalSize<u32>, width: u32, height: u32, pixel_aspect: f32) -> Self {
+        let window_w = window.width;
+        let window_h = window.height;
+        if window_w == 0 || window_h == 0 {
+            return Self::full(window_w, window_h);
+        }
+
+        let aspect_width = width as f32 * pixel_aspect;
+        let max_scale_w = (window_w as f32 / aspect_width) as u32;
+        let max_scale_h = window_h / height;
+        let scale = match max_scale_w.min(max_scale_h) {
+            0 => (window_w as f32 / aspect_width).min(window_h as f32 / height as f32),
+            scale => scale as f32,
+        };
+        let target_w = ((aspect_width * scale).round() as u32).clamp(1, window_w);
+        let target_h = ((height as f32 * scale).round() as u32).clamp(1, window_h);
+        let x = window_w.saturating_sub(target_w) / 2;
+        let y = window_h.saturating_sub(target_h) / 2;
+
+        Self {
+            x: x as f32,
+            y: y as f32,
+            width: target_w as f32,
+            height: target_h as f32,
+            scissor_x: x,
+            scissor_y: y,
+            scissor_width: target_w,
+            scissor_height: target_h,
+        }
+    }
 }
 
 fn toggle_borderless_fullscreen(window: &winit::window::Window) {
@@ -1568,6 +3614,8 @@ fn slint_key_text(code: KeyCode) -> Option<slint::SharedString> {
 struct Overlay {
     entries: Vec<OverlayEntry>,
     enabled: bool,
+    visible: u32,
+    preset: usize,
     font: FontArc,
     scale: PxScale,
 }
@@ -1586,6 +3634,8 @@ impl Overlay {
         Self {
             entries: Vec::new(),
             enabled: false,
+            visible: OVERLAY_PRESETS[0],
+            preset: 0,
             font,
             scale: PxScale::from(24.0),
         }
@@ -1608,8 +3658,22 @@ impl Overlay {
         self.enabled = !self.enabled;
     }
 
+    fn cycle_metrics(&mut self) {
+        self.preset = (self.preset + 1) % OVERLAY_PRESETS.len();
+        self.visible = OVERLAY_PRESETS[self.preset];
+    }
+
+    /// Entries whose metric is in the visible set; hidden ones keep updating regardless.
+    fn visible_entries(&self) -> Vec<&OverlayEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| self.visible & overlay_metric_bit(&entry.label) != 0)
+            .collect()
+    }
+
     fn draw(&self, rgba: &mut [u8], bytes_per_row: u32, width: usize, height: usize) {
-        if !self.enabled || self.entries.is_empty() {
+        let entries = self.visible_entries();
+        if !self.enabled || entries.is_empty() {
             return;
         }
         let stride = bytes_per_row as usize / 4;
@@ -1624,11 +3688,10 @@ impl Overlay {
         let margin = 6;
 
         let mut max_width: f32 = 0.0;
-        for entry in &self.entries {
+        for entry in &entries {
             max_width = max_width.max(text_width(&self.font, self.scale, &entry.text));
         }
-        let text_height = self
-            .entries
+        let text_height = entries
             .len()
             .saturating_mul(line_height)
             .saturating_sub(line_gap);
@@ -1652,7 +3715,7 @@ impl Overlay {
         );
 
         let mut y = box_y + text_padding;
-        for entry in &self.entries {
+        for entry in entries {
             let line_width = text_width(&self.font, self.scale, &entry.text).ceil() as usize;
             let x = box_x + text_padding + (max_width.ceil() as usize).saturating_sub(line_width);
             draw_text(
@@ -1672,6 +3735,22 @@ impl Overlay {
     }
 }
 
+/// With `skip` frames skipped between draws, whether redraw `frame_index` gets drawn.
+fn should_render_frame(frame_index: u64, skip: u32) -> bool {
+    frame_index.is_multiple_of(u64::from(skip) + 1)
+}
+
+fn overlay_metric_bit(label: &str) -> u32 {
+    match label {
+        "FPS" => OVERLAY_FPS,
+        "Frame" => OVERLAY_FRAME,
+        "Target" => OVERLAY_TARGET,
+        "Palette" => OVERLAY_PALETTE,
+        "Shader" => OVERLAY_SHADER,
+        _ => OVERLAY_OTHER,
+    }
+}
+
 fn text_width(font: &FontArc, scale: PxScale, text: &str) -> f32 {
     let scaled = font.as_scaled(scale);
     let mut width = 0.0;
@@ -1734,6 +3813,58 @@ fn draw_text(
     }
 }
 
+/// A red square in the top-right corner while a clip is recording.
+fn draw_rec_indicator(rgba: &mut [u8], bytes_per_row: u32) {
+    let size = 6;
+    draw_rect_blend(
+        rgba,
+        bytes_per_row as usize / 4,
+        FRAME_WIDTH,
+        FRAME_HEIGHT,
+        FRAME_WIDTH - size - 4,
+        4,
+        size,
+        size,
+        [0xFF, 0x20, 0x20],
+        230,
+    );
+}
+
+fn draw_sprite_boxes(rgba: &mut [u8], bytes_per_row: u32, sprites: &[SpriteInfo]) {
+    let stride = bytes_per_row as usize / 4;
+    let color = [0xFF, 0x40, 0x40];
+    let alpha = 200;
+    for sprite in sprites {
+        // Clip to the frame; sprites may hang off the top or left edge.
+        let left = sprite.x.max(0) as usize;
+        let top = sprite.y.max(0) as usize;
+        let right = (sprite.x + 8).min(FRAME_WIDTH as i16) as usize;
+        let bottom = (sprite.y + sprite.height as i16).min(FRAME_HEIGHT as i16) as usize;
+        let w = right.saturating_sub(left);
+        let h = bottom.saturating_sub(top);
+        let edges = [
+            (left, top, w, 1),
+            (left, bottom.saturating_sub(1), w, 1),
+            (left, top, 1, h),
+            (right.saturating_sub(1), top, 1, h),
+        ];
+        for (x, y, w, h) in edges {
+            draw_rect_blend(
+                rgba,
+                stride,
+                FRAME_WIDTH,
+                FRAME_HEIGHT,
+                x,
+                y,
+                w,
+                h,
+                color,
+                alpha,
+            );
+        }
+    }
+}
+
 fn draw_rect_blend(
     rgba: &mut [u8],
     stride: usize,
@@ -1773,3 +3904,450 @@ fn blend_pixel(rgba: &mut [u8], idx: usize, color: [u8; 3], alpha: u8) {
     rgba[idx + 2] = ((rgba[idx + 2] as u16 * inv + color[2] as u16 * alpha) / 255) as u8;
     rgba[idx + 3] = 0xFF;
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "gamepad")]
+    use super::{
+        AxisLatch, InputState, KeyBindings, StickAxis, next_pinned_gamepad, select_gamepad,
+    };
+    use super::{
+        DEFAULT_PALETTE_INDEX, DISPLAY_HEIGHT, FRAME_HEIGHT, FRAME_SIZE, FRAME_WIDTH, Framebuffer,
+        GameSettings, LINE_A, LINE_START, LatestFrame, LoadGate, LoadGuard, MenuMode, Overlay,
+        PALETTES, PresentMode, SPLASH_PALETTE, Screenshot, State, TILE_MAP_SIZE, TILE_MAP_WIDTH,
+        VISUALIZER_BG, VISUALIZER_PEAK, Viewport, capture_screenshot, cycled_index, hold_peaks,
+        pack_rgba_rows, padded_bytes_per_row, prepare_framebuffer_upload, prepare_tile_map_upload,
+        select_present_mode, should_render_frame, splash_palette_by_name, unpad_rows,
+    };
+    #[cfg(feature = "gamepad")]
+    use crate::domain::{Autofire, Cartridge, DPAD_RIGHT, Emulator, SocdMode};
+    use crate::infrastructure::config::GameConfig;
+    use std::time::Instant;
+    use winit::dpi::PhysicalSize;
+    #[cfg(feature = "gamepad")]
+    use winit::keyboard::KeyCode;
+
+    #[test]
+    fn viewport_stretches_pixels_to_the_configured_aspect() {
+        let window = PhysicalSize::new(800, 600);
+        let square = Viewport::fit(window, 160, 176, 1.0);
+        assert_eq!((square.scissor_width, square.scissor_height), (480, 528));
+
+        let wide = Viewport::fit(window, 160, 176, 1.2);
+        assert_eq!((wide.scissor_width, wide.scissor_height), (576, 528));
+        assert_eq!((wide.scissor_x, wide.scissor_y), (112, 36));
+
+        // Pixels too wide for the window drop to a smaller integer scale.
+        let stretched = Viewport::fit(window, 160, 176, 2.0);
+        assert_eq!(
+            (stretched.scissor_width, stretched.scissor_height),
+            (640, 352)
+        );
+    }
+
+    #[test]
+    fn viewport_shrinks_to_fit_a_window_narrower_than_one_scale() {
+        let window = PhysicalSize::new(160, 176);
+        let viewport = Viewport::fit(window, 160, 176, 2.0);
+
+        assert_eq!((viewport.scissor_width, viewport.scissor_height), (160, 88));
+        assert_eq!((viewport.scissor_x, viewport.scissor_y), (0, 44));
+        assert!(viewport.scissor_x + viewport.scissor_width <= window.width);
+        assert!(viewport.scissor_y + viewport.scissor_height <= window.height);
+    }
+
+    #[test]
+    fn rom_tile_splash_uses_its_own_palette() {
+        let rom = vec![0; 0x8000];
+        let mut framebuffer = Framebuffer::new();
+        State::render_rom_tiles(&mut framebuffer, &rom, splash_palette_by_name(None));
+
+        let background = SPLASH_PALETTE.colors[0];
+        assert!(
+            framebuffer
+                .as_slice()
+                .chunks_exact(3)
+                .all(|px| px == background)
+        );
+        assert_ne!(background, PALETTES[DEFAULT_PALETTE_INDEX].colors[0]);
+        assert_eq!(splash_palette_by_name(Some("pocket")), PALETTES[1].colors);
+    }
+
+    #[test]
+    fn clean_screenshot_leaves_out_the_overlay() {
+        let frame = vec![0x10; FRAME_SIZE];
+        let (mut padded, bytes_per_row) = prepare_framebuffer_upload(&frame, &[], &[]);
+        let mut overlay = Overlay::new();
+        overlay.toggle();
+        overlay.set_metric("FPS", "59.7");
+        overlay.draw(&mut padded, bytes_per_row, FRAME_WIDTH, FRAME_HEIGHT);
+
+        let (clean, width, height) =
+            capture_screenshot(Screenshot::Clean, &frame, &padded, bytes_per_row);
+        assert_eq!(
+            (width as usize, height as usize),
+            (FRAME_WIDTH, FRAME_HEIGHT)
+        );
+        assert!(
+            clean
+                .chunks_exact(4)
+                .all(|px| px == [0x10, 0x10, 0x10, 0xFF])
+        );
+
+        let (full, width, height) =
+            capture_screenshot(Screenshot::Full, &frame, &padded, bytes_per_row);
+        assert_eq!(
+            (width as usize, height as usize),
+            (FRAME_WIDTH, DISPLAY_HEIGHT)
+        );
+        assert_eq!(full.len(), FRAME_WIDTH * DISPLAY_HEIGHT * 4);
+        let game_rows = &full[..FRAME_WIDTH * FRAME_HEIGHT * 4];
+        assert!(
+            game_rows
+                .chunks_exact(4)
+                .any(|px| px != [0x10, 0x10, 0x10, 0xFF])
+        );
+    }
+
+    #[test]
+    fn present_mode_uses_the_request_or_falls_back_to_fifo() {
+        let available = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox];
+        assert_eq!(
+            select_present_mode(Some(PresentMode::Mailbox), &available),
+            wgpu::PresentMode::Mailbox
+        );
+        assert_eq!(
+            select_present_mode(Some(PresentMode::Immediate), &available),
+            wgpu::PresentMode::Fifo
+        );
+        assert_eq!(
+            select_present_mode(None, &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo]),
+            wgpu::PresentMode::Mailbox
+        );
+        assert_eq!(
+            PresentMode::from_name("immediate"),
+            Some(PresentMode::Immediate)
+        );
+        assert_eq!(PresentMode::from_name("vsync"), None);
+    }
+
+    #[test]
+    fn cycling_palettes_backwards_wraps_to_the_last() {
+        let last = PALETTES.len() - 1;
+        assert_eq!(cycled_index(0, -1, PALETTES.len()), last);
+        assert_eq!(cycled_index(last, 1, PALETTES.len()), 0);
+        assert_eq!(cycled_index(1, -1, PALETTES.len()), 0);
+    }
+
+    #[test]
+    fn tile_map_outline_wraps_around_the_map() {
+        let map = vec![0x10; TILE_MAP_SIZE];
+        let (rgba, bytes_per_row) = prepare_tile_map_upload(&map, Some((200, 250)));
+        let pixel = |x: usize, y: usize| {
+            let idx = y * bytes_per_row as usize + x * 4;
+            [rgba[idx], rgba[idx + 1], rgba[idx + 2], rgba[idx + 3]]
+        };
+        let edge = [0xFF, 0x40, 0x40, 0xFF];
+        assert_eq!(pixel(200, 250), edge);
+        // The right edge lands at x = (200 + 159) % 256 and the bottom at (250 + 143) % 256.
+        assert_eq!(pixel(103, 10), edge);
+        assert_eq!(pixel(10, 137), edge);
+        assert_eq!(pixel(50, 50), [0x10, 0x10, 0x10, 0xFF]);
+        assert_eq!(rgba.len(), bytes_per_row as usize * TILE_MAP_WIDTH);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn stick_directions_hold_until_well_inside_the_threshold() {
+        let axis = StickAxis::default();
+        let mut latch = AxisLatch::default();
+        let held: Vec<bool> = [0.3, 0.4, 0.3, 0.35, 0.2, 0.3]
+            .into_iter()
+            .map(|value| {
+                latch.update(axis.shape(value));
+                latch.positive
+            })
+            .collect();
+        assert_eq!(held, [false, true, true, true, false, false]);
+
+        latch.update(axis.shape(-0.5));
+        assert_eq!(
+            latch,
+            AxisLatch {
+                negative: true,
+                positive: false
+            }
+        );
+
+        // A softer curve presses the same deflection that the linear one ignored.
+        let mut soft = AxisLatch::default();
+        soft.update(StickAxis::new(0.15, 0.5).shape(0.3));
+        assert!(soft.positive);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn gamepad_selection_prefers_pinned_then_last_used() {
+        let connected = [3, 5, 8];
+        assert_eq!(select_gamepad(None, None, &connected), Some(3));
+        assert_eq!(select_gamepad(None, Some(5), &connected), Some(5));
+        assert_eq!(select_gamepad(Some(8), Some(5), &connected), Some(8));
+        // Pads that went away are skipped.
+        assert_eq!(select_gamepad(Some(9), Some(5), &connected), Some(5));
+        assert_eq!(select_gamepad(Some(9), Some(7), &connected), Some(3));
+        assert_eq!(select_gamepad::<u32>(None, Some(5), &[]), None);
+
+        assert_eq!(next_pinned_gamepad(None, &connected), Some(3));
+        assert_eq!(next_pinned_gamepad(Some(3), &connected), Some(5));
+        assert_eq!(next_pinned_gamepad(Some(8), &connected), None);
+        assert_eq!(next_pinned_gamepad(Some(9), &connected), Some(3));
+    }
+
+    #[test]
+    fn peaks_jump_up_and_fall_slowly() {
+        let mut peaks = [0.0, 0.5];
+        hold_peaks(&mut peaks, &[0.8, 0.2]);
+        assert_eq!(peaks[0], 0.8);
+        assert!(peaks[1] < 0.5 && peaks[1] > 0.2, "{peaks:?}");
+
+        // The bars fall faster than the peaks, which stay on or above them.
+        let mut levels = [0.8f32, 0.2];
+        for _ in 0..30 {
+            levels = levels.map(|level| level * 0.88);
+            let before = peaks;
+            hold_peaks(&mut peaks, &levels);
+            for i in 0..2 {
+                assert!(peaks[i] >= levels[i]);
+                assert!(peaks[i] <= before[i]);
+            }
+        }
+        assert!(peaks[0] > levels[0]);
+
+        let frame = vec![0; FRAME_SIZE];
+        let (rgba, bytes_per_row) = prepare_framebuffer_upload(&frame, &[0.0], &[0.5]);
+        let peak_row = (DISPLAY_HEIGHT - 1 - 15) * bytes_per_row as usize;
+        assert_eq!(&rgba[peak_row..peak_row + 3], &VISUALIZER_PEAK);
+    }
+
+    #[test]
+    fn one_pixel_bars_still_draw() {
+        let frame = vec![0; FRAME_SIZE];
+        let bars = vec![1.0; FRAME_WIDTH];
+        let (rgba, bytes_per_row) = prepare_framebuffer_upload(&frame, &bars, &bars);
+        let bottom = (DISPLAY_HEIGHT - 2) * bytes_per_row as usize;
+        for x in 0..FRAME_WIDTH {
+            let px = bottom + x * 4;
+            assert_ne!(&rgba[px..px + 3], &VISUALIZER_BG, "bar {x} left no pixels");
+        }
+    }
+
+    #[test]
+    fn readback_rows_drop_wgpu_padding() {
+        let bytes_per_row = padded_bytes_per_row(FRAME_WIDTH as u32);
+        assert_eq!(bytes_per_row, 768);
+        assert_eq!(padded_bytes_per_row(64), 256);
+
+        let mut padded = vec![0xEE; bytes_per_row as usize * 3];
+        for (y, row) in padded.chunks_exact_mut(bytes_per_row as usize).enumerate() {
+            row[..FRAME_WIDTH * 4].fill(y as u8);
+        }
+        let rgba = unpad_rows(&padded, bytes_per_row, FRAME_WIDTH as u32, 3);
+        assert_eq!(rgba.len(), FRAME_WIDTH * 4 * 3);
+        for (y, row) in rgba.chunks_exact(FRAME_WIDTH * 4).enumerate() {
+            assert!(row.iter().all(|&byte| byte == y as u8));
+        }
+    }
+
+    #[test]
+    fn packed_rows_match_with_and_without_padding() {
+        let width = 64;
+        let rgb: Vec<u8> = (0..width * 4 * 3).map(|i| i as u8).collect();
+        let unpadded = width * 4;
+        let mut contiguous = vec![0; unpadded * 4];
+        pack_rgba_rows(&rgb, width, unpadded, &mut contiguous);
+        let padded = unpadded + 256;
+        let mut spaced = vec![0; padded * 4];
+        pack_rgba_rows(&rgb, width, padded, &mut spaced);
+
+        for (fast, slow) in contiguous
+            .chunks_exact(unpadded)
+            .zip(spaced.chunks_exact(padded))
+        {
+            assert_eq!(fast, &slow[..unpadded]);
+        }
+        assert_eq!(&contiguous[4..8], &[3, 4, 5, 0xFF]);
+    }
+
+    /// `cargo test --release --lib bench_pack_rgba_rows -- --ignored --nocapture`
+    #[test]
+    #[ignore = "timing benchmark"]
+    fn bench_pack_rgba_rows() {
+        const RUNS: u32 = 2_000;
+        let width = TILE_MAP_WIDTH;
+        let rgb: Vec<u8> = (0..TILE_MAP_SIZE).map(|i| i as u8).collect();
+        let unpadded = width * 4;
+        for (label, padded) in [("single pass", unpadded), ("row by row", unpadded + 256)] {
+            let mut out = vec![0; padded * width];
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                pack_rgba_rows(std::hint::black_box(&rgb), width, padded, &mut out);
+                std::hint::black_box(&out);
+            }
+            println!(
+                "{label}: {:?} per {width}x{width} upload",
+                start.elapsed() / RUNS
+            );
+        }
+    }
+
+    #[test]
+    fn menu_mode_controls_visibility_on_press_and_release() {
+        let toggle = MenuMode::Toggle;
+        assert!(toggle.visibility_after(false, true));
+        assert!(toggle.visibility_after(true, false));
+        assert!(!toggle.visibility_after(true, true));
+        assert!(!toggle.visibility_after(false, false));
+
+        let hold = MenuMode::Hold;
+        assert!(hold.visibility_after(false, true));
+        assert!(hold.visibility_after(true, true));
+        assert!(!hold.visibility_after(true, false));
+        assert!(!hold.visibility_after(false, false));
+
+        assert_eq!(MenuMode::from_name("hold"), Some(MenuMode::Hold));
+        assert_eq!(toggle.next(), hold);
+    }
+
+    #[test]
+    fn latest_frame_delivers_the_newest_and_counts_dropped() {
+        let frames = LatestFrame::new();
+        assert_eq!(frames.take(), None);
+
+        frames.publish(1);
+        assert_eq!(frames.take(), Some(1));
+        assert_eq!(frames.take(), None);
+
+        frames.publish(2);
+        frames.publish(3);
+        frames.publish(4);
+        assert_eq!(frames.take(), Some(4));
+        assert_eq!(frames.take_dropped(), 2);
+        assert_eq!(frames.take_dropped(), 0);
+    }
+
+    #[test]
+    fn frame_skip_renders_every_skip_plus_one_frames() {
+        let rendered = |skip| {
+            (0..8)
+                .filter(|&frame| should_render_frame(frame, skip))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rendered(0), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(rendered(1), [0, 2, 4, 6]);
+        assert_eq!(rendered(2), [0, 3, 6]);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn keyboard_and_gamepad_input_combine() {
+        let mut emulator = Emulator::new();
+        let cartridge = Cartridge::from_bytes(vec![0; 0x8000]).expect("cartridge");
+        emulator.load_cartridge(cartridge).expect("load");
+
+        let mut input = InputState::default();
+        input.handle_key(&KeyBindings::default(), KeyCode::KeyZ, true);
+        input.gamepad.dpad.set(DPAD_RIGHT, true);
+        input.apply(&mut emulator, SocdMode::Passthrough, &Autofire::new(), 0);
+
+        let frame = emulator.movie_frame();
+        assert_eq!((frame.buttons, frame.dpad), (0x0E, 0x0E));
+
+        // A gamepad poll with nothing held leaves the keyboard's A alone.
+        input.gamepad = Default::default();
+        input.apply(&mut emulator, SocdMode::Passthrough, &Autofire::new(), 1);
+        let frame = emulator.movie_frame();
+        assert_eq!((frame.buttons, frame.dpad), (0x0E, 0x0F));
+    }
+
+    #[test]
+    fn game_file_keeps_only_settings_that_differ_from_global() {
+        let mut global = GameConfig::new();
+        global.palette = Some(PALETTES[1].name.to_string());
+        global.speed = Some(2.0);
+        let mut game = GameConfig::new();
+        game.speed = Some(1.5);
+        game.frame_skip = Some(2);
+        let merged = global.merged_with(&game);
+
+        let mut current = GameSettings::from_config(&merged);
+        current.speed = 2.0;
+        current.colorize = true;
+        current.write_overrides(&GameSettings::from_config(&global), &mut game);
+
+        assert_eq!(game.palette, None);
+        assert_eq!(game.speed, None);
+        assert_eq!(game.colorize, Some(true));
+        assert_eq!(game.frame_skip, Some(2));
+        assert_eq!(game.shader, None);
+    }
+
+    #[test]
+    fn load_guard_holds_back_input_from_before_the_load() {
+        let mut guard = LoadGuard::new(LINE_A, LoadGate::Off);
+        assert_eq!(guard.visible(LINE_A | LINE_START), LINE_START);
+
+        guard.update(0);
+        guard.update(LINE_A);
+        assert_eq!(guard.visible(LINE_A), LINE_A);
+    }
+
+    #[test]
+    fn load_gate_swallows_presses_until_it_opens() {
+        let mut guard = LoadGuard::new(0, LoadGate::Start);
+        guard.update(LINE_A);
+        assert_eq!(guard.visible(LINE_A), 0);
+
+        guard.update(LINE_A | LINE_START);
+        assert_eq!(guard.visible(LINE_A | LINE_START), 0);
+
+        // The opening Start press and A, held through it, stay out until pressed again.
+        guard.update(LINE_A);
+        assert_eq!(guard.visible(LINE_A), 0);
+        guard.update(LINE_START);
+        assert_eq!(guard.visible(LINE_START), LINE_START);
+
+        let mut any = LoadGuard::new(0, LoadGate::AnyButton);
+        any.update(LINE_A);
+        any.update(0);
+        any.update(LINE_A);
+        assert_eq!(any.visible(LINE_A), LINE_A);
+        assert_eq!(LoadGate::from_name("any"), Some(LoadGate::AnyButton));
+    }
+
+    #[test]
+    fn overlay_lays_out_only_enabled_metrics() {
+        let mut overlay = Overlay::new();
+        overlay.set_metric("FPS", "59.7");
+        overlay.set_metric("Frame", "16.7 ms");
+        overlay.set_metric("Palette", "DMG");
+
+        let labels = |overlay: &Overlay| {
+            overlay
+                .visible_entries()
+                .iter()
+                .map(|entry| entry.label.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(&overlay), ["FPS", "Frame", "Palette"]);
+
+        overlay.cycle_metrics();
+        assert_eq!(labels(&overlay), ["FPS"]);
+
+        overlay.set_metric("Palette", "Pocket");
+        overlay.cycle_metrics();
+        assert_eq!(labels(&overlay), ["FPS", "Frame"]);
+        overlay.cycle_metrics();
+        assert_eq!(labels(&overlay), ["FPS", "Frame", "Palette"]);
+        assert_eq!(overlay.visible_entries()[2].text, "Palette: Pocket");
+    }
+}
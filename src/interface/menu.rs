@@ -17,6 +17,9 @@ slint::slint! {
         in property <bool> has_rom;
         callback load_rom();
         callback resume();
+        callback save_game_config();
+        callback soft_reset();
+        callback power_cycle();
         callback quit();
         callback browse_files();
         background: transparent;
@@ -28,8 +31,8 @@ slint::slint! {
         }
 
         Rectangle {
-            width: min(parent.width * 0.8, 400px);
-            height: min(parent.height * 0.8, 320px);
+            width: min(parent.width * 0.8, 480px);
+            height: min(parent.height * 0.8, 360px);
             x: (parent.width - self.width) / 2;
             y: (parent.height - self.height) / 2;
             background: #141a22;
@@ -101,12 +104,33 @@ slint::slint! {
                         enabled: root.has_rom;
                         clicked => { root.resume(); }
                     }
+                    Button {
+                        text: "Save Game Settings";
+                        enabled: root.has_rom;
+                        clicked => { root.save_game_config(); }
+                    }
                     Button {
                         text: "Quit";
                         clicked => { root.quit(); }
                     }
                 }
 
+                HorizontalLayout {
+                    spacing: 8px;
+                    alignment: end;
+
+                    Button {
+                        text: "Reset";
+                        enabled: root.has_rom;
+                        clicked => { root.soft_reset(); }
+                    }
+                    Button {
+                        text: "Power Cycle";
+                        enabled: root.has_rom;
+                        clicked => { root.power_cycle(); }
+                    }
+                }
+
                 Rectangle {
                     height: 1px;
                     background: #30363d;
@@ -119,7 +143,7 @@ slint::slint! {
                 }
 
                 Text {
-                    text: "Esc: menu";
+                    text: "Esc: menu  Ctrl+R: reset  Ctrl+Shift+R: power cycle";
                     color: #6e7681;
                     font-size: 9px;
                 }
@@ -139,6 +163,9 @@ slint::slint! {
 pub enum MenuAction {
     LoadRom(String),
     Resume,
+    SaveGameConfig,
+    SoftReset,
+    PowerCycle,
     Quit,
     ShowFilePicker,
 }
@@ -210,6 +237,25 @@ impl MenuOverlay {
             actions_browse.borrow_mut().push(MenuAction::ShowFilePicker);
         });
 
+        let actions_save_config = actions.clone();
+        ui.on_save_game_config(move || {
+            actions_save_config
+                .borrow_mut()
+                .push(MenuAction::SaveGameConfig);
+        });
+
+        let actions_soft_reset = actions.clone();
+        ui.on_soft_reset(move || {
+            actions_soft_reset.borrow_mut().push(MenuAction::SoftReset);
+        });
+
+        let actions_power_cycle = actions.clone();
+        ui.on_power_cycle(move || {
+            actions_power_cycle
+                .borrow_mut()
+                .push(MenuAction::PowerCycle);
+        });
+
         let actions_quit = actions.clone();
         ui.on_quit(move || {
             actions_quit.borrow_mut().push(MenuAction::Quit);
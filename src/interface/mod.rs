@@ -1,6 +1,12 @@
+#[cfg(not(target_arch = "wasm32"))]
 pub mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod clip;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod gui;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod menu;
+pub mod web;
 
-#[cfg(feature = "audio")]
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
 pub mod audio;
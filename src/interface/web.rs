@@ -0,0 +1,78 @@
+use crate::domain::{Emulator, FRAME_HEIGHT, FRAME_WIDTH};
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+pub const INPUT_RIGHT: u8 = 0x01;
+pub const INPUT_LEFT: u8 = 0x02;
+pub const INPUT_UP: u8 = 0x04;
+pub const INPUT_DOWN: u8 = 0x08;
+pub const INPUT_A: u8 = 0x10;
+pub const INPUT_B: u8 = 0x20;
+pub const INPUT_SELECT: u8 = 0x40;
+pub const INPUT_START: u8 = 0x80;
+
+/// Browser-facing wrapper around the emulator core. Exported through wasm-bindgen with
+/// the `wasm` feature; natively it is a plain struct with the same API.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct WebEmulator {
+    emulator: Emulator,
+}
+
+impl Default for WebEmulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WebEmulator {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self {
+            emulator: Emulator::new(),
+        }
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.emulator
+            .load_rom_bytes(rom, None)
+            .map_err(|err| format!("{err:?}"))
+    }
+
+    pub fn step_frame(&mut self) -> Result<u32, String> {
+        self.emulator.step_frame().map_err(|err| format!("{err:?}"))
+    }
+
+    /// Address of the RGB framebuffer in linear memory, valid until the next call.
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.emulator.framebuffer().present().as_ptr()
+    }
+
+    pub fn framebuffer_len(&self) -> usize {
+        self.emulator.framebuffer().len()
+    }
+
+    pub fn frame_width() -> usize {
+        FRAME_WIDTH
+    }
+
+    pub fn frame_height() -> usize {
+        FRAME_HEIGHT
+    }
+
+    /// Sets every button at once from a mask of the `INPUT_*` bits, set meaning pressed.
+    pub fn set_input(&mut self, pressed: u8) {
+        let dpad = !pressed & 0x0F;
+        let buttons = !(pressed >> 4) & 0x0F;
+        self.emulator.set_joyp_dpad(dpad);
+        self.emulator.set_joyp_buttons(buttons);
+    }
+}
+
+impl WebEmulator {
+    pub fn framebuffer(&self) -> &[u8] {
+        self.emulator.framebuffer().present()
+    }
+}
@@ -1,29 +1,101 @@
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    use craterboy::interface::cli::InputMovie;
+
     let mut args = std::env::args();
     let _program = args.next();
     let mut gui = false;
     let mut rom_path: Option<std::path::PathBuf> = None;
     let mut boot_rom_path: Option<std::path::PathBuf> = None;
+    let mut boot_timing = false;
+    let mut threaded = false;
+    let mut frames: Option<u32> = None;
+    let mut movie: Option<InputMovie> = None;
+    let mut present_mode: Option<String> = None;
 
     while let Some(arg) = args.next() {
         if arg == "--gui" {
             gui = true;
             continue;
         }
+        if arg == "--boot-timing" {
+            boot_timing = true;
+            continue;
+        }
+        if arg == "--threaded" {
+            threaded = true;
+            continue;
+        }
         if arg == "--boot-rom" {
             if let Some(path) = args.next() {
                 boot_rom_path = Some(std::path::PathBuf::from(path));
             }
             continue;
         }
-        if gui && rom_path.is_none() && !arg.starts_with('-') {
+        if arg == "--record-inputs" || arg == "--replay-inputs" {
+            let Some(path) = args.next().map(std::path::PathBuf::from) else {
+                eprintln!("{} expects a movie path", arg);
+                std::process::exit(2);
+            };
+            movie = Some(if arg == "--record-inputs" {
+                InputMovie::Record(path)
+            } else {
+                InputMovie::Replay(path)
+            });
+            continue;
+        }
+        if arg == "--present-mode" {
+            let Some(mode) = args.next() else {
+                eprintln!("--present-mode expects fifo, mailbox or immediate");
+                std::process::exit(2);
+            };
+            present_mode = Some(mode);
+            continue;
+        }
+        if arg == "--frames" {
+            match args.next().map(|value| value.parse()) {
+                Some(Ok(count)) => frames = Some(count),
+                _ => {
+                    eprintln!("--frames expects a frame count");
+                    std::process::exit(2);
+                }
+            }
+            continue;
+        }
+        // `cli::run` parses this one itself; skip its value so it isn't taken for the ROM.
+        if arg == "--save-root" {
+            args.next();
+            continue;
+        }
+        if rom_path.is_none() && !arg.starts_with('-') {
             rom_path = Some(std::path::PathBuf::from(arg));
         }
     }
 
     if gui {
-        craterboy::interface::gui::run(rom_path, boot_rom_path);
+        craterboy::interface::gui::run(
+            rom_path,
+            boot_rom_path,
+            boot_timing,
+            threaded,
+            movie,
+            present_mode,
+        );
+    } else if frames.is_some() || movie.is_some() {
+        let Some(rom_path) = rom_path else {
+            eprintln!("--frames needs a ROM path");
+            std::process::exit(2);
+        };
+        if frames.is_none() && matches!(movie, Some(InputMovie::Record(_))) {
+            eprintln!("--record-inputs needs --frames when running headless");
+            std::process::exit(2);
+        }
+        craterboy::interface::cli::run_rom(&rom_path, frames, movie);
     } else {
         craterboy::interface::cli::run();
     }
 }
+
+// The browser build drives the core through `interface::web` instead.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
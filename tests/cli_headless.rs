@@ -0,0 +1,93 @@
+use craterboy::domain::{Cartridge, Movie, MovieError, MovieFrame};
+use craterboy::interface::cli::{
+    HeadlessError, run_headless, run_headless_record, run_headless_replay, run_headless_until,
+};
+
+fn spin_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100] = 0x18; // JR -2
+    rom[0x0101] = 0xFE;
+    rom
+}
+
+/// Copies the d-pad lines from JOYP into BGP forever, so a press changes the shade of
+/// every background pixel.
+fn joypad_to_palette_rom() -> Vec<u8> {
+    const PROGRAM: [u8; 10] = [
+        0x3E, 0x20, // LD A,0x20 (select the d-pad)
+        0xE0, 0x00, // LDH (JOYP),A
+        0xF0, 0x00, // LDH A,(JOYP)
+        0xE0, 0x47, // LDH (BGP),A
+        0x18, 0xFA, // JR -6
+    ];
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP 0x0150
+    rom[0x0150..0x0150 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+    rom
+}
+
+#[test]
+fn headless_run_prints_state_and_frame_hash() {
+    let cartridge = Cartridge::from_bytes(spin_rom()).expect("cartridge");
+    let mut out = Vec::new();
+
+    let hash = run_headless(cartridge, 5, &mut out).expect("headless run");
+
+    let text = String::from_utf8(out).expect("utf8 output");
+    assert!(text.contains("Frames: 5"));
+    assert!(text.contains("CPU: A:"));
+    assert!(text.contains(&format!("Frame Hash (fnv1a64): {:016X}", hash)));
+}
+
+#[test]
+fn headless_run_stops_when_predicate_matches() {
+    let cartridge = Cartridge::from_bytes(spin_rom()).expect("cartridge");
+    let mut out = Vec::new();
+    let mut frames_seen = 0;
+
+    run_headless_until(
+        cartridge,
+        100,
+        |_| {
+            frames_seen += 1;
+            frames_seen == 3
+        },
+        &mut out,
+    )
+    .expect("headless run");
+
+    let text = String::from_utf8(out).expect("utf8 output");
+    assert!(text.contains("Frames: 3"));
+}
+
+#[test]
+fn recorded_movie_replays_to_the_same_frame_hash() {
+    let cartridge = Cartridge::from_bytes(joypad_to_palette_rom()).expect("cartridge");
+    let press_right_late = |frame| MovieFrame {
+        buttons: 0x0F,
+        dpad: if frame >= 5 { 0x0E } else { 0x0F },
+    };
+    let (recorded_hash, movie) =
+        run_headless_record(cartridge.clone(), 10, press_right_late, &mut Vec::new())
+            .expect("record run");
+    assert_eq!(movie.len(), 10);
+    assert_eq!(movie.frame(4), MovieFrame::RELEASED);
+    assert_eq!(movie.frame(5).dpad, 0x0E);
+
+    let idle_hash = run_headless(cartridge.clone(), 10, &mut Vec::new()).expect("idle run");
+    assert_ne!(recorded_hash, idle_hash);
+
+    let movie = Movie::decode(&movie.encode()).expect("decode movie");
+    let replayed_hash =
+        run_headless_replay(cartridge, &movie, 10, &mut Vec::new()).expect("replay run");
+    assert_eq!(replayed_hash, recorded_hash);
+
+    let mut other_rom = joypad_to_palette_rom();
+    other_rom[0x0200] = 0x42;
+    let other = Cartridge::from_bytes(other_rom).expect("cartridge");
+    let err = run_headless_replay(other, &movie, 10, &mut Vec::new()).expect_err("wrong ROM");
+    assert!(matches!(
+        err,
+        HeadlessError::Movie(MovieError::RomMismatch { .. })
+    ));
+}
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use craterboy::domain::Emulator;
+
+const CYCLES_PER_SECOND: u64 = 4_194_304;
+const CYCLE_CAP: u64 = CYCLES_PER_SECOND * 120;
+const IDLE_FRAMES: u32 = 120;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("roms")
+        .join(name)
+}
+
+/// Runs a test ROM headless and returns everything it printed over the serial port.
+/// Stops once output has been quiet for `IDLE_FRAMES` frames, or at `CYCLE_CAP`.
+fn run_blargg(rom_bytes: &[u8]) -> String {
+    let mut emulator = Emulator::new();
+    emulator
+        .load_rom_bytes(rom_bytes, None)
+        .expect("load test rom");
+
+    let mut output = Vec::new();
+    let mut cycles: u64 = 0;
+    let mut idle_frames = 0;
+    while cycles < CYCLE_CAP {
+        cycles += u64::from(emulator.step_frame().expect("step frame"));
+        let sent = emulator.take_serial_output();
+        if sent.is_empty() {
+            idle_frames += 1;
+        } else {
+            output.extend_from_slice(&sent);
+            idle_frames = 0;
+        }
+        if !output.is_empty() && idle_frames >= IDLE_FRAMES {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// A 32 KB ROM that sends a NUL-terminated string from 0x0200 over serial, then spins.
+fn serial_hello_rom(message: &[u8]) -> Vec<u8> {
+    #[rustfmt::skip]
+    const PROGRAM: [u8; 17] = [
+        0x21, 0x00, 0x02, // 0150: ld hl, $0200
+        0x2A,             // 0153: ld a, [hl+]
+        0xA7,             // 0154: and a
+        0x28, 0x08,       // 0155: jr z, $015F
+        0xE0, 0x01,       // 0157: ldh [SB], a
+        0x3E, 0x81,       // 0159: ld a, $81
+        0xE0, 0x02,       // 015B: ldh [SC], a   ; start, internal clock
+        0x18, 0xF4,       // 015D: jr $0153
+        0x18, 0xFE,       // 015F: jr $015F
+    ];
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // nop; jp $0150
+    rom[0x0134..0x0140].copy_from_slice(b"SERIALHELLO\0");
+    rom[0x0150..0x0150 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+    rom[0x0200..0x0200 + message.len()].copy_from_slice(message);
+    rom[0x014D] = rom[0x0134..0x014D]
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1));
+    rom
+}
+
+/// Reads an optional test ROM, which isn't checked in for licensing reasons.
+fn optional_rom(name: &str) -> Option<Vec<u8>> {
+    let path = fixture_path(name);
+    match std::fs::read(&path) {
+        Ok(bytes) => Some(bytes),
+        Err(_) => {
+            eprintln!("skipping: {} not found", path.display());
+            None
+        }
+    }
+}
+
+#[test]
+fn serial_hello_prints_known_string() {
+    let rom = serial_hello_rom(b"craterboy serial ok\n");
+
+    assert_eq!(run_blargg(&rom), "craterboy serial ok\n");
+}
+
+#[test]
+fn blargg_cpu_instrs_passes() {
+    let Some(rom) = optional_rom("blargg/cpu_instrs.gb") else {
+        return;
+    };
+
+    let output = run_blargg(&rom);
+
+    assert!(output.contains("Passed all tests"), "{output}");
+}
@@ -0,0 +1,35 @@
+use craterboy::domain::{FRAME_HEIGHT, FRAME_SIZE, FRAME_WIDTH};
+use craterboy::interface::web::{INPUT_A, INPUT_RIGHT, WebEmulator};
+
+fn spin_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100] = 0x18; // JR -2
+    rom[0x0101] = 0xFE;
+    rom
+}
+
+#[test]
+fn web_emulator_loads_bytes_and_steps_a_frame() {
+    let mut emulator = WebEmulator::new();
+    emulator.load_rom(&spin_rom()).expect("load rom");
+    emulator.set_input(INPUT_RIGHT | INPUT_A);
+
+    let cycles = emulator.step_frame().expect("step frame");
+
+    assert!(cycles > 0);
+    assert_eq!(emulator.framebuffer_len(), FRAME_SIZE);
+    assert_eq!(emulator.framebuffer_ptr(), emulator.framebuffer().as_ptr());
+    assert_eq!(
+        WebEmulator::frame_width() * WebEmulator::frame_height(),
+        FRAME_WIDTH * FRAME_HEIGHT
+    );
+}
+
+#[test]
+fn web_emulator_reports_load_errors_as_strings() {
+    let mut emulator = WebEmulator::new();
+
+    let err = emulator.load_rom(&[0; 0x10]).expect_err("truncated rom");
+
+    assert!(err.contains("TooSmall"), "{err}");
+}